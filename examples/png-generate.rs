@@ -1,5 +1,4 @@
 // For reading and opening files
-use png;
 use png::text_metadata::{ITXtChunk, ZTXtChunk};
 use std::env;
 use std::fs::File;
@@ -10,9 +9,9 @@ fn main() {
         .nth(1)
         .expect("Expected a filename to output to.");
     let file = File::create(path).unwrap();
-    let ref mut w = BufWriter::new(file);
+    let mut w = BufWriter::new(file);
 
-    let mut encoder = png::Encoder::new(w, 2, 1); // Width is 2 pixels and height is 1.
+    let mut encoder = png::Encoder::new(&mut w, 2, 1); // Width is 2 pixels and height is 1.
     encoder.set_color(png::ColorType::Rgba);
     encoder.set_depth(png::BitDepth::Eight);
     // Adding text chunks to the header
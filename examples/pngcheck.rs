@@ -31,10 +31,7 @@ fn parse_args() -> Matches {
             Err(err) => println!("{}", err),
         }
     }
-    println!(
-        "{}",
-        opts.usage(&format!("Usage: pngcheck [-cpt] [file ...]"))
-    );
+    println!("{}", opts.usage("Usage: pngcheck [-cpt] [file ...]"));
     std::process::exit(0);
 }
 
@@ -169,7 +166,7 @@ fn check_image<P: AsRef<Path>>(c: Config, fname: P) -> io::Result<()> {
         print!(" ({}) bytes", data.len())
     }
     loop {
-        if buf.len() == 0 {
+        if buf.is_empty() {
             // circumvent borrow checker
             assert!(!data.is_empty());
             let n = reader.read(data)?;
@@ -214,7 +211,7 @@ fn check_image<P: AsRef<Path>>(c: Config, fname: P) -> io::Result<()> {
                         100.0 * (1.0 - c_ratio!())
                     )
                 } else if !c.quiet {
-                    println!("");
+                    println!();
                     if c.color {
                         t.fg(color::GREEN)?;
                         t.attr(Attr::Bold)?;
@@ -248,7 +245,7 @@ fn check_image<P: AsRef<Path>>(c: Config, fname: P) -> io::Result<()> {
                         n_chunks += 1;
                         if c.verbose {
                             let chunk = type_str;
-                            println!("");
+                            println!();
                             print!("  chunk ");
                             if c.color {
                                 t.fg(color::YELLOW)?;
@@ -279,27 +276,24 @@ fn check_image<P: AsRef<Path>>(c: Config, fname: P) -> io::Result<()> {
                     }
                     ChunkComplete(_, type_str) if c.verbose => {
                         use png::chunk::*;
-                        match type_str {
-                            IHDR => {
-                                println!("");
-                                print!(
-                                    "    {} x {} image, {}{}, {}",
-                                    width,
-                                    height,
-                                    display_image_type(bits, color),
-                                    (if trns { "+trns" } else { "" }),
-                                    display_interlaced(interlaced),
-                                );
-                            }
-                            _ => (),
+                        if type_str == IHDR {
+                            println!();
+                            print!(
+                                "    {} x {} image, {}{}, {}",
+                                width,
+                                height,
+                                display_image_type(bits, color),
+                                (if trns { "+trns" } else { "" }),
+                                display_interlaced(interlaced),
+                            );
                         }
                     }
                     AnimationControl(actl) => {
-                        println!("");
+                        println!();
                         print!("    {} frames, {} plays", actl.num_frames, actl.num_plays,);
                     }
                     FrameControl(fctl) => {
-                        println!("");
+                        println!();
                         println!(
                             "    sequence #{}, {} x {} pixels @ ({}, {})",
                             fctl.sequence_number,
@@ -0,0 +1,94 @@
+#![no_main]
+//! Encodes arbitrary pixels with arbitrary encoder settings, then decodes the file with arbitrary
+//! decoder settings. Unlike the other targets this explores the combinations of options, such as
+//! a small limit with 16-bit samples and a transformation.
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+struct EncodeOptions {
+    width: u8,
+    height: u8,
+    color_type: u8,
+    bit_depth: u8,
+    filter: u8,
+    adaptive_filter: bool,
+    compression: u8,
+    bottom_up: bool,
+}
+
+#[derive(Arbitrary, Debug)]
+struct DecodeOptions {
+    transformations: u32,
+    limit_bytes: u32,
+    bottom_up: bool,
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    encode: EncodeOptions,
+    decode: DecodeOptions,
+    pixels: Vec<u8>,
+}
+
+fn encode(options: &EncodeOptions, pixels: &[u8]) -> Option<Vec<u8>> {
+    let color_type = png::ColorType::from_u8(options.color_type)?;
+    let bit_depth = png::BitDepth::from_u8(options.bit_depth)?;
+    let width = u32::from(options.width).max(1);
+    let height = u32::from(options.height).max(1);
+
+    let mut file = Vec::new();
+    let mut encoder = png::Encoder::new(&mut file, width, height);
+    encoder.set_color(color_type);
+    encoder.set_depth(bit_depth);
+    if color_type == png::ColorType::Indexed {
+        encoder.set_palette(vec![0; 3 << bit_depth as u8]);
+    }
+    encoder.set_filter(png::FilterType::from_u8(options.filter % 5)?);
+    if options.adaptive_filter {
+        encoder.set_adaptive_filter(png::AdaptiveFilterType::Adaptive);
+    }
+    encoder.set_compression(match options.compression % 5 {
+        0 => png::Compression::Default,
+        1 => png::Compression::Fast,
+        2 => png::Compression::Best,
+        3 => png::Compression::Huffman,
+        _ => png::Compression::Rle,
+    });
+    encoder.set_bottom_up(options.bottom_up);
+
+    let mut info = png::Info::with_size(width, height);
+    info.color_type = color_type;
+    info.bit_depth = bit_depth;
+    // Without the filter type byte of each row.
+    let mut data = vec![0; info.raw_bytes() - height as usize];
+    let mut writer = encoder.write_header().ok()?;
+    for (byte, pixel) in data.iter_mut().zip(pixels.iter().cycle()) {
+        *byte = *pixel;
+    }
+    writer.write_image_data(&data).ok()?;
+    writer.finish().ok()?;
+    Some(file)
+}
+
+fuzz_target!(|input: Input| {
+    let file = match encode(&input.encode, &input.pixels) {
+        Some(file) => file,
+        None => return,
+    };
+
+    let limits = png::Limits {
+        bytes: input.decode.limit_bytes as usize,
+    };
+    let mut decoder = png::Decoder::new_with_limits(&file[..], limits);
+    decoder.set_transformations(png::Transformations::from_bits_truncate(
+        input.decode.transformations,
+    ));
+    decoder.set_bottom_up(input.decode.bottom_up);
+    let mut reader = match decoder.read_info() {
+        Ok(reader) => reader,
+        Err(_) => return,
+    };
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let _ = reader.next_frame(&mut buf);
+});
@@ -0,0 +1,214 @@
+//! The pass geometry of the Adam7 interlacing method.
+//!
+//! Adam7 splits an image into seven passes, each a sub-sampled image of its own. The pixels of the
+//! first pass are spaced eight pixels apart, the last pass contains every odd row. The pattern of
+//! passes in each 8x8 block of the image is:
+//!
+//! ```text
+//! 16462646
+//! 77777777
+//! 56565656
+//! 77777777
+//! 36463646
+//! 77777777
+//! 56565656
+//! 77777777
+//! ```
+//!
+//! Passes are numbered from `1` to `7`. All functions taking a pass panic if it is out of range.
+
+/// The horizontal offset and spacing, then the vertical offset and spacing of a pass.
+fn pattern(pass: u8) -> (u32, u32, u32, u32) {
+    match pass {
+        1 => (0, 8, 0, 8),
+        2 => (4, 8, 0, 8),
+        3 => (0, 4, 4, 8),
+        4 => (2, 4, 0, 4),
+        5 => (0, 2, 2, 4),
+        6 => (1, 2, 0, 2),
+        7 => (0, 1, 1, 2),
+        _ => panic!("Adam7 pass out of range: {}", pass),
+    }
+}
+
+/// The width and height of a pass over an image, which are zero for empty passes.
+///
+/// ```
+/// use png::adam7;
+/// assert_eq!(adam7::pass_size(1, 9, 9), (2, 2));
+/// assert_eq!(adam7::pass_size(2, 4, 9), (0, 2));
+/// assert_eq!(adam7::pass_size(7, 9, 9), (9, 4));
+/// ```
+pub fn pass_size(pass: u8, width: u32, height: u32) -> (u32, u32) {
+    let (x, dx, y, dy) = pattern(pass);
+    let size = |len: u32, offset: u32, spacing: u32| {
+        if len > offset {
+            (len - offset - 1) / spacing + 1
+        } else {
+            0
+        }
+    };
+    (size(width, x, dx), size(height, y, dy))
+}
+
+/// The image column of the column `x` of a pass.
+pub fn image_column(pass: u8, x: u32) -> u32 {
+    let (offset, spacing, _, _) = pattern(pass);
+    x * spacing + offset
+}
+
+/// The image row of the row `y` of a pass.
+pub fn image_row(pass: u8, y: u32) -> u32 {
+    let (_, _, offset, spacing) = pattern(pass);
+    y * spacing + offset
+}
+
+/// The image coordinates of the pixel at `(x, y)` in a pass.
+pub fn to_image(pass: u8, x: u32, y: u32) -> (u32, u32) {
+    (image_column(pass, x), image_row(pass, y))
+}
+
+/// The pass and the coordinates within that pass of the image pixel at `(x, y)`.
+///
+/// ```
+/// use png::adam7;
+/// assert_eq!(adam7::from_image(12, 0), (2, 1, 0));
+/// assert_eq!(adam7::to_image(2, 1, 0), (12, 0));
+/// ```
+pub fn from_image(x: u32, y: u32) -> (u8, u32, u32) {
+    let pass = match (x % 8, y % 8) {
+        (0, 0) => 1,
+        (4, 0) => 2,
+        (0, 4) | (4, 4) => 3,
+        (2, 0) | (6, 0) | (2, 4) | (6, 4) => 4,
+        (_, 2) | (_, 6) if x % 2 == 0 => 5,
+        (_, row) if row % 2 == 0 => 6,
+        _ => 7,
+    };
+    let (x_offset, dx, y_offset, dy) = pattern(pass);
+    (pass, (x - x_offset) / dx, (y - y_offset) / dy)
+}
+
+/// A pixel of an interlaced image, in the order it appears in the image data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PassPixel {
+    /// The pass of the pixel.
+    pub pass: u8,
+    /// The column within the pass.
+    pub x: u32,
+    /// The row within the pass.
+    pub y: u32,
+    /// The column in the deinterlaced image.
+    pub image_x: u32,
+    /// The row in the deinterlaced image.
+    pub image_y: u32,
+}
+
+/// An iterator over the pixels of an interlaced image, see [`pixels`].
+#[derive(Clone, Debug)]
+pub struct Pixels {
+    width: u32,
+    height: u32,
+    pass: u8,
+    pass_width: u32,
+    pass_height: u32,
+    x: u32,
+    y: u32,
+}
+
+/// Iterates over all pixels of an interlaced image of the given size.
+///
+/// The pixels are returned pass by pass and row by row, as they are stored in the image data.
+///
+/// ```
+/// use png::adam7;
+/// let first: Vec<_> = adam7::pixels(16, 16)
+///     .take(3)
+///     .map(|pixel| (pixel.image_x, pixel.image_y))
+///     .collect();
+/// assert_eq!(first, [(0, 0), (8, 0), (0, 8)]);
+/// ```
+pub fn pixels(width: u32, height: u32) -> Pixels {
+    let (pass_width, pass_height) = pass_size(1, width, height);
+    Pixels {
+        width,
+        height,
+        pass: 1,
+        pass_width,
+        pass_height,
+        x: 0,
+        y: 0,
+    }
+}
+
+impl Iterator for Pixels {
+    type Item = PassPixel;
+
+    fn next(&mut self) -> Option<PassPixel> {
+        while self.x >= self.pass_width || self.y >= self.pass_height {
+            if self.x >= self.pass_width && self.pass_width > 0 && self.y + 1 < self.pass_height {
+                self.x = 0;
+                self.y += 1;
+                continue;
+            }
+            if self.pass == 7 {
+                return None;
+            }
+            self.pass += 1;
+            let (pass_width, pass_height) = pass_size(self.pass, self.width, self.height);
+            self.pass_width = pass_width;
+            self.pass_height = pass_height;
+            self.x = 0;
+            self.y = 0;
+        }
+
+        let (x, y) = (self.x, self.y);
+        self.x += 1;
+        let (image_x, image_y) = to_image(self.pass, x, y);
+        Some(PassPixel {
+            pass: self.pass,
+            x,
+            y,
+            image_x,
+            image_y,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixels_cover_image() {
+        for &(width, height) in &[(1, 1), (3, 5), (8, 8), (13, 7), (1, 17)] {
+            let mut seen = vec![false; (width * height) as usize];
+            for pixel in pixels(width, height) {
+                let (pass_width, pass_height) = pass_size(pixel.pass, width, height);
+                assert!(pixel.x < pass_width && pixel.y < pass_height);
+                assert_eq!(
+                    from_image(pixel.image_x, pixel.image_y),
+                    (pixel.pass, pixel.x, pixel.y)
+                );
+                let index = (pixel.image_y * width + pixel.image_x) as usize;
+                assert!(!seen[index], "{:?}", pixel);
+                seen[index] = true;
+            }
+            assert!(seen.iter().all(|&seen| seen), "{}x{}", width, height);
+        }
+    }
+
+    #[test]
+    fn pass_pattern() {
+        let pattern: Vec<String> = (0..8)
+            .map(|y| (0..8).map(|x| from_image(x, y).0.to_string()).collect())
+            .collect();
+        assert_eq!(
+            pattern,
+            [
+                "16462646", "77777777", "56565656", "77777777", "36463646", "77777777", "56565656",
+                "77777777",
+            ]
+        );
+    }
+}
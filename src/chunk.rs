@@ -2,10 +2,59 @@
 #![allow(dead_code)]
 #![allow(non_upper_case_globals)]
 use core::fmt;
+use std::convert::TryFrom;
+use std::error;
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+
+use crc32fast::Hasher as Crc32;
+
+use crate::decoder::{DecodingError, FormatErrorInner};
+use crate::encoder::{self, EncodingError, FormatErrorKind};
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ChunkType(pub [u8; 4]);
 
+impl ChunkType {
+    /// Creates a chunk type, checking that it is valid.
+    ///
+    /// All four bytes must be ASCII letters, and the third one has to be uppercase as the reserved
+    /// bit must not be set. The case of the other letters decides whether the chunk is critical,
+    /// private and safe to copy.
+    ///
+    /// This is a `const fn`, so the chunk types of constants can be checked as well.
+    pub const fn new(bytes: [u8; 4]) -> Result<Self, ParseChunkTypeError> {
+        let chunk_type = ChunkType(bytes);
+        // Iterators are not available in a `const fn`.
+        let mut i = 0;
+        while i < 4 {
+            let byte = bytes[i];
+            if !matches!(byte, b'A'..=b'Z' | b'a'..=b'z') {
+                let kind = ParseChunkTypeErrorKind::Byte(byte);
+                return Err(ParseChunkTypeError { kind });
+            }
+            i += 1;
+        }
+        if reserved_set(chunk_type) {
+            let kind = ParseChunkTypeErrorKind::Reserved;
+            Err(ParseChunkTypeError { kind })
+        } else {
+            Ok(chunk_type)
+        }
+    }
+
+    /// Creates a chunk type without checking that it is valid.
+    pub const fn new_unchecked(bytes: [u8; 4]) -> Self {
+        ChunkType(bytes)
+    }
+
+    /// Whether the chunk type consists of ASCII letters and does not set the reserved bit.
+    pub fn is_valid(&self) -> bool {
+        ChunkType::new(self.0).is_ok()
+    }
+}
+
 // -- Critical chunks --
 
 /// Image header
@@ -31,6 +80,12 @@ pub const pHYs: ChunkType = ChunkType(*b"pHYs");
 pub const cHRM: ChunkType = ChunkType(*b"cHRM");
 /// Source system's gamma value
 pub const gAMA: ChunkType = ChunkType(*b"gAMA");
+/// Significant bits
+pub const sBIT: ChunkType = ChunkType(*b"sBIT");
+/// Palette histogram
+pub const hIST: ChunkType = ChunkType(*b"hIST");
+/// Suggested palette
+pub const sPLT: ChunkType = ChunkType(*b"sPLT");
 /// sRGB color space chunk
 pub const sRGB: ChunkType = ChunkType(*b"sRGB");
 /// ICC profile chunk
@@ -50,6 +105,122 @@ pub const acTL: ChunkType = ChunkType(*b"acTL");
 pub const fcTL: ChunkType = ChunkType(*b"fcTL");
 /// Frame data
 pub const fdAT: ChunkType = ChunkType(*b"fdAT");
+/// Digital signature
+pub const dSIG: ChunkType = ChunkType(*b"dSIG");
+/// Stereo image layout
+pub const sTER: ChunkType = ChunkType(*b"sTER");
+/// GIF graphic control extension
+pub const gIFg: ChunkType = ChunkType(*b"gIFg");
+/// GIF application extension
+pub const gIFx: ChunkType = ChunkType(*b"gIFx");
+/// Fractal image parameters
+pub const fRAc: ChunkType = ChunkType(*b"fRAc");
+/// Exchangeable image file format (Exif) profile
+pub const eXIf: ChunkType = ChunkType(*b"eXIf");
+/// Coding-independent code points for video signal type identification
+pub const cICP: ChunkType = ChunkType(*b"cICP");
+
+// -- Known chunk types --
+
+macro_rules! known_chunks {
+    ($($(#[doc = $doc:literal])* $name:ident,)*) => {
+        /// The chunk types known to this crate, as an enum to match on.
+        ///
+        /// Convert a [`ChunkType`] with `KnownChunk::try_from`, which returns the raw chunk type
+        /// back for unknown chunks. New variants are added when this crate learns about more
+        /// chunks, so a match has to handle the remaining ones with a wildcard arm.
+        #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        #[non_exhaustive]
+        pub enum KnownChunk {
+            $($(#[doc = $doc])* $name,)*
+        }
+
+        impl From<KnownChunk> for ChunkType {
+            fn from(known: KnownChunk) -> ChunkType {
+                match known {
+                    $(KnownChunk::$name => $name,)*
+                }
+            }
+        }
+
+        impl TryFrom<ChunkType> for KnownChunk {
+            type Error = ChunkType;
+
+            fn try_from(chunk_type: ChunkType) -> Result<KnownChunk, ChunkType> {
+                match chunk_type {
+                    $($name => Ok(KnownChunk::$name),)*
+                    unknown => Err(unknown),
+                }
+            }
+        }
+    };
+}
+
+known_chunks! {
+    /// Image header
+    IHDR,
+    /// Palette
+    PLTE,
+    /// Image data
+    IDAT,
+    /// Image trailer
+    IEND,
+    /// Transparency
+    tRNS,
+    /// Background colour
+    bKGD,
+    /// Image last-modification time
+    tIME,
+    /// Physical pixel dimensions
+    pHYs,
+    /// Source system's pixel chromaticities
+    cHRM,
+    /// Source system's gamma value
+    gAMA,
+    /// Significant bits
+    sBIT,
+    /// Palette histogram
+    hIST,
+    /// Suggested palette
+    sPLT,
+    /// sRGB color space chunk
+    sRGB,
+    /// ICC profile chunk
+    iCCP,
+    /// Latin-1 uncompressed textual data
+    tEXt,
+    /// Latin-1 compressed textual data
+    zTXt,
+    /// UTF-8 textual data
+    iTXt,
+    /// Animation control
+    acTL,
+    /// Frame control
+    fcTL,
+    /// Frame data
+    fdAT,
+    /// Digital signature
+    dSIG,
+    /// Stereo image layout
+    sTER,
+    /// GIF graphic control extension
+    gIFg,
+    /// GIF application extension
+    gIFx,
+    /// Fractal image parameters
+    fRAc,
+    /// Exchangeable image file format (Exif) profile
+    eXIf,
+    /// Coding-independent code points for video signal type identification
+    cICP,
+}
+
+impl fmt::Display for KnownChunk {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        ChunkType::from(*self).fmt(f)
+    }
+}
 
 // -- Chunk type determination --
 
@@ -65,7 +236,7 @@ pub fn is_private(ChunkType(type_): ChunkType) -> bool {
 
 /// Checks whether the reserved bit of the chunk name is set.
 /// If it is set the chunk name is invalid.
-pub fn reserved_set(ChunkType(type_): ChunkType) -> bool {
+pub const fn reserved_set(ChunkType(type_): ChunkType) -> bool {
     type_[2] & 32 != 0
 }
 
@@ -74,6 +245,21 @@ pub fn safe_to_copy(ChunkType(type_): ChunkType) -> bool {
     type_[3] & 32 != 0
 }
 
+// -- Checksums --
+
+/// Computes the CRC of a chunk, which covers its type and data but not its length.
+pub fn crc(chunk_type: ChunkType, data: &[u8]) -> u32 {
+    let mut hasher = Crc32::new();
+    hasher.update(&chunk_type.0);
+    hasher.update(data);
+    hasher.finalize()
+}
+
+/// Returns true if `crc` is the CRC of a chunk with the given type and data.
+pub fn verify_crc(chunk_type: ChunkType, data: &[u8], crc: u32) -> bool {
+    self::crc(chunk_type, data) == crc
+}
+
 impl fmt::Debug for ChunkType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         struct DebugType([u8; 4]);
@@ -96,3 +282,868 @@ impl fmt::Debug for ChunkType {
             .finish()
     }
 }
+
+impl fmt::Display for ChunkType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &c in &self.0[..] {
+            write!(f, "{}", char::from(c).escape_default())?;
+        }
+        Ok(())
+    }
+}
+
+/// An error from parsing a [`ChunkType`] from its name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseChunkTypeError {
+    kind: ParseChunkTypeErrorKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum ParseChunkTypeErrorKind {
+    Length(usize),
+    Byte(u8),
+    Reserved,
+}
+
+impl fmt::Display for ParseChunkTypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ParseChunkTypeErrorKind::Length(len) => {
+                write!(f, "chunk type must be 4 bytes long, got {} bytes", len)
+            }
+            ParseChunkTypeErrorKind::Byte(byte) => write!(
+                f,
+                "chunk type must consist of ASCII letters, got byte {:#04x}",
+                byte
+            ),
+            ParseChunkTypeErrorKind::Reserved => {
+                write!(f, "chunk type must not set the reserved bit")
+            }
+        }
+    }
+}
+
+impl error::Error for ParseChunkTypeError {}
+
+impl TryFrom<&str> for ChunkType {
+    type Error = ParseChunkTypeError;
+
+    /// Parses the four ASCII letters of a chunk type, such as `"tEXt"`, see [`ChunkType::new`].
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        let bytes = name.as_bytes();
+        if bytes.len() != 4 {
+            let kind = ParseChunkTypeErrorKind::Length(bytes.len());
+            return Err(ParseChunkTypeError { kind });
+        }
+        ChunkType::new([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+}
+
+impl FromStr for ChunkType {
+    type Err = ParseChunkTypeError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        ChunkType::try_from(name)
+    }
+}
+
+/// A chunk as it is stored in a PNG file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawChunk {
+    /// The type of the chunk.
+    pub chunk_type: ChunkType,
+    /// The data of the chunk, its length is stored before the chunk type.
+    pub data: Vec<u8>,
+    /// The CRC stored after the data.
+    pub crc: u32,
+    /// Whether `crc` matches the chunk type and data.
+    pub crc_ok: bool,
+}
+
+/// Reads the chunks of a PNG file one by one, without interpreting them.
+///
+/// The iterator first checks the PNG signature and then yields every chunk up to and including
+/// `IEND`. A chunk with a wrong CRC is returned as well, with [`RawChunk::crc_ok`] unset, so
+/// that tools inspecting damaged files can continue. Reaching the end of the input before the
+/// `IEND` chunk is an error.
+///
+/// ```
+/// # fn chunk_types(png: &[u8]) -> Result<Vec<png::chunk::ChunkType>, png::DecodingError> {
+/// png::chunk::ChunkReader::new(png)
+///     .map(|chunk| chunk.map(|chunk| chunk.chunk_type))
+///     .collect()
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ChunkReader<R: Read> {
+    r: R,
+    state: ChunkReaderState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChunkReaderState {
+    Signature,
+    Chunks,
+    Done,
+}
+
+impl<R: Read> ChunkReader<R> {
+    /// Creates a reader for the PNG file at the start of `r`.
+    pub fn new(r: R) -> Self {
+        ChunkReader {
+            r,
+            state: ChunkReaderState::Signature,
+        }
+    }
+
+    /// Returns the underlying reader, positioned after the last chunk that was read.
+    pub fn into_inner(self) -> R {
+        self.r
+    }
+
+    fn read_chunk(&mut self) -> Result<RawChunk, DecodingError> {
+        let (length, chunk_type) = self.read_header()?;
+        self.read_data(length, chunk_type)
+    }
+
+    /// Finds the first chunk of the given type, unless the image data or end is reached before.
+    ///
+    /// The data of other chunks is skipped without being buffered or checked.
+    pub(crate) fn find_before_image(
+        &mut self,
+        wanted: ChunkType,
+    ) -> Result<Option<RawChunk>, DecodingError> {
+        while self.state != ChunkReaderState::Done {
+            let (length, chunk_type) = self.read_header()?;
+            if chunk_type == IDAT || chunk_type == IEND {
+                self.state = ChunkReaderState::Done;
+            } else if chunk_type == wanted {
+                return self.read_data(length, chunk_type).map(Some);
+            } else {
+                let skip = u64::from(length) + 4;
+                if io::copy(&mut (&mut self.r).take(skip), &mut io::sink())? != skip {
+                    return Err(DecodingError::Format(
+                        FormatErrorInner::UnexpectedEof.into(),
+                    ));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn read_header(&mut self) -> Result<(u32, ChunkType), DecodingError> {
+        if self.state == ChunkReaderState::Signature {
+            let mut signature = [0; 8];
+            self.read_exact(&mut signature)?;
+            if signature != [137, 80, 78, 71, 13, 10, 26, 10] {
+                return Err(DecodingError::Format(
+                    FormatErrorInner::InvalidSignature.into(),
+                ));
+            }
+            self.state = ChunkReaderState::Chunks;
+        }
+
+        let mut header = [0; 8];
+        self.read_exact(&mut header)?;
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let chunk_type = ChunkType([header[4], header[5], header[6], header[7]]);
+        Ok((length, chunk_type))
+    }
+
+    fn read_data(&mut self, length: u32, chunk_type: ChunkType) -> Result<RawChunk, DecodingError> {
+        // Only allocate what is actually present, not what the length claims.
+        let mut data = Vec::new();
+        (&mut self.r)
+            .take(u64::from(length))
+            .read_to_end(&mut data)?;
+        if data.len() != length as usize {
+            return Err(DecodingError::Format(
+                FormatErrorInner::UnexpectedEof.into(),
+            ));
+        }
+
+        let mut crc = [0; 4];
+        self.read_exact(&mut crc)?;
+        let crc = u32::from_be_bytes(crc);
+
+        Ok(RawChunk {
+            crc_ok: verify_crc(chunk_type, &data, crc),
+            chunk_type,
+            data,
+            crc,
+        })
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), DecodingError> {
+        self.r.read_exact(buf).map_err(|err| match err.kind() {
+            io::ErrorKind::UnexpectedEof => {
+                DecodingError::Format(FormatErrorInner::UnexpectedEof.into())
+            }
+            _ => DecodingError::IoError(err),
+        })
+    }
+}
+
+impl<R: Read> Iterator for ChunkReader<R> {
+    type Item = Result<RawChunk, DecodingError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.state == ChunkReaderState::Done {
+            return None;
+        }
+
+        let chunk = self.read_chunk();
+        match &chunk {
+            Ok(chunk) if chunk.chunk_type != IEND => {}
+            _ => self.state = ChunkReaderState::Done,
+        }
+        Some(chunk)
+    }
+}
+
+/// A chunk located in a PNG file in memory by [`ChunkIndex`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IndexedChunk<'a> {
+    /// The type of the chunk.
+    pub chunk_type: ChunkType,
+    /// The offset of the chunk in the file, where its length is stored.
+    pub offset: usize,
+    /// The data of the chunk.
+    pub data: &'a [u8],
+    /// The CRC stored after the data.
+    pub crc: u32,
+}
+
+impl IndexedChunk<'_> {
+    /// Returns true if the CRC matches the chunk type and data, which is not checked by the index.
+    pub fn crc_ok(&self) -> bool {
+        verify_crc(self.chunk_type, self.data, self.crc)
+    }
+}
+
+/// A chunk read by the decoder, see [`Decoder::set_chunk_inventory`](crate::Decoder::set_chunk_inventory).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChunkRecord {
+    /// The type of the chunk.
+    pub chunk_type: ChunkType,
+    /// The offset of the chunk in the file, where its length is stored.
+    pub offset: u64,
+    /// The length of the data of the chunk.
+    pub length: u32,
+    /// The CRC stored after the data.
+    pub crc: u32,
+    /// Whether the CRC matches the chunk type and data.
+    pub crc_ok: bool,
+}
+
+/// The positions of all chunks of a PNG file that is entirely in memory, such as a memory-mapped
+/// file.
+///
+/// Building the index only reads the length and type of every chunk and skips their data, which
+/// is borrowed from the file instead of being copied. Metadata chunks and the frames of an
+/// animation can then be accessed directly.
+///
+/// ```
+/// use png::chunk::{self, ChunkIndex};
+///
+/// let file = std::fs::read("tests/pngsuite/basn0g08.png").unwrap();
+/// let index = ChunkIndex::new(&file).unwrap();
+/// assert_eq!(index.find(chunk::IHDR).unwrap().data.len(), 13);
+/// assert_eq!(index.frame_count(), 0);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ChunkIndex<'a> {
+    chunks: Vec<IndexedChunk<'a>>,
+    /// For each `fcTL` chunk the range of it and the following image data chunks.
+    frames: Vec<std::ops::Range<usize>>,
+}
+
+impl<'a> ChunkIndex<'a> {
+    /// Indexes the chunks of the PNG file `data` up to and including `IEND`.
+    ///
+    /// An error is returned if the signature is wrong or a chunk is truncated.
+    pub fn new(data: &'a [u8]) -> Result<Self, DecodingError> {
+        let eof = || DecodingError::Format(FormatErrorInner::UnexpectedEof.into());
+        match data.get(..8) {
+            Some(signature) if signature == [137, 80, 78, 71, 13, 10, 26, 10] => {}
+            Some(_) => {
+                return Err(DecodingError::Format(
+                    FormatErrorInner::InvalidSignature.into(),
+                ))
+            }
+            None => return Err(eof()),
+        }
+
+        let mut chunks = Vec::new();
+        let mut pos = 8;
+        loop {
+            let header = data.get(pos..pos + 8).ok_or_else(eof)?;
+            let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+            let chunk_type = ChunkType([header[4], header[5], header[6], header[7]]);
+            let start = pos + 8;
+            let end = start
+                .checked_add(length as usize)
+                .filter(|&end| end <= data.len().saturating_sub(4))
+                .ok_or_else(eof)?;
+            let crc = &data[end..end + 4];
+            chunks.push(IndexedChunk {
+                chunk_type,
+                offset: pos,
+                data: &data[start..end],
+                crc: u32::from_be_bytes([crc[0], crc[1], crc[2], crc[3]]),
+            });
+            pos = end + 4;
+            if chunk_type == IEND {
+                break;
+            }
+        }
+
+        let mut frames = Vec::new();
+        for (index, chunk) in chunks.iter().enumerate() {
+            if chunk.chunk_type == fcTL {
+                let data_chunks = chunks[index + 1..]
+                    .iter()
+                    .take_while(|chunk| chunk.chunk_type == IDAT || chunk.chunk_type == fdAT)
+                    .count();
+                frames.push(index..index + 1 + data_chunks);
+            }
+        }
+        Ok(ChunkIndex { chunks, frames })
+    }
+
+    /// All chunks in the order of the file.
+    pub fn chunks(&self) -> &[IndexedChunk<'a>] {
+        &self.chunks
+    }
+
+    /// The first chunk of the given type.
+    pub fn find(&self, chunk_type: ChunkType) -> Option<&IndexedChunk<'a>> {
+        self.chunks
+            .iter()
+            .find(|chunk| chunk.chunk_type == chunk_type)
+    }
+
+    /// All chunks of the given type, such as the text chunks.
+    pub fn find_all(&self, chunk_type: ChunkType) -> impl Iterator<Item = &IndexedChunk<'a>> + '_ {
+        self.chunks
+            .iter()
+            .filter(move |chunk| chunk.chunk_type == chunk_type)
+    }
+
+    /// The number of `fcTL` chunks, which is the number of frames of an animation.
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// The `fcTL` chunk of a frame of an animation, followed by its `IDAT` or `fdAT` chunks.
+    pub fn frame(&self, index: usize) -> Option<&[IndexedChunk<'a>]> {
+        self.frames
+            .get(index)
+            .map(|range| &self.chunks[range.clone()])
+    }
+}
+
+/// Writes the raw chunks of a PNG file, starting with the signature.
+///
+/// Every chunk is framed with its length and CRC. This does not check the order of the chunks,
+/// and their data is written as is.
+///
+/// ```
+/// # fn copy(png: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+/// use png::chunk::{self, ChunkReader, ChunkWriter};
+///
+/// // Remove all text chunks.
+/// let mut writer = ChunkWriter::new(Vec::new());
+/// for raw in ChunkReader::new(png) {
+///     let raw = raw?;
+///     if ![chunk::tEXt, chunk::zTXt, chunk::iTXt].contains(&raw.chunk_type) {
+///         writer.write_chunk(raw.chunk_type, &raw.data)?;
+///     }
+/// }
+/// # Ok(writer.into_inner())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ChunkWriter<W: Write> {
+    w: W,
+    signature_written: bool,
+}
+
+impl<W: Write> ChunkWriter<W> {
+    /// Creates a writer for a PNG file, whose signature is written with the first chunk.
+    pub fn new(w: W) -> Self {
+        ChunkWriter {
+            w,
+            signature_written: false,
+        }
+    }
+
+    /// Writes a chunk with the given type and data.
+    ///
+    /// An error is returned if the chunk type is not valid (see [`ChunkType::is_valid`]) or the
+    /// data is too long for a chunk.
+    pub fn write_chunk(&mut self, chunk_type: ChunkType, data: &[u8]) -> Result<(), EncodingError> {
+        if !chunk_type.is_valid() {
+            let kind = FormatErrorKind::InvalidChunkType(chunk_type);
+            return Err(EncodingError::Format(kind.into()));
+        }
+        encoder::validate_chunk_len(data)?;
+
+        if !self.signature_written {
+            self.w.write_all(&[137, 80, 78, 71, 13, 10, 26, 10])?;
+            self.signature_written = true;
+        }
+        encoder::write_chunk(&mut self.w, chunk_type, data)
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+}
+
+/// The chunks that may appear at most once in a PNG file.
+pub(crate) const UNIQUE_CHUNKS: [ChunkType; 17] = [
+    IHDR, PLTE, IEND, cHRM, gAMA, iCCP, sBIT, sRGB, bKGD, hIST, tRNS, pHYs, tIME, acTL, sTER, cICP,
+    eXIf,
+];
+
+/// The chunks that have to appear before the `PLTE` chunk and the image data.
+pub(crate) const BEFORE_PALETTE: [ChunkType; 6] = [cHRM, gAMA, iCCP, sBIT, sRGB, cICP];
+
+/// The other chunks that have to appear before the image data.
+pub(crate) const BEFORE_IMAGE_DATA: [ChunkType; 8] =
+    [PLTE, tRNS, bKGD, hIST, pHYs, sPLT, acTL, sTER];
+
+/// The ancillary chunks defined by the specification and its registered extensions.
+pub(crate) const KNOWN_ANCILLARY: [ChunkType; 26] = [
+    tRNS,
+    bKGD,
+    tIME,
+    pHYs,
+    cHRM,
+    gAMA,
+    sBIT,
+    hIST,
+    sPLT,
+    sRGB,
+    iCCP,
+    tEXt,
+    zTXt,
+    iTXt,
+    acTL,
+    fcTL,
+    fdAT,
+    dSIG,
+    sTER,
+    gIFg,
+    gIFx,
+    fRAc,
+    eXIf,
+    cICP,
+    ChunkType(*b"oFFs"),
+    ChunkType(*b"pCAL"),
+];
+
+/// A violation of the chunk ordering rules, found by [`check_order`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OrderViolation {
+    /// The position of the offending chunk in the sequence.
+    ///
+    /// For a missing chunk this is the position where it was expected.
+    pub index: usize,
+    /// The type of the offending or missing chunk.
+    pub chunk_type: ChunkType,
+    /// The rule that is violated.
+    pub kind: OrderViolationKind,
+}
+
+/// The ordering rule violated by a chunk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OrderViolationKind {
+    /// A required chunk does not appear at all.
+    Missing,
+    /// The chunk may appear at most once.
+    Duplicate,
+    /// The chunk has to be the first one.
+    NotFirst,
+    /// The chunk appears after the `IEND` chunk.
+    AfterEnd,
+    /// The chunk has to appear before all chunks of the given type.
+    MustPrecede(ChunkType),
+    /// The chunk has to appear after a chunk of the given type.
+    MustFollow(ChunkType),
+    /// The `IDAT` chunks are interrupted by other chunks.
+    NotConsecutive,
+}
+
+/// The result of checking a sequence of chunks with [`check_order`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OrderReport {
+    violations: Vec<OrderViolation>,
+}
+
+impl OrderReport {
+    /// Returns true if no ordering rule is violated.
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// The violations in the order of the chunks they were found at.
+    pub fn violations(&self) -> &[OrderViolation] {
+        &self.violations
+    }
+}
+
+impl fmt::Display for OrderViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::OrderViolationKind::*;
+        let (index, chunk_type) = (self.index, self.chunk_type);
+        match self.kind {
+            Missing => write!(f, "missing {} chunk", chunk_type),
+            Duplicate => write!(f, "duplicate {} chunk at {}", chunk_type, index),
+            NotFirst => write!(
+                f,
+                "{} chunk at {} is not the first chunk",
+                chunk_type, index
+            ),
+            AfterEnd => write!(
+                f,
+                "{} chunk at {} follows the IEND chunk",
+                chunk_type, index
+            ),
+            MustPrecede(other) => write!(f, "{} chunk at {} after {}", chunk_type, index, other),
+            MustFollow(other) => write!(f, "{} chunk at {} before {}", chunk_type, index, other),
+            NotConsecutive => write!(f, "{} chunk at {} is not consecutive", chunk_type, index),
+        }
+    }
+}
+
+/// Checks the order of the chunks in a PNG file against the rules of the specification.
+///
+/// Only the chunk types are needed, so this works without decoding the image. Besides the
+/// placement rules of the standard chunks, this checks that the animation chunks follow the
+/// `acTL` chunk and that `fdAT` chunks follow the `IDAT` chunks.
+///
+/// ```
+/// use png::chunk::{self, ChunkReader};
+///
+/// # fn check(png: &[u8]) -> Result<(), png::DecodingError> {
+/// let types = ChunkReader::new(png)
+///     .map(|raw| raw.map(|raw| raw.chunk_type))
+///     .collect::<Result<Vec<_>, _>>()?;
+/// for violation in chunk::check_order(types).violations() {
+///     println!("{}", violation);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn check_order<I>(chunk_types: I) -> OrderReport
+where
+    I: IntoIterator<Item = ChunkType>,
+{
+    let mut violations = Vec::new();
+    let mut checker = OrderChecker::default();
+    for chunk_type in chunk_types {
+        checker.check(chunk_type, &mut violations);
+    }
+    checker.finish(&mut violations);
+    OrderReport { violations }
+}
+
+/// The state of [`check_order`] between chunks, for checking one chunk at a time.
+#[derive(Debug, Default)]
+pub(crate) struct OrderChecker {
+    seen: Vec<ChunkType>,
+    previous: Option<ChunkType>,
+    count: usize,
+}
+
+impl OrderChecker {
+    /// Checks the next chunk, adding the rules it violates to `violations`.
+    pub(crate) fn check(&mut self, chunk_type: ChunkType, violations: &mut Vec<OrderViolation>) {
+        let index = self.count;
+        self.count += 1;
+        let mut report = |kind| {
+            violations.push(OrderViolation {
+                index,
+                chunk_type,
+                kind,
+            })
+        };
+        let seen = &self.seen;
+        let has = |other: ChunkType| seen.contains(&other);
+
+        if has(IEND) {
+            report(OrderViolationKind::AfterEnd);
+            return;
+        }
+        if UNIQUE_CHUNKS.contains(&chunk_type) && has(chunk_type) {
+            report(OrderViolationKind::Duplicate);
+        } else if chunk_type == IHDR && index != 0 {
+            report(OrderViolationKind::NotFirst);
+        }
+
+        // The first chunk of the list that was already seen is the one that is violated.
+        let must_precede: &[ChunkType] = match chunk_type {
+            _ if BEFORE_PALETTE.contains(&chunk_type) => &[PLTE, IDAT],
+            PLTE => &[tRNS, bKGD, hIST, IDAT],
+            _ if BEFORE_IMAGE_DATA.contains(&chunk_type) => &[IDAT],
+            IDAT if self.previous != Some(IDAT) && has(IDAT) => {
+                report(OrderViolationKind::NotConsecutive);
+                &[]
+            }
+            _ => &[],
+        };
+        if let Some(&other) = must_precede.iter().find(|&&other| has(other)) {
+            report(OrderViolationKind::MustPrecede(other));
+        }
+
+        let must_follow: &[ChunkType] = match chunk_type {
+            hIST => &[PLTE],
+            fcTL => &[acTL],
+            fdAT => &[acTL, IDAT],
+            _ => &[],
+        };
+        if let Some(&other) = must_follow.iter().find(|&&other| !has(other)) {
+            report(OrderViolationKind::MustFollow(other));
+        }
+
+        if !has(chunk_type) {
+            self.seen.push(chunk_type);
+        }
+        self.previous = Some(chunk_type);
+    }
+
+    /// Adds the required chunks that did not appear to `violations`.
+    pub(crate) fn finish(self, violations: &mut Vec<OrderViolation>) {
+        for &(index, required) in [(0, IHDR), (self.count, IDAT), (self.count, IEND)].iter() {
+            if !self.seen.contains(&required) {
+                violations.push(OrderViolation {
+                    index,
+                    chunk_type: required,
+                    kind: OrderViolationKind::Missing,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunk_type_names() {
+        assert_eq!(tEXt.to_string(), "tEXt");
+        assert_eq!(ChunkType(*b"a\0b\n").to_string(), "a\\u{0}b\\n");
+        assert_eq!("tEXt".parse::<ChunkType>(), Ok(tEXt));
+        assert_eq!(ChunkType::try_from("prVt"), Ok(ChunkType(*b"prVt")));
+        assert!("IDA".parse::<ChunkType>().is_err());
+        assert!("IDATA".parse::<ChunkType>().is_err());
+        assert!("ID9T".parse::<ChunkType>().is_err());
+        assert!("IDÄ".parse::<ChunkType>().is_err());
+        assert!("IDaT".parse::<ChunkType>().is_err());
+    }
+
+    #[test]
+    fn known_chunks() {
+        assert_eq!(KnownChunk::try_from(IHDR), Ok(KnownChunk::IHDR));
+        assert_eq!(KnownChunk::try_from(fRAc), Ok(KnownChunk::fRAc));
+        assert_eq!(ChunkType::from(KnownChunk::tEXt), tEXt);
+        assert_eq!(KnownChunk::acTL.to_string(), "acTL");
+
+        let private = ChunkType(*b"prIv");
+        assert_eq!(KnownChunk::try_from(private), Err(private));
+        // Every known variant converts back to itself.
+        for &chunk_type in &[
+            PLTE, IDAT, IEND, tRNS, iCCP, iTXt, fcTL, fdAT, dSIG, sTER, gIFx,
+        ] {
+            let known = KnownChunk::try_from(chunk_type).unwrap();
+            assert_eq!(ChunkType::from(known), chunk_type);
+        }
+    }
+
+    #[test]
+    fn chunk_type_validity() {
+        const PRIVATE: ChunkType = ChunkType::new_unchecked(*b"prIv");
+        assert!(PRIVATE.is_valid());
+        assert!(is_private(PRIVATE));
+        assert_eq!(ChunkType::new(*b"prIv"), Ok(PRIVATE));
+        assert!(ChunkType::new(*b"priv").is_err());
+        assert!(ChunkType::new(*b"pr1v").is_err());
+        assert!(!ChunkType::new_unchecked(*b"pr v").is_valid());
+        const CHECKED: Result<ChunkType, ParseChunkTypeError> = ChunkType::new(*b"prIv");
+        const INVALID: Result<ChunkType, ParseChunkTypeError> = ChunkType::new(*b"prV\0");
+        assert_eq!(CHECKED, Ok(PRIVATE));
+        assert!(INVALID.is_err());
+        assert!([IHDR, PLTE, IDAT, IEND, tRNS, iTXt, acTL, fdAT]
+            .iter()
+            .all(ChunkType::is_valid));
+    }
+
+    #[test]
+    fn read_chunks() {
+        let mut png = Vec::new();
+        let mut encoder = crate::Encoder::new(&mut png, 1, 1);
+        encoder.add_text_chunk("k".into(), "v".into()).unwrap();
+        encoder
+            .write_header()
+            .unwrap()
+            .write_image_data(&[0])
+            .unwrap();
+
+        let chunks: Vec<_> = ChunkReader::new(&png[..]).map(Result::unwrap).collect();
+        let types: Vec<_> = chunks.iter().map(|chunk| chunk.chunk_type).collect();
+        assert_eq!(types, [IHDR, tEXt, IDAT, IEND]);
+        assert_eq!(chunks[1].data, b"k\0v");
+        assert!(chunks.iter().all(|chunk| chunk.crc_ok));
+
+        // Damage the text of the tEXt chunk.
+        let text = png.len() - 12 - (12 + chunks[2].data.len()) - 4 - 1;
+        png[text] ^= 1;
+        let chunks: Vec<_> = ChunkReader::new(&png[..]).map(Result::unwrap).collect();
+        assert!(!chunks[1].crc_ok);
+
+        let mut reader = ChunkReader::new(&png[..png.len() - 1]);
+        assert!(reader.by_ref().take(3).all(|chunk| chunk.is_ok()));
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+        assert!(ChunkReader::new(&png[1..]).next().unwrap().is_err());
+    }
+
+    #[test]
+    fn index_chunks() {
+        let mut png = Vec::new();
+        let mut encoder = crate::Encoder::new(&mut png, 2, 1);
+        encoder.set_animated(2, 0).unwrap();
+        encoder.add_text_chunk("k".into(), "v".into()).unwrap();
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[0, 0]).unwrap();
+        writer.write_image_data(&[1, 1]).unwrap();
+        writer.finish().unwrap();
+
+        let index = ChunkIndex::new(&png).unwrap();
+        let raw: Vec<_> = ChunkReader::new(&png[..]).map(Result::unwrap).collect();
+        assert_eq!(index.chunks().len(), raw.len());
+        for (indexed, raw) in index.chunks().iter().zip(&raw) {
+            assert_eq!(indexed.chunk_type, raw.chunk_type);
+            assert_eq!(indexed.data, &raw.data[..]);
+            assert!(indexed.crc_ok());
+        }
+        assert_eq!(index.find(tEXt).unwrap().data, b"k\0v");
+        assert_eq!(index.find_all(fcTL).count(), 2);
+        assert_eq!(index.frame_count(), 2);
+        let types = |frame: &[IndexedChunk]| frame.iter().map(|c| c.chunk_type).collect::<Vec<_>>();
+        assert_eq!(types(index.frame(0).unwrap()), [fcTL, IDAT]);
+        assert_eq!(types(index.frame(1).unwrap()), [fcTL, fdAT]);
+        assert!(index.frame(2).is_none());
+
+        let ihdr = index.find(IHDR).unwrap();
+        assert_eq!(&png[ihdr.offset + 8..][..13], ihdr.data);
+
+        assert!(ChunkIndex::new(&png[..png.len() - 1]).is_err());
+        assert!(ChunkIndex::new(&png[1..]).is_err());
+        assert!(ChunkIndex::new(&png[..4]).is_err());
+
+        let mut reader = crate::Decoder::from_mmap(&png).read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut buf).unwrap();
+        reader.next_frame(&mut buf).unwrap();
+        assert_eq!(buf, [1, 1]);
+    }
+
+    #[test]
+    fn write_chunks() {
+        let mut writer = ChunkWriter::new(Vec::new());
+        assert!(writer.write_chunk(ChunkType(*b"bad!"), &[]).is_err());
+        writer
+            .write_chunk(IHDR, &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0])
+            .unwrap();
+        writer.write_chunk(ChunkType(*b"prIv"), b"private").unwrap();
+        writer.write_chunk(IEND, &[]).unwrap();
+        let png = writer.into_inner();
+
+        let chunks: Vec<_> = ChunkReader::new(&png[..]).map(Result::unwrap).collect();
+        let types: Vec<_> = chunks.iter().map(|chunk| chunk.chunk_type).collect();
+        assert_eq!(types, [IHDR, ChunkType(*b"prIv"), IEND]);
+        assert_eq!(chunks[1].data, b"private");
+        assert!(chunks.iter().all(|chunk| chunk.crc_ok));
+    }
+
+    #[test]
+    fn chunk_crc() {
+        // The CRC of an IEND chunk is the same in every PNG file.
+        assert_eq!(crc(IEND, &[]), 0xae42_6082);
+        assert!(verify_crc(IEND, &[], 0xae42_6082));
+        assert!(!verify_crc(IEND, &[0], 0xae42_6082));
+
+        let mut png = Vec::new();
+        crate::Encoder::new(&mut png, 1, 1)
+            .write_header()
+            .unwrap()
+            .write_image_data(&[0])
+            .unwrap();
+        for chunk in ChunkReader::new(&png[..]) {
+            let chunk = chunk.unwrap();
+            assert_eq!(crc(chunk.chunk_type, &chunk.data), chunk.crc);
+        }
+    }
+
+    #[test]
+    fn chunk_order() {
+        use self::OrderViolationKind::*;
+
+        let violations = |types: &[ChunkType]| {
+            check_order(types.iter().copied())
+                .violations()
+                .iter()
+                .map(|violation| (violation.index, violation.chunk_type, violation.kind))
+                .collect::<Vec<_>>()
+        };
+
+        assert!(check_order(vec![IHDR, gAMA, PLTE, tRNS, IDAT, IDAT, tEXt, IEND]).is_valid());
+        assert!(check_order(vec![IHDR, acTL, fcTL, IDAT, fcTL, fdAT, IEND]).is_valid());
+
+        assert_eq!(
+            violations(&[IHDR, IDAT, PLTE, gAMA, IEND]),
+            [(2, PLTE, MustPrecede(IDAT)), (3, gAMA, MustPrecede(PLTE))]
+        );
+        assert_eq!(
+            violations(&[IHDR, tRNS, PLTE, IDAT, IEND]),
+            [(2, PLTE, MustPrecede(tRNS))]
+        );
+        assert_eq!(
+            violations(&[IHDR, IHDR, IDAT, tEXt, IDAT, IEND, tEXt]),
+            [
+                (1, IHDR, Duplicate),
+                (4, IDAT, NotConsecutive),
+                (6, tEXt, AfterEnd)
+            ]
+        );
+        assert_eq!(
+            violations(&[IHDR, fdAT, acTL, IDAT, hIST, IEND]),
+            [
+                (1, fdAT, MustFollow(acTL)),
+                (4, hIST, MustPrecede(IDAT)),
+                (4, hIST, MustFollow(PLTE))
+            ]
+        );
+        assert_eq!(
+            violations(&[IHDR, eXIf, PLTE, cICP, sTER, IDAT, sTER, eXIf, IEND]),
+            [
+                (3, cICP, MustPrecede(PLTE)),
+                (6, sTER, Duplicate),
+                (6, sTER, MustPrecede(IDAT)),
+                (7, eXIf, Duplicate)
+            ]
+        );
+        assert_eq!(
+            violations(&[tEXt, IHDR]),
+            [(1, IHDR, NotFirst), (2, IDAT, Missing), (2, IEND, Missing)]
+        );
+        assert_eq!(violations(&[]).len(), 3);
+    }
+}
@@ -1,11 +1,16 @@
 //! Common types shared between the encoder and decoder
-use crate::text_metadata::{EncodableTextChunk, ITXtChunk, TEXtChunk, ZTXtChunk};
-use crate::{chunk, encoder};
+use crate::text_metadata::{
+    EncodableTextChunk, ITXtChunk, KeywordError, StandardKeywords, TEXtChunk, TextChunkRef,
+    ZTXtChunk,
+};
+use crate::{chunk, encoder, DecodingError};
 use io::Write;
-use std::{borrow::Cow, convert::TryFrom, fmt, io};
+use std::time::{Duration, SystemTime};
+use std::{borrow::Cow, convert::TryFrom, fmt, io, num::NonZeroU32};
 
 /// Describes how a pixel is encoded.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum ColorType {
     /// 1 grayscale sample.
@@ -68,20 +73,41 @@ impl ColorType {
         }
     }
 
-    pub(crate) fn is_combination_invalid(self, bit_depth: BitDepth) -> bool {
+    /// Returns whether the specification allows the bit depth for this color type.
+    ///
+    /// ```
+    /// use png::{BitDepth, ColorType};
+    ///
+    /// assert!(ColorType::Indexed.is_combination_allowed(BitDepth::Four));
+    /// assert!(!ColorType::Indexed.is_combination_allowed(BitDepth::Sixteen));
+    /// ```
+    pub fn is_combination_allowed(self, bit_depth: BitDepth) -> bool {
+        self.allowed_bit_depths().contains(&bit_depth)
+    }
+
+    /// The bit depths that the specification allows for this color type, from low to high.
+    ///
+    /// ```
+    /// use png::{BitDepth, ColorType};
+    ///
+    /// assert_eq!(ColorType::Rgba.allowed_bit_depths(), [BitDepth::Eight, BitDepth::Sixteen]);
+    /// ```
+    pub fn allowed_bit_depths(self) -> &'static [BitDepth] {
         // Section 11.2.2 of the PNG standard disallows several combinations
         // of bit depth and color type
-        ((bit_depth == BitDepth::One || bit_depth == BitDepth::Two || bit_depth == BitDepth::Four)
-            && (self == ColorType::Rgb
-                || self == ColorType::GrayscaleAlpha
-                || self == ColorType::Rgba))
-            || (bit_depth == BitDepth::Sixteen && self == ColorType::Indexed)
+        use BitDepth::*;
+        match self {
+            ColorType::Grayscale => &[One, Two, Four, Eight, Sixteen],
+            ColorType::Indexed => &[One, Two, Four, Eight],
+            ColorType::Rgb | ColorType::GrayscaleAlpha | ColorType::Rgba => &[Eight, Sixteen],
+        }
     }
 }
 
 /// Bit depth of the PNG file.
 /// Specifies the number of bits per sample.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum BitDepth {
     One = 1,
@@ -91,13 +117,13 @@ pub enum BitDepth {
     Sixteen = 16,
 }
 
-/// Internal count of bytes per pixel.
+/// Count of bytes per pixel.
 /// This is used for filtering which never uses sub-byte units. This essentially reduces the number
 /// of possible byte chunk lengths to a very small set of values appropriate to be defined as an
-/// enum.
-#[derive(Debug, Clone, Copy)]
+/// enum. Pixels smaller than a byte count as one byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
-pub(crate) enum BytesPerPixel {
+pub enum BytesPerPixel {
     One = 1,
     Two = 2,
     Three = 3,
@@ -126,6 +152,7 @@ impl BitDepth {
 
 /// Pixel dimensions information
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PixelDimensions {
     /// Pixels per unit, X axis
     pub xppu: u32,
@@ -135,7 +162,35 @@ pub struct PixelDimensions {
     pub unit: Unit,
 }
 
+impl PixelDimensions {
+    /// The resolution in dots per inch, X axis then Y axis.
+    ///
+    /// Returns `None` unless the unit is *Meter*.
+    pub fn to_dpi(&self) -> Option<(f64, f64)> {
+        const METERS_PER_INCH: f64 = 0.0254;
+        match self.unit {
+            Unit::Meter => Some((
+                f64::from(self.xppu) * METERS_PER_INCH,
+                f64::from(self.yppu) * METERS_PER_INCH,
+            )),
+            Unit::Unspecified => None,
+        }
+    }
+
+    /// The width of a pixel divided by its height, which is also known for an unspecified unit.
+    ///
+    /// Returns `None` if either axis has no pixels per unit.
+    pub fn aspect_ratio(&self) -> Option<f64> {
+        if self.xppu == 0 || self.yppu == 0 {
+            None
+        } else {
+            Some(f64::from(self.yppu) / f64::from(self.xppu))
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 /// Physical unit of the pixel dimensions
 pub enum Unit {
@@ -156,6 +211,7 @@ impl Unit {
 
 /// How to reset buffer of an animated png (APNG) at the end of a frame.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum DisposeOp {
     /// Leave the buffer unchanged.
@@ -191,6 +247,7 @@ impl fmt::Display for DisposeOp {
 
 /// How pixels are written into the buffer.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum BlendOp {
     /// Pixels overwrite the value at their position.
@@ -221,7 +278,8 @@ impl fmt::Display for BlendOp {
 }
 
 /// Frame control information
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FrameControl {
     /// Sequence number of the animation chunk, starting from 0
     pub sequence_number: u32,
@@ -268,7 +326,32 @@ impl FrameControl {
         self.sequence_number += i;
     }
 
-    pub fn encode<W: Write>(self, w: &mut W) -> encoder::Result<()> {
+    /// Parses the data of an `fcTL` chunk.
+    ///
+    /// Returns `None` if the data does not have the correct length or the dispose or blend
+    /// operation is unknown. The frame is not checked against the image size.
+    pub fn from_bytes(data: &[u8]) -> Option<FrameControl> {
+        if data.len() != 26 {
+            return None;
+        }
+        let be_u32 =
+            |at: usize| u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+        let be_u16 = |at: usize| u16::from_be_bytes([data[at], data[at + 1]]);
+        Some(FrameControl {
+            sequence_number: be_u32(0),
+            width: be_u32(4),
+            height: be_u32(8),
+            x_offset: be_u32(12),
+            y_offset: be_u32(16),
+            delay_num: be_u16(20),
+            delay_den: be_u16(22),
+            dispose_op: DisposeOp::from_u8(data[24])?,
+            blend_op: BlendOp::from_u8(data[25])?,
+        })
+    }
+
+    /// The data of the `fcTL` chunk with this frame control.
+    pub fn to_bytes(&self) -> [u8; 26] {
         let mut data = [0u8; 26];
         data[..4].copy_from_slice(&self.sequence_number.to_be_bytes());
         data[4..8].copy_from_slice(&self.width.to_be_bytes());
@@ -279,13 +362,17 @@ impl FrameControl {
         data[22..24].copy_from_slice(&self.delay_den.to_be_bytes());
         data[24] = self.dispose_op as u8;
         data[25] = self.blend_op as u8;
+        data
+    }
 
-        encoder::write_chunk(w, chunk::fcTL, &data)
+    pub fn encode<W: Write>(self, w: &mut W) -> encoder::Result<()> {
+        encoder::write_chunk(w, chunk::fcTL, &self.to_bytes())
     }
 }
 
 /// Animation control information
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AnimationControl {
     /// Number of frames
     pub num_frames: u32,
@@ -294,16 +381,99 @@ pub struct AnimationControl {
 }
 
 impl AnimationControl {
-    pub fn encode<W: Write>(self, w: &mut W) -> encoder::Result<()> {
+    /// How many times the animation is played.
+    pub fn loops(&self) -> Loops {
+        Loops::from_num_plays(self.num_plays)
+    }
+
+    /// Parses the data of an `acTL` chunk, returning `None` if it does not have the correct length.
+    pub fn from_bytes(data: &[u8]) -> Option<AnimationControl> {
+        if data.len() != 8 {
+            return None;
+        }
+        Some(AnimationControl {
+            num_frames: u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+            num_plays: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+        })
+    }
+
+    /// The data of the `acTL` chunk with this animation control.
+    pub fn to_bytes(&self) -> [u8; 8] {
         let mut data = [0; 8];
         data[..4].copy_from_slice(&self.num_frames.to_be_bytes());
         data[4..].copy_from_slice(&self.num_plays.to_be_bytes());
-        encoder::write_chunk(w, chunk::acTL, &data)
+        data
+    }
+
+    pub fn encode<W: Write>(self, w: &mut W) -> encoder::Result<()> {
+        encoder::write_chunk(w, chunk::acTL, &self.to_bytes())
+    }
+}
+
+/// The contents of an `fdAT` chunk, which holds compressed image data of an animation frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameData<'a> {
+    /// Sequence number of the animation chunk, shared with the `fcTL` chunks.
+    pub sequence_number: u32,
+    /// The compressed image data, continuing that of the previous `fdAT` chunk of the frame.
+    pub data: &'a [u8],
+}
+
+impl<'a> FrameData<'a> {
+    /// Parses the data of an `fdAT` chunk, returning `None` if it has no sequence number.
+    pub fn from_bytes(bytes: &'a [u8]) -> Option<FrameData<'a>> {
+        if bytes.len() < 4 {
+            return None;
+        }
+        Some(FrameData {
+            sequence_number: u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            data: &bytes[4..],
+        })
+    }
+
+    /// The data of the `fdAT` chunk, the sequence number followed by the image data.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(4 + self.data.len());
+        bytes.extend_from_slice(&self.sequence_number.to_be_bytes());
+        bytes.extend_from_slice(self.data);
+        bytes
+    }
+
+    pub fn encode<W: Write>(self, w: &mut W) -> encoder::Result<()> {
+        encoder::write_chunk(w, chunk::fdAT, &self.to_bytes())
+    }
+}
+
+/// How many times an animation is played.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Loops {
+    /// The animation repeats forever.
+    Infinite,
+    /// The animation is played this many times, then stops.
+    Count(NonZeroU32),
+}
+
+impl Loops {
+    /// Interprets the `num_plays` field of the `acTL` chunk, where 0 means infinite.
+    pub fn from_num_plays(num_plays: u32) -> Self {
+        match NonZeroU32::new(num_plays) {
+            Some(count) => Loops::Count(count),
+            None => Loops::Infinite,
+        }
+    }
+
+    /// The value of the `num_plays` field of the `acTL` chunk.
+    pub fn num_plays(self) -> u32 {
+        match self {
+            Loops::Infinite => 0,
+            Loops::Count(count) => count.get(),
+        }
     }
 }
 
 /// The type and strength of applied compression.
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Compression {
     /// Default level
     Default,
@@ -322,6 +492,7 @@ pub enum Compression {
 /// An unsigned integer scaled version of a floating point value,
 /// equivalent to an integer quotient with fixed denominator (100_000)).
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ScaledFloat(u32);
 
 impl ScaledFloat {
@@ -329,7 +500,7 @@ impl ScaledFloat {
 
     /// Gets whether the value is within the clamped range of this type.
     pub fn in_range(value: f32) -> bool {
-        value >= 0.0 && (value * Self::SCALING).floor() <= std::u32::MAX as f32
+        value >= 0.0 && (value * Self::SCALING).floor() <= u32::MAX as f32
     }
 
     /// Gets whether the value can be exactly converted in round-trip.
@@ -351,14 +522,12 @@ impl ScaledFloat {
     /// Slightly inaccurate scaling and quantization.
     /// Clamps the value into the representable range if it is negative or too large.
     pub fn new(value: f32) -> Self {
-        Self {
-            0: Self::forward(value),
-        }
+        Self(Self::forward(value))
     }
 
     /// Fully accurate construction from a value scaled as per specification.
     pub fn from_scaled(val: u32) -> Self {
-        Self { 0: val }
+        Self(val)
     }
 
     /// Get the accurate encoded value.
@@ -368,7 +537,7 @@ impl ScaledFloat {
 
     /// Get the unscaled value as a floating point.
     pub fn into_value(self) -> f32 {
-        Self::reverse(self.0) as f32
+        Self::reverse(self.0)
     }
 
     pub(crate) fn encode_gama<W: Write>(self, w: &mut W) -> encoder::Result<()> {
@@ -376,8 +545,298 @@ impl ScaledFloat {
     }
 }
 
+/// A lookup table applying gamma correction to decoded samples.
+///
+/// The table maps samples encoded with the gamma of the `gAMA` chunk to samples for a display with
+/// another gamma, which is the exponent of its transfer function such as `2.2`. A sample `s` in
+/// `0.0..=1.0` becomes `s.powf(1.0 / (source_gamma * display_gamma))`.
+///
+/// ```
+/// use png::{BitDepth, ColorType, GammaLut, ScaledFloat};
+///
+/// let lut = GammaLut::new(ScaledFloat::new(1.0), 2.0, BitDepth::Eight);
+/// let mut rgba = [0, 64, 255, 64];
+/// lut.apply(ColorType::Rgba, &mut rgba);
+/// assert_eq!(rgba, [0, 128, 255, 64]);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GammaLut {
+    depth: BitDepth,
+    table: Vec<u16>,
+}
+
+impl GammaLut {
+    /// Build the table for samples of a bit depth.
+    ///
+    /// A source gamma of zero, which the specification forbids, builds an identity table.
+    pub fn new(source_gamma: ScaledFloat, display_gamma: f32, depth: BitDepth) -> Self {
+        let max = (1u32 << depth.into_u8()) - 1;
+        let exponent = 1.0 / (f64::from(source_gamma.into_value()) * f64::from(display_gamma));
+        let table = (0..=max)
+            .map(|sample| {
+                if !exponent.is_finite() {
+                    return sample as u16;
+                }
+                let value = (f64::from(sample) / f64::from(max)).powf(exponent);
+                (value * f64::from(max)).round() as u16
+            })
+            .collect();
+        GammaLut { depth, table }
+    }
+
+    /// The bit depth of the samples that the table applies to.
+    pub fn bit_depth(&self) -> BitDepth {
+        self.depth
+    }
+
+    /// The corrected samples, indexed by the decoded sample.
+    pub fn table(&self) -> &[u16] {
+        &self.table
+    }
+
+    /// Whether the table leaves all samples unchanged.
+    pub fn is_identity(&self) -> bool {
+        self.table
+            .iter()
+            .enumerate()
+            .all(|(sample, &value)| usize::from(value) == sample)
+    }
+
+    /// Correct a decoded buffer of the color type and the bit depth of the table in place.
+    ///
+    /// Alpha samples are left unchanged, as are indexed buffers, whose palette can be corrected
+    /// with an 8-bit table as [`ColorType::Rgb`] instead. Samples of 16 bits are big endian.
+    pub fn apply(&self, color_type: ColorType, buf: &mut [u8]) {
+        let samples = color_type.samples();
+        let has_alpha = matches!(color_type, ColorType::GrayscaleAlpha | ColorType::Rgba);
+        let is_color = |index: usize| !has_alpha || index % samples != samples - 1;
+        match self.depth {
+            _ if color_type == ColorType::Indexed => {}
+            BitDepth::Sixteen => {
+                for (index, sample) in buf.chunks_exact_mut(2).enumerate() {
+                    if is_color(index) {
+                        let value =
+                            self.table[usize::from(u16::from_be_bytes([sample[0], sample[1]]))];
+                        sample.copy_from_slice(&value.to_be_bytes());
+                    }
+                }
+            }
+            BitDepth::Eight => {
+                for (index, sample) in buf.iter_mut().enumerate() {
+                    if is_color(index) {
+                        *sample = self.table[usize::from(*sample)] as u8;
+                    }
+                }
+            }
+            depth => {
+                // Only grayscale has samples smaller than a byte, none of them alpha.
+                let bits = depth.into_u8();
+                let mask = (1u8 << bits) - 1;
+                for byte in buf.iter_mut() {
+                    let mut corrected = 0;
+                    for shift in (0..8).step_by(usize::from(bits)) {
+                        let sample = (*byte >> shift) & mask;
+                        corrected |= (self.table[usize::from(sample)] as u8) << shift;
+                    }
+                    *byte = corrected;
+                }
+            }
+        }
+    }
+}
+
+/// The weights of the red, green and blue light in the luminance of [`GrayscaleConverter`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LumaWeights {
+    /// The weights of ITU-R BT.709 for HD video, which match the primaries of sRGB. This is the
+    /// default.
+    Rec709,
+    /// The weights of ITU-R BT.601 for SD video.
+    Rec601,
+}
+
+impl LumaWeights {
+    pub(crate) fn weights(self) -> [f64; 3] {
+        match self {
+            LumaWeights::Rec709 => [0.2126, 0.7152, 0.0722],
+            LumaWeights::Rec601 => [0.299, 0.587, 0.114],
+        }
+    }
+}
+
+impl Default for LumaWeights {
+    fn default() -> Self {
+        LumaWeights::Rec709
+    }
+}
+
+/// Converts decoded RGB and RGBA samples to grayscale in linear light.
+///
+/// The samples are decoded with the gamma of the image, mixed by their luminance and encoded with
+/// the same gamma again, which keeps the brightness of colors unlike averaging their samples. The
+/// gamma is that of the `gAMA` chunk unless [`Info::source_color`] is the sRGB color space or not
+/// calibrated, which use the sRGB transfer function.
+///
+/// ```
+/// use png::{BitDepth, ColorType, GrayscaleConverter, Info, LumaWeights};
+///
+/// let converter = GrayscaleConverter::new(&Info::default(), BitDepth::Eight, LumaWeights::Rec709);
+/// let gray = converter.convert(ColorType::Rgba, &[0, 255, 0, 128]).unwrap();
+/// assert_eq!(gray, [220, 128]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct GrayscaleConverter {
+    depth: BitDepth,
+    weights: [f64; 3],
+    gamma: Option<f64>,
+    linear: Vec<f64>,
+}
+
+impl GrayscaleConverter {
+    /// Create a converter for the samples of an image with 8 or 16 bits per sample.
+    pub fn new(info: &Info<'_>, depth: BitDepth, weights: LumaWeights) -> Self {
+        let gamma = match info.source_color() {
+            SourceColor::Calibrated {
+                gamma: Some(gamma), ..
+            } if gamma.into_scaled() > 0 => Some(f64::from(gamma.into_value())),
+            _ => None,
+        };
+        let max = if depth == BitDepth::Sixteen {
+            u16::MAX
+        } else {
+            u16::from(u8::MAX)
+        };
+        let linear = (0..=max)
+            .map(|sample| {
+                let value = f64::from(sample) / f64::from(max);
+                match gamma {
+                    Some(gamma) => value.powf(1.0 / gamma),
+                    None => crate::srgb::eotf_f64(value),
+                }
+            })
+            .collect();
+        GrayscaleConverter {
+            depth,
+            weights: weights.weights(),
+            gamma,
+            linear,
+        }
+    }
+
+    /// Convert RGB samples to grayscale and RGBA samples to grayscale with alpha.
+    ///
+    /// Grayscale samples are returned unchanged and indexed ones as `None`, since their palette
+    /// has to be expanded first. Samples of 16 bits are big endian.
+    pub fn convert(&self, color_type: ColorType, data: &[u8]) -> Option<Vec<u8>> {
+        let bytes = if self.depth == BitDepth::Sixteen {
+            2
+        } else {
+            1
+        };
+        let channels = match color_type {
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+            ColorType::Grayscale | ColorType::GrayscaleAlpha => return Some(data.to_vec()),
+            ColorType::Indexed => return None,
+        };
+        let max = (self.linear.len() - 1) as f64;
+        let sample = |bytes: &[u8]| match *bytes {
+            [high, low] => usize::from(u16::from_be_bytes([high, low])),
+            _ => usize::from(bytes[0]),
+        };
+
+        let mut gray = Vec::with_capacity(data.len() / channels * (channels - 2));
+        for pixel in data.chunks_exact(channels * bytes) {
+            let luminance: f64 = pixel
+                .chunks_exact(bytes)
+                .zip(&self.weights)
+                .map(|(color, weight)| self.linear[sample(color)] * weight)
+                .sum();
+            let encoded = match self.gamma {
+                Some(gamma) => luminance.powf(gamma),
+                None => crate::srgb::oetf_f64(luminance),
+            };
+            let value = (encoded.max(0.0).min(1.0) * max).round() as u16;
+            if bytes == 2 {
+                gray.extend_from_slice(&value.to_be_bytes());
+            } else {
+                gray.push(value as u8);
+            }
+            if channels == 4 {
+                gray.extend_from_slice(&pixel[3 * bytes..]);
+            }
+        }
+        Some(gray)
+    }
+}
+
+/// Finds the data of a tag in the tag table of an ICC profile.
+fn icc_tag<'a>(profile: &'a [u8], signature: &[u8; 4]) -> Option<&'a [u8]> {
+    let be_u32 = |offset: usize| -> Option<usize> {
+        let bytes = profile.get(offset..offset + 4)?;
+        Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    };
+    let count = be_u32(128)?;
+    (0..count.min(profile.len() / 12)).find_map(|index| {
+        let entry = 132 + 12 * index;
+        if profile.get(entry..entry + 4)? != signature {
+            return None;
+        }
+        let offset = be_u32(entry + 4)?;
+        profile.get(offset..offset.checked_add(be_u32(entry + 8)?)?)
+    })
+}
+
+/// The XYZ values of the red, green and blue colorants of an RGB ICC profile.
+fn icc_colorants(profile: &[u8]) -> Option<[[f64; 3]; 3]> {
+    if profile.get(16..20)? != b"RGB " {
+        return None;
+    }
+    let xyz = |signature: &[u8; 4]| -> Option<[f64; 3]> {
+        let tag = icc_tag(profile, signature)?;
+        if tag.get(..4)? != b"XYZ " || tag.len() < 20 {
+            return None;
+        }
+        let fixed = |offset: usize| {
+            let bytes = [
+                tag[offset],
+                tag[offset + 1],
+                tag[offset + 2],
+                tag[offset + 3],
+            ];
+            f64::from(i32::from_be_bytes(bytes)) / 65536.0
+        };
+        Some([fixed(8), fixed(12), fixed(16)])
+    };
+    let colorants = [xyz(b"rXYZ")?, xyz(b"gXYZ")?, xyz(b"bXYZ")?];
+    let valid = colorants
+        .iter()
+        .all(|xyz| xyz.iter().all(|&value| value >= 0.0) && xyz.iter().sum::<f64>() > 0.0);
+    if valid {
+        Some(colorants)
+    } else {
+        None
+    }
+}
+
+/// The code points of the `cicp` tag of an ICC profile.
+fn icc_cicp(profile: &[u8]) -> Option<CodingIndependentCodePoints> {
+    let tag = icc_tag(profile, b"cicp")?;
+    if tag.get(..4)? != b"cicp" {
+        return None;
+    }
+    CodingIndependentCodePoints::from_bytes(tag.get(8..12)?)
+}
+
+/// The area of the triangle of three chromaticities.
+fn gamut_area(primaries: &[(f64, f64); 3]) -> f64 {
+    let [(rx, ry), (gx, gy), (bx, by)] = *primaries;
+    ((gx - rx) * (by - ry) - (bx - rx) * (gy - ry)).abs() / 2.0
+}
+
 /// Chromaticities of the color space primaries
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SourceChromaticities {
     pub white: (ScaledFloat, ScaledFloat),
     pub red: (ScaledFloat, ScaledFloat),
@@ -420,13 +879,88 @@ impl SourceChromaticities {
     pub fn encode<W: Write>(self, w: &mut W) -> encoder::Result<()> {
         encoder::write_chunk(w, chunk::cHRM, &self.to_be_bytes())
     }
+
+    /// Compute the matrix converting linear RGB values with these primaries to CIE XYZ.
+    ///
+    /// The matrix is in row-major order and maps the white point with `Y = 1` to RGB values of one.
+    /// Returns `None` if the chromaticities do not describe a color space, such as when a `y`
+    /// coordinate is zero or the primaries lie on a line.
+    ///
+    /// ```
+    /// let xyz = png::srgb::substitute_chromaticities().rgb_to_xyz().unwrap();
+    /// assert!((xyz[0][0] - 0.4124).abs() < 1e-3);
+    /// assert!((xyz[1].iter().sum::<f32>() - 1.0).abs() < 1e-6);
+    /// ```
+    pub fn rgb_to_xyz(&self) -> Option<[[f32; 3]; 3]> {
+        let xyz = |(x, y): (ScaledFloat, ScaledFloat)| {
+            let x = f64::from(x.into_value());
+            let y = f64::from(y.into_value());
+            if y == 0.0 {
+                None
+            } else {
+                Some([x / y, 1.0, (1.0 - x - y) / y])
+            }
+        };
+        let red = xyz(self.red)?;
+        let green = xyz(self.green)?;
+        let blue = xyz(self.blue)?;
+        let white = xyz(self.white)?;
+
+        // Solve for the scale of each primary whose sum is the white point, by Cramer's rule.
+        let det = |a: [f64; 3], b: [f64; 3], c: [f64; 3]| {
+            a[0] * (b[1] * c[2] - c[1] * b[2]) - b[0] * (a[1] * c[2] - c[1] * a[2])
+                + c[0] * (a[1] * b[2] - b[1] * a[2])
+        };
+        let primaries = det(red, green, blue);
+        if primaries.abs() < 1e-12 {
+            return None;
+        }
+        let scale = [
+            det(white, green, blue) / primaries,
+            det(red, white, blue) / primaries,
+            det(red, green, white) / primaries,
+        ];
+
+        let mut matrix = [[0.0; 3]; 3];
+        for (row, values) in matrix.iter_mut().enumerate() {
+            for (column, (value, primary)) in values.iter_mut().zip(&[red, green, blue]).enumerate()
+            {
+                *value = (primary[row] * scale[column]) as f32;
+            }
+        }
+        Some(matrix)
+    }
+
+    /// Whether every coordinate differs from those of `other` by at most `tolerance`.
+    pub fn approx_eq(&self, other: &SourceChromaticities, tolerance: f32) -> bool {
+        let coordinates = |c: &SourceChromaticities| {
+            [
+                c.white.0, c.white.1, c.red.0, c.red.1, c.green.0, c.green.1, c.blue.0, c.blue.1,
+            ]
+        };
+        coordinates(self)
+            .iter()
+            .zip(&coordinates(other))
+            .all(|(a, b)| (a.into_value() - b.into_value()).abs() <= tolerance)
+    }
+
+    /// Whether these are the primaries and white point of sRGB, up to a `tolerance` such as
+    /// `0.0001` for the rounding of encoders.
+    pub fn is_srgb(&self, tolerance: f32) -> bool {
+        self.approx_eq(&crate::srgb::substitute_chromaticities(), tolerance)
+    }
 }
 
 /// The rendering intent for an sRGB image.
 ///
 /// Presence of this data also indicates that the image conforms to the sRGB color space.
+///
+/// The intents are numbered like those of ICC profiles, which color management systems such as
+/// Little CMS use as well, see [`SrgbRenderingIntent::from_icc`] and
+/// [`SrgbRenderingIntent::to_icc`].
 #[repr(u8)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SrgbRenderingIntent {
     /// For images preferring good adaptation to the output device gamut at the expense of colorimetric accuracy, such as photographs.
     Perceptual = 0,
@@ -453,13 +987,643 @@ impl SrgbRenderingIntent {
         }
     }
 
+    /// The intent of an ICC rendering intent number, as in the header of a profile.
+    ///
+    /// ```
+    /// use png::SrgbRenderingIntent;
+    ///
+    /// assert_eq!(SrgbRenderingIntent::from_icc(1), Some(SrgbRenderingIntent::RelativeColorimetric));
+    /// assert_eq!(SrgbRenderingIntent::from_icc(4), None);
+    /// ```
+    pub fn from_icc(intent: u32) -> Option<Self> {
+        u8::try_from(intent).ok().and_then(Self::from_raw)
+    }
+
+    /// The ICC rendering intent number of the intent.
+    pub fn to_icc(self) -> u32 {
+        self.into_raw().into()
+    }
+
     pub fn encode<W: Write>(self, w: &mut W) -> encoder::Result<()> {
         encoder::write_chunk(w, chunk::sRGB, &[self.into_raw()])
     }
 }
 
+impl From<SrgbRenderingIntent> for u8 {
+    /// The value of the `sRGB` chunk for the intent.
+    fn from(intent: SrgbRenderingIntent) -> u8 {
+        intent.into_raw()
+    }
+}
+
+impl TryFrom<u8> for SrgbRenderingIntent {
+    type Error = u8;
+
+    /// The intent of the value of an `sRGB` chunk, returning invalid values as error.
+    fn try_from(raw: u8) -> Result<Self, u8> {
+        Self::from_raw(raw).ok_or(raw)
+    }
+}
+
+/// The background color of a `bKGD` chunk, in the samples of the image.
+///
+/// The variant matches the color type: indexed images have a palette index, gray images with or
+/// without alpha a gray sample, and color images an RGB color. Gray and RGB samples have the bit
+/// depth of the image, even for images with fewer than 16 bits.
+///
+/// ```
+/// let decoder = png::Decoder::new(std::fs::File::open("tests/pngsuite/bgyn6a16.png").unwrap());
+/// let reader = decoder.read_info().unwrap();
+/// let yellow = png::BackgroundColor::Rgb(0xffff, 0xffff, 0);
+/// assert_eq!(reader.info().background_color, Some(yellow));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BackgroundColor {
+    /// An index into the palette.
+    Indexed(u8),
+    /// A gray sample.
+    Gray(u16),
+    /// The red, green and blue samples.
+    Rgb(u16, u16, u16),
+}
+
+impl BackgroundColor {
+    /// Parse the data of a `bKGD` chunk of an image with the given color type.
+    pub(crate) fn from_bytes(color_type: ColorType, data: &[u8]) -> Option<Self> {
+        let sample = |index: usize| u16::from_be_bytes([data[2 * index], data[2 * index + 1]]);
+        match (color_type, data.len()) {
+            (ColorType::Indexed, 1) => Some(BackgroundColor::Indexed(data[0])),
+            (ColorType::Grayscale, 2) | (ColorType::GrayscaleAlpha, 2) => {
+                Some(BackgroundColor::Gray(sample(0)))
+            }
+            (ColorType::Rgb, 6) | (ColorType::Rgba, 6) => {
+                Some(BackgroundColor::Rgb(sample(0), sample(1), sample(2)))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The layout of the two views of a stereo image, from an `sTER` chunk.
+///
+/// The left and right eye views are placed side by side and padded to the same width.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StereoLayout {
+    /// The right eye view is on the left, for free viewing with crossed eyes.
+    CrossFuse = 0,
+    /// The left eye view is on the left, for free viewing with diverging eyes.
+    DivergingFuse = 1,
+}
+
+impl StereoLayout {
+    pub(crate) fn into_raw(self) -> u8 {
+        self as u8
+    }
+
+    pub(crate) fn from_raw(raw: u8) -> Option<Self> {
+        match raw {
+            0 => Some(StereoLayout::CrossFuse),
+            1 => Some(StereoLayout::DivergingFuse),
+            _ => None,
+        }
+    }
+
+    pub fn encode<W: Write>(self, w: &mut W) -> encoder::Result<()> {
+        encoder::write_chunk(w, chunk::sTER, &[self.into_raw()])
+    }
+}
+
+/// The time of the last modification of the image, from a `tIME` chunk, in UTC.
+///
+/// The conversions are to and from [`SystemTime`](std::time::SystemTime) only. There are no
+/// conversions for the `OffsetDateTime` of the `time` crate or the `DateTime<Utc>` of `chrono`,
+/// both of which convert from and to `SystemTime` themselves.
+///
+/// ```
+/// use png::ModificationTime;
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let time = ModificationTime::new(2024, 10, 14, 17, 30, 0).unwrap();
+/// let system_time = UNIX_EPOCH + Duration::from_secs(1_728_927_000);
+/// assert_eq!(time.to_system_time(), Some(system_time));
+/// assert_eq!(ModificationTime::from_system_time(system_time), Some(time));
+/// assert_eq!(time.to_string(), "2024-10-14T17:30:00Z");
+/// assert_eq!(ModificationTime::new(2024, 2, 30, 0, 0, 0), None);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ModificationTime {
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+}
+
+impl ModificationTime {
+    /// A date and time, or `None` if a field is out of its range.
+    ///
+    /// The month is from 1 to 12, the day from 1 to the days of the month, the hour from 0 to 23,
+    /// the minute from 0 to 59 and the second from 0 to 60 for a leap second.
+    pub fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Option<Self> {
+        let valid = (1..=12).contains(&month)
+            && day >= 1
+            && day <= crate::text_metadata::days_in_month(year, month)
+            && hour <= 23
+            && minute <= 59
+            && second <= 60;
+        if valid {
+            Some(ModificationTime {
+                year,
+                month,
+                day,
+                hour,
+                minute,
+                second,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// The time of a system clock, or `None` if its year is not representable.
+    pub fn from_system_time(time: SystemTime) -> Option<Self> {
+        let seconds = match time.duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(since) => i64::try_from(since.as_secs()).ok()?,
+            // Times before the epoch are rounded down to the second.
+            Err(err) => {
+                let before = err.duration();
+                let rounded = before.as_secs() + u64::from(before.subsec_nanos() > 0);
+                i64::try_from(rounded).ok()?.checked_neg()?
+            }
+        };
+        let (days, second_of_day) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+        let (year, month, day) = civil_from_days(days);
+        ModificationTime::new(
+            u16::try_from(year).ok()?,
+            month,
+            day,
+            (second_of_day / 3600) as u8,
+            (second_of_day / 60 % 60) as u8,
+            (second_of_day % 60) as u8,
+        )
+    }
+
+    /// The time on a system clock, or `None` if it is not representable.
+    ///
+    /// A leap second is the first second of the next minute.
+    pub fn to_system_time(&self) -> Option<SystemTime> {
+        let days = days_from_civil(i64::from(self.year), self.month, self.day);
+        let seconds = days * 86_400
+            + i64::from(self.hour) * 3600
+            + i64::from(self.minute) * 60
+            + i64::from(self.second);
+        if seconds >= 0 {
+            SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(seconds as u64))
+        } else {
+            SystemTime::UNIX_EPOCH.checked_sub(Duration::from_secs((-seconds) as u64))
+        }
+    }
+
+    /// The year, with all its digits.
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+
+    /// The month, from 1 to 12.
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+
+    /// The day of the month, starting at 1.
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+
+    /// The hour, from 0 to 23.
+    pub fn hour(&self) -> u8 {
+        self.hour
+    }
+
+    /// The minute, from 0 to 59.
+    pub fn minute(&self) -> u8 {
+        self.minute
+    }
+
+    /// The second, from 0 to 60 for a leap second.
+    pub fn second(&self) -> u8 {
+        self.second
+    }
+
+    pub(crate) fn from_bytes(data: &[u8]) -> Option<Self> {
+        match *data {
+            [year_high, year_low, month, day, hour, minute, second] => {
+                let year = u16::from_be_bytes([year_high, year_low]);
+                ModificationTime::new(year, month, day, hour, minute, second)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn encode<W: Write>(self, w: &mut W) -> encoder::Result<()> {
+        let [year_high, year_low] = self.year.to_be_bytes();
+        let data = [
+            year_high,
+            year_low,
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+            self.second,
+        ];
+        encoder::write_chunk(w, chunk::tIME, &data)
+    }
+}
+
+impl fmt::Display for ModificationTime {
+    /// Formats the time in ISO 8601, such as `2024-10-14T17:30:00Z`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// The days since 1970-01-01 of a date, counting the leap years from March 1st.
+fn days_from_civil(year: i64, month: u8, day: u8) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month + 9) % 12;
+    let day_of_year = (153 * month + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The date of a number of days since 1970-01-01, the inverse of [`days_from_civil`].
+fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month + 2) / 5 + 1) as u8;
+    let month = if month < 10 { month + 3 } else { month - 9 } as u8;
+    (year_of_era + era * 400 + i64::from(month <= 2), month, day)
+}
+
+/// The video signal type of the `cICP` chunk, as code points of ITU-T H.273.
+///
+/// This identifies the color space of HDR images, such as those with the perceptual quantizer
+/// (PQ) or hybrid log-gamma (HLG) transfer function, and overrides the other color space chunks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CodingIndependentCodePoints {
+    /// The color primaries, such as 1 for BT.709 and sRGB or 9 for BT.2020.
+    pub color_primaries: u8,
+    /// The transfer function, such as 13 for sRGB, 16 for PQ or 18 for HLG.
+    pub transfer_function: u8,
+    /// The matrix coefficients, which are 0 for the RGB samples of PNG.
+    pub matrix_coefficients: u8,
+    /// Whether the samples use their full range instead of the narrow range of video.
+    pub is_video_full_range_image: bool,
+}
+
+impl CodingIndependentCodePoints {
+    /// The transfer function code point of the perceptual quantizer of SMPTE ST 2084.
+    pub const PQ: u8 = 16;
+    /// The transfer function code point of the hybrid log-gamma of ARIB STD-B67.
+    pub const HLG: u8 = 18;
+
+    /// Parses the data of a `cICP` chunk, returning `None` if it does not have the correct length
+    /// or the full range flag is neither 0 nor 1.
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        match *data {
+            [color_primaries, transfer_function, matrix_coefficients, full_range @ 0..=1] => {
+                Some(CodingIndependentCodePoints {
+                    color_primaries,
+                    transfer_function,
+                    matrix_coefficients,
+                    is_video_full_range_image: full_range == 1,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// The data of the `cICP` chunk with these code points.
+    pub fn to_bytes(&self) -> [u8; 4] {
+        [
+            self.color_primaries,
+            self.transfer_function,
+            self.matrix_coefficients,
+            self.is_video_full_range_image as u8,
+        ]
+    }
+
+    /// Whether the transfer function is PQ or HLG.
+    pub fn is_hdr(&self) -> bool {
+        matches!(self.transfer_function, Self::PQ | Self::HLG)
+    }
+
+    pub fn encode<W: Write>(self, w: &mut W) -> encoder::Result<()> {
+        encoder::write_chunk(w, chunk::cICP, &self.to_bytes())
+    }
+}
+
+/// The color space of an image, resolved from its color space chunks by [`Info::source_color`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum SourceColor<'a> {
+    /// The video signal type of the `cICP` chunk.
+    Cicp(CodingIndependentCodePoints),
+    /// The decompressed ICC profile of the `iCCP` chunk.
+    IccProfile(&'a [u8]),
+    /// The sRGB color space with the rendering intent of the `sRGB` chunk.
+    Srgb(SrgbRenderingIntent),
+    /// The gamma of the `gAMA` chunk and the chromaticities of the `cHRM` chunk, at least one of
+    /// which is present.
+    Calibrated {
+        gamma: Option<ScaledFloat>,
+        chromaticities: Option<SourceChromaticities>,
+    },
+    /// The image does not describe its color space, which is commonly treated as sRGB.
+    Unknown,
+}
+
+/// A kind of color space description, as ordered by [`ColorPrecedence`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ColorChunk {
+    /// The `cICP` chunk.
+    Cicp,
+    /// The `iCCP` chunk.
+    IccProfile,
+    /// The `sRGB` chunk.
+    Srgb,
+    /// The `gAMA` and `cHRM` chunks.
+    Calibrated,
+}
+
+/// The order in which [`Info::source_color`] considers the color space chunks.
+///
+/// ```
+/// use png::{ColorChunk, ColorPrecedence};
+///
+/// let legacy = ColorPrecedence::new(&[ColorChunk::IccProfile, ColorChunk::Srgb]);
+/// assert_eq!(legacy.order(), [ColorChunk::IccProfile, ColorChunk::Srgb]);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ColorPrecedence {
+    order: [ColorChunk; 4],
+    len: u8,
+}
+
+impl ColorPrecedence {
+    /// The precedence of the specification: `cICP`, `iCCP`, `sRGB`, then `gAMA` and `cHRM`.
+    pub const SPECIFICATION: ColorPrecedence = ColorPrecedence {
+        order: [
+            ColorChunk::Cicp,
+            ColorChunk::IccProfile,
+            ColorChunk::Srgb,
+            ColorChunk::Calibrated,
+        ],
+        len: 4,
+    };
+
+    /// The precedence of decoders predating the `cICP` chunk, which prefer an ICC profile.
+    pub const ICC_FIRST: ColorPrecedence = ColorPrecedence {
+        order: [
+            ColorChunk::IccProfile,
+            ColorChunk::Cicp,
+            ColorChunk::Srgb,
+            ColorChunk::Calibrated,
+        ],
+        len: 4,
+    };
+
+    /// Consider the chunks in the given order, ignoring repeated ones and those not listed.
+    pub fn new(order: &[ColorChunk]) -> Self {
+        let mut precedence = ColorPrecedence {
+            order: Self::SPECIFICATION.order,
+            len: 0,
+        };
+        for &chunk in order {
+            if !precedence.order().contains(&chunk) {
+                precedence.order[usize::from(precedence.len)] = chunk;
+                precedence.len += 1;
+            }
+        }
+        precedence
+    }
+
+    /// The chunks that are considered, from the highest precedence to the lowest.
+    pub fn order(&self) -> &[ColorChunk] {
+        &self.order[..usize::from(self.len)]
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for ColorPrecedence {
+    /// Serialize the chunks in their order.
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.order().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ColorPrecedence {
+    /// Deserialize the chunks in their order, see [`ColorPrecedence::new`].
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let order = Vec::<ColorChunk>::deserialize(deserializer)?;
+        Ok(ColorPrecedence::new(&order))
+    }
+}
+
+impl Default for ColorPrecedence {
+    fn default() -> Self {
+        ColorPrecedence::SPECIFICATION
+    }
+}
+
+/// A pair of `dSIG` chunks, which sign the chunks between them.
+///
+/// The opening chunk follows the `IHDR` chunk and the closing chunk precedes the `IEND` chunk,
+/// with the pairs nested into each other. Their data is kept as is, this crate does neither create
+/// nor verify signatures.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DigitalSignature {
+    /// The data of the opening chunk.
+    pub opening: Vec<u8>,
+    /// The data of the closing chunk.
+    ///
+    /// This is `None` while decoding until the closing chunk after the image data has been read.
+    pub closing: Option<Vec<u8>>,
+}
+
+/// The wall time spent in each phase of decoding or encoding an image.
+///
+/// The timings are collected after [`Decoder::set_phase_timings`](crate::Decoder::set_phase_timings)
+/// or [`Encoder::set_phase_timings`](crate::Encoder::set_phase_timings) and returned by
+/// [`Reader::phase_timings`](crate::Reader::phase_timings) and
+/// [`Writer::phase_timings`](crate::Writer::phase_timings). The phases of the other direction stay
+/// zero, as do all phases on `wasm32-unknown-unknown`, which has no clock.
+///
+/// ```
+/// let mut decoder = png::Decoder::new(std::fs::File::open("tests/pngsuite/basi2c08.png").unwrap());
+/// decoder.set_phase_timings(true);
+/// let mut reader = decoder.read_info().unwrap();
+/// let mut buf = vec![0; reader.output_buffer_size()];
+/// reader.next_frame(&mut buf).unwrap();
+/// let timings = reader.phase_timings().unwrap();
+/// println!("{:?} decompressing of {:?} in total", timings.inflate, timings.total());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PhaseTimings {
+    /// Reading the input of the decoder.
+    pub read: Duration,
+    /// Decompressing the image data.
+    pub inflate: Duration,
+    /// Reversing the filters of the rows.
+    pub unfilter: Duration,
+    /// Applying the [`Transformations`] and compositing over a background.
+    pub expand: Duration,
+    /// Converting rows with a [`ColorTransform`](crate::ColorTransform) or into linear light.
+    pub color_transform: Duration,
+    /// Filtering the rows to encode.
+    pub filter: Duration,
+    /// Compressing the filtered rows.
+    pub deflate: Duration,
+    /// Writing the output of the encoder.
+    pub write: Duration,
+}
+
+impl PhaseTimings {
+    /// The sum of all phases.
+    pub fn total(&self) -> Duration {
+        self.read
+            + self.inflate
+            + self.unfilter
+            + self.expand
+            + self.color_transform
+            + self.filter
+            + self.deflate
+            + self.write
+    }
+}
+
+/// The entries of a `PLTE` chunk, with the alpha of the `tRNS` chunk of an indexed image.
+///
+/// ```
+/// let decoder = png::Decoder::new(std::fs::File::open("tests/pngsuite/tbbn3p08.png").unwrap());
+/// let reader = decoder.read_info().unwrap();
+/// let palette = reader.info().palette_colors().unwrap();
+/// for (index, [red, green, blue, alpha]) in palette.iter_rgba().enumerate() {
+///     assert_eq!(palette.get(index), Some([red, green, blue]));
+///     assert_eq!(palette.alpha(index), Some(alpha));
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette<'a> {
+    rgb: &'a [u8],
+    alpha: &'a [u8],
+}
+
+impl<'a> Palette<'a> {
+    /// A palette of RGB triples, with the alpha of the entries of a `tRNS` chunk.
+    ///
+    /// Entries without alpha are opaque, and a trailing partial entry is ignored.
+    pub fn new(rgb: &'a [u8], trns: Option<&'a [u8]>) -> Self {
+        let rgb = &rgb[..rgb.len() / 3 * 3];
+        let alpha = trns.unwrap_or(&[]);
+        Palette {
+            rgb,
+            alpha: &alpha[..alpha.len().min(rgb.len() / 3)],
+        }
+    }
+
+    /// The number of entries.
+    pub fn len(&self) -> usize {
+        self.rgb.len() / 3
+    }
+
+    /// Whether the palette has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.rgb.is_empty()
+    }
+
+    /// Whether any entry is not opaque.
+    pub fn has_alpha(&self) -> bool {
+        self.alpha.iter().any(|&alpha| alpha != 0xff)
+    }
+
+    /// The color of an entry.
+    pub fn get(&self, index: usize) -> Option<[u8; 3]> {
+        let rgb = self.rgb.get(3 * index..3 * index + 3)?;
+        Some([rgb[0], rgb[1], rgb[2]])
+    }
+
+    /// The alpha of an entry.
+    pub fn alpha(&self, index: usize) -> Option<u8> {
+        if index < self.len() {
+            Some(self.alpha.get(index).copied().unwrap_or(0xff))
+        } else {
+            None
+        }
+    }
+
+    /// The color and alpha of an entry.
+    pub fn get_rgba(&self, index: usize) -> Option<[u8; 4]> {
+        let [red, green, blue] = self.get(index)?;
+        Some([red, green, blue, self.alpha(index)?])
+    }
+
+    /// Iterate over the colors of the entries.
+    pub fn iter(&self) -> impl Iterator<Item = [u8; 3]> + 'a {
+        self.rgb.chunks_exact(3).map(|rgb| [rgb[0], rgb[1], rgb[2]])
+    }
+
+    /// Iterate over the colors and alpha of the entries.
+    pub fn iter_rgba(&self) -> impl Iterator<Item = [u8; 4]> + 'a {
+        let alpha = self.alpha;
+        self.iter()
+            .enumerate()
+            .map(move |(index, [red, green, blue])| {
+                [red, green, blue, alpha.get(index).copied().unwrap_or(0xff)]
+            })
+    }
+
+    /// The raw RGB triples.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.rgb
+    }
+}
+
+/// The transparency of a `tRNS` chunk, by the color type of the image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transparency<'a> {
+    /// The alpha of the first entries of the palette, the others are opaque.
+    PaletteAlpha(&'a [u8]),
+    /// The gray sample that is fully transparent.
+    GrayKey(u16),
+    /// The red, green and blue samples of the color that is fully transparent.
+    RgbKey(u16, u16, u16),
+}
+
 /// PNG info struct
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 #[non_exhaustive]
 pub struct Info<'a> {
     pub width: u32,
@@ -486,12 +1650,49 @@ pub struct Info<'a> {
     pub srgb: Option<SrgbRenderingIntent>,
     /// The ICC profile for the image.
     pub icc_profile: Option<Cow<'a, [u8]>>,
+    /// The Exif data of the `eXIf` chunk, starting with the byte order mark of its TIFF header.
+    pub exif_metadata: Option<Cow<'a, [u8]>>,
     /// tEXt field
     pub uncompressed_latin1_text: Vec<TEXtChunk>,
     /// zTXt field
     pub compressed_latin1_text: Vec<ZTXtChunk>,
     /// iTXt field
     pub utf8_text: Vec<ITXtChunk>,
+    /// The pairs of `dSIG` chunks, from the outermost to the innermost one.
+    pub signatures: Vec<DigitalSignature>,
+    /// The layout of a stereo image, if it has an `sTER` chunk.
+    pub stereo_layout: Option<StereoLayout>,
+    /// The video signal type of the `cICP` chunk.
+    pub coding_independent_code_points: Option<CodingIndependentCodePoints>,
+    /// The time of the last modification of the `tIME` chunk.
+    ///
+    /// A chunk with a field out of its range is ignored by the decoder.
+    pub modification_time: Option<ModificationTime>,
+    /// The background color of the `bKGD` chunk.
+    ///
+    /// A palette index outside of the palette is ignored by the decoder.
+    pub background_color: Option<BackgroundColor>,
+    /// The order in which [`Info::source_color`] considers the color space chunks, which is set
+    /// by [`Decoder::set_color_precedence`](crate::Decoder::set_color_precedence).
+    pub color_precedence: ColorPrecedence,
+    /// The raw `gIFg`, `gIFx` and `fRAc` chunks, which are kept as is for conversions from GIF.
+    pub legacy_chunks: Vec<(chunk::ChunkType, Vec<u8>)>,
+    /// The keywords of decoded text chunks that violate the rules of the specification, when
+    /// checked with [`KeywordCheck::Warn`](crate::text_metadata::KeywordCheck::Warn).
+    pub keyword_warnings: Vec<(String, KeywordError)>,
+    /// Every chunk read so far including `IHDR`, in the order of the file, when enabled with
+    /// [`Decoder::set_chunk_inventory`](crate::Decoder::set_chunk_inventory).
+    ///
+    /// Unlike the other fields this covers the image data, unknown chunks and chunks that were
+    /// skipped because of an error.
+    pub chunk_inventory: Vec<chunk::ChunkRecord>,
+    /// The types of the decoded text chunks in the order of the file.
+    pub(crate) text_order: Vec<chunk::ChunkType>,
+    /// The data of the `sBIT` chunk set by [`Encoder::set_significant_bits`], the decoder does
+    /// not read this chunk.
+    ///
+    /// [`Encoder::set_significant_bits`]: crate::Encoder::set_significant_bits
+    pub(crate) significant_bits: Option<Vec<u8>>,
 }
 
 impl Default for Info<'_> {
@@ -514,9 +1715,21 @@ impl Default for Info<'_> {
             source_chromaticities: None,
             srgb: None,
             icc_profile: None,
+            exif_metadata: None,
             uncompressed_latin1_text: Vec::new(),
             compressed_latin1_text: Vec::new(),
             utf8_text: Vec::new(),
+            signatures: Vec::new(),
+            stereo_layout: None,
+            coding_independent_code_points: None,
+            modification_time: None,
+            background_color: None,
+            color_precedence: ColorPrecedence::default(),
+            legacy_chunks: Vec::new(),
+            keyword_warnings: Vec::new(),
+            chunk_inventory: Vec::new(),
+            text_order: Vec::new(),
+            significant_bits: None,
         }
     }
 }
@@ -531,6 +1744,49 @@ impl Info<'_> {
         }
     }
 
+    /// Clone the info without its large payloads, for example to pass it to another thread.
+    ///
+    /// The ICC profile, the Exif data, the text chunks, the digital signatures and the legacy GIF
+    /// chunks are left out, so that [`Info::source_color`] no longer sees the profile. The palette
+    /// and the `tRNS` chunk, which are at most a few hundred bytes, are copied so that the result
+    /// does not borrow from the original.
+    pub fn clone_metadata(&self) -> Info<'static> {
+        let owned =
+            |data: &Option<Cow<'_, [u8]>>| data.as_deref().map(|data| Cow::Owned(data.to_vec()));
+        Info {
+            width: self.width,
+            height: self.height,
+            bit_depth: self.bit_depth,
+            color_type: self.color_type,
+            interlaced: self.interlaced,
+            palette: owned(&self.palette),
+            trns: owned(&self.trns),
+            pixel_dims: self.pixel_dims,
+            source_gamma: self.source_gamma,
+            frame_control: self.frame_control,
+            animation_control: self.animation_control,
+            compression: self.compression,
+            source_chromaticities: self.source_chromaticities,
+            srgb: self.srgb,
+            icc_profile: None,
+            exif_metadata: None,
+            uncompressed_latin1_text: Vec::new(),
+            compressed_latin1_text: Vec::new(),
+            utf8_text: Vec::new(),
+            signatures: Vec::new(),
+            stereo_layout: self.stereo_layout,
+            coding_independent_code_points: self.coding_independent_code_points,
+            modification_time: self.modification_time,
+            background_color: self.background_color,
+            color_precedence: self.color_precedence,
+            legacy_chunks: Vec::new(),
+            keyword_warnings: self.keyword_warnings.clone(),
+            chunk_inventory: self.chunk_inventory.clone(),
+            text_order: Vec::new(),
+            significant_bits: self.significant_bits.clone(),
+        }
+    }
+
     /// Size of the image, width then height.
     pub fn size(&self) -> (u32, u32) {
         (self.width, self.height)
@@ -541,6 +1797,23 @@ impl Info<'_> {
         self.frame_control.is_some() && self.animation_control.is_some()
     }
 
+    /// Reads the color space, the rendering intent and the description of [`Info::icc_profile`].
+    ///
+    /// Returns `None` if the image has no profile or its header is invalid.
+    pub fn icc_summary(&self) -> Option<crate::icc::IccSummary> {
+        self.icc_profile
+            .as_deref()
+            .and_then(crate::icc::IccSummary::parse)
+    }
+
+    /// Reads the orientation, the timestamps and the GPS position from [`Info::exif_metadata`].
+    ///
+    /// Returns `None` if the image has no `eXIf` chunk.
+    #[cfg(feature = "exif")]
+    pub fn parsed_exif(&self) -> Option<Result<crate::exif::Exif, crate::exif::ExifError>> {
+        self.exif_metadata.as_deref().map(crate::exif::Exif::parse)
+    }
+
     /// Returns the frame control information of the image.
     pub fn animation_control(&self) -> Option<&AnimationControl> {
         self.animation_control.as_ref()
@@ -551,6 +1824,257 @@ impl Info<'_> {
         self.frame_control.as_ref()
     }
 
+    /// Returns the transparency of the `tRNS` chunk, interpreted by the color type.
+    ///
+    /// The samples of a key have the bit depth of the image. Returns `None` if there is no
+    /// `tRNS` chunk, or if it is too short or not allowed for the color type.
+    pub fn transparency(&self) -> Option<Transparency<'_>> {
+        let trns = self.trns.as_deref()?;
+        let samples = match self.color_type {
+            ColorType::Indexed => return Some(Transparency::PaletteAlpha(trns)),
+            ColorType::Grayscale => 1,
+            ColorType::Rgb => 3,
+            _ => return None,
+        };
+        // The decoder keeps a single byte per sample for depths below 16 bits.
+        let narrow = trns.len() < 2 * samples && self.bit_depth != BitDepth::Sixteen;
+        let sample = |index: usize| -> Option<u16> {
+            if narrow {
+                trns.get(index).copied().map(u16::from)
+            } else {
+                let bytes = trns.get(2 * index..2 * index + 2)?;
+                Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+            }
+        };
+        match samples {
+            1 => sample(0).map(Transparency::GrayKey),
+            _ => Some(Transparency::RgbKey(sample(0)?, sample(1)?, sample(2)?)),
+        }
+    }
+
+    /// Returns the resolution of the `pHYs` chunk in dots per inch, X axis then Y axis.
+    ///
+    /// See [`PixelDimensions::to_dpi`].
+    ///
+    /// ```
+    /// let decoder = png::Decoder::new(std::fs::File::open("tests/pngsuite/cdun2c08.png").unwrap());
+    /// let reader = decoder.read_info().unwrap();
+    /// let (x, y) = reader.info().dpi().unwrap();
+    /// assert_eq!((x.round(), y.round()), (25.0, 25.0));
+    /// ```
+    pub fn dpi(&self) -> Option<(f64, f64)> {
+        self.pixel_dims?.to_dpi()
+    }
+
+    /// Returns the width of a pixel divided by its height from the `pHYs` chunk.
+    ///
+    /// See [`PixelDimensions::aspect_ratio`].
+    pub fn pixel_aspect_ratio(&self) -> Option<f64> {
+        self.pixel_dims?.aspect_ratio()
+    }
+
+    /// Returns the palette, with the alpha of the `tRNS` chunk for indexed images.
+    ///
+    /// Images of other color types may have a suggested palette, but their `tRNS` chunk is a
+    /// color key and not part of the palette.
+    pub fn palette_colors(&self) -> Option<Palette<'_>> {
+        let trns = match self.color_type {
+            ColorType::Indexed => self.trns.as_deref(),
+            _ => None,
+        };
+        Some(Palette::new(self.palette.as_deref()?, trns))
+    }
+
+    /// Returns the color space of the image by the precedence of its color space chunks.
+    ///
+    /// By default the `cICP` chunk takes precedence over the `iCCP` chunk, which takes precedence
+    /// over the `sRGB` chunk, which in turn overrides the `gAMA` and `cHRM` chunks. Another order
+    /// can be set in [`Info::color_precedence`].
+    ///
+    /// ```
+    /// let decoder = png::Decoder::new(std::fs::File::open("tests/pngsuite/g04n2c08.png").unwrap());
+    /// let reader = decoder.read_info().unwrap();
+    /// match reader.info().source_color() {
+    ///     png::SourceColor::Calibrated { gamma, .. } => {
+    ///         assert_eq!(gamma, Some(png::ScaledFloat::from_scaled(45000)))
+    ///     }
+    ///     other => panic!("unexpected color space {:?}", other),
+    /// }
+    /// ```
+    pub fn source_color(&self) -> SourceColor<'_> {
+        self.color_precedence
+            .order()
+            .iter()
+            .find_map(|chunk| match chunk {
+                ColorChunk::Cicp => self.coding_independent_code_points.map(SourceColor::Cicp),
+                ColorChunk::IccProfile => self.icc_profile.as_deref().map(SourceColor::IccProfile),
+                ColorChunk::Srgb => self.srgb.map(SourceColor::Srgb),
+                ColorChunk::Calibrated => {
+                    if self.source_gamma.is_none() && self.source_chromaticities.is_none() {
+                        return None;
+                    }
+                    Some(SourceColor::Calibrated {
+                        gamma: self.source_gamma,
+                        chromaticities: self.source_chromaticities,
+                    })
+                }
+            })
+            .unwrap_or(SourceColor::Unknown)
+    }
+
+    /// Returns the text of the first text chunk with the given keyword.
+    ///
+    /// The `tEXt`, `zTXt` and `iTXt` chunks are searched in that order, see
+    /// [`Info::texts_matching`].
+    pub fn text_value(&self, keyword: &str) -> Result<Option<String>, DecodingError> {
+        self.texts(keyword).next().transpose()
+    }
+
+    /// Returns the texts of all text chunks with the given keyword.
+    ///
+    /// The `tEXt` chunks come first, followed by the `zTXt` and `iTXt` chunks, as the order of
+    /// chunks of different kinds is not kept. Compressed texts are decompressed up to their limit,
+    /// see [`ZTXtChunk::get_text`].
+    pub fn texts_matching(&self, keyword: &str) -> Result<Vec<String>, DecodingError> {
+        self.texts(keyword).collect()
+    }
+
+    /// Returns all text chunks, in the order in which they were decoded.
+    ///
+    /// Chunks that were added to the lists of text chunks afterwards follow, first the `tEXt`,
+    /// then the `zTXt` and finally the `iTXt` chunks. This order is also used when encoding.
+    pub fn text_chunks(&self) -> impl Iterator<Item = TextChunkRef<'_>> {
+        let mut text = self.uncompressed_latin1_text.iter();
+        let mut ztxt = self.compressed_latin1_text.iter();
+        let mut itxt = self.utf8_text.iter();
+        let mut decoded: Vec<TextChunkRef<'_>> = Vec::new();
+        for &chunk_type in &self.text_order {
+            let next = match chunk_type {
+                chunk::tEXt => text.next().map(TextChunkRef::Uncompressed),
+                chunk::zTXt => ztxt.next().map(TextChunkRef::Compressed),
+                _ => itxt.next().map(TextChunkRef::International),
+            };
+            decoded.extend(next);
+        }
+        decoded
+            .into_iter()
+            .chain(text.map(TextChunkRef::Uncompressed))
+            .chain(ztxt.map(TextChunkRef::Compressed))
+            .chain(itxt.map(TextChunkRef::International))
+    }
+
+    /// Returns whether the image has a wider color gamut than sRGB.
+    ///
+    /// This is a heuristic on the color space of [`Info::source_color`]: the color primaries of
+    /// the `cICP` chunk are BT.2020, XYZ or one of the P3 variants, or the triangle of the
+    /// primaries of the `cHRM` chunk or of the colorants of an RGB ICC profile is noticeably
+    /// larger than that of sRGB.
+    pub fn is_wide_gamut(&self) -> bool {
+        // The primaries of sRGB, also adapted to the D50 white of ICC profiles.
+        const SRGB: [(f64, f64); 3] = [(0.64, 0.33), (0.30, 0.60), (0.15, 0.06)];
+        const SRGB_D50: [[f64; 3]; 3] = [
+            [0.4361, 0.2225, 0.0139],
+            [0.3851, 0.7169, 0.0971],
+            [0.1431, 0.0606, 0.7141],
+        ];
+        match self.source_color() {
+            SourceColor::Cicp(cicp) => matches!(cicp.color_primaries, 9..=12),
+            SourceColor::IccProfile(profile) => {
+                let xy = |[x, y, z]: [f64; 3]| (x / (x + y + z), y / (x + y + z));
+                let reference = gamut_area(&[xy(SRGB_D50[0]), xy(SRGB_D50[1]), xy(SRGB_D50[2])]);
+                icc_colorants(profile).map_or(false, |colorants| {
+                    let primaries = [xy(colorants[0]), xy(colorants[1]), xy(colorants[2])];
+                    gamut_area(&primaries) > 1.05 * reference
+                })
+            }
+            SourceColor::Calibrated {
+                chromaticities: Some(chromaticities),
+                ..
+            } => {
+                let xy = |(x, y): (ScaledFloat, ScaledFloat)| {
+                    (f64::from(x.into_value()), f64::from(y.into_value()))
+                };
+                let primaries = [
+                    xy(chromaticities.red),
+                    xy(chromaticities.green),
+                    xy(chromaticities.blue),
+                ];
+                gamut_area(&primaries) > 1.05 * gamut_area(&SRGB)
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns whether the image has a high dynamic range.
+    ///
+    /// This holds if the transfer function of the `cICP` chunk, or that of the `cicp` tag of an
+    /// ICC profile, is PQ or HLG, see [`Info::source_color`].
+    pub fn is_hdr(&self) -> bool {
+        match self.source_color() {
+            SourceColor::Cicp(cicp) => cicp.is_hdr(),
+            SourceColor::IccProfile(profile) => {
+                icc_cicp(profile).map_or(false, |cicp| cicp.is_hdr())
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the texts with the keywords registered by the specification.
+    pub fn standard_keywords(&self) -> StandardKeywords<'_> {
+        StandardKeywords::new(self)
+    }
+
+    /// Returns the `iTXt` chunk with the given keyword that best matches a language tag.
+    ///
+    /// The language tags are compared without regard to case, as in [BCP 47]. A chunk with exactly
+    /// the requested tag is preferred, then one with the same primary language subtag, such as
+    /// `en-GB` for a requested `en-US`, and finally one without a language tag.
+    ///
+    /// [BCP 47]: https://www.rfc-editor.org/info/bcp47
+    pub fn localized_text(&self, keyword: &str, language_tag: &str) -> Option<&ITXtChunk> {
+        fn primary_subtag(tag: &str) -> &str {
+            tag.split('-').next().unwrap_or(tag)
+        }
+
+        let candidates = || {
+            self.utf8_text
+                .iter()
+                .filter(move |chunk| chunk.keyword == keyword)
+        };
+        candidates()
+            .find(|chunk| chunk.language_tag.eq_ignore_ascii_case(language_tag))
+            .or_else(|| {
+                let primary = primary_subtag(language_tag);
+                candidates().find(|chunk| {
+                    !primary.is_empty()
+                        && primary_subtag(&chunk.language_tag).eq_ignore_ascii_case(primary)
+                })
+            })
+            .or_else(|| candidates().find(|chunk| chunk.language_tag.is_empty()))
+    }
+
+    fn texts<'s>(
+        &'s self,
+        keyword: &'s str,
+    ) -> impl Iterator<Item = Result<String, DecodingError>> + 's {
+        let uncompressed = self
+            .uncompressed_latin1_text
+            .iter()
+            .filter(move |chunk| chunk.keyword == keyword)
+            .map(|chunk| Ok(chunk.text.clone()));
+        let compressed = self
+            .compressed_latin1_text
+            .iter()
+            .filter(move |chunk| chunk.keyword == keyword)
+            .map(ZTXtChunk::get_text);
+        let utf8 = self
+            .utf8_text
+            .iter()
+            .filter(move |chunk| chunk.keyword == keyword)
+            .map(ITXtChunk::get_text);
+        uncompressed.chain(compressed).chain(utf8)
+    }
+
     /// Returns the number of bits per pixel.
     pub fn bits_per_pixel(&self) -> usize {
         self.color_type.samples() * self.bit_depth as usize
@@ -592,7 +2116,16 @@ impl Info<'_> {
         self.raw_row_length_from_width(self.width)
     }
 
-    pub(crate) fn checked_raw_row_length(&self) -> Option<usize> {
+    /// Returns the number of bytes needed for one deinterlaced image, like [`Info::raw_bytes`],
+    /// or `None` if it does not fit into `usize`.
+    pub fn checked_raw_bytes(&self) -> Option<usize> {
+        let height: usize = TryFrom::try_from(self.height).ok()?;
+        self.checked_raw_row_length()?.checked_mul(height)
+    }
+
+    /// Returns the number of bytes needed for one deinterlaced row, like
+    /// [`Info::raw_row_length`], or `None` if it does not fit into `usize`.
+    pub fn checked_raw_row_length(&self) -> Option<usize> {
         self.color_type
             .checked_raw_row_length(self.bit_depth, self.width)
     }
@@ -617,6 +2150,19 @@ impl Info<'_> {
         data[12] = self.interlaced as u8;
         encoder::write_chunk(&mut w, chunk::IHDR, &data)?;
 
+        // The signed content starts directly after the header.
+        for signature in &self.signatures {
+            encoder::write_chunk(&mut w, chunk::dSIG, &signature.opening)?;
+        }
+
+        if let Some(cicp) = self.coding_independent_code_points {
+            cicp.encode(&mut w)?;
+        }
+
+        if let Some(sbit) = &self.significant_bits {
+            encoder::write_chunk(&mut w, chunk::sBIT, sbit)?;
+        }
+
         if let Some(p) = &self.palette {
             encoder::write_chunk(&mut w, chunk::PLTE, p)?;
         };
@@ -640,20 +2186,22 @@ impl Info<'_> {
                 chrms.encode(&mut w)?;
             }
         }
+        if let Some(stereo_layout) = self.stereo_layout {
+            stereo_layout.encode(&mut w)?;
+        }
+        if let Some(time) = self.modification_time {
+            time.encode(&mut w)?;
+        }
         if let Some(actl) = self.animation_control {
             actl.encode(&mut w)?;
         }
 
-        for text_chunk in &self.uncompressed_latin1_text {
+        for text_chunk in self.text_chunks() {
             text_chunk.encode(&mut w)?;
         }
 
-        for text_chunk in &self.compressed_latin1_text {
-            text_chunk.encode(&mut w)?;
-        }
-
-        for text_chunk in &self.utf8_text {
-            text_chunk.encode(&mut w)?;
+        for (chunk_type, data) in &self.legacy_chunks {
+            encoder::write_chunk(&mut w, *chunk_type, data)?;
         }
 
         Ok(())
@@ -661,7 +2209,21 @@ impl Info<'_> {
 }
 
 impl BytesPerPixel {
-    pub(crate) fn into_usize(self) -> usize {
+    /// usize -> Self, for the byte counts of pixels in the supported bit depths and color types.
+    pub fn from_usize(n: usize) -> Option<BytesPerPixel> {
+        match n {
+            1 => Some(BytesPerPixel::One),
+            2 => Some(BytesPerPixel::Two),
+            3 => Some(BytesPerPixel::Three),
+            4 => Some(BytesPerPixel::Four),
+            6 => Some(BytesPerPixel::Six),
+            8 => Some(BytesPerPixel::Eight),
+            _ => None,
+        }
+    }
+
+    /// The number of bytes.
+    pub fn into_usize(self) -> usize {
         self as usize
     }
 }
@@ -751,6 +2313,16 @@ pub(crate) enum ParameterErrorKind {
     /// library will perform the checks necessary to ensure that data was accurate or error with a
     /// format error otherwise.
     PolledAfterEndOfImage,
+    /// Linear light output needs samples of at least 8 bits without palette.
+    UnexpandedLinearOutput,
+    /// Typed pixels need the color type and bit depth of the output.
+    #[cfg_attr(not(feature = "bytemuck"), allow(dead_code))]
+    PixelType,
+    /// The encoded file does not fit into the slice given to a
+    /// [`SliceWriter`](crate::SliceWriter).
+    OutputBufferSize { needed: usize, available: usize },
+    /// Decoding was asked to continue after an error that left the stream unusable.
+    NotRecoverable,
 }
 
 impl From<ParameterErrorKind> for ParameterError {
@@ -767,6 +2339,157 @@ impl fmt::Display for ParameterError {
                 write!(fmt, "wrong data size, expected {} got {}", expected, actual)
             }
             PolledAfterEndOfImage => write!(fmt, "End of image has been reached"),
+            UnexpandedLinearOutput => {
+                write!(
+                    fmt,
+                    "linear output requires expanding palettes and small samples"
+                )
+            }
+            PixelType => write!(fmt, "pixel type does not match the output color type"),
+            OutputBufferSize { needed, available } => write!(
+                fmt,
+                "output buffer too small, needed {} bytes but got {}",
+                needed, available
+            ),
+            NotRecoverable => write!(fmt, "decoding can not continue after a fatal error"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allowed_bit_depths() {
+        use crate::Encoder;
+
+        let color_types = (0..7).filter_map(ColorType::from_u8);
+        let depths: Vec<_> = (0..17).filter_map(BitDepth::from_u8).collect();
+        let mut allowed = 0;
+        for color_type in color_types {
+            let bit_depths = color_type.allowed_bit_depths();
+            assert!(bit_depths
+                .windows(2)
+                .all(|pair| (pair[0] as u8) < pair[1] as u8));
+            for &depth in &depths {
+                let is_allowed = color_type.is_combination_allowed(depth);
+                assert_eq!(is_allowed, bit_depths.contains(&depth));
+                allowed += usize::from(is_allowed);
+
+                // The encoder validates the same combinations.
+                let mut encoder = Encoder::new(std::io::sink(), 1, 1);
+                encoder.set_color(color_type);
+                encoder.set_depth(depth);
+                encoder.set_palette(vec![0; 3]);
+                assert_eq!(encoder.write_header().is_ok(), is_allowed);
+            }
+        }
+        // The 15 combinations of table 11.1 of the specification.
+        assert_eq!(allowed, 15);
+    }
+
+    #[test]
+    fn gamma_lut_identity() {
+        for &depth in &[
+            BitDepth::One,
+            BitDepth::Four,
+            BitDepth::Eight,
+            BitDepth::Sixteen,
+        ] {
+            assert!(GammaLut::new(ScaledFloat::new(0.5), 2.0, depth).is_identity());
+            // A source gamma of zero is invalid, and leaves the samples unchanged.
+            assert!(GammaLut::new(ScaledFloat::from_scaled(0), 2.2, depth).is_identity());
+            // Black and white, the only samples of one bit, are never changed.
+            let identity = GammaLut::new(ScaledFloat::new(1.0), 2.2, depth).is_identity();
+            assert_eq!(identity, depth == BitDepth::One);
+        }
+        let lut = GammaLut::new(ScaledFloat::new(0.5), 2.0, BitDepth::Four);
+        assert_eq!(lut.bit_depth(), BitDepth::Four);
+        assert_eq!(lut.table().len(), 16);
+    }
+
+    #[test]
+    fn gamma_lut_apply() {
+        let gamma = ScaledFloat::new(1.0);
+        let lut = GammaLut::new(gamma, 2.0, BitDepth::Sixteen);
+        let mut rgba = [0x40, 0, 0xff, 0xff, 0, 0, 0x40, 0];
+        lut.apply(ColorType::Rgba, &mut rgba);
+        assert_eq!(rgba, [0x80, 0, 0xff, 0xff, 0, 0, 0x40, 0]);
+
+        // Samples smaller than a byte are corrected one by one.
+        let lut = GammaLut::new(gamma, 2.0, BitDepth::Four);
+        let mut gray = [0x4f, 0x00];
+        lut.apply(ColorType::Grayscale, &mut gray);
+        assert_eq!(gray, [0x8f, 0x00]);
+
+        let lut = GammaLut::new(gamma, 2.0, BitDepth::Eight);
+        let mut indices = [0x40, 0x80];
+        lut.apply(ColorType::Indexed, &mut indices);
+        assert_eq!(indices, [0x40, 0x80]);
+        let mut gray_alpha = [0x40, 0x40];
+        lut.apply(ColorType::GrayscaleAlpha, &mut gray_alpha);
+        assert_eq!(gray_alpha, [0x80, 0x40]);
+    }
+
+    #[test]
+    fn color_precedence_order() {
+        use ColorChunk::*;
+        assert_eq!(
+            ColorPrecedence::default().order(),
+            ColorPrecedence::SPECIFICATION.order()
+        );
+        let precedence = ColorPrecedence::new(&[Srgb, Cicp, Srgb, Cicp, Calibrated]);
+        assert_eq!(precedence.order(), [Srgb, Cicp, Calibrated]);
+        assert_eq!(
+            ColorPrecedence::new(&[IccProfile, Cicp, Srgb, Calibrated]),
+            ColorPrecedence::ICC_FIRST
+        );
+        assert!(ColorPrecedence::new(&[]).order().is_empty());
+    }
+
+    #[test]
+    fn modification_time_bounds() {
+        assert!(ModificationTime::new(2024, 12, 31, 23, 59, 60).is_some());
+        assert!(ModificationTime::new(0, 1, 1, 0, 0, 0).is_some());
+        assert!(ModificationTime::new(2024, 2, 29, 0, 0, 0).is_some());
+        assert!(ModificationTime::new(2000, 2, 29, 0, 0, 0).is_some());
+        for &(month, day, hour, minute, second) in &[
+            (0, 1, 0, 0, 0),
+            (13, 1, 0, 0, 0),
+            (1, 0, 0, 0, 0),
+            (4, 31, 0, 0, 0),
+            (1, 1, 24, 0, 0),
+            (1, 1, 0, 60, 0),
+            (1, 1, 0, 0, 61),
+        ] {
+            assert_eq!(
+                ModificationTime::new(2024, month, day, hour, minute, second),
+                None
+            );
+        }
+        assert_eq!(ModificationTime::new(2023, 2, 29, 0, 0, 0), None);
+        assert_eq!(ModificationTime::new(1900, 2, 29, 0, 0, 0), None);
+    }
+
+    #[test]
+    fn palette_with_short_transparency() {
+        let palette = Palette::new(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10], Some(&[0x80]));
+        assert_eq!(palette.len(), 3);
+        assert!(palette.has_alpha());
+        assert_eq!(palette.get_rgba(0), Some([1, 2, 3, 0x80]));
+        // Entries past the end of the tRNS chunk are opaque.
+        assert_eq!(palette.get_rgba(2), Some([7, 8, 9, 0xff]));
+        assert_eq!(palette.get_rgba(3), None);
+        assert_eq!(
+            palette.iter_rgba().collect::<Vec<_>>(),
+            [[1, 2, 3, 0x80], [4, 5, 6, 0xff], [7, 8, 9, 0xff]]
+        );
+
+        // Alpha beyond the last entry is ignored.
+        let palette = Palette::new(&[1, 2, 3], Some(&[0xff, 0]));
+        assert!(!palette.has_alpha());
+        assert_eq!(palette.alpha(1), None);
+        assert!(Palette::new(&[1, 2], None).is_empty());
+    }
+}
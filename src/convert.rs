@@ -0,0 +1,220 @@
+//! Conversions between the sample layouts of PNG images.
+//!
+//! The functions work on rows or whole images of 8 or 16 bit samples, with 16 bit samples in big
+//! endian as in the image data. Each converts as many whole pixels as both slices hold and
+//! returns that number. They panic for other bit depths and for color types they do not apply
+//! to.
+//!
+//! ```
+//! use png::{convert, BitDepth, ColorType};
+//!
+//! let gray = [0, 128];
+//! let mut rgb = [0; 6];
+//! convert::gray_to_rgb(&gray, BitDepth::Eight, &mut rgb);
+//! let mut rgba = [0; 8];
+//! convert::add_alpha(&rgb, ColorType::Rgb, BitDepth::Eight, &mut rgba);
+//! assert_eq!(rgba, [0, 0, 0, 255, 128, 128, 128, 255]);
+//! ```
+
+use crate::common::{BitDepth, ColorType, LumaWeights};
+
+fn sample_len(depth: BitDepth) -> usize {
+    match depth {
+        BitDepth::Eight => 1,
+        BitDepth::Sixteen => 2,
+        other => panic!("Samples of {} bits can not be converted", other as u8),
+    }
+}
+
+/// Converts grayscale samples to RGB by repeating them.
+pub fn gray_to_rgb(gray: &[u8], depth: BitDepth, rgb: &mut [u8]) -> usize {
+    let len = sample_len(depth);
+    rgb.chunks_exact_mut(3 * len)
+        .zip(gray.chunks_exact(len))
+        .map(|(rgb, gray)| {
+            for channel in rgb.chunks_exact_mut(len) {
+                channel.copy_from_slice(gray);
+            }
+        })
+        .count()
+}
+
+/// Converts RGB samples to grayscale by weighting the encoded samples.
+///
+/// This is fast but mixes the samples without decoding their gamma, which darkens saturated
+/// colors. [`GrayscaleConverter`](crate::GrayscaleConverter) converts in linear light instead.
+pub fn rgb_to_gray(rgb: &[u8], depth: BitDepth, weights: LumaWeights, gray: &mut [u8]) -> usize {
+    let len = sample_len(depth);
+    let [r, g, b] = weights.weights();
+    gray.chunks_exact_mut(len)
+        .zip(rgb.chunks_exact(3 * len))
+        .map(|(gray, rgb)| {
+            let sample = |i: usize| match len {
+                1 => f64::from(rgb[i]),
+                _ => f64::from(u16::from_be_bytes([rgb[2 * i], rgb[2 * i + 1]])),
+            };
+            let luma = (r * sample(0) + g * sample(1) + b * sample(2)).round();
+            match len {
+                1 => gray[0] = luma as u8,
+                _ => gray.copy_from_slice(&(luma as u16).to_be_bytes()),
+            }
+        })
+        .count()
+}
+
+/// Adds an opaque alpha channel to grayscale or RGB samples.
+pub fn add_alpha(input: &[u8], color_type: ColorType, depth: BitDepth, output: &mut [u8]) -> usize {
+    let channels = match color_type {
+        ColorType::Grayscale => 1,
+        ColorType::Rgb => 3,
+        other => panic!("{:?} samples can not get an alpha channel", other),
+    };
+    let len = sample_len(depth);
+    output
+        .chunks_exact_mut((channels + 1) * len)
+        .zip(input.chunks_exact(channels * len))
+        .map(|(output, input)| {
+            let (color, alpha) = output.split_at_mut(channels * len);
+            color.copy_from_slice(input);
+            alpha.iter_mut().for_each(|byte| *byte = 0xff);
+        })
+        .count()
+}
+
+/// Removes the alpha channel of grayscale with alpha or RGBA samples.
+pub fn remove_alpha(
+    input: &[u8],
+    color_type: ColorType,
+    depth: BitDepth,
+    output: &mut [u8],
+) -> usize {
+    let channels = match color_type {
+        ColorType::GrayscaleAlpha => 1,
+        ColorType::Rgba => 3,
+        other => panic!("{:?} samples have no alpha channel", other),
+    };
+    let len = sample_len(depth);
+    output
+        .chunks_exact_mut(channels * len)
+        .zip(input.chunks_exact((channels + 1) * len))
+        .map(|(output, input)| output.copy_from_slice(&input[..channels * len]))
+        .count()
+}
+
+/// Widens 8 bit samples to 16 bits, mapping `255` to `65535`.
+pub fn eight_to_sixteen(input: &[u8], output: &mut [u8]) -> usize {
+    output
+        .chunks_exact_mut(2)
+        .zip(input)
+        .map(|(output, &sample)| output.copy_from_slice(&[sample, sample]))
+        .count()
+}
+
+/// Narrows 16 bit samples to 8 bits by keeping their high byte, like
+/// [`Transformations::STRIP_16`](crate::Transformations::STRIP_16).
+pub fn sixteen_to_eight(input: &[u8], output: &mut [u8]) -> usize {
+    output
+        .iter_mut()
+        .zip(input.chunks_exact(2))
+        .map(|(output, sample)| *output = sample[0])
+        .count()
+}
+
+/// Splits interleaved samples into one plane per channel, stored one after another.
+///
+/// The pixels are counted first, so each plane holds the returned number of samples.
+pub fn to_planar(
+    packed: &[u8],
+    color_type: ColorType,
+    depth: BitDepth,
+    planar: &mut [u8],
+) -> usize {
+    let len = sample_len(depth);
+    let pixel_len = color_type.samples() * len;
+    let pixels = packed.len().min(planar.len()) / pixel_len;
+    for (pixel, samples) in packed.chunks_exact(pixel_len).take(pixels).enumerate() {
+        for (channel, sample) in samples.chunks_exact(len).enumerate() {
+            let start = (channel * pixels + pixel) * len;
+            planar[start..start + len].copy_from_slice(sample);
+        }
+    }
+    pixels
+}
+
+/// Interleaves planes of samples, stored one after another, into pixels.
+pub fn from_planar(
+    planar: &[u8],
+    color_type: ColorType,
+    depth: BitDepth,
+    packed: &mut [u8],
+) -> usize {
+    let len = sample_len(depth);
+    let pixel_len = color_type.samples() * len;
+    let pixels = packed.len().min(planar.len()) / pixel_len;
+    for (pixel, samples) in packed.chunks_exact_mut(pixel_len).take(pixels).enumerate() {
+        for (channel, sample) in samples.chunks_exact_mut(len).enumerate() {
+            let start = (channel * pixels + pixel) * len;
+            sample.copy_from_slice(&planar[start..start + len]);
+        }
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sixteen_bit_samples() {
+        let gray = [0x12, 0x34, 0xff, 0xff];
+        let mut rgb = [0; 12];
+        assert_eq!(gray_to_rgb(&gray, BitDepth::Sixteen, &mut rgb), 2);
+        assert_eq!(rgb[..6], [0x12, 0x34, 0x12, 0x34, 0x12, 0x34]);
+        let mut back = [0; 4];
+        rgb_to_gray(&rgb, BitDepth::Sixteen, LumaWeights::Rec709, &mut back);
+        assert_eq!(back, gray);
+
+        let mut rgba = [0; 16];
+        assert_eq!(
+            add_alpha(&rgb, ColorType::Rgb, BitDepth::Sixteen, &mut rgba),
+            2
+        );
+        assert_eq!(rgba[6..8], [0xff, 0xff]);
+        let mut stripped = [0; 12];
+        remove_alpha(&rgba, ColorType::Rgba, BitDepth::Sixteen, &mut stripped);
+        assert_eq!(stripped, rgb);
+
+        let mut narrow = [0; 2];
+        assert_eq!(sixteen_to_eight(&gray, &mut narrow), 2);
+        assert_eq!(narrow, [0x12, 0xff]);
+        let mut wide = [0; 4];
+        eight_to_sixteen(&narrow, &mut wide);
+        assert_eq!(wide, [0x12, 0x12, 0xff, 0xff]);
+    }
+
+    #[test]
+    fn planar_roundtrip() {
+        let packed = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
+        let mut planar = [0; 12];
+        assert_eq!(
+            to_planar(&packed, ColorType::Rgb, BitDepth::Eight, &mut planar),
+            4
+        );
+        assert_eq!(planar, [1, 4, 7, 10, 2, 5, 8, 11, 3, 6, 9, 12]);
+        let mut interleaved = [0; 12];
+        from_planar(&planar, ColorType::Rgb, BitDepth::Eight, &mut interleaved);
+        assert_eq!(interleaved, packed);
+
+        let mut planar = [0; 12];
+        assert_eq!(
+            to_planar(
+                &packed,
+                ColorType::GrayscaleAlpha,
+                BitDepth::Sixteen,
+                &mut planar
+            ),
+            3
+        );
+        assert_eq!(planar, [1, 2, 5, 6, 9, 10, 3, 4, 7, 8, 11, 12]);
+    }
+}
@@ -0,0 +1,318 @@
+//! Reading and writing PNG images as `data:image/png;base64,...` URLs.
+//!
+//! This module is only built with the `data-url` feature. [`DataUrlReader`] and
+//! [`DataUrlWriter`] convert base64 while the image is decoded or encoded, so the binary file is
+//! never held in memory as a whole.
+//!
+//! ```
+//! use png::data_url::{self, DataUrlWriter};
+//!
+//! let mut url = DataUrlWriter::new(String::new().into_bytes())?;
+//! {
+//!     let mut encoder = png::Encoder::new(&mut url, 2, 1);
+//!     encoder.set_color(png::ColorType::Grayscale);
+//!     let mut writer = encoder.write_header()?;
+//!     writer.write_image_data(&[0, 255])?;
+//! }
+//! let url = String::from_utf8(url.finish()?).unwrap();
+//! assert!(url.starts_with("data:image/png;base64,iVBORw0KGgo"));
+//!
+//! let mut reader = data_url::decoder(&url)?.read_info()?;
+//! let mut buf = vec![0; reader.output_buffer_size()];
+//! reader.next_frame(&mut buf)?;
+//! assert_eq!(buf, [0, 255]);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use std::io::{self, Read, Write};
+
+use crate::decoder::{Decoder, DecodingError};
+
+/// The start of a data URL of a PNG image with base64 content.
+pub const PREFIX: &str = "data:image/png;base64,";
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Creates a decoder of the image in a data URL.
+pub fn decoder(url: &str) -> Result<Decoder<DataUrlReader<&[u8]>>, DecodingError> {
+    Ok(Decoder::new(DataUrlReader::new(url.as_bytes())?))
+}
+
+/// Encodes a PNG file as a data URL.
+pub fn encode(png: &[u8]) -> String {
+    let url = Vec::with_capacity(PREFIX.len() + (png.len() + 2) / 3 * 4);
+    let mut writer = DataUrlWriter::new(url).expect("writing to a vector does not fail");
+    writer
+        .write_all(png)
+        .expect("writing to a vector does not fail");
+    let url = writer.finish().expect("writing to a vector does not fail");
+    String::from_utf8(url).expect("base64 is ASCII")
+}
+
+/// Writes a data URL of the PNG file written to it.
+///
+/// The URL is complete after [`DataUrlWriter::finish`], or when the writer is dropped.
+pub struct DataUrlWriter<W: Write> {
+    /// The writer, until the URL is finished.
+    writer: Option<W>,
+    /// Bytes that do not yet fill a group of three.
+    pending: [u8; 3],
+    pending_len: usize,
+}
+
+impl<W: Write> DataUrlWriter<W> {
+    /// Create a writer of a data URL, writing its [`PREFIX`] to `writer`.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(PREFIX.as_bytes())?;
+        Ok(DataUrlWriter {
+            writer: Some(writer),
+            pending: [0; 3],
+            pending_len: 0,
+        })
+    }
+
+    /// Writes the padded end of the URL and returns the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_tail()?;
+        Ok(self
+            .writer
+            .take()
+            .expect("the writer is only taken when finishing"))
+    }
+
+    fn write_tail(&mut self) -> io::Result<()> {
+        if let Some(writer) = self.writer.as_mut() {
+            if self.pending_len > 0 {
+                let mut group = [b'='; 4];
+                let encoded = encode_group(self.pending, self.pending_len);
+                group[..self.pending_len + 1].copy_from_slice(&encoded[..self.pending_len + 1]);
+                self.pending_len = 0;
+                writer.write_all(&group)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for DataUrlWriter<W> {
+    fn write(&mut self, mut buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len();
+        let writer = match self.writer.as_mut() {
+            Some(writer) => writer,
+            None => return Err(io::Error::new(io::ErrorKind::Other, "data URL is finished")),
+        };
+        while self.pending_len > 0 && self.pending_len < 3 && !buf.is_empty() {
+            self.pending[self.pending_len] = buf[0];
+            self.pending_len += 1;
+            buf = &buf[1..];
+        }
+        match self.pending_len {
+            0 => {}
+            3 => {
+                writer.write_all(&encode_group(self.pending, 3))?;
+                self.pending_len = 0;
+            }
+            _ => return Ok(len),
+        }
+
+        let mut encoded = Vec::with_capacity(buf.len() / 3 * 4);
+        let mut groups = buf.chunks_exact(3);
+        for group in &mut groups {
+            encoded.extend_from_slice(&encode_group([group[0], group[1], group[2]], 3));
+        }
+        writer.write_all(&encoded)?;
+        let rest = groups.remainder();
+        self.pending[..rest.len()].copy_from_slice(rest);
+        self.pending_len = rest.len();
+        Ok(len)
+    }
+
+    /// Flushes the inner writer, the last one or two bytes are only written when finishing.
+    fn flush(&mut self) -> io::Result<()> {
+        match self.writer.as_mut() {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<W: Write> Drop for DataUrlWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.write_tail();
+    }
+}
+
+/// Reads the PNG file of a data URL.
+///
+/// Whitespace within the base64 content is skipped. The URL is read byte by byte, so a file or
+/// socket should be buffered.
+pub struct DataUrlReader<R: Read> {
+    reader: R,
+    /// Decoded bytes that were not yet read.
+    decoded: [u8; 3],
+    decoded_pos: usize,
+    decoded_len: usize,
+    /// Whether the padding at the end was read.
+    at_end: bool,
+}
+
+impl<R: Read> DataUrlReader<R> {
+    /// Create a reader, reading and checking the media type of the URL.
+    ///
+    /// The URL must begin with `data:image/png`, optionally followed by parameters, and use the
+    /// base64 encoding.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut header = Vec::new();
+        loop {
+            let mut byte = [0];
+            if reader.read(&mut byte)? == 0 || header.len() > 256 {
+                return Err(invalid_data("not a data URL"));
+            }
+            if byte[0] == b',' {
+                break;
+            }
+            header.push(byte[0].to_ascii_lowercase());
+        }
+        let header = &header[..];
+        if !header.starts_with(b"data:image/png") || !header.ends_with(b";base64") {
+            return Err(invalid_data("not a base64 data URL of a PNG image"));
+        }
+        Ok(DataUrlReader {
+            reader,
+            decoded: [0; 3],
+            decoded_pos: 0,
+            decoded_len: 0,
+            at_end: false,
+        })
+    }
+
+    /// Decodes the next group of four characters, returning false at the end.
+    fn next_group(&mut self) -> io::Result<bool> {
+        let mut group = [0u8; 4];
+        let mut len = 0;
+        while len < 4 {
+            let mut byte = [0];
+            if self.reader.read(&mut byte)? == 0 {
+                if len == 0 {
+                    return Ok(false);
+                }
+                return Err(invalid_data("truncated base64"));
+            }
+            if !byte[0].is_ascii_whitespace() {
+                group[len] = byte[0];
+                len += 1;
+            }
+        }
+
+        let padding = match group {
+            [_, _, b'=', b'='] => 2,
+            [_, _, _, b'='] => 1,
+            _ => 0,
+        };
+        let mut bits = 0u32;
+        for &c in &group[..4 - padding] {
+            bits = bits << 6 | u32::from(decode_char(c)?);
+        }
+        bits <<= 6 * padding as u32;
+        self.decoded = [(bits >> 16) as u8, (bits >> 8) as u8, bits as u8];
+        self.decoded_pos = 0;
+        self.decoded_len = 3 - padding;
+        self.at_end = padding > 0;
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for DataUrlReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut read = 0;
+        while read < buf.len() {
+            if self.decoded_pos == self.decoded_len {
+                if self.at_end || !self.next_group()? {
+                    break;
+                }
+                continue;
+            }
+            let available = &self.decoded[self.decoded_pos..self.decoded_len];
+            let len = available.len().min(buf.len() - read);
+            buf[read..read + len].copy_from_slice(&available[..len]);
+            self.decoded_pos += len;
+            read += len;
+        }
+        Ok(read)
+    }
+}
+
+/// Encodes the first `len` of three bytes as four characters, of which `len + 1` are used.
+fn encode_group(bytes: [u8; 3], len: usize) -> [u8; 4] {
+    let mut padded = [0; 3];
+    padded[..len].copy_from_slice(&bytes[..len]);
+    let bits = u32::from(padded[0]) << 16 | u32::from(padded[1]) << 8 | u32::from(padded[2]);
+    [
+        ALPHABET[(bits >> 18) as usize & 63],
+        ALPHABET[(bits >> 12) as usize & 63],
+        ALPHABET[(bits >> 6) as usize & 63],
+        ALPHABET[bits as usize & 63],
+    ]
+}
+
+fn decode_char(c: u8) -> io::Result<u8> {
+    match c {
+        b'A'..=b'Z' => Ok(c - b'A'),
+        b'a'..=b'z' => Ok(c - b'a' + 26),
+        b'0'..=b'9' => Ok(c - b'0' + 52),
+        b'+' => Ok(62),
+        b'/' => Ok(63),
+        _ => Err(invalid_data("invalid base64 character")),
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_vectors() {
+        let vectors: &[(&[u8], &str)] = &[
+            (b"", ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (b"fooba", "Zm9vYmE="),
+            (b"foobar", "Zm9vYmFy"),
+        ];
+        for &(raw, base64) in vectors {
+            let url = encode(raw);
+            assert_eq!(&url[PREFIX.len()..], base64);
+
+            let mut streamed = DataUrlWriter::new(Vec::new()).unwrap();
+            for byte in raw {
+                streamed.write_all(&[*byte]).unwrap();
+            }
+            assert_eq!(streamed.finish().unwrap(), url.as_bytes());
+
+            let mut decoded = Vec::new();
+            let spaced = url.replace("Zm", "Z\n m");
+            DataUrlReader::new(spaced.as_bytes())
+                .unwrap()
+                .read_to_end(&mut decoded)
+                .unwrap();
+            assert_eq!(decoded, raw);
+        }
+    }
+
+    #[test]
+    fn invalid_urls() {
+        assert!(DataUrlReader::new(&b"data:image/jpeg;base64,AAAA"[..]).is_err());
+        assert!(DataUrlReader::new(&b"data:image/png,AAAA"[..]).is_err());
+        assert!(DataUrlReader::new(&b"data:image/png;name=a.png;base64,AAAA"[..]).is_ok());
+
+        let mut reader = DataUrlReader::new(&b"data:image/png;base64,Zm9v*A=="[..]).unwrap();
+        assert!(reader.read_to_end(&mut Vec::new()).is_err());
+        let mut reader = DataUrlReader::new(&b"data:image/png;base64,Zm9vY"[..]).unwrap();
+        assert!(reader.read_to_end(&mut Vec::new()).is_err());
+    }
+}
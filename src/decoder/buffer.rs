@@ -0,0 +1,92 @@
+/// A source of the large buffers of a [`Reader`](crate::Reader).
+///
+/// The reader takes its row buffers, the buffer of raw image data and the temporary frames of
+/// [`Reader::next_frame_linear`](crate::Reader::next_frame_linear) from the provider, and hands
+/// them back once they are replaced or the reader is dropped. This lets an embedder pool buffers
+/// across images or take them from a memory region of its choice. The buffers of the frames
+/// themselves are always provided by the caller.
+///
+/// ```
+/// use png::{BufferProvider, Decoder};
+///
+/// /// Reuses the buffers of previous images.
+/// #[derive(Default)]
+/// struct Pool(Vec<Vec<u8>>);
+///
+/// impl BufferProvider for Pool {
+///     fn allocate(&mut self, capacity: usize) -> Vec<u8> {
+///         match self.0.iter().position(|buffer| buffer.capacity() >= capacity) {
+///             Some(index) => self.0.swap_remove(index),
+///             None => Vec::with_capacity(capacity),
+///         }
+///     }
+///
+///     fn release(&mut self, mut buffer: Vec<u8>) {
+///         buffer.clear();
+///         self.0.push(buffer);
+///     }
+/// }
+///
+/// let mut decoder = Decoder::new(std::fs::File::open("tests/pngsuite/basn0g08.png").unwrap());
+/// decoder.set_buffer_provider(Pool::default());
+/// let mut reader = decoder.read_info().unwrap();
+/// let mut buf = vec![0; reader.output_buffer_size()];
+/// reader.next_frame(&mut buf).unwrap();
+/// ```
+pub trait BufferProvider: Send {
+    /// Returns an empty buffer with a capacity of at least `capacity` bytes.
+    fn allocate(&mut self, capacity: usize) -> Vec<u8>;
+
+    /// Takes back a buffer that is no longer used, which drops it by default.
+    fn release(&mut self, buffer: Vec<u8>) {
+        drop(buffer);
+    }
+}
+
+/// The buffers of a reader, from a provider or the global allocator.
+#[derive(Default)]
+pub(super) struct Buffers {
+    provider: Option<Box<dyn BufferProvider>>,
+}
+
+impl Buffers {
+    pub(super) fn new(provider: Option<Box<dyn BufferProvider>>) -> Self {
+        Buffers { provider }
+    }
+
+    /// A buffer of `len` zeros.
+    pub(super) fn zeroed(&mut self, len: usize) -> Vec<u8> {
+        match self.provider {
+            Some(ref mut provider) => {
+                let mut buffer = provider.allocate(len);
+                buffer.clear();
+                buffer.resize(len, 0);
+                buffer
+            }
+            None => vec![0; len],
+        }
+    }
+
+    /// Makes room for `additional` more bytes in `buffer`.
+    pub(super) fn reserve(&mut self, buffer: &mut Vec<u8>, additional: usize) {
+        let len = buffer.len().saturating_add(additional);
+        if buffer.capacity() >= len {
+            return;
+        }
+        if let Some(ref mut provider) = self.provider {
+            let mut larger = provider.allocate(len);
+            larger.clear();
+            larger.extend_from_slice(buffer);
+            provider.release(std::mem::replace(buffer, larger));
+        }
+        buffer.reserve(additional);
+    }
+
+    pub(super) fn release(&mut self, buffer: Vec<u8>) {
+        if let Some(ref mut provider) = self.provider {
+            if buffer.capacity() > 0 {
+                provider.release(buffer);
+            }
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! Color management of decoded image data through an external color management system.
+use crate::common::{
+    BitDepth, CodingIndependentCodePoints, ColorType, Info, ScaledFloat, SourceChromaticities,
+    SourceColor, SrgbRenderingIntent,
+};
+use crate::decoder::DecodingError;
+
+/// The color space of the decoded image data, as described by the chunks of the image.
+///
+/// The fields are in the order of precedence of the specification: the code points of the `cICP`
+/// chunk override an ICC profile, which overrides the `sRGB` chunk, which in turn overrides the
+/// gamma and chromaticities. An image without any of them is commonly treated as sRGB. The
+/// precedence can be changed, and is resolved in [`ColorSource::color`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct ColorSource<'a> {
+    /// The color space by the configured precedence, see [`Info::source_color`].
+    pub color: SourceColor<'a>,
+    /// The video signal type of the `cICP` chunk.
+    pub cicp: Option<CodingIndependentCodePoints>,
+    /// The decompressed ICC profile of the `iCCP` chunk.
+    pub icc_profile: Option<&'a [u8]>,
+    /// The rendering intent of the `sRGB` chunk.
+    pub srgb: Option<SrgbRenderingIntent>,
+    /// The gamma of the `gAMA` chunk.
+    pub gamma: Option<ScaledFloat>,
+    /// The chromaticities of the `cHRM` chunk.
+    pub chromaticities: Option<SourceChromaticities>,
+}
+
+impl<'a> ColorSource<'a> {
+    /// Collect the color space chunks of an image.
+    pub fn new(info: &'a Info<'_>) -> Self {
+        ColorSource {
+            color: info.source_color(),
+            cicp: info.coding_independent_code_points,
+            icc_profile: info.icc_profile.as_deref(),
+            srgb: info.srgb,
+            gamma: info.source_gamma,
+            chromaticities: info.source_chromaticities,
+        }
+    }
+
+    /// Whether the image describes its color space at all.
+    pub fn is_tagged(&self) -> bool {
+        self.cicp.is_some()
+            || self.icc_profile.is_some()
+            || self.srgb.is_some()
+            || self.gamma.is_some()
+            || self.chromaticities.is_some()
+    }
+}
+
+/// The color space that a [`ColorTransform`] converts the decoded image data into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ColorDestination {
+    /// Samples encoded with the sRGB transfer function. This is the default.
+    Srgb,
+    /// Samples of linear light with the sRGB primaries.
+    LinearSrgb,
+}
+
+impl Default for ColorDestination {
+    fn default() -> Self {
+        ColorDestination::Srgb
+    }
+}
+
+/// A color conversion applied by the [`Reader`](crate::Reader) to every decoded row.
+///
+/// This is the hook for a color management system such as Little CMS or qcms, which builds its
+/// transform from the [`ColorSource`] in [`ColorTransform::prepare`] and then converts the rows
+/// in place. Configure it with [`Decoder::set_color_transform`](crate::Decoder::set_color_transform).
+pub trait ColorTransform: Send + Sync {
+    /// Prepare the conversion from the color space of the image into the destination.
+    ///
+    /// This is called once by [`Decoder::read_info`](crate::Decoder::read_info) with the color
+    /// type and bit depth of the rows that are passed to [`ColorTransform::transform_row`], which
+    /// are those after the [`Transformations`](crate::Transformations) of the decoder. An error
+    /// is returned by `read_info`.
+    fn prepare(
+        &mut self,
+        source: &ColorSource<'_>,
+        destination: ColorDestination,
+        color_type: ColorType,
+        bit_depth: BitDepth,
+    ) -> Result<(), DecodingError>;
+
+    /// Convert a row of samples in place. Samples of 16 bits are big endian.
+    ///
+    /// Rows of indexed images are not passed to the transform, so these should be expanded with
+    /// [`Transformations::EXPAND`](crate::Transformations::EXPAND). The rows of interlaced
+    /// images are passed pass by pass, each a shorter row of pixels.
+    fn transform_row(&mut self, row: &mut [u8]);
+}
+
+/// The scale of the linear light samples of [`Reader::next_frame_linear`](crate::Reader::next_frame_linear).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinearScale {
+    /// The natural range of the transfer function. This is the default.
+    ///
+    /// For PQ a sample of 1.0 is 10000 cd/m², for HLG it is the brightest scene light, and for
+    /// other transfer functions it is the white of the image.
+    Normalized,
+    /// A sample of 1.0 is 80 cd/m², the reference white of scRGB.
+    ///
+    /// HLG is converted to display light for a display with a peak luminance of 1000 cd/m². The
+    /// color primaries are kept, so images with other primaries than sRGB must still be converted.
+    ScRgb,
+}
+
+impl Default for LinearScale {
+    fn default() -> Self {
+        LinearScale::Normalized
+    }
+}
+
+/// The transfer function applied by a [`Linearizer`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Transfer {
+    Linear,
+    Srgb,
+    Bt1886,
+    Gamma(f64),
+    Pq,
+    Hlg,
+}
+
+/// Converts decoded samples to linear light.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Linearizer {
+    transfer: Transfer,
+    scale: LinearScale,
+    narrow_range: bool,
+    color_type: ColorType,
+    bit_depth: BitDepth,
+}
+
+const SCRGB_WHITE: f64 = 80.0;
+const HLG_PEAK: f64 = 1000.0;
+
+impl Linearizer {
+    /// Choose the transfer function of the image for samples of 8 or 16 bits.
+    pub(crate) fn new(
+        info: &Info<'_>,
+        color_type: ColorType,
+        bit_depth: BitDepth,
+        scale: LinearScale,
+    ) -> Self {
+        let mut narrow_range = false;
+        let transfer = match info.source_color() {
+            SourceColor::Cicp(cicp) => {
+                narrow_range = !cicp.is_video_full_range_image;
+                match cicp.transfer_function {
+                    CodingIndependentCodePoints::PQ => Transfer::Pq,
+                    CodingIndependentCodePoints::HLG => Transfer::Hlg,
+                    1 | 6 | 14 | 15 => Transfer::Bt1886,
+                    8 => Transfer::Linear,
+                    _ => Transfer::Srgb,
+                }
+            }
+            SourceColor::Calibrated {
+                gamma: Some(gamma), ..
+            } if gamma.into_scaled() > 0 => Transfer::Gamma(1.0 / f64::from(gamma.into_value())),
+            // The transfer function of an ICC profile is not evaluated.
+            _ => Transfer::Srgb,
+        };
+        Linearizer {
+            transfer,
+            scale,
+            narrow_range,
+            color_type,
+            bit_depth,
+        }
+    }
+
+    /// Convert a buffer of big endian samples, writing one float for each sample.
+    pub(crate) fn convert(&self, samples: &[u8], out: &mut [f32]) {
+        let (max, bytes) = match self.bit_depth {
+            BitDepth::Sixteen => (f64::from(u16::MAX), 2),
+            _ => (f64::from(u8::MAX), 1),
+        };
+        // The narrow range of video puts black at 16 and white at 235 for 8 bits.
+        let (black, white) = if self.narrow_range {
+            let unit = max / 255.0;
+            (16.0 * unit, 235.0 * unit)
+        } else {
+            (0.0, max)
+        };
+        let channels = self.color_type.samples();
+        let has_alpha = matches!(self.color_type, ColorType::GrayscaleAlpha | ColorType::Rgba);
+        let colors = if has_alpha { channels - 1 } else { channels };
+
+        let sample = |index: usize| match bytes {
+            2 => f64::from(u16::from_be_bytes([
+                samples[2 * index],
+                samples[2 * index + 1],
+            ])),
+            _ => f64::from(samples[index]),
+        };
+        for (pixel, out) in out.chunks_mut(channels).enumerate() {
+            let mut light = [0.0; 3];
+            for (channel, light) in light.iter_mut().enumerate().take(colors) {
+                let value = sample(pixel * channels + channel);
+                *light = self.eotf(((value - black) / (white - black)).max(0.0).min(1.0));
+            }
+            let factor = self.light_factor(&light[..colors]);
+            for (channel, out) in out.iter_mut().enumerate() {
+                *out = if channel < colors {
+                    (light[channel] * factor) as f32
+                } else {
+                    (sample(pixel * channels + channel) / max) as f32
+                };
+            }
+        }
+    }
+
+    /// The linear light of a normalized sample.
+    fn eotf(&self, value: f64) -> f64 {
+        match self.transfer {
+            Transfer::Linear => value,
+            Transfer::Srgb => crate::srgb::eotf_f64(value),
+            Transfer::Bt1886 => value.powf(2.4),
+            Transfer::Gamma(exponent) => value.powf(exponent),
+            Transfer::Pq => {
+                // The inverse of the encoding of SMPTE ST 2084, relative to 10000 cd/m².
+                const M1: f64 = 2610.0 / 16384.0;
+                const M2: f64 = 2523.0 / 4096.0 * 128.0;
+                const C1: f64 = 3424.0 / 4096.0;
+                const C2: f64 = 2413.0 / 4096.0 * 32.0;
+                const C3: f64 = 2392.0 / 4096.0 * 32.0;
+                let power = value.powf(1.0 / M2);
+                ((power - C1).max(0.0) / (C2 - C3 * power)).powf(1.0 / M1)
+            }
+            Transfer::Hlg => {
+                // The inverse of the opto-electronic transfer function of ARIB STD-B67.
+                const A: f64 = 0.178_832_77;
+                const B: f64 = 0.284_668_92;
+                const C: f64 = 0.559_910_73;
+                if value <= 0.5 {
+                    value * value / 3.0
+                } else {
+                    (((value - C) / A).exp() + B) / 12.0
+                }
+            }
+        }
+    }
+
+    /// The factor from the linear light of a pixel to the requested scale.
+    fn light_factor(&self, light: &[f64]) -> f64 {
+        match (self.scale, self.transfer) {
+            (LinearScale::Normalized, _) => 1.0,
+            (LinearScale::ScRgb, Transfer::Pq) => 10000.0 / SCRGB_WHITE,
+            (LinearScale::ScRgb, Transfer::Hlg) => {
+                // The optical-optical transfer function of BT.2100 with the luminance of BT.2020.
+                let luminance = match *light {
+                    [red, green, blue] => 0.2627 * red + 0.6780 * green + 0.0593 * blue,
+                    [gray] => gray,
+                    _ => 0.0,
+                };
+                HLG_PEAK * luminance.powf(0.2) / SCRGB_WHITE
+            }
+            (LinearScale::ScRgb, _) => 1.0,
+        }
+    }
+}
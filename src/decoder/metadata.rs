@@ -0,0 +1,99 @@
+//! Reading single metadata chunks without decoding the image.
+use std::io::Read;
+
+use super::stream::{decompress_icc_profile, DecodingError, FormatErrorInner};
+use crate::chunk::{self, ChunkReader, ChunkType};
+
+/// Reads the ICC profile of a PNG file from its `iCCP` chunk.
+///
+/// Only the chunks before the image data are read, and of those only the `iCCP` chunk is checked
+/// and decompressed. This is much faster than [`Decoder::read_info`](crate::Decoder::read_info)
+/// when only the profile is needed. Returns `None` if the image has no profile.
+///
+/// ```
+/// let file = std::fs::File::open("tests/pngsuite/basn0g01.png").unwrap();
+/// assert_eq!(png::read_icc_profile(file).unwrap(), None);
+/// ```
+pub fn read_icc_profile<R: Read>(r: R) -> Result<Option<Vec<u8>>, DecodingError> {
+    match read_before_image(r, chunk::iCCP)? {
+        Some(data) => decompress_icc_profile(&data).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Reads the Exif data of a PNG file from its `eXIf` chunk, see [`read_icc_profile`].
+///
+/// The data is returned as is, starting with the byte order mark of the TIFF header.
+pub fn read_exif<R: Read>(r: R) -> Result<Option<Vec<u8>>, DecodingError> {
+    read_before_image(r, chunk::eXIf)
+}
+
+/// Returns the data of the first chunk of the given type before the image data.
+fn read_before_image<R: Read>(
+    r: R,
+    chunk_type: ChunkType,
+) -> Result<Option<Vec<u8>>, DecodingError> {
+    let raw = match ChunkReader::new(r).find_before_image(chunk_type)? {
+        Some(raw) => raw,
+        None => return Ok(None),
+    };
+    if !raw.crc_ok {
+        return Err(DecodingError::Format(
+            FormatErrorInner::CrcMismatch {
+                recover: 0,
+                crc_val: raw.crc,
+                crc_sum: chunk::crc(chunk_type, &raw.data),
+                chunk: chunk_type,
+            }
+            .into(),
+        ));
+    }
+    Ok(Some(raw.data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Encoder;
+
+    fn with_chunks(chunks: &[(ChunkType, &[u8])]) -> Vec<u8> {
+        let mut png = Vec::new();
+        let mut writer = Encoder::new(&mut png, 1, 1).write_header().unwrap();
+        for &(chunk_type, data) in chunks {
+            writer.write_chunk(chunk_type, data).unwrap();
+        }
+        writer.write_image_data(&[0]).unwrap();
+        writer.finish().unwrap();
+        png
+    }
+
+    #[test]
+    fn read_metadata() {
+        let profile = b"not really a profile".to_vec();
+        let mut iccp = b"name\0\0".to_vec();
+        iccp.extend(deflate::deflate_bytes_zlib(&profile));
+        let exif = b"MM\0\x2a\0\0\0\x08\0\0";
+
+        let png = with_chunks(&[(ChunkType(*b"prIv"), b"skipped"), (chunk::iCCP, &iccp)]);
+        assert_eq!(read_icc_profile(&png[..]).unwrap(), Some(profile.clone()));
+        let reader = crate::Decoder::new(&png[..]).read_info().unwrap();
+        assert_eq!(reader.info().icc_profile.as_deref(), Some(&profile[..]));
+        assert_eq!(read_exif(&png[..]).unwrap(), None);
+
+        let png = with_chunks(&[(chunk::eXIf, exif)]);
+        assert_eq!(read_exif(&png[..]).unwrap().as_deref(), Some(&exif[..]));
+        {
+            let reader = crate::Decoder::new(&png[..]).read_info().unwrap();
+            let info = reader.info();
+            assert_eq!(info.exif_metadata.as_deref(), Some(&exif[..]));
+            #[cfg(feature = "exif")]
+            assert_eq!(info.parsed_exif(), Some(Ok(crate::exif::Exif::default())));
+        }
+
+        // Damage the Exif data.
+        let mut png = png;
+        png[8 + 25 + 8] ^= 1;
+        assert!(read_exif(&png[..]).is_err());
+        assert!(read_exif(&png[..40]).is_err());
+    }
+}
@@ -1,18 +1,34 @@
+mod buffer;
+mod color;
+mod metadata;
+mod push;
 mod stream;
 mod zlib;
 
-pub use self::stream::{Decoded, DecodingError, StreamingDecoder};
-use self::stream::{FormatErrorInner, CHUNCK_BUFFER_SIZE};
+pub use self::buffer::BufferProvider;
+use self::buffer::Buffers;
+use self::color::Linearizer;
+pub use self::color::{ColorDestination, ColorSource, ColorTransform, LinearScale};
+pub use self::metadata::{read_exif, read_icc_profile};
+pub use self::push::{PushDecoder, PushEvent};
+pub(crate) use self::stream::FormatErrorInner;
+use self::stream::CHUNCK_BUFFER_SIZE;
+pub use self::stream::{
+    ChunkContext, CompressionStats, Decoded, DecodingError, ErrorSeverity, StreamingDecoder,
+};
+pub use self::zlib::ZlibStream;
 
-use std::io::{BufRead, BufReader, Read, Write};
+use std::io::{self, BufRead, BufReader, Read, Write};
 use std::mem;
 use std::ops::Range;
 
 use crate::chunk;
 use crate::common::{
-    BitDepth, BytesPerPixel, ColorType, Info, ParameterErrorKind, Transformations,
+    BackgroundColor, BitDepth, BytesPerPixel, ColorPrecedence, ColorType, Info, ParameterErrorKind,
+    PhaseTimings, Transformations,
 };
 use crate::filter::{unfilter, FilterType};
+use crate::text_metadata::{KeywordCheck, Latin1Mode, DECOMPRESSION_LIMIT};
 use crate::utils;
 
 /*
@@ -50,6 +66,41 @@ impl OutputInfo {
     pub fn buffer_size(&self) -> usize {
         self.line_size * self.height as usize
     }
+
+    /// The height, width and samples per pixel of the frame, as the shape of a 3D array.
+    ///
+    /// Together with the buffer this describes a row-major array, like the `ndarray` view created
+    /// with `ArrayView3::from_shape(info.shape().unwrap(), &buf[..info.buffer_size()])`. Samples
+    /// of 16 bits take two bytes each, big-endian, and samples of less than 8 bits are packed into
+    /// bytes, for which there is no shape.
+    ///
+    /// The crate has no `ndarray` feature of its own. The shape and the buffer are enough to create
+    /// a view with any version of `ndarray`, without tying this crate to one.
+    ///
+    /// ```
+    /// use png::{Decoder, Transformations};
+    ///
+    /// let mut decoder = Decoder::new(std::fs::File::open("tests/pngsuite/basn3p04.png").unwrap());
+    /// let mut reader = decoder.read_info().unwrap();
+    /// let mut buf = vec![0; reader.output_buffer_size()];
+    /// assert_eq!(reader.next_frame(&mut buf).unwrap().shape(), None);
+    ///
+    /// let mut decoder = Decoder::new(std::fs::File::open("tests/pngsuite/basn3p04.png").unwrap());
+    /// decoder.set_transformations(Transformations::EXPAND);
+    /// let mut reader = decoder.read_info().unwrap();
+    /// let mut buf = vec![0; reader.output_buffer_size()];
+    /// assert_eq!(reader.next_frame(&mut buf).unwrap().shape(), Some([32, 32, 3]));
+    /// ```
+    pub fn shape(&self) -> Option<[usize; 3]> {
+        if (self.bit_depth as u8) < 8 {
+            return None;
+        }
+        Some([
+            self.height as usize,
+            self.width as usize,
+            self.color_type.samples(),
+        ])
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -67,6 +118,28 @@ impl Default for Limits {
     }
 }
 
+/// How the [`Reader`] composites transparent pixels over a background color.
+///
+/// The alpha channel is removed from the output, so that [`Reader::output_color_type`] is gray
+/// or RGB. This blends the encoded samples and not linear light, like most viewers do. Only
+/// images whose output has an alpha channel are composited, so the transparency of a `tRNS`
+/// chunk requires [`Transformations::EXPAND`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Composite {
+    /// Keep the alpha channel. This is the default.
+    None,
+    /// Composite over the color of the `bKGD` chunk, or the given color if there is none.
+    Background(BackgroundColor),
+    /// Composite over the given color, ignoring the `bKGD` chunk.
+    Color(BackgroundColor),
+}
+
+impl Default for Composite {
+    fn default() -> Self {
+        Composite::None
+    }
+}
+
 /// PNG Decoder
 pub struct Decoder<R: Read> {
     /// Reader
@@ -75,6 +148,52 @@ pub struct Decoder<R: Read> {
     transform: Transformations,
     /// Limits on resources the Decoder is allowed to use
     limits: Limits,
+    /// The largest accepted data length of specific chunk types.
+    chunk_limits: Vec<(chunk::ChunkType, u32)>,
+    latin1_mode: Latin1Mode,
+    text_decompression_limit: usize,
+    keyword_check: KeywordCheck,
+    color_transform: Option<(Box<dyn ColorTransform>, ColorDestination)>,
+    color_precedence: ColorPrecedence,
+    composite: Composite,
+    bottom_up: bool,
+    #[cfg(feature = "parallel")]
+    executor: crate::parallel::Executor,
+    /// The methods of `r` if it is buffered already.
+    buffered: Option<BufReadFns<R>>,
+    buffer_provider: Option<Box<dyn BufferProvider>>,
+    zlib_stream: Option<Box<dyn ZlibStream>>,
+    skip_recoverable: bool,
+    phase_timings: bool,
+    chunk_inventory: bool,
+}
+
+/// The methods of a reader that implements `BufRead`.
+struct BufReadFns<R> {
+    fill_buf: fn(&mut R) -> io::Result<&[u8]>,
+    consume: fn(&mut R, usize),
+}
+
+/// The input of a reader, which is buffered by the decoder unless it is buffered already.
+enum Input<R: Read> {
+    Buffered(BufReader<R>),
+    Direct(R, BufReadFns<R>),
+}
+
+impl<R: Read> Input<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match self {
+            Input::Buffered(reader) => reader.fill_buf(),
+            Input::Direct(reader, fns) => (fns.fill_buf)(reader),
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        match self {
+            Input::Buffered(reader) => reader.consume(amt),
+            Input::Direct(reader, fns) => (fns.consume)(reader, amt),
+        }
+    }
 }
 
 /// A row of data with interlace information attached.
@@ -125,6 +244,17 @@ impl<'data> Row<'data> {
     }
 }
 
+impl<'a> Decoder<&'a [u8]> {
+    /// Create a new decoder configuration for a PNG file that is entirely in memory, such as a
+    /// memory-mapped file.
+    ///
+    /// The file is parsed directly from the slice, see [`Decoder::new_buffered`]. Its chunks can be
+    /// located without decoding with [`ChunkIndex`](crate::chunk::ChunkIndex).
+    pub fn from_mmap(data: &'a [u8]) -> Self {
+        Decoder::new_buffered(data)
+    }
+}
+
 impl<R: Read> Decoder<R> {
     /// Create a new decoder configuration with default limits.
     pub fn new(r: R) -> Decoder<R> {
@@ -137,9 +267,51 @@ impl<R: Read> Decoder<R> {
             r,
             transform: Transformations::IDENTITY,
             limits,
+            chunk_limits: Vec::new(),
+            latin1_mode: Latin1Mode::default(),
+            text_decompression_limit: DECOMPRESSION_LIMIT,
+            keyword_check: KeywordCheck::default(),
+            color_transform: None,
+            color_precedence: ColorPrecedence::default(),
+            composite: Composite::default(),
+            bottom_up: false,
+            #[cfg(feature = "parallel")]
+            executor: crate::parallel::Executor::default(),
+            buffered: None,
+            buffer_provider: None,
+            zlib_stream: None,
+            skip_recoverable: false,
+            phase_timings: false,
+            chunk_inventory: false,
         }
     }
 
+    /// Create a new decoder configuration for an input that is buffered already.
+    ///
+    /// The data is parsed directly from the buffer of `r` instead of being copied into a buffer of
+    /// the decoder, and only the bytes that were decoded are consumed from it.
+    ///
+    /// ```
+    /// use std::io::BufReader;
+    /// use png::Decoder;
+    ///
+    /// let file = BufReader::new(std::fs::File::open("tests/pngsuite/basn0g08.png").unwrap());
+    /// let mut reader = Decoder::new_buffered(file).read_info().unwrap();
+    /// let mut buf = vec![0; reader.output_buffer_size()];
+    /// reader.next_frame(&mut buf).unwrap();
+    /// ```
+    pub fn new_buffered(r: R) -> Decoder<R>
+    where
+        R: BufRead,
+    {
+        let mut decoder = Decoder::new(r);
+        decoder.buffered = Some(BufReadFns {
+            fill_buf: R::fill_buf,
+            consume: R::consume,
+        });
+        decoder
+    }
+
     /// Limit resource usage.
     ///
     /// Note that your allocations, e.g. when reading into a pre-allocated buffer, are __NOT__
@@ -167,19 +339,214 @@ impl<R: Read> Decoder<R> {
         self.limits = limits;
     }
 
+    /// Limit the data length of chunks of the given type, independently of the other limits.
+    ///
+    /// Decoding fails with [`DecodingError::LimitsExceeded`] when a longer chunk is encountered,
+    /// see [`StreamingDecoder::set_chunk_limit`]. Setting a limit again for the same chunk type
+    /// replaces the previous one.
+    ///
+    /// ```
+    /// use png::{chunk, Decoder};
+    ///
+    /// let mut decoder = Decoder::new(std::fs::File::open("tests/pngsuite/ct1n0g04.png").unwrap());
+    /// decoder.set_chunk_limit(chunk::tEXt, 16);
+    /// assert!(decoder.read_info().is_err());
+    /// ```
+    pub fn set_chunk_limit(&mut self, chunk_type: chunk::ChunkType, max_len: u32) {
+        self.chunk_limits.push((chunk_type, max_len));
+    }
+
+    /// Set the handling of characters without meaning in the Latin-1 text of `tEXt` and `zTXt`
+    /// chunks.
+    ///
+    /// By default every byte is decoded to the character of the same value. With
+    /// [`Latin1Mode::Strict`] a text chunk with control characters other than the linefeed is an
+    /// error.
+    ///
+    /// ```
+    /// use png::{text_metadata::Latin1Mode, Decoder};
+    ///
+    /// let mut decoder = Decoder::new(std::fs::File::open("tests/pngsuite/ct1n0g04.png").unwrap());
+    /// decoder.set_latin1_mode(Latin1Mode::Strict);
+    /// let reader = decoder.read_info().unwrap();
+    /// assert!(!reader.info().uncompressed_latin1_text.is_empty());
+    /// ```
+    pub fn set_latin1_mode(&mut self, mode: Latin1Mode) {
+        self.latin1_mode = mode;
+    }
+
+    /// Limit the number of bytes that each `zTXt` and `iTXt` chunk decompresses to.
+    ///
+    /// The text is not decompressed while decoding but only when requested, for example by
+    /// [`ZTXtChunk::get_text`](crate::text_metadata::ZTXtChunk::get_text), which then fails for a
+    /// longer text. This is independent of the [`Limits`] and defaults to
+    /// [`DECOMPRESSION_LIMIT`].
+    pub fn set_text_decompression_limit(&mut self, limit: usize) {
+        self.text_decompression_limit = limit;
+    }
+
+    /// Set the handling of text chunks whose keyword violates
+    /// [`check_keyword`](crate::text_metadata::check_keyword).
+    ///
+    /// By default only the length of keywords is checked.
+    pub fn set_keyword_check(&mut self, check: KeywordCheck) {
+        self.keyword_check = check;
+    }
+
+    /// Skip ancillary chunks with recoverable errors instead of failing.
+    ///
+    /// The skipped errors are kept in [`Reader::skipped_errors`]. Unlike
+    /// [`Reader::skip_and_continue`] this also covers the chunks read by [`Decoder::read_info`],
+    /// so a viewer can show an image whose text chunks are damaged.
+    pub fn set_skip_recoverable(&mut self, skip: bool) {
+        self.skip_recoverable = skip;
+    }
+
+    /// Measure the time spent in each phase of decoding, see [`Reader::phase_timings`].
+    ///
+    /// Reading the clock costs a little time for every row and buffer, so this is off by default.
+    pub fn set_phase_timings(&mut self, enabled: bool) {
+        self.phase_timings = enabled;
+    }
+
+    /// Record the type, position, length and CRC of every chunk in [`Info::chunk_inventory`].
+    ///
+    /// The chunks are recorded as they are read. The reader stops at the end of the image data of
+    /// the last frame, the chunks after it are read and recorded by [`Reader::finish`].
+    ///
+    /// ```
+    /// use png::{chunk, Decoder};
+    ///
+    /// let mut decoder = Decoder::new(std::fs::File::open("tests/pngsuite/basn0g08.png").unwrap());
+    /// decoder.set_chunk_inventory(true);
+    /// let mut reader = decoder.read_info().unwrap();
+    /// let mut buf = vec![0; reader.output_buffer_size()];
+    /// reader.next_frame(&mut buf).unwrap();
+    /// assert_eq!(reader.info().chunk_inventory.last().unwrap().chunk_type, chunk::IDAT);
+    /// reader.finish().unwrap();
+    /// let inventory = &reader.info().chunk_inventory;
+    /// assert_eq!(inventory[0].chunk_type, chunk::IHDR);
+    /// assert_eq!(inventory.last().unwrap().chunk_type, chunk::IEND);
+    /// assert!(inventory.iter().all(|chunk| chunk.crc_ok));
+    /// ```
+    pub fn set_chunk_inventory(&mut self, enabled: bool) {
+        self.chunk_inventory = enabled;
+    }
+
+    /// Convert the decoded rows into a color space with an external color management system.
+    ///
+    /// The transform is prepared by [`Decoder::read_info`] and applied to the rows returned by
+    /// [`Reader::next_frame`] and [`Reader::next_row`], see [`ColorTransform`].
+    pub fn set_color_transform<T: ColorTransform + 'static>(
+        &mut self,
+        transform: T,
+        destination: ColorDestination,
+    ) {
+        self.color_transform = Some((Box::new(transform), destination));
+    }
+
+    /// Set the order in which the color space chunks are considered.
+    ///
+    /// This is kept in [`Info::color_precedence`] for [`Info::source_color`], and applies to the
+    /// color transform and to [`Reader::next_frame_linear`] as well.
+    ///
+    /// ```
+    /// use png::{ColorPrecedence, Decoder};
+    /// use std::fs::File;
+    ///
+    /// let mut decoder = Decoder::new(File::open("tests/pngsuite/basn0g01.png").unwrap());
+    /// decoder.set_color_precedence(ColorPrecedence::ICC_FIRST);
+    /// let reader = decoder.read_info().unwrap();
+    /// assert_eq!(reader.info().color_precedence, ColorPrecedence::ICC_FIRST);
+    /// ```
+    pub fn set_color_precedence(&mut self, precedence: ColorPrecedence) {
+        self.color_precedence = precedence;
+    }
+
     /// Reads all meta data until the first IDAT chunk
     pub fn read_info(self) -> Result<Reader<R>, DecodingError> {
-        let mut reader = Reader::new(self.r, StreamingDecoder::new(), self.transform, self.limits);
+        let mut decoder = StreamingDecoder::new();
+        for &(chunk_type, max_len) in &self.chunk_limits {
+            decoder.set_chunk_limit(chunk_type, max_len);
+        }
+        decoder.set_latin1_mode(self.latin1_mode);
+        decoder.set_text_decompression_limit(self.text_decompression_limit);
+        decoder.set_keyword_check(self.keyword_check);
+        decoder.set_color_precedence(self.color_precedence);
+        decoder.set_chunk_inventory(self.chunk_inventory);
+        if let Some(stream) = self.zlib_stream {
+            decoder.inflater = stream;
+        }
+        if self.phase_timings {
+            decoder.timings = Some(PhaseTimings::default());
+        }
+        let input = match self.buffered {
+            Some(fns) => Input::Direct(self.r, fns),
+            None => Input::Buffered(BufReader::with_capacity(CHUNCK_BUFFER_SIZE, self.r)),
+        };
+        let mut reader = Reader::new(input, decoder, self.transform, self.limits);
+        reader.buffers = Buffers::new(self.buffer_provider);
+        reader.decoder.skip_recoverable = self.skip_recoverable;
         reader.init()?;
+        reader.background = resolve_background(self.composite, &reader);
+        reader.bottom_up = self.bottom_up;
+        #[cfg(feature = "parallel")]
+        {
+            reader.executor = self.executor;
+        }
+
+        if let Some((mut transform, destination)) = self.color_transform {
+            let (color_type, bit_depth) = reader.output_color_type();
+            let source = ColorSource::new(reader.info());
+            transform.prepare(&source, destination, color_type, bit_depth)?;
+            reader.color_transform = Some(transform);
+        }
 
         // Check if the output buffer can be represented at all.
         if reader.checked_output_buffer_size().is_none() {
+            debug!("output buffer size of {:?} overflows", reader.info().size());
             return Err(DecodingError::LimitsExceeded);
         }
 
         Ok(reader)
     }
 
+    /// Composite transparent pixels over a background color, producing opaque output.
+    ///
+    /// The color is given in the samples of the image, like the `bKGD` chunk. A palette index
+    /// outside of the palette is black, and an RGB color of a gray image is converted to its
+    /// luma.
+    pub fn set_composite(&mut self, composite: Composite) {
+        self.composite = composite;
+    }
+
+    /// Store the rows of frames bottom-to-top, as for OpenGL textures or BMP files.
+    ///
+    /// This applies to the buffers filled by [`Reader::next_frame`] and
+    /// [`Reader::next_frame_linear`]. Rows read one by one keep their order in the image data.
+    pub fn set_bottom_up(&mut self, bottom_up: bool) {
+        self.bottom_up = bottom_up;
+    }
+
+    /// Set the threads that decode concurrently, see [`parallel`](crate::parallel).
+    ///
+    /// With [`Executor::Sequential`](crate::parallel::Executor::Sequential) all work is done on
+    /// the calling thread.
+    #[cfg(feature = "parallel")]
+    pub fn set_executor(&mut self, executor: crate::parallel::Executor) {
+        self.executor = executor;
+    }
+
+    /// Take the large internal buffers of the reader from `provider`, see [`BufferProvider`].
+    pub fn set_buffer_provider<P: BufferProvider + 'static>(&mut self, provider: P) {
+        self.buffer_provider = Some(Box::new(provider));
+    }
+
+    /// Decompress the image data with `stream`, see [`StreamingDecoder::set_zlib_stream`].
+    pub fn set_zlib_stream<Z: ZlibStream + 'static>(&mut self, stream: Z) {
+        self.zlib_stream = Some(Box::new(stream));
+    }
+
     /// Set the allowed and performed transformations.
     ///
     /// A transformation is a pre-processing on the raw image data modifying content or encoding.
@@ -190,9 +557,14 @@ impl<R: Read> Decoder<R> {
 }
 
 struct ReadDecoder<R: Read> {
-    reader: BufReader<R>,
+    reader: Input<R>,
     decoder: StreamingDecoder,
     at_eof: bool,
+    /// The number of bytes consumed from `reader`.
+    position: u64,
+    skip_recoverable: bool,
+    /// The recoverable errors skipped because of `skip_recoverable`.
+    skipped: Vec<DecodingError>,
 }
 
 impl<R: Read> ReadDecoder<R> {
@@ -201,15 +573,25 @@ impl<R: Read> ReadDecoder<R> {
     fn decode_next(&mut self, image_data: &mut Vec<u8>) -> Result<Option<Decoded>, DecodingError> {
         while !self.at_eof {
             let (consumed, result) = {
-                let buf = self.reader.fill_buf()?;
+                let timer = utils::start_phase(&self.decoder.timings);
+                let buf = self.reader.fill_buf();
+                utils::stop_phase(&mut self.decoder.timings, timer, |t| &mut t.read);
+                let buf = buf?;
                 if buf.is_empty() {
                     return Err(DecodingError::Format(
                         FormatErrorInner::UnexpectedEof.into(),
                     ));
                 }
-                self.decoder.update(buf, image_data)?
+                match self.decoder.update(buf, image_data) {
+                    Ok(update) => update,
+                    Err(err) => {
+                        self.recover(err)?;
+                        continue;
+                    }
+                }
             };
             self.reader.consume(consumed);
+            self.position += consumed as u64;
             match result {
                 Decoded::Nothing => (),
                 Decoded::ImageEnd => self.at_eof = true,
@@ -221,14 +603,24 @@ impl<R: Read> ReadDecoder<R> {
 
     fn finished_decoding(&mut self) -> Result<(), DecodingError> {
         while !self.at_eof {
-            let buf = self.reader.fill_buf()?;
+            let timer = utils::start_phase(&self.decoder.timings);
+            let buf = self.reader.fill_buf();
+            utils::stop_phase(&mut self.decoder.timings, timer, |t| &mut t.read);
+            let buf = buf?;
             if buf.is_empty() {
                 return Err(DecodingError::Format(
                     FormatErrorInner::UnexpectedEof.into(),
                 ));
             }
-            let (consumed, event) = self.decoder.update(buf, &mut vec![])?;
+            let (consumed, event) = match self.decoder.update(buf, &mut vec![]) {
+                Ok(update) => update,
+                Err(err) => {
+                    self.recover(err)?;
+                    continue;
+                }
+            };
             self.reader.consume(consumed);
+            self.position += consumed as u64;
             match event {
                 Decoded::Nothing => (),
                 Decoded::ImageEnd => self.at_eof = true,
@@ -245,14 +637,36 @@ impl<R: Read> ReadDecoder<R> {
         ))
     }
 
-    fn info(&self) -> Option<&Info> {
+    fn info(&self) -> Option<&Info<'_>> {
         self.decoder.info.as_ref()
     }
+
+    /// Skips the chunk of a recoverable error if requested, otherwise returns the error.
+    fn recover(&mut self, err: DecodingError) -> Result<(), DecodingError> {
+        if self.skip_recoverable && self.skip_and_continue() {
+            self.skipped.push(err);
+            Ok(())
+        } else {
+            Err(err)
+        }
+    }
+
+    fn skip_and_continue(&mut self) -> bool {
+        if !self.decoder.skip_and_continue() {
+            return false;
+        }
+        // The bytes decoded before the error are still in the buffer of the reader.
+        let skipped = self.decoder.current_offset() - self.position;
+        self.reader.consume(skipped as usize);
+        self.position += skipped;
+        true
+    }
 }
 
 /// PNG reader (mostly high-level interface)
 ///
-/// Provides a high level that iterates over lines or whole images.
+/// Provides a high level that iterates over lines or whole images. The reader is `Send` if `R`
+/// is, so it can be moved to a worker thread between frames.
 pub struct Reader<R: Read> {
     decoder: ReadDecoder<R>,
     bpp: BytesPerPixel,
@@ -273,6 +687,21 @@ pub struct Reader<R: Read> {
     /// Processed line
     processed: Vec<u8>,
     limits: Limits,
+    /// The color conversion applied to processed lines.
+    color_transform: Option<Box<dyn ColorTransform>>,
+    /// The background color that processed lines are composited over, in output samples.
+    background: Option<[u16; 3]>,
+    /// Whether frames are stored with their last row first.
+    bottom_up: bool,
+    /// Whether the rows of the current frame in `current` were already unfiltered.
+    prefiltered: bool,
+    /// The length and interlace information of a row whose data could not be read yet.
+    pending_row: Option<(usize, InterlaceInfo)>,
+    /// The rows of the frame that `next_frame` wrote before it failed, to resume it.
+    frame_rows: Option<usize>,
+    #[cfg(feature = "parallel")]
+    executor: crate::parallel::Executor,
+    buffers: Buffers,
 }
 
 /// The subframe specific information.
@@ -318,12 +747,15 @@ macro_rules! get_info(
 
 impl<R: Read> Reader<R> {
     /// Creates a new PNG reader
-    fn new(r: R, d: StreamingDecoder, t: Transformations, limits: Limits) -> Reader<R> {
+    fn new(r: Input<R>, d: StreamingDecoder, t: Transformations, limits: Limits) -> Reader<R> {
         Reader {
             decoder: ReadDecoder {
-                reader: BufReader::with_capacity(CHUNCK_BUFFER_SIZE, r),
+                reader: r,
                 decoder: d,
                 at_eof: false,
+                position: 0,
+                skip_recoverable: false,
+                skipped: Vec::new(),
             },
             bpp: BytesPerPixel::One,
             subframe: SubframeInfo::not_yet_init(),
@@ -335,6 +767,15 @@ impl<R: Read> Reader<R> {
             transform: t,
             processed: Vec::new(),
             limits,
+            color_transform: None,
+            background: None,
+            bottom_up: false,
+            prefiltered: false,
+            pending_row: None,
+            frame_rows: None,
+            #[cfg(feature = "parallel")]
+            executor: crate::parallel::Executor::default(),
+            buffers: Buffers::default(),
         }
     }
 
@@ -387,7 +828,8 @@ impl<R: Read> Reader<R> {
             self.subframe = SubframeInfo::new(info);
         }
         self.allocate_out_buf()?;
-        self.prev = vec![0; self.subframe.rowlen];
+        let prev = self.buffers.zeroed(self.subframe.rowlen);
+        self.buffers.release(mem::replace(&mut self.prev, prev));
         Ok(self.output_info())
     }
 
@@ -409,15 +851,73 @@ impl<R: Read> Reader<R> {
     fn reset_current(&mut self) {
         self.current.clear();
         self.scan_start = 0;
+        self.prefiltered = false;
     }
 
     /// Get information on the image.
     ///
     /// The structure will change as new frames of an animated image are decoded.
-    pub fn info(&self) -> &Info {
+    pub fn info(&self) -> &Info<'_> {
         self.decoder.info().unwrap()
     }
 
+    /// Statistics on the compressed image data decoded so far.
+    ///
+    /// After the last frame was read the statistics describe the whole image.
+    pub fn compression_stats(&self) -> &CompressionStats {
+        self.decoder.decoder.compression_stats()
+    }
+
+    /// The offset in the file up to which the data has been decoded.
+    ///
+    /// The reader reads ahead of the decoded data, so the underlying reader is usually at a later
+    /// position. See [`StreamingDecoder::current_offset`].
+    pub fn current_offset(&self) -> u64 {
+        self.decoder.decoder.current_offset()
+    }
+
+    /// Continue after a recoverable error by skipping the chunk in which it occurred.
+    ///
+    /// After an error whose [`severity`](DecodingError::severity) is
+    /// [`ErrorSeverity::Recoverable`], the failed method, such as [`Reader::next_frame`], can be
+    /// called again and continues where it stopped. Returns an error if decoding can not continue.
+    pub fn skip_and_continue(&mut self) -> Result<(), DecodingError> {
+        if self.decoder.skip_and_continue() {
+            Ok(())
+        } else {
+            Err(DecodingError::Parameter(
+                ParameterErrorKind::NotRecoverable.into(),
+            ))
+        }
+    }
+
+    /// The time spent in each phase so far, if enabled with [`Decoder::set_phase_timings`].
+    ///
+    /// The time of [`Decoder::read_info`] is included, the phases of the image data are measured
+    /// per row.
+    pub fn phase_timings(&self) -> Option<PhaseTimings> {
+        self.decoder.decoder.timings
+    }
+
+    /// The errors skipped because of [`Decoder::set_skip_recoverable`], in the order of the file.
+    pub fn skipped_errors(&self) -> &[DecodingError] {
+        &self.decoder.skipped
+    }
+
+    /// Reads the rest of the file up to the image trailer.
+    ///
+    /// The chunks after the image data of the last frame, such as text chunks, are added to the
+    /// [`Info`], and recorded in [`Info::chunk_inventory`] with [`Decoder::set_chunk_inventory`].
+    /// Frames that were not decoded yet are skipped, no frame can be decoded afterwards.
+    pub fn finish(&mut self) -> Result<(), DecodingError> {
+        self.next_frame = SubframeIdx::End;
+        let mut image_data = Vec::new();
+        while self.decoder.decode_next(&mut image_data)?.is_some() {
+            image_data.clear();
+        }
+        Ok(())
+    }
+
     /// Get the subframe index of the current info.
     fn subframe_idx(&self) -> SubframeIdx {
         let info = match self.decoder.info() {
@@ -470,6 +970,14 @@ impl<R: Read> Reader<R> {
     ///
     /// Output lines will be written in row-major, packed matrix with width and height of the read
     /// frame (or subframe), all samples are in big endian byte order where this matters.
+    ///
+    /// The passes of interlaced images are expanded straight into their place in `buf`, without
+    /// an intermediate image, so `buf` may be reused between frames without clearing it.
+    ///
+    /// A non-blocking reader may fail with [`io::ErrorKind::WouldBlock`], after which the call is
+    /// repeated with the same `buf` once more data is available. The rows written before are kept
+    /// and the frame continues where it stopped. This holds for [`Reader::next_row`] as well, but
+    /// not for [`Decoder::read_info`], which needs the header and metadata to be available.
     pub fn next_frame(&mut self, buf: &mut [u8]) -> Result<OutputInfo, DecodingError> {
         // Advance until we've read the info / fcTL for this frame.
         let info = self.init()?;
@@ -485,9 +993,22 @@ impl<R: Read> Reader<R> {
             ));
         }
 
-        self.reset_current();
+        // Continue a frame that failed with an error that can be retried.
+        let rows = match self.frame_rows {
+            Some(rows) => rows,
+            None => {
+                self.reset_current();
+                0
+            }
+        };
+        self.frame_rows = Some(rows);
         let width = self.info().width;
+        let bottom_up = self.bottom_up;
         if self.info().interlaced {
+            #[cfg(feature = "parallel")]
+            if !self.prefiltered {
+                self.unfilter_passes()?;
+            }
             while let Some(InterlacedRow {
                 data: row,
                 interlace,
@@ -498,13 +1019,28 @@ impl<R: Read> Reader<R> {
                     InterlaceInfo::Adam7 { line, pass, .. } => (line, pass),
                     InterlaceInfo::Null => unreachable!("expected interlace information"),
                 };
-                let samples = color_type.samples() as u8;
-                utils::expand_pass(buf, width, row, pass, line, samples * (bit_depth as u8));
+                let bits_pp = color_type.samples() as u8 * (bit_depth as u8);
+                if bottom_up {
+                    let prog_line = info.height - 1 - crate::adam7::image_row(pass, line);
+                    utils::expand_pass_to_row(buf, width, row, pass, prog_line, bits_pp);
+                } else {
+                    utils::expand_pass(buf, width, row, pass, line, bits_pp);
+                }
+            }
+        } else if self.bottom_up {
+            let mut end = info.buffer_size().saturating_sub(rows * info.line_size);
+            while let Some(Row { data: row, .. }) = self.next_row()? {
+                let start = end.saturating_sub(info.line_size);
+                let len = row.len().min(end - start);
+                buf[start..start + len].copy_from_slice(&row[..len]);
+                end = start;
+                self.frame_rows = self.frame_rows.map(|rows| rows + 1);
             }
         } else {
-            let mut len = 0;
+            let mut len = rows * info.line_size;
             while let Some(Row { data: row, .. }) = self.next_row()? {
                 len += (&mut buf[len..]).write(row)?;
+                self.frame_rows = self.frame_rows.map(|rows| rows + 1);
             }
         }
         // Advance over the rest of data for this (sub-)frame.
@@ -513,18 +1049,163 @@ impl<R: Read> Reader<R> {
         }
         // Advance our state to expect the next frame.
         self.finished_frame();
+        self.prefiltered = false;
+        self.frame_rows = None;
+
+        Ok(info)
+    }
+
+    /// Decompresses all passes of an interlaced frame and unfilters them concurrently.
+    ///
+    /// The passes are independent after decompression. The unfiltered rows stay in `current` and
+    /// are then processed one by one as usual. Frames whose raw data exceeds the memory limit are
+    /// unfiltered while reading instead.
+    #[cfg(feature = "parallel")]
+    fn unfilter_passes(&mut self) -> Result<(), DecodingError> {
+        let (width, height) = (self.subframe.width, self.subframe.height);
+        let passes: Vec<(usize, usize)> = (1..=7)
+            .map(|pass| match crate::adam7::pass_size(pass, width, height) {
+                (0, _) => (0, 0),
+                (width, rows) => (self.info().raw_row_length_from_width(width), rows as usize),
+            })
+            .collect();
+        let total = passes.iter().try_fold(0usize, |total, &(rowlen, rows)| {
+            total.checked_add(rowlen.checked_mul(rows)?)
+        });
+        let total = match total {
+            Some(total) if total <= self.limits.bytes => total,
+            _ => return Ok(()),
+        };
+
+        let additional = total.saturating_sub(self.current.len());
+        self.buffers.reserve(&mut self.current, additional);
+        while self.current.len() < total {
+            if self.subframe.consumed_and_flushed {
+                return Err(DecodingError::Format(
+                    FormatErrorInner::NoMoreImageData.into(),
+                ));
+            }
+            match self.decoder.decode_next(&mut self.current)? {
+                Some(Decoded::ImageDataFlushed) => self.subframe.consumed_and_flushed = true,
+                None => {
+                    return Err(DecodingError::Format(
+                        FormatErrorInner::UnexpectedEndOfChunk.into(),
+                    ))
+                }
+                _ => {}
+            }
+        }
+
+        let mut start = 0;
+        let mut jobs = Vec::with_capacity(passes.len());
+        for &(rowlen, rows) in &passes {
+            let end = start + rowlen * rows;
+            jobs.push((self.current[start..end].to_vec(), rowlen, self.bpp));
+            start = end;
+        }
+
+        let timer = utils::start_phase(&self.decoder.decoder.timings);
+        let results = self.executor.map(jobs, unfilter_pass);
+        utils::stop_phase(&mut self.decoder.decoder.timings, timer, |t| {
+            &mut t.unfilter
+        });
+        let mut start = 0;
+        let stats = &mut self.decoder.decoder.stats.filter_types;
+        for result in results {
+            let (data, counts) = result?;
+            self.current[start..start + data.len()].copy_from_slice(&data);
+            start += data.len();
+            for (total, count) in stats.iter_mut().zip(&counts) {
+                *total += count;
+            }
+        }
+        self.prefiltered = true;
+        Ok(())
+    }
+
+    /// Decodes the next frame into linear light, one float for each sample.
+    ///
+    /// The transfer function is the one of the `cICP` chunk, such as PQ or HLG for HDR images,
+    /// and otherwise the gamma of the `gAMA` chunk or that of sRGB, subject to the precedence of
+    /// [`Info::source_color`]. An ICC profile is treated as sRGB. Alpha samples are scaled to
+    /// `0.0..=1.0` but not converted. Images with palette or with samples smaller than 8 bits
+    /// require [`Transformations::EXPAND`].
+    ///
+    /// The buffer holds at least [`Reader::output_buffer_size`] divided by the bytes of a sample.
+    pub fn next_frame_linear(
+        &mut self,
+        buf: &mut [f32],
+        scale: LinearScale,
+    ) -> Result<OutputInfo, DecodingError> {
+        self.init()?;
+        let (color_type, bit_depth) = self.output_color_type();
+        if color_type == ColorType::Indexed || (bit_depth as u8) < 8 {
+            return Err(DecodingError::Parameter(
+                ParameterErrorKind::UnexpandedLinearOutput.into(),
+            ));
+        }
+        let bytes_per_sample = if bit_depth == BitDepth::Sixteen { 2 } else { 1 };
+        let samples = self.output_buffer_size() / bytes_per_sample;
+        if buf.len() < samples {
+            return Err(DecodingError::Parameter(
+                ParameterErrorKind::ImageBufferSize {
+                    expected: buf.len(),
+                    actual: samples,
+                }
+                .into(),
+            ));
+        }
+
+        let mut data = self.buffers.zeroed(self.output_buffer_size());
+        let result = self.next_frame(&mut data);
+        if let Ok(ref info) = result {
+            let linearizer = Linearizer::new(self.info(), color_type, bit_depth, scale);
+            let samples = info.buffer_size() / bytes_per_sample;
+            let timer = utils::start_phase(&self.decoder.decoder.timings);
+            linearizer.convert(&data[..info.buffer_size()], &mut buf[..samples]);
+            utils::stop_phase(&mut self.decoder.decoder.timings, timer, |t| {
+                &mut t.color_transform
+            });
+        }
+        self.buffers.release(data);
+        result
+    }
 
+    /// Decodes the next frame into typed pixels, see [`pixel`](crate::pixel).
+    ///
+    /// The pixel type must match [`Reader::output_color_type`]. Samples of 16 bits are converted
+    /// to native endianness.
+    #[cfg(feature = "bytemuck")]
+    pub fn next_frame_pixels<P: crate::pixel::Pixel>(
+        &mut self,
+        buf: &mut [P],
+    ) -> Result<OutputInfo, DecodingError> {
+        use crate::pixel::Sample;
+
+        self.init()?;
+        let (color_type, bit_depth) = self.output_color_type();
+        if (color_type, bit_depth) != (P::COLOR_TYPE, P::Sample::DEPTH) {
+            return Err(DecodingError::Parameter(
+                ParameterErrorKind::PixelType.into(),
+            ));
+        }
+        let info = self.next_frame(bytemuck::cast_slice_mut(buf))?;
+        let pixels = info.buffer_size() / mem::size_of::<P>();
+        crate::pixel::swap_big_endian(&mut buf[..pixels]);
         Ok(info)
     }
 
     /// Returns the next processed row of the image
-    pub fn next_row(&mut self) -> Result<Option<Row>, DecodingError> {
+    ///
+    /// After an error of the reader, such as [`io::ErrorKind::WouldBlock`], the call can be
+    /// repeated to get the same row.
+    pub fn next_row(&mut self) -> Result<Option<Row<'_>>, DecodingError> {
         self.next_interlaced_row()
             .map(|v| v.map(|v| Row { data: v.data }))
     }
 
     /// Returns the next processed row of the image
-    pub fn next_interlaced_row(&mut self) -> Result<Option<InterlacedRow>, DecodingError> {
+    pub fn next_interlaced_row(&mut self) -> Result<Option<InterlacedRow<'_>>, DecodingError> {
         match self.next_interlaced_row_impl() {
             Err(err) => Err(err),
             Ok(None) => Ok(None),
@@ -533,17 +1214,27 @@ impl<R: Read> Reader<R> {
     }
 
     /// Fetch the next interlaced row and filter it according to our own transformations.
-    fn next_interlaced_row_impl(&mut self) -> Result<Option<InterlacedRow>, DecodingError> {
+    fn next_interlaced_row_impl(&mut self) -> Result<Option<InterlacedRow<'_>>, DecodingError> {
         use crate::common::ColorType::*;
         let transform = self.transform;
 
-        if transform == Transformations::IDENTITY {
+        if transform == Transformations::IDENTITY
+            && self.color_transform.is_none()
+            && self.background.is_none()
+        {
             return self.next_raw_interlaced_row();
         }
 
         // swap buffer to circumvent borrow issues
         let mut buffer = mem::take(&mut self.processed);
-        let (got_next, adam7) = if let Some(row) = self.next_raw_interlaced_row()? {
+        let row = match self.next_raw_interlaced_row() {
+            Ok(row) => row,
+            Err(err) => {
+                self.processed = buffer;
+                return Err(err);
+            }
+        };
+        let (got_next, adam7) = if let Some(row) = row {
             (&mut buffer[..]).write_all(row.data)?;
             (true, row.interlace)
         } else {
@@ -560,6 +1251,9 @@ impl<R: Read> Reader<R> {
             let info = self.info();
             (info.color_type, info.bit_depth as u8, info.trns.is_some())
         };
+        let background = self
+            .background
+            .map(|background| (background, self.unblended_color_type()));
         let output_buffer = if let InterlaceInfo::Adam7 { width, .. } = adam7 {
             let width = self
                 .line_size(width)
@@ -569,6 +1263,7 @@ impl<R: Read> Reader<R> {
             &mut *self.processed
         };
 
+        let timer = utils::start_phase(&self.decoder.decoder.timings);
         let mut len = output_buffer.len();
         if transform.contains(Transformations::EXPAND) {
             match color_type {
@@ -580,9 +1275,9 @@ impl<R: Read> Reader<R> {
                     let channels = color_type.samples();
                     let trns = get_info!(self).trns.as_ref().unwrap();
                     if bit_depth == 8 {
-                        utils::expand_trns_line(output_buffer, &*trns, channels);
+                        utils::expand_trns_line(output_buffer, trns, channels);
                     } else {
-                        utils::expand_trns_line16(output_buffer, &*trns, channels);
+                        utils::expand_trns_line16(output_buffer, trns, channels);
                     }
                 }
                 _ => (),
@@ -596,6 +1291,21 @@ impl<R: Read> Reader<R> {
             }
         }
 
+        if let Some((background, (color_type, bit_depth))) = background {
+            len = composite_line(&mut output_buffer[..len], color_type, bit_depth, background);
+        }
+        utils::stop_phase(&mut self.decoder.decoder.timings, timer, |t| &mut t.expand);
+
+        if let Some(color_transform) = &mut self.color_transform {
+            if color_type != Indexed || transform.contains(Transformations::EXPAND) {
+                let timer = utils::start_phase(&self.decoder.decoder.timings);
+                color_transform.transform_row(&mut output_buffer[..len]);
+                utils::stop_phase(&mut self.decoder.decoder.timings, timer, |t| {
+                    &mut t.color_transform
+                });
+            }
+        }
+
         Ok(Some(InterlacedRow {
             data: &output_buffer[..len],
             interlace: adam7,
@@ -605,6 +1315,18 @@ impl<R: Read> Reader<R> {
     /// Returns the color type and the number of bits per sample
     /// of the data returned by `Reader::next_row` and Reader::frames`.
     pub fn output_color_type(&self) -> (ColorType, BitDepth) {
+        let (color_type, bit_depth) = self.unblended_color_type();
+        match color_type {
+            ColorType::GrayscaleAlpha if self.background.is_some() => {
+                (ColorType::Grayscale, bit_depth)
+            }
+            ColorType::Rgba if self.background.is_some() => (ColorType::Rgb, bit_depth),
+            _ => (color_type, bit_depth),
+        }
+    }
+
+    /// The output color type before compositing over a background color.
+    fn unblended_color_type(&self) -> (ColorType, BitDepth) {
         use crate::common::ColorType::*;
         let t = self.transform;
         let info = self.info();
@@ -643,21 +1365,56 @@ impl<R: Read> Reader<R> {
     fn validate_buffer_sizes(&self) -> Result<(), DecodingError> {
         // Check if the decoding buffer of a single raw line has a valid size.
         if self.info().checked_raw_row_length().is_none() {
+            debug!("row length of width {} overflows", self.info().width);
             return Err(DecodingError::LimitsExceeded);
         }
 
         // Check if the output buffer has a valid size.
         if self.checked_output_buffer_size().is_none() {
+            debug!("output buffer size of {:?} overflows", self.info().size());
             return Err(DecodingError::LimitsExceeded);
         }
 
         Ok(())
     }
 
-    fn checked_output_buffer_size(&self) -> Option<usize> {
+    /// Returns the number of bytes required to hold a deinterlaced image, like
+    /// [`Reader::output_buffer_size`], or `None` if it does not fit into `usize`.
+    pub fn checked_output_buffer_size(&self) -> Option<usize> {
         let (width, height) = self.info().size();
-        let (color, depth) = self.output_color_type();
-        let rowlen = color.checked_raw_row_length(depth, width)? - 1;
+        self.checked_output_size(width, height)
+    }
+
+    /// Returns the number of bytes of the current frame that [`Reader::next_frame`] writes, which
+    /// is smaller than the buffer for frames of an animation that do not cover the whole image.
+    /// The current frame is the one of the frame control read last, see [`Info::frame_control`].
+    ///
+    /// Returns `None` if it does not fit into `usize`.
+    pub fn checked_frame_buffer_size(&self) -> Option<usize> {
+        self.checked_output_size(self.subframe.width, self.subframe.height)
+    }
+
+    /// Returns the total number of bytes of the rows that [`Reader::next_interlaced_row`] returns
+    /// for the current frame, which are the shorter rows of each pass for interlaced images.
+    ///
+    /// Returns `None` if it does not fit into `usize`.
+    pub fn checked_interlaced_buffer_size(&self) -> Option<usize> {
+        let (width, height) = (self.subframe.width, self.subframe.height);
+        if !self.info().interlaced {
+            return self.checked_output_size(width, height);
+        }
+        (1..=7).try_fold(0usize, |total, pass| {
+            let (width, height) = crate::adam7::pass_size(pass, width, height);
+            if width == 0 {
+                Some(total)
+            } else {
+                total.checked_add(self.checked_output_size(width, height)?)
+            }
+        })
+    }
+
+    fn checked_output_size(&self, width: u32, height: u32) -> Option<usize> {
+        let rowlen = self.checked_output_line_size(width)?;
         let height: usize = std::convert::TryFrom::try_from(height).ok()?;
         rowlen.checked_mul(height)
     }
@@ -668,6 +1425,13 @@ impl<R: Read> Reader<R> {
         color.raw_row_length_from_width(depth, width) - 1
     }
 
+    /// Returns the number of bytes required to hold a deinterlaced row, or `None` if it does not
+    /// fit into `usize`.
+    pub fn checked_output_line_size(&self, width: u32) -> Option<usize> {
+        let (color, depth) = self.output_color_type();
+        Some(color.checked_raw_row_length(depth, width)? - 1)
+    }
+
     /// Returns the number of bytes required to decode a deinterlaced row.
     fn line_size(&self, width: u32) -> Option<usize> {
         use crate::common::ColorType::*;
@@ -702,8 +1466,16 @@ impl<R: Read> Reader<R> {
         let buflen = match self.line_size(width) {
             Some(buflen) if buflen <= bytes => buflen,
             // Should we differentiate between platform limits and others?
-            _ => return Err(DecodingError::LimitsExceeded),
+            _ => {
+                debug!(
+                    "row of width {} exceeds the limit of {} bytes",
+                    width, bytes
+                );
+                return Err(DecodingError::LimitsExceeded);
+            }
         };
+        let additional = buflen.saturating_sub(self.processed.len());
+        self.buffers.reserve(&mut self.processed, additional);
         self.processed.resize(buflen, 0u8);
         Ok(())
     }
@@ -731,12 +1503,21 @@ impl<R: Read> Reader<R> {
     /// The scanline is filtered against the previous scanline according to the specification.
     fn next_raw_interlaced_row(&mut self) -> Result<Option<InterlacedRow<'_>>, DecodingError> {
         let bpp = self.bpp;
-        let (rowlen, passdata) = match self.next_pass() {
+        let (rowlen, passdata) = match self.pending_row.take().or_else(|| self.next_pass()) {
             Some((rowlen, passdata)) => (rowlen, passdata),
             None => return Ok(None),
         };
         loop {
-            if self.current.len() - self.scan_start >= rowlen {
+            if self.prefiltered && self.current.len() - self.scan_start >= rowlen {
+                let row = &self.current[self.scan_start..];
+                self.prev[..rowlen].copy_from_slice(&row[..rowlen]);
+                self.scan_start += rowlen;
+
+                return Ok(Some(InterlacedRow {
+                    data: &self.prev[1..rowlen],
+                    interlace: passdata,
+                }));
+            } else if self.current.len() - self.scan_start >= rowlen {
                 let row = &mut self.current[self.scan_start..];
                 let filter = match FilterType::from_u8(row[0]) {
                     None => {
@@ -747,10 +1528,14 @@ impl<R: Read> Reader<R> {
                     }
                     Some(filter) => filter,
                 };
+                self.decoder.decoder.stats.filter_types[filter as usize] += 1;
 
-                if let Err(message) =
-                    unfilter(filter, bpp, &self.prev[1..rowlen], &mut row[1..rowlen])
-                {
+                let timer = utils::start_phase(&self.decoder.decoder.timings);
+                let result = unfilter(filter, bpp, &self.prev[1..rowlen], &mut row[1..rowlen]);
+                utils::stop_phase(&mut self.decoder.decoder.timings, timer, |t| {
+                    &mut t.unfilter
+                });
+                if let Err(message) = result {
                     return Err(DecodingError::Format(
                         FormatErrorInner::BadFilter(message).into(),
                     ));
@@ -776,7 +1561,14 @@ impl<R: Read> Reader<R> {
                     self.scan_start = 0;
                 }
 
-                let val = self.decoder.decode_next(&mut self.current)?;
+                let val = match self.decoder.decode_next(&mut self.current) {
+                    Ok(val) => val,
+                    Err(err) => {
+                        // Read the same row when the call is repeated.
+                        self.pending_row = Some((rowlen, passdata));
+                        return Err(err);
+                    }
+                };
                 match val {
                     Some(Decoded::ImageData) => {}
                     Some(Decoded::ImageDataFlushed) => {
@@ -798,6 +1590,14 @@ impl<R: Read> Reader<R> {
     }
 }
 
+impl<R: Read> Drop for Reader<R> {
+    fn drop(&mut self) {
+        self.buffers.release(mem::take(&mut self.prev));
+        self.buffers.release(mem::take(&mut self.current));
+        self.buffers.release(mem::take(&mut self.processed));
+    }
+}
+
 impl SubframeInfo {
     fn not_yet_init() -> Self {
         SubframeInfo {
@@ -834,6 +1634,89 @@ impl SubframeInfo {
     }
 }
 
+/// The background color of a composite in the samples of the output, for outputs with alpha.
+fn resolve_background<R: Read>(composite: Composite, reader: &Reader<R>) -> Option<[u16; 3]> {
+    let info = reader.info();
+    let color = match composite {
+        Composite::None => return None,
+        Composite::Background(fallback) => info.background_color.unwrap_or(fallback),
+        Composite::Color(color) => color,
+    };
+    let (color_type, bit_depth) = reader.unblended_color_type();
+    if !matches!(color_type, ColorType::GrayscaleAlpha | ColorType::Rgba) {
+        return None;
+    }
+
+    let (rgb, depth) = match color {
+        BackgroundColor::Indexed(index) => {
+            let index = usize::from(index);
+            let rgb = info
+                .palette
+                .as_deref()
+                .and_then(|palette| palette.get(3 * index..3 * index + 3))
+                .map_or([0; 3], |rgb| [rgb[0].into(), rgb[1].into(), rgb[2].into()]);
+            (rgb, 8)
+        }
+        BackgroundColor::Gray(gray) => ([gray; 3], info.bit_depth as u8),
+        BackgroundColor::Rgb(red, green, blue) => ([red, green, blue], info.bit_depth as u8),
+    };
+    let depth = if info.color_type == ColorType::Indexed {
+        8
+    } else {
+        depth
+    };
+    let source_max = (1u32 << depth) - 1;
+    let output_max = (1u32 << bit_depth as u8) - 1;
+    let scale = |sample: u16| {
+        let sample = u32::from(sample).min(source_max);
+        ((sample * output_max + source_max / 2) / source_max) as u16
+    };
+    let [red, green, blue] = [scale(rgb[0]), scale(rgb[1]), scale(rgb[2])];
+    if color_type == ColorType::GrayscaleAlpha && !(red == green && green == blue) {
+        let luma = (2126 * u32::from(red) + 7152 * u32::from(green) + 722 * u32::from(blue) + 5000)
+            / 10000;
+        Some([luma as u16; 3])
+    } else {
+        Some([red, green, blue])
+    }
+}
+
+/// Composites a line with alpha over the background in place, returning the opaque length.
+fn composite_line(
+    buffer: &mut [u8],
+    color_type: ColorType,
+    bit_depth: BitDepth,
+    background: [u16; 3],
+) -> usize {
+    let channels = color_type.samples();
+    let bytes = if bit_depth == BitDepth::Sixteen { 2 } else { 1 };
+    let max = (1u32 << (8 * bytes)) - 1;
+    let read = |buffer: &[u8], index: usize| match bytes {
+        2 => u32::from(u16::from_be_bytes([
+            buffer[2 * index],
+            buffer[2 * index + 1],
+        ])),
+        _ => u32::from(buffer[index]),
+    };
+
+    let pixels = buffer.len() / (channels * bytes);
+    for pixel in 0..pixels {
+        let alpha = read(buffer, pixel * channels + channels - 1);
+        for (channel, &background) in background.iter().enumerate().take(channels - 1) {
+            let color = read(buffer, pixel * channels + channel);
+            let blended = (color * alpha + u32::from(background) * (max - alpha) + max / 2) / max;
+            // The opaque pixels are never behind the ones still to be read.
+            let index = pixel * (channels - 1) + channel;
+            match bytes {
+                2 => buffer[2 * index..2 * index + 2]
+                    .copy_from_slice(&(blended as u16).to_be_bytes()),
+                _ => buffer[index] = blended as u8,
+            }
+        }
+    }
+    pixels * (channels - 1) * bytes
+}
+
 fn expand_paletted(buffer: &mut [u8], info: &Info) -> Result<(), DecodingError> {
     if let Some(palette) = info.palette.as_ref() {
         if let BitDepth::Sixteen = info.bit_depth {
@@ -879,6 +1762,29 @@ fn expand_paletted(buffer: &mut [u8], info: &Info) -> Result<(), DecodingError>
     }
 }
 
+/// Unfilters the rows of one interlacing pass, returning them with the count of each filter type.
+#[cfg(feature = "parallel")]
+fn unfilter_pass(
+    (mut data, rowlen, bpp): (Vec<u8>, usize, BytesPerPixel),
+) -> Result<(Vec<u8>, [u64; 5]), DecodingError> {
+    let mut counts = [0; 5];
+    if rowlen == 0 {
+        return Ok((data, counts));
+    }
+    let mut prev = vec![0; rowlen];
+    for row in data.chunks_exact_mut(rowlen) {
+        let filter = FilterType::from_u8(row[0]).ok_or_else(|| {
+            DecodingError::Format(FormatErrorInner::UnknownFilterMethod(row[0]).into())
+        })?;
+        counts[filter as usize] += 1;
+        unfilter(filter, bpp, &prev[1..], &mut row[1..]).map_err(|message| {
+            DecodingError::Format(FormatErrorInner::BadFilter(message).into())
+        })?;
+        prev.copy_from_slice(row);
+    }
+    Ok((data, counts))
+}
+
 fn expand_gray_u8(buffer: &mut [u8], info: &Info) {
     let rescale = true;
     let scaling_factor = if rescale {
@@ -940,6 +1846,64 @@ mod tests {
         }
     }
 
+    /// Encode a 1x1 grayscale image with the given chunks before the image data.
+    fn with_chunks(chunks: &[(crate::chunk::ChunkType, &[u8])]) -> Vec<u8> {
+        let mut png = Vec::new();
+        {
+            let encoder = crate::Encoder::new(&mut png, 1, 1);
+            let mut writer = encoder.write_header().unwrap();
+            for &(kind, data) in chunks {
+                writer.write_chunk(kind, data).unwrap();
+            }
+            writer.write_image_data(&[0]).unwrap();
+        }
+        png
+    }
+
+    #[test]
+    fn output_info_shape() {
+        use super::OutputInfo;
+        use crate::{BitDepth, ColorType};
+        let info = |color_type, bit_depth| OutputInfo {
+            width: 3,
+            height: 2,
+            color_type,
+            bit_depth,
+            line_size: 0,
+        };
+        assert_eq!(
+            info(ColorType::Grayscale, BitDepth::Eight).shape(),
+            Some([2, 3, 1])
+        );
+        assert_eq!(
+            info(ColorType::GrayscaleAlpha, BitDepth::Eight).shape(),
+            Some([2, 3, 2])
+        );
+        assert_eq!(
+            info(ColorType::Rgba, BitDepth::Sixteen).shape(),
+            Some([2, 3, 4])
+        );
+        assert_eq!(info(ColorType::Grayscale, BitDepth::Four).shape(), None);
+        assert_eq!(info(ColorType::Indexed, BitDepth::One).shape(), None);
+    }
+
+    #[test]
+    fn icc_profile_is_complete() {
+        let profile: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+        let mut iccp = b"profile\0\0".to_vec();
+        iccp.extend(deflate::deflate_bytes_zlib(&profile));
+        let mut png = Vec::new();
+        {
+            let encoder = crate::Encoder::new(&mut png, 1, 1);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_chunk(crate::chunk::iCCP, &iccp).unwrap();
+            writer.write_image_data(&[0]).unwrap();
+        }
+
+        let reader = Decoder::new(&png[..]).read_info().unwrap();
+        assert_eq!(reader.info().icc_profile.as_deref(), Some(&profile[..]));
+    }
+
     #[test]
     fn no_data_dup_on_finish() {
         const IMG: &[u8] = include_bytes!(concat!(
@@ -960,4 +1924,1428 @@ mod tests {
 
         assert_eq!(discriminant(&normal), discriminant(&smal));
     }
+
+    #[test]
+    fn text_decompression_limit() {
+        let mut png = Vec::new();
+        {
+            let mut encoder = crate::Encoder::new(&mut png, 1, 1);
+            let text = "0".repeat(1 << 16);
+            encoder
+                .add_ztxt_chunk("zeros".into(), text.clone())
+                .unwrap();
+            let mut itxt = crate::text_metadata::ITXtChunk::new("zeros", text);
+            itxt.compressed = true;
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_text_chunk(&itxt).unwrap();
+            writer.write_image_data(&[0]).unwrap();
+        }
+
+        let mut decoder = Decoder::new(&png[..]);
+        decoder.set_text_decompression_limit(1 << 10);
+        let reader = decoder.read_info().unwrap();
+        let info = reader.info();
+        assert!(info.compressed_latin1_text[0].get_text().is_err());
+        assert!(info.utf8_text[0].get_text().is_err());
+        assert!(info.text_value("zeros").is_err());
+
+        let mut ztxt = info.compressed_latin1_text[0].clone();
+        ztxt.decompress_text_with_limit(1 << 16).unwrap();
+        assert_eq!(ztxt.get_text().unwrap().len(), 1 << 16);
+
+        let reader = Decoder::new(&png[..]).read_info().unwrap();
+        assert_eq!(
+            reader.info().utf8_text[0].get_text().unwrap().len(),
+            1 << 16
+        );
+    }
+
+    #[test]
+    fn gamma_lut() {
+        use crate::{BitDepth, ColorType, GammaLut, ScaledFloat};
+
+        let decoder = Decoder::new(std::fs::File::open("tests/pngsuite/g04n2c08.png").unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let gamma = reader.info().source_gamma.unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut buf).unwrap();
+
+        let lut = GammaLut::new(gamma, 2.2, BitDepth::Eight);
+        assert!(!lut.is_identity());
+        let mut corrected = buf.clone();
+        lut.apply(ColorType::Rgb, &mut corrected);
+        let exponent = 1.0 / (gamma.into_value() * 2.2);
+        for (&sample, &value) in buf.iter().zip(&corrected) {
+            let expected = (f32::from(sample) / 255.0).powf(exponent) * 255.0;
+            assert!((f32::from(value) - expected).abs() <= 0.5 + 1e-3);
+        }
+
+        let lut = GammaLut::new(ScaledFloat::new(0.5), 2.0, BitDepth::Sixteen);
+        assert!(lut.is_identity());
+        let lut = GammaLut::new(ScaledFloat::new(1.0), 2.0, BitDepth::Sixteen);
+        let mut rgba = [0x40, 0, 0xff, 0xff, 0, 0, 0x40, 0];
+        lut.apply(ColorType::GrayscaleAlpha, &mut rgba);
+        assert_eq!(rgba, [0x80, 0, 0xff, 0xff, 0, 0, 0x40, 0]);
+
+        let lut = GammaLut::new(ScaledFloat::new(1.0), 0.5, BitDepth::Two);
+        assert_eq!(lut.table(), [0, 0, 1, 3]);
+        let mut gray = [0b00_01_10_11];
+        lut.apply(ColorType::Grayscale, &mut gray);
+        assert_eq!(gray, [0b00_00_01_11]);
+        lut.apply(ColorType::Indexed, &mut gray);
+        assert_eq!(gray, [0b00_00_01_11]);
+    }
+
+    #[test]
+    fn chromaticity_matrix() {
+        use crate::SourceChromaticities;
+
+        let decoder = Decoder::new(std::fs::File::open("tests/pngsuite/ccwn2c08.png").unwrap());
+        let reader = decoder.read_info().unwrap();
+        let chrm = reader.info().source_chromaticities.unwrap();
+        assert!(chrm.is_srgb(1e-4));
+        let xyz = chrm.rgb_to_xyz().unwrap();
+        let white = chrm.white;
+        let (x, y) = (white.0.into_value(), white.1.into_value());
+        let expected = [x / y, 1.0, (1.0 - x - y) / y];
+        for (row, &value) in xyz.iter().zip(&expected) {
+            assert!((row.iter().sum::<f32>() - value).abs() < 1e-4);
+        }
+
+        let srgb = crate::srgb::substitute_chromaticities();
+        assert!(srgb.is_srgb(0.0));
+        let rec709 =
+            SourceChromaticities::new((0.3127, 0.3290), (0.64, 0.33), (0.30, 0.60), (0.15, 0.06));
+        assert!(rec709.is_srgb(1e-4));
+        let rec2020 = SourceChromaticities::new(
+            (0.3127, 0.3290),
+            (0.708, 0.292),
+            (0.170, 0.797),
+            (0.131, 0.046),
+        );
+        assert!(!rec2020.is_srgb(1e-4));
+        assert!(rec2020.approx_eq(&srgb, 0.2));
+        let xyz = srgb.rgb_to_xyz().unwrap();
+        let reference = [
+            [0.4124, 0.3576, 0.1805],
+            [0.2126, 0.7152, 0.0722],
+            [0.0193, 0.1192, 0.9505],
+        ];
+        for (row, expected) in xyz.iter().zip(&reference) {
+            for (value, expected) in row.iter().zip(expected) {
+                assert!((value - expected).abs() < 1e-3);
+            }
+        }
+
+        let degenerate =
+            SourceChromaticities::new((0.3127, 0.3290), (0.2, 0.2), (0.3, 0.3), (0.4, 0.4));
+        assert_eq!(degenerate.rgb_to_xyz(), None);
+        let zero_y = SourceChromaticities::new((0.3, 0.0), (0.64, 0.33), (0.3, 0.6), (0.15, 0.06));
+        assert_eq!(zero_y.rgb_to_xyz(), None);
+    }
+
+    #[test]
+    fn palette_colors() {
+        use crate::{Palette, Transformations};
+
+        let file = std::fs::File::open("tests/pngsuite/tbbn3p08.png").unwrap();
+        let mut decoder = Decoder::new(file);
+        decoder.set_transformations(Transformations::EXPAND);
+        let mut reader = decoder.read_info().unwrap();
+        let palette = reader.info().palette_colors().unwrap();
+        assert!(palette.has_alpha());
+        let colors: Vec<_> = palette.iter_rgba().collect();
+        assert_eq!(colors.len(), palette.len());
+
+        // Expanding the indices gives the same colors.
+        let indices = {
+            let file = std::fs::File::open("tests/pngsuite/tbbn3p08.png").unwrap();
+            let mut reader = Decoder::new(file).read_info().unwrap();
+            let mut buf = vec![0; reader.output_buffer_size()];
+            reader.next_frame(&mut buf).unwrap();
+            buf
+        };
+        let mut buf = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut buf).unwrap();
+        for (&index, rgba) in indices.iter().zip(buf.chunks(4)) {
+            assert_eq!(colors[usize::from(index)][..], *rgba);
+        }
+
+        let palette = Palette::new(&[1, 2, 3, 4, 5, 6, 7], Some(&[0x80, 0, 0]));
+        assert_eq!(palette.len(), 2);
+        assert_eq!(palette.get_rgba(0), Some([1, 2, 3, 0x80]));
+        assert_eq!(palette.alpha(1), Some(0));
+        assert_eq!(palette.get(2), None);
+        assert_eq!(palette.alpha(2), None);
+        let opaque = Palette::new(&[1, 2, 3], None);
+        assert!(!opaque.has_alpha());
+        assert_eq!(opaque.iter_rgba().collect::<Vec<_>>(), [[1, 2, 3, 0xff]]);
+    }
+
+    #[test]
+    fn transparency() {
+        use crate::{BitDepth, ColorType, Info, Transparency};
+
+        let decode = |name: &str| {
+            let file = std::fs::File::open(format!("tests/pngsuite/{}.png", name)).unwrap();
+            Decoder::new(file).read_info().unwrap()
+        };
+        let check = |name: &str, expected: Option<Transparency>| {
+            assert_eq!(decode(name).info().transparency(), expected);
+        };
+        check("tbbn0g04", Some(Transparency::GrayKey(15)));
+        check("tbwn0g16", Some(Transparency::GrayKey(0xffff)));
+        check("tbrn2c08", Some(Transparency::RgbKey(255, 255, 255)));
+        let white = 0xffff;
+        check("tbbn2c16", Some(Transparency::RgbKey(white, white, white)));
+        check("tbbn3p08", Some(Transparency::PaletteAlpha(&[0])));
+        check("basn2c08", None);
+
+        // The raw chunk data as set for the encoder has two bytes per sample.
+        let mut info = Info::with_size(1, 1);
+        info.bit_depth = BitDepth::Eight;
+        info.color_type = ColorType::Grayscale;
+        info.trns = Some(std::borrow::Cow::Borrowed(&[0, 7]));
+        assert_eq!(info.transparency(), Some(Transparency::GrayKey(7)));
+        info.color_type = ColorType::Rgb;
+        assert_eq!(info.transparency(), None);
+        info.color_type = ColorType::Rgba;
+        assert_eq!(info.transparency(), None);
+    }
+
+    #[test]
+    fn buffer_sizes() {
+        use crate::Transformations;
+
+        let transformations = [
+            Transformations::IDENTITY,
+            Transformations::EXPAND,
+            Transformations::STRIP_16,
+            Transformations::EXPAND | Transformations::STRIP_16,
+        ];
+        for entry in std::fs::read_dir("tests/pngsuite").unwrap() {
+            let path = entry.unwrap().path();
+            let name = path.file_name().unwrap().to_str().unwrap();
+            if name.starts_with('x') || !name.ends_with(".png") {
+                continue;
+            }
+            for &transform in &transformations {
+                let mut decoder = Decoder::new(std::fs::File::open(&path).unwrap());
+                decoder.set_transformations(transform);
+                let mut reader = decoder.read_info().unwrap();
+                let info = reader.info();
+                assert_eq!(info.checked_raw_bytes(), Some(info.raw_bytes()));
+                let size = reader.checked_output_buffer_size();
+                assert_eq!(size, Some(reader.output_buffer_size()), "{}", name);
+                assert_eq!(reader.checked_frame_buffer_size(), size, "{}", name);
+
+                let expected = reader.checked_interlaced_buffer_size().unwrap();
+                let mut total = 0;
+                while let Some(row) = reader.next_interlaced_row().unwrap() {
+                    total += row.data().len();
+                }
+                assert_eq!(total, expected, "{} {:?}", name, transform);
+            }
+        }
+
+        // The frames of an animation can be smaller than the image.
+        let mut png = Vec::new();
+        let mut encoder = crate::Encoder::new(&mut png, 4, 3);
+        encoder.set_animated(2, 0).unwrap();
+        let mut writer = encoder.write_header().unwrap();
+        writer.add_frame(&[0; 12], Default::default()).unwrap();
+        let frame = crate::FrameOptions {
+            size: Some((2, 1)),
+            ..Default::default()
+        };
+        writer.add_frame(&[0; 2], frame).unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = Decoder::new(&png[..]).read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut buf).unwrap();
+        assert_eq!(reader.checked_frame_buffer_size(), Some(12));
+        reader.next_frame(&mut buf).unwrap();
+        assert_eq!(reader.checked_frame_buffer_size(), Some(2));
+        assert_eq!(reader.checked_output_buffer_size(), Some(12));
+    }
+
+    #[test]
+    fn modification_time() {
+        use crate::ModificationTime;
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let time = |name: &str| {
+            let file = std::fs::File::open(format!("tests/pngsuite/{}.png", name)).unwrap();
+            let reader = Decoder::new(file).read_info().unwrap();
+            reader.info().modification_time
+        };
+        assert_eq!(
+            time("cm0n0g04"),
+            ModificationTime::new(2000, 1, 1, 12, 34, 56)
+        );
+        assert_eq!(
+            time("cm9n0g04"),
+            ModificationTime::new(1999, 12, 31, 23, 59, 59)
+        );
+        let epoch = time("cm7n0g04").unwrap();
+        assert_eq!(epoch.to_system_time(), Some(UNIX_EPOCH));
+        assert_eq!(time("basn0g01"), None);
+
+        let before = ModificationTime::new(1969, 12, 31, 23, 59, 59).unwrap();
+        assert_eq!(
+            before.to_system_time(),
+            Some(UNIX_EPOCH - Duration::from_secs(1))
+        );
+        let rounded = UNIX_EPOCH - Duration::from_millis(1500);
+        assert_eq!(
+            ModificationTime::from_system_time(rounded),
+            ModificationTime::new(1969, 12, 31, 23, 59, 58)
+        );
+        let leap = ModificationTime::new(2016, 12, 31, 23, 59, 60).unwrap();
+        let new_year = ModificationTime::new(2017, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(leap.to_system_time(), new_year.to_system_time());
+        assert!(leap < new_year);
+        assert_eq!(
+            ModificationTime::from_bytes(&[0x07, 0xd0, 13, 1, 0, 0, 0]),
+            None
+        );
+
+        let mut png = Vec::new();
+        let mut encoder = crate::Encoder::new(&mut png, 1, 1);
+        encoder.set_modification_time(leap);
+        encoder
+            .write_header()
+            .unwrap()
+            .write_image_data(&[0])
+            .unwrap();
+        let reader = Decoder::new(&png[..]).read_info().unwrap();
+        assert_eq!(reader.info().modification_time, Some(leap));
+
+        // An invalid or duplicate chunk is ignored.
+        let time = |chunks: &[&[u8]]| {
+            let chunks: Vec<_> = chunks
+                .iter()
+                .map(|&data| (crate::chunk::tIME, data))
+                .collect();
+            let png = with_chunks(&chunks);
+            let reader = Decoder::new(&png[..]).read_info().unwrap();
+            reader.info().modification_time
+        };
+        let first = [0x07, 0xe8, 10, 14, 17, 30, 0];
+        let second = [0x07, 0xe9, 1, 1, 0, 0, 0];
+        assert_eq!(time(&[&first[..6]]), None);
+        assert_eq!(time(&[&[0x07, 0xe8, 2, 30, 0, 0, 0]]), None);
+        assert_eq!(
+            time(&[&first, &second]),
+            ModificationTime::new(2024, 10, 14, 17, 30, 0)
+        );
+    }
+
+    #[test]
+    fn pixel_dimensions() {
+        let info = |name: &str| {
+            let file = std::fs::File::open(format!("tests/pngsuite/{}.png", name)).unwrap();
+            let reader = Decoder::new(file).read_info().unwrap();
+            (reader.info().dpi(), reader.info().pixel_aspect_ratio())
+        };
+        assert_eq!(info("cdfn2c08"), (None, Some(4.0)));
+        assert_eq!(info("cdhn2c08"), (None, Some(0.25)));
+        assert_eq!(info("cdsn2c08"), (None, Some(1.0)));
+        assert_eq!(info("basn2c08"), (None, None));
+        let (dpi, ratio) = info("cdun2c08");
+        let (x, y) = dpi.unwrap();
+        assert!((x - 25.4).abs() < 1e-9 && (y - 25.4).abs() < 1e-9);
+        assert_eq!(ratio, Some(1.0));
+
+        let dims = crate::PixelDimensions {
+            xppu: 0,
+            yppu: 3780,
+            unit: crate::Unit::Meter,
+        };
+        assert_eq!(dims.aspect_ratio(), None);
+        assert_eq!(dims.to_dpi().map(|(_, y)| y.round()), Some(96.0));
+    }
+
+    #[test]
+    fn background_color() {
+        use crate::{BackgroundColor, ColorType};
+
+        let background = |name: &str| {
+            let file = std::fs::File::open(format!("tests/pngsuite/{}.png", name)).unwrap();
+            let reader = Decoder::new(file).read_info().unwrap();
+            reader.info().background_color
+        };
+        assert_eq!(background("bggn4a16"), Some(BackgroundColor::Gray(43908)));
+        assert_eq!(background("tbbn0g04"), Some(BackgroundColor::Gray(0)));
+        assert_eq!(background("tbyn3p08"), Some(BackgroundColor::Indexed(245)));
+        assert_eq!(background("bgan6a08"), None);
+
+        // An index outside of the palette of 15 entries is ignored.
+        let mut png = std::fs::read("tests/pngsuite/basn3p04.png").unwrap();
+        let idat = png.windows(4).position(|window| window == b"IDAT").unwrap() - 4;
+        let mut chunk = vec![0, 0, 0, 1];
+        chunk.extend_from_slice(b"bKGD\x0f");
+        chunk.extend_from_slice(&crc32fast::hash(&chunk[4..]).to_be_bytes());
+        png.splice(idat..idat, chunk);
+        let reader = Decoder::new(&png[..]).read_info().unwrap();
+        assert_eq!(reader.info().background_color, None);
+
+        // A chunk of the wrong length or a duplicate is ignored.
+        let background = |chunks: &[&[u8]]| {
+            let chunks: Vec<_> = chunks
+                .iter()
+                .map(|&data| (crate::chunk::bKGD, data))
+                .collect();
+            let png = with_chunks(&chunks);
+            let reader = Decoder::new(&png[..]).read_info().unwrap();
+            reader.info().background_color
+        };
+        assert_eq!(background(&[&[0, 1, 2]]), None);
+        assert_eq!(
+            background(&[&[0, 1, 2], &[0, 3]]),
+            Some(BackgroundColor::Gray(3))
+        );
+        assert_eq!(
+            background(&[&[0, 1], &[0, 3]]),
+            Some(BackgroundColor::Gray(1))
+        );
+
+        let parse = |color_type, data: &[u8]| BackgroundColor::from_bytes(color_type, data);
+        assert_eq!(
+            parse(ColorType::GrayscaleAlpha, &[1, 2]),
+            Some(BackgroundColor::Gray(0x102))
+        );
+        assert_eq!(parse(ColorType::Grayscale, &[1]), None);
+        assert_eq!(parse(ColorType::Indexed, &[1, 2]), None);
+        assert_eq!(
+            parse(ColorType::Rgba, &[0, 1, 0, 2, 0, 3]),
+            Some(BackgroundColor::Rgb(1, 2, 3))
+        );
+    }
+
+    #[test]
+    fn composite_background() {
+        use crate::{BackgroundColor, BitDepth, ColorType, Composite};
+
+        let decode = |name: &str, composite: Composite| {
+            let file = std::fs::File::open(format!("tests/pngsuite/{}.png", name)).unwrap();
+            let mut decoder = Decoder::new(file);
+            decoder.set_composite(composite);
+            let mut reader = decoder.read_info().unwrap();
+            let mut buf = vec![0; reader.output_buffer_size()];
+            let info = reader.next_frame(&mut buf).unwrap();
+            (reader.info().background_color, info, buf)
+        };
+
+        let (background, plain, rgba) = decode("bgwn6a08", Composite::None);
+        assert_eq!(background, Some(BackgroundColor::Rgb(255, 255, 255)));
+        assert_eq!(plain.color_type, ColorType::Rgba);
+
+        let black = BackgroundColor::Rgb(0, 0, 0);
+        let blend = |background: u32| -> Vec<u8> {
+            rgba.chunks(4)
+                .flat_map(|pixel| {
+                    let alpha = u32::from(pixel[3]);
+                    let blend = move |color: u8| {
+                        ((u32::from(color) * alpha + background * (255 - alpha) + 127) / 255) as u8
+                    };
+                    vec![blend(pixel[0]), blend(pixel[1]), blend(pixel[2])]
+                })
+                .collect()
+        };
+        let (_, info, white) = decode("bgwn6a08", Composite::Background(black));
+        assert_eq!(
+            (info.color_type, info.bit_depth),
+            (ColorType::Rgb, BitDepth::Eight)
+        );
+        assert_eq!(info.line_size, 3 * 32);
+        assert_eq!(white, blend(255));
+        let (_, _, over_black) = decode("bgwn6a08", Composite::Color(black));
+        assert_eq!(over_black, blend(0));
+
+        // Without a bKGD chunk the fallback is used, converted to gray for a gray image.
+        let (background, info, gray) = decode(
+            "basn4a16",
+            Composite::Background(BackgroundColor::Rgb(0, 0xffff, 0)),
+        );
+        assert_eq!(background, None);
+        assert_eq!(
+            (info.color_type, info.bit_depth),
+            (ColorType::Grayscale, BitDepth::Sixteen)
+        );
+        let (_, _, graya) = decode("basn4a16", Composite::None);
+        let luma = 46871;
+        for (gray, pixel) in gray.chunks(2).zip(graya.chunks(4)) {
+            let alpha = u32::from(u16::from_be_bytes([pixel[2], pixel[3]]));
+            let color = u32::from(u16::from_be_bytes([pixel[0], pixel[1]]));
+            let expected = (color * alpha + luma * (0xffff - alpha) + 0x7fff) / 0xffff;
+            assert_eq!(u32::from(u16::from_be_bytes([gray[0], gray[1]])), expected);
+        }
+
+        // Images without alpha are left as they are.
+        let (_, info, _) = decode("basn2c08", Composite::Color(black));
+        assert_eq!(info.color_type, ColorType::Rgb);
+    }
+
+    #[test]
+    fn color_transform() {
+        use crate::{
+            BitDepth, ColorDestination, ColorSource, ColorTransform, ColorType, DecodingError,
+            Transformations,
+        };
+        use std::sync::{Arc, Mutex};
+
+        type Prepared = (bool, ColorDestination, ColorType, BitDepth);
+
+        #[derive(Default)]
+        struct Invert {
+            prepared: Arc<Mutex<Option<Prepared>>>,
+        }
+
+        impl ColorTransform for Invert {
+            fn prepare(
+                &mut self,
+                source: &ColorSource<'_>,
+                destination: ColorDestination,
+                color_type: ColorType,
+                bit_depth: BitDepth,
+            ) -> std::result::Result<(), DecodingError> {
+                let tagged = source.is_tagged() && source.icc_profile.is_none();
+                *self.prepared.lock().unwrap() = Some((tagged, destination, color_type, bit_depth));
+                Ok(())
+            }
+
+            fn transform_row(&mut self, row: &mut [u8]) {
+                row.iter_mut().for_each(|sample| *sample = !*sample);
+            }
+        }
+
+        let decode = |name: &str, transform: Option<Invert>| {
+            let path = format!("tests/pngsuite/{}.png", name);
+            let mut decoder = Decoder::new(std::fs::File::open(path).unwrap());
+            decoder.set_transformations(Transformations::EXPAND);
+            if let Some(transform) = transform {
+                decoder.set_color_transform(transform, ColorDestination::LinearSrgb);
+            }
+            let mut reader = decoder.read_info().unwrap();
+            let mut buf = vec![0; reader.output_buffer_size()];
+            reader.next_frame(&mut buf).unwrap();
+            buf
+        };
+
+        for name in &["g04n2c08", "basi3p02"] {
+            let invert = Invert::default();
+            let prepared = invert.prepared.clone();
+            let inverted = decode(name, Some(invert));
+            let expected: Vec<u8> = decode(name, None).iter().map(|sample| !sample).collect();
+            assert_eq!(inverted, expected);
+
+            let (tagged, destination, color_type, bit_depth) = prepared.lock().unwrap().unwrap();
+            assert!(tagged);
+            assert_eq!(destination, ColorDestination::LinearSrgb);
+            assert_eq!(color_type, ColorType::Rgb);
+            assert_eq!(bit_depth, BitDepth::Eight);
+        }
+    }
+
+    #[test]
+    fn linear_hdr_output() {
+        use crate::{BitDepth, CodingIndependentCodePoints, ColorType, LinearScale};
+
+        let encode = |cicp: CodingIndependentCodePoints, color: ColorType, depth, data: &[u8]| {
+            let mut png = Vec::new();
+            let mut encoder = crate::Encoder::new(&mut png, 1, 1);
+            encoder.set_color(color);
+            encoder.set_depth(depth);
+            encoder.set_coding_independent_code_points(cicp);
+            encoder
+                .write_header()
+                .unwrap()
+                .write_image_data(data)
+                .unwrap();
+            png
+        };
+        let linear = |png: &[u8], scale| {
+            let mut reader = Decoder::new(png).read_info().unwrap();
+            let mut buf = vec![0.0; 4];
+            let info = reader.next_frame_linear(&mut buf, scale).unwrap();
+            buf.truncate(info.color_type.samples());
+            (reader.info().coding_independent_code_points, buf)
+        };
+        let close = |values: &[f32], expected: &[f32]| {
+            values.len() == expected.len()
+                && values
+                    .iter()
+                    .zip(expected)
+                    .all(|(a, b)| (a - b).abs() < 1e-4 * b.max(1.0))
+        };
+
+        let pq = CodingIndependentCodePoints {
+            color_primaries: 9,
+            transfer_function: CodingIndependentCodePoints::PQ,
+            matrix_coefficients: 0,
+            is_video_full_range_image: true,
+        };
+        let png = encode(
+            pq,
+            ColorType::Rgba,
+            BitDepth::Sixteen,
+            &[0xff, 0xff, 0, 0, 0x80, 0, 0x80, 0],
+        );
+        let (cicp, values) = linear(&png, LinearScale::Normalized);
+        assert_eq!(cicp, Some(pq));
+        // The code value of one half is close to 92 cd/m².
+        assert!(close(&values[..2], &[1.0, 0.0]));
+        assert!((values[2] * 10000.0 - 92.2).abs() < 0.5);
+        assert!((values[3] - 0.5).abs() < 1e-4);
+        let (_, values) = linear(&png, LinearScale::ScRgb);
+        assert!(close(&values[..2], &[125.0, 0.0]));
+
+        let narrow = CodingIndependentCodePoints {
+            is_video_full_range_image: false,
+            ..pq
+        };
+        let png = encode(narrow, ColorType::Rgb, BitDepth::Eight, &[235, 16, 255]);
+        assert!(close(
+            &linear(&png, LinearScale::Normalized).1,
+            &[1.0, 0.0, 1.0]
+        ));
+
+        let hlg = CodingIndependentCodePoints {
+            transfer_function: CodingIndependentCodePoints::HLG,
+            ..pq
+        };
+        let png = encode(hlg, ColorType::Grayscale, BitDepth::Sixteen, &[0x80, 0x00]);
+        let half = 0x8000 as f32 / 65535.0;
+        let scene = half * half / 3.0;
+        assert!(close(&linear(&png, LinearScale::Normalized).1, &[scene]));
+        let display = scene * 1000.0 * scene.powf(0.2) / 80.0;
+        assert!(close(&linear(&png, LinearScale::ScRgb).1, &[display]));
+
+        let decoder = Decoder::new(std::fs::File::open("tests/pngsuite/basn3p02.png").unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0.0; reader.output_buffer_size()];
+        assert!(reader
+            .next_frame_linear(&mut buf, LinearScale::Normalized)
+            .is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "parallel")]
+    fn parallel_unfilter_matches_sequential() {
+        use crate::parallel::Executor;
+        use crate::Transformations;
+
+        for entry in std::fs::read_dir("tests/pngsuite").unwrap() {
+            let path = entry.unwrap().path();
+            let name = path.file_name().unwrap().to_str().unwrap();
+            if !name.starts_with("basi") {
+                continue;
+            }
+            let png = std::fs::read(&path).unwrap();
+            // The images of the suite are also available without interlacing.
+            let progressive = path.with_file_name(name.replace("basi", "basn"));
+            let progressive = std::fs::read(progressive).unwrap();
+            for &transform in &[Transformations::IDENTITY, Transformations::EXPAND] {
+                let decode = |png: &[u8], executor| {
+                    let mut decoder = Decoder::new(png);
+                    decoder.set_transformations(transform);
+                    decoder.set_executor(executor);
+                    let mut reader = decoder.read_info().unwrap();
+                    let mut buf = vec![0xaa; reader.output_buffer_size()];
+                    let info = reader.next_frame(&mut buf).unwrap();
+                    (info, buf)
+                };
+                let expected = decode(&progressive, Executor::Sequential);
+                for &executor in &[Executor::Sequential, Executor::Threads(3)] {
+                    assert!(decode(&png, executor) == expected, "{}", name);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn linear_output_follows_color_precedence() {
+        use crate::{
+            CodingIndependentCodePoints, ColorChunk, ColorPrecedence, LinearScale, ScaledFloat,
+        };
+
+        let mut png = Vec::new();
+        {
+            let mut encoder = crate::Encoder::new(&mut png, 1, 1);
+            encoder.set_coding_independent_code_points(CodingIndependentCodePoints {
+                color_primaries: 1,
+                transfer_function: 8,
+                matrix_coefficients: 0,
+                is_video_full_range_image: true,
+            });
+            encoder.set_source_gamma(ScaledFloat::new(0.5));
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[0x80]).unwrap();
+        }
+        let linear = |precedence| {
+            let mut decoder = Decoder::new(&png[..]);
+            decoder.set_color_precedence(precedence);
+            let mut reader = decoder.read_info().unwrap();
+            let mut buf = [0.0];
+            reader
+                .next_frame_linear(&mut buf, LinearScale::Normalized)
+                .unwrap();
+            buf[0]
+        };
+
+        let sample = 128.0 / 255.0;
+        // The transfer function of the code points is linear, that of the gamma is a square.
+        assert!((linear(ColorPrecedence::SPECIFICATION) - sample).abs() < 1e-6);
+        let calibrated = ColorPrecedence::new(&[ColorChunk::Calibrated, ColorChunk::Cicp]);
+        assert!((linear(calibrated) - sample * sample).abs() < 1e-6);
+        // Without any chunk considered, the samples are sRGB.
+        let srgb = ((sample + 0.055) / 1.055).powf(2.4);
+        assert!((linear(ColorPrecedence::new(&[])) - srgb).abs() < 1e-6);
+    }
+
+    #[test]
+    fn grayscale_conversion() {
+        use crate::{BitDepth, ColorType, GrayscaleConverter, Info, LumaWeights, ScaledFloat};
+
+        // The samples of the image are linear, so the luminance is their weighted average.
+        let decoder = Decoder::new(std::fs::File::open("tests/pngsuite/basn2c16.png").unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        assert_eq!(reader.info().source_gamma, Some(ScaledFloat::new(1.0)));
+        let mut buf = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut buf).unwrap();
+        for &weights in &[LumaWeights::Rec709, LumaWeights::Rec601] {
+            let converter = GrayscaleConverter::new(reader.info(), BitDepth::Sixteen, weights);
+            let gray = converter.convert(ColorType::Rgb, &buf).unwrap();
+            assert_eq!(gray.len(), buf.len() / 3);
+            let factors = match weights {
+                LumaWeights::Rec709 => [0.2126, 0.7152, 0.0722],
+                LumaWeights::Rec601 => [0.299, 0.587, 0.114],
+            };
+            for (rgb, gray) in buf.chunks_exact(6).zip(gray.chunks_exact(2)) {
+                let expected: f64 = rgb
+                    .chunks_exact(2)
+                    .zip(&factors)
+                    .map(|(sample, factor)| {
+                        f64::from(u16::from_be_bytes([sample[0], sample[1]])) * factor
+                    })
+                    .sum();
+                let gray = f64::from(u16::from_be_bytes([gray[0], gray[1]]));
+                assert!((gray - expected).abs() <= 0.5 + 1e-6);
+            }
+        }
+
+        let mut info = Info::default();
+        let converter = GrayscaleConverter::new(&info, BitDepth::Eight, LumaWeights::Rec709);
+        let gray = converter.convert(ColorType::Rgb, &[255, 255, 255, 255, 0, 0, 0, 0, 0]);
+        // Red has a fifth of the luminance of white, whose encoding is close to half of it.
+        assert_eq!(gray.unwrap(), [255, 127, 0]);
+        assert_eq!(converter.convert(ColorType::Indexed, &[0]), None);
+        assert_eq!(converter.convert(ColorType::Grayscale, &[7]).unwrap(), [7]);
+
+        info.source_gamma = Some(ScaledFloat::new(0.5));
+        let converter = GrayscaleConverter::new(&info, BitDepth::Eight, LumaWeights::Rec709);
+        let gray = converter.convert(ColorType::Rgba, &[255, 0, 0, 9]).unwrap();
+        assert_eq!(gray, [(0.2126f64.sqrt() * 255.0).round() as u8, 9]);
+    }
+
+    #[test]
+    fn keyword_check() {
+        use crate::text_metadata::{KeywordCheck, KeywordError, TEXtChunk};
+
+        let mut png = Vec::new();
+        {
+            let mut encoder = crate::Encoder::new(&mut png, 1, 1);
+            encoder
+                .add_text_chunk("Title".into(), "fine".into())
+                .unwrap();
+            encoder
+                .add_text_chunk("Two  spaces".into(), "not fine".into())
+                .unwrap();
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[0]).unwrap();
+        }
+
+        let reader = Decoder::new(&png[..]).read_info().unwrap();
+        assert_eq!(reader.info().uncompressed_latin1_text.len(), 2);
+        assert!(reader.info().keyword_warnings.is_empty());
+
+        let mut decoder = Decoder::new(&png[..]);
+        decoder.set_keyword_check(KeywordCheck::Warn);
+        let reader = decoder.read_info().unwrap();
+        assert_eq!(reader.info().uncompressed_latin1_text.len(), 2);
+        assert_eq!(
+            reader.info().keyword_warnings,
+            [("Two  spaces".to_owned(), KeywordError::ConsecutiveSpaces)]
+        );
+
+        let mut decoder = Decoder::new(&png[..]);
+        decoder.set_keyword_check(KeywordCheck::Reject);
+        assert!(decoder.read_info().is_err());
+
+        let mut out = Vec::new();
+        let mut encoder = crate::Encoder::new(&mut out, 1, 1);
+        encoder.set_strict_keywords(true);
+        encoder
+            .add_text_chunk(" Leading".into(), "space".into())
+            .unwrap();
+        assert!(encoder.write_header().is_err());
+
+        let mut encoder = crate::Encoder::new(&mut out, 1, 1);
+        encoder.set_strict_keywords(true);
+        let mut writer = encoder.write_header().unwrap();
+        writer
+            .write_text_chunk(&TEXtChunk::new("Comment", "fine"))
+            .unwrap();
+        assert!(writer
+            .write_text_chunk(&TEXtChunk::new("Bell\u{7}", "not fine"))
+            .is_err());
+    }
+
+    #[test]
+    fn text_reader() {
+        use std::io::Read;
+
+        // Printable ASCII from a linear congruential generator, which barely compresses.
+        let mut state = 1u32;
+        let text: String = (0..200_000)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (b' ' + ((state >> 16) % 95) as u8) as char
+            })
+            .collect();
+        let mut png = Vec::new();
+        {
+            let mut encoder = crate::Encoder::new(&mut png, 1, 1);
+            encoder
+                .add_ztxt_chunk("large".into(), text.clone())
+                .unwrap();
+            let mut itxt = crate::text_metadata::ITXtChunk::new("large", text.clone());
+            itxt.compressed = true;
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_text_chunk(&itxt).unwrap();
+            writer.write_image_data(&[0]).unwrap();
+        }
+
+        let mut decoder = Decoder::new(&png[..]);
+        decoder.set_text_decompression_limit(1 << 10);
+        let reader = decoder.read_info().unwrap();
+        let info = reader.info();
+        let mut ztxt = info.compressed_latin1_text[0].text_reader();
+        let mut buf = [0; 1000];
+        let mut decompressed = Vec::new();
+        loop {
+            let len = ztxt.read(&mut buf).unwrap();
+            if len == 0 {
+                break;
+            }
+            decompressed.extend_from_slice(&buf[..len]);
+        }
+        assert_eq!(decompressed, text.as_bytes());
+
+        let mut decompressed = Vec::new();
+        let mut itxt = info.utf8_text[0].text_reader();
+        itxt.read_to_end(&mut decompressed).unwrap();
+        assert_eq!(decompressed, text.as_bytes());
+
+        let compressed = info.compressed_latin1_text[0].compressed_text().unwrap();
+        assert!(compressed.len() > super::stream::CHUNCK_BUFFER_SIZE);
+        let truncated = crate::text_metadata::ZTXtChunk::decode(
+            b"large",
+            0,
+            &compressed[..compressed.len() / 2],
+            Default::default(),
+            0,
+        )
+        .unwrap();
+        let err = truncated
+            .text_reader()
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn chunk_limits() {
+        use crate::{chunk, DecodingError};
+
+        let mut png = Vec::new();
+        {
+            let mut encoder = crate::Encoder::new(&mut png, 8, 8);
+            encoder
+                .add_itxt_chunk("comment".into(), "x".repeat(100))
+                .unwrap();
+            encoder
+                .write_header()
+                .unwrap()
+                .write_image_data(&[0; 64])
+                .unwrap();
+        }
+
+        let mut decoder = Decoder::new(&png[..]);
+        decoder.set_chunk_limit(chunk::iTXt, 100);
+        assert!(matches!(
+            decoder.read_info(),
+            Err(DecodingError::LimitsExceeded)
+        ));
+
+        // Other chunks are not affected, and a later limit replaces an earlier one.
+        let mut decoder = Decoder::new(&png[..]);
+        decoder.set_chunk_limit(chunk::iTXt, 100);
+        decoder.set_chunk_limit(chunk::iTXt, 200);
+        decoder.set_chunk_limit(chunk::IDAT, 1000);
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut buf).unwrap();
+        assert_eq!(reader.info().utf8_text.len(), 1);
+
+        let mut decoder = Decoder::new(&png[..]);
+        decoder.set_chunk_limit(chunk::IDAT, 4);
+        assert!(decoder.read_info().is_err());
+    }
+
+    #[test]
+    fn compression_stats() {
+        use crate::FilterType;
+
+        for &name in &["f01n2c08", "oi9n2c16", "basn0g04"] {
+            let png = std::fs::read(format!("tests/pngsuite/{}.png", name)).unwrap();
+            // Walk the chunks for the expected image data counts.
+            let (mut chunks, mut compressed, mut pos) = (0, 0, 8);
+            while pos < png.len() {
+                let len = u32::from_be_bytes([png[pos], png[pos + 1], png[pos + 2], png[pos + 3]]);
+                if &png[pos + 4..pos + 8] == b"IDAT" {
+                    chunks += 1;
+                    compressed += u64::from(len);
+                }
+                pos += 12 + len as usize;
+            }
+
+            let mut reader = Decoder::new(&png[..]).read_info().unwrap();
+            let raw_bytes = reader.info().checked_raw_bytes().unwrap() as u64;
+            let mut buf = vec![0; reader.output_buffer_size()];
+            reader.next_frame(&mut buf).unwrap();
+
+            let stats = *reader.compression_stats();
+            assert_eq!(stats.chunks, chunks, "{}", name);
+            assert_eq!(stats.compressed_bytes, compressed, "{}", name);
+            assert_eq!(stats.decompressed_bytes, raw_bytes, "{}", name);
+            assert!(stats.compression_ratio().unwrap() > 0.0);
+            if name == "f01n2c08" {
+                assert_eq!(stats.filter_count(FilterType::Sub), 32);
+                assert_eq!(stats.filter_types.iter().sum::<u64>(), 32);
+            }
+        }
+    }
+
+    #[test]
+    fn bottom_up() {
+        for &name in &["basn2c08", "basi0g01", "s07n3p02", "s06i3p02"] {
+            let decode = |bottom_up: bool| {
+                let file = std::fs::File::open(format!("tests/pngsuite/{}.png", name)).unwrap();
+                let mut decoder = Decoder::new(file);
+                decoder.set_bottom_up(bottom_up);
+                let mut reader = decoder.read_info().unwrap();
+                let mut buf = vec![0; reader.output_buffer_size()];
+                let info = reader.next_frame(&mut buf).unwrap();
+                (info.line_size, buf)
+            };
+
+            let (line_size, top_down) = decode(false);
+            let (_, bottom_up) = decode(true);
+            let flipped: Vec<u8> = top_down
+                .chunks(line_size)
+                .rev()
+                .flatten()
+                .copied()
+                .collect();
+            assert_eq!(bottom_up, flipped, "{}", name);
+        }
+    }
+
+    #[test]
+    fn reused_interlaced_buffer() {
+        for &name in &["basi0g01", "basi3p02", "basi0g04", "basi2c08"] {
+            let decode = |fill: u8| {
+                let file = std::fs::File::open(format!("tests/pngsuite/{}.png", name)).unwrap();
+                let mut reader = Decoder::new(file).read_info().unwrap();
+                let mut buf = vec![fill; reader.output_buffer_size()];
+                reader.next_frame(&mut buf).unwrap();
+                buf
+            };
+            assert_eq!(decode(0xff), decode(0), "{}", name);
+        }
+    }
+
+    #[test]
+    fn buffered_input() {
+        let mut png = std::fs::read("tests/pngsuite/basn2c08.png").unwrap();
+        let expected = {
+            let mut reader = Decoder::new(&*png).read_info().unwrap();
+            let mut buf = vec![0; reader.output_buffer_size()];
+            reader.next_frame(&mut buf).unwrap();
+            buf
+        };
+
+        png.extend_from_slice(b"trailer");
+        let mut input = &*png;
+        let mut reader = Decoder::new_buffered(&mut input).read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut buf).unwrap();
+        assert_eq!(buf, expected);
+        drop(reader);
+        // Nothing beyond the decoded bytes was consumed.
+        assert!(input.len() > b"trailer".len() && input.ends_with(b"trailer"));
+    }
+
+    #[test]
+    fn buffer_provider() {
+        use super::BufferProvider;
+        use std::sync::{Arc, Mutex};
+
+        /// Records the capacity of the allocated and the released buffers.
+        #[derive(Clone, Default)]
+        struct Recorder(Arc<Mutex<(Vec<usize>, Vec<usize>)>>);
+
+        impl BufferProvider for Recorder {
+            fn allocate(&mut self, capacity: usize) -> Vec<u8> {
+                let buffer = Vec::with_capacity(capacity);
+                self.0.lock().unwrap().0.push(buffer.capacity());
+                buffer
+            }
+
+            fn release(&mut self, buffer: Vec<u8>) {
+                self.0.lock().unwrap().1.push(buffer.capacity());
+            }
+        }
+
+        let recorder = Recorder::default();
+        let file = std::fs::File::open("tests/pngsuite/basn6a16.png").unwrap();
+        let mut decoder = Decoder::new(file);
+        decoder.set_buffer_provider(recorder.clone());
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0.0; reader.output_buffer_size() / 2];
+        reader
+            .next_frame_linear(&mut buf, Default::default())
+            .unwrap();
+        drop(reader);
+
+        let (mut allocated, mut released) = recorder.0.lock().unwrap().clone();
+        // The frame of linear output and the buffers of a row.
+        assert!(allocated.contains(&(32 * 32 * 8)));
+        assert!(allocated.len() >= 3);
+        allocated.sort_unstable();
+        released.sort_unstable();
+        assert!(allocated.iter().all(|capacity| released.contains(capacity)));
+    }
+
+    #[test]
+    fn custom_zlib_stream() {
+        use super::{DecodingError, ZlibStream};
+
+        /// Buffers the whole stream and decompresses it at the end.
+        #[derive(Default)]
+        struct WholeStream(Vec<u8>);
+
+        impl ZlibStream for WholeStream {
+            fn decompress(
+                &mut self,
+                data: &[u8],
+                _: &mut Vec<u8>,
+            ) -> std::result::Result<usize, DecodingError> {
+                self.0.extend_from_slice(data);
+                Ok(data.len())
+            }
+
+            fn finish_compressed_chunks(
+                &mut self,
+                image_data: &mut Vec<u8>,
+            ) -> std::result::Result<(), DecodingError> {
+                let data = miniz_oxide::inflate::decompress_to_vec_zlib(&self.0).map_err(|_| {
+                    std::io::Error::new(std::io::ErrorKind::InvalidData, "corrupt stream")
+                })?;
+                image_data.extend_from_slice(&data);
+                self.0.clear();
+                Ok(())
+            }
+
+            fn reset(&mut self) {
+                self.0.clear();
+            }
+        }
+
+        for name in &[
+            "pngsuite/basn2c08",
+            "pngsuite/basi0g04",
+            "animated/basic_f20",
+        ] {
+            let png = std::fs::read(format!("tests/{}.png", name)).unwrap();
+            let decode = |custom: bool| {
+                let mut decoder = Decoder::new(&*png);
+                if custom {
+                    decoder.set_zlib_stream(WholeStream::default());
+                }
+                let mut reader = decoder.read_info().unwrap();
+                let mut frames = Vec::new();
+                let mut buf = vec![0; reader.output_buffer_size()];
+                while let Ok(info) = reader.next_frame(&mut buf) {
+                    frames.push(buf[..info.buffer_size()].to_vec());
+                }
+                frames
+            };
+            assert_eq!(decode(true), decode(false), "{}", name);
+        }
+    }
+
+    #[test]
+    fn send_types() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Decoder<std::fs::File>>();
+        assert_send::<crate::Reader<std::fs::File>>();
+        assert_send::<crate::StreamingDecoder>();
+        assert_send::<crate::PushDecoder>();
+    }
+
+    /// Fails with `WouldBlock` before every read once armed, and reads up to five bytes.
+    struct NonBlocking {
+        data: Vec<u8>,
+        pos: usize,
+        armed: std::rc::Rc<std::cell::Cell<bool>>,
+        blocked: bool,
+    }
+
+    impl Read for NonBlocking {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            if self.armed.get() && !self.blocked {
+                self.blocked = true;
+                return Err(std::io::ErrorKind::WouldBlock.into());
+            }
+            self.blocked = false;
+            let len = buf.len().min(5).min(self.data.len() - self.pos);
+            buf[..len].copy_from_slice(&self.data[self.pos..self.pos + len]);
+            self.pos += len;
+            Ok(len)
+        }
+    }
+
+    fn would_block(err: &super::DecodingError) -> bool {
+        match err {
+            super::DecodingError::IoError(err) => err.kind() == std::io::ErrorKind::WouldBlock,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn resume_after_would_block() {
+        use crate::Transformations;
+
+        let decode = |name: &str, transform, bottom_up, block| {
+            let path = format!("tests/pngsuite/{}.png", name);
+            let armed = std::rc::Rc::new(std::cell::Cell::new(false));
+            let mut decoder = Decoder::new(NonBlocking {
+                data: std::fs::read(path).unwrap(),
+                pos: 0,
+                armed: armed.clone(),
+                blocked: false,
+            });
+            decoder.set_transformations(transform);
+            decoder.set_bottom_up(bottom_up);
+            let mut reader = decoder.read_info().unwrap();
+            armed.set(block);
+
+            let mut buf = vec![0; reader.output_buffer_size()];
+            let mut blocks = 0;
+            loop {
+                match reader.next_frame(&mut buf) {
+                    Ok(_) => break,
+                    Err(ref err) if would_block(err) => blocks += 1,
+                    Err(err) => panic!("{}: {:?}", name, err),
+                }
+            }
+            assert_eq!(blocks > 0, block);
+            buf
+        };
+
+        for &(name, transform, bottom_up) in &[
+            ("basi2c08", Transformations::IDENTITY, false),
+            ("basn3p04", Transformations::EXPAND, false),
+            ("basn0g16", Transformations::IDENTITY, true),
+        ] {
+            assert_eq!(
+                decode(name, transform, bottom_up, true),
+                decode(name, transform, bottom_up, false),
+                "{}",
+                name
+            );
+        }
+
+        // The rows of an interlaced image, one by one.
+        let rows = |block| {
+            let armed = std::rc::Rc::new(std::cell::Cell::new(false));
+            let mut reader = Decoder::new(NonBlocking {
+                data: std::fs::read("tests/pngsuite/basi2c08.png").unwrap(),
+                pos: 0,
+                armed: armed.clone(),
+                blocked: false,
+            })
+            .read_info()
+            .unwrap();
+            armed.set(block);
+            let mut rows = Vec::new();
+            loop {
+                match reader.next_row() {
+                    Ok(Some(row)) => rows.push(row.data().to_vec()),
+                    Ok(None) => break,
+                    Err(ref err) if would_block(err) => {}
+                    Err(err) => panic!("{:?}", err),
+                }
+            }
+            rows
+        };
+        assert_eq!(rows(true), rows(false));
+    }
+
+    /// An animation with a `tEXt` chunk before each frame, with wrong CRCs for the given frames.
+    fn damaged_text(damaged: &[usize]) -> Vec<u8> {
+        let mut png = Vec::new();
+        let mut encoder = crate::Encoder::new(&mut png, 1, 1);
+        encoder.set_animated(2, 0).unwrap();
+        let mut writer = encoder.write_header().unwrap();
+        for frame in 0..2u8 {
+            writer.write_chunk(crate::chunk::tEXt, b"a\0b").unwrap();
+            writer.write_image_data(&[frame + 1]).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let texts: Vec<usize> = png
+            .windows(4)
+            .enumerate()
+            .filter(|(_, window)| window == b"tEXt")
+            .map(|(pos, _)| pos)
+            .collect();
+        for &frame in damaged {
+            png[texts[frame] + 7] ^= 1;
+        }
+        png
+    }
+
+    #[test]
+    fn skip_recoverable() {
+        let mut png = damaged_text(&[0, 1]);
+        let err = Decoder::new(&png[..]).read_info().err().unwrap();
+        assert_eq!(err.severity(), crate::ErrorSeverity::Recoverable);
+
+        let mut decoder = Decoder::new(&png[..]);
+        decoder.set_skip_recoverable(true);
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = [0];
+        reader.next_frame(&mut buf).unwrap();
+        assert_eq!(buf, [1]);
+        reader.next_frame(&mut buf).unwrap();
+        assert_eq!(buf, [2]);
+        assert_eq!(reader.skipped_errors().len(), 2);
+        drop(reader);
+
+        // A damaged header is fatal.
+        png[29] ^= 1;
+        let mut decoder = Decoder::new(&png[..]);
+        decoder.set_skip_recoverable(true);
+        let err = decoder.read_info().err().unwrap();
+        assert_eq!(err.severity(), crate::ErrorSeverity::Fatal);
+    }
+
+    #[test]
+    fn skip_and_continue() {
+        let png = damaged_text(&[1]);
+        let mut reader = Decoder::new(&png[..]).read_info().unwrap();
+        let mut buf = [0];
+        reader.next_frame(&mut buf).unwrap();
+        let err = reader.next_frame(&mut buf).unwrap_err();
+        assert_eq!(err.severity(), crate::ErrorSeverity::Recoverable);
+        reader.skip_and_continue().unwrap();
+        reader.next_frame(&mut buf).unwrap();
+        assert_eq!(buf, [2]);
+        assert!(reader.skip_and_continue().is_err());
+    }
+
+    #[test]
+    fn skipped_chunk_leaves_no_trace() {
+        // A damaged keyword fails the CRC check before the text is added to the info.
+        let mut png = Vec::new();
+        {
+            let encoder = crate::Encoder::new(&mut png, 1, 1);
+            let mut writer = encoder.write_header().unwrap();
+            writer
+                .write_chunk(crate::chunk::tEXt, b"Title\0PNG")
+                .unwrap();
+            writer.write_image_data(&[0]).unwrap();
+        }
+        let text = png.windows(4).position(|window| window == b"tEXt").unwrap();
+        png[text + 4] ^= 0x20;
+        let mut decoder = Decoder::new(&png[..]);
+        decoder.set_skip_recoverable(true);
+        let reader = decoder.read_info().unwrap();
+        assert_eq!(reader.skipped_errors().len(), 1);
+        assert!(reader.info().uncompressed_latin1_text.is_empty());
+
+        // Skipping an animation chunk would leave the frames out of sequence.
+        let mut png = damaged_text(&[]);
+        let fctl = png.windows(4).position(|window| window == b"fcTL").unwrap();
+        png[fctl + 4 + 26] ^= 1;
+        let mut decoder = Decoder::new(&png[..]);
+        decoder.set_skip_recoverable(true);
+        let err = decoder.read_info().err().unwrap();
+        assert_eq!(err.severity(), crate::ErrorSeverity::Fatal);
+    }
+
+    #[test]
+    fn phase_timings() {
+        use crate::Transformations;
+        use std::time::Duration;
+
+        let file = include_bytes!("../../tests/pngsuite/basi3p08.png");
+        let mut reader = Decoder::new(&file[..]).read_info().unwrap();
+        assert_eq!(reader.phase_timings(), None);
+        let mut buf = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut buf).unwrap();
+        assert_eq!(reader.phase_timings(), None);
+
+        let mut decoder = Decoder::new(&file[..]);
+        decoder.set_transformations(Transformations::EXPAND);
+        decoder.set_phase_timings(true);
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut buf).unwrap();
+        let timings = reader.phase_timings().unwrap();
+        // A coarse clock can measure no time at all, so only the phases that did not run are
+        // known exactly.
+        let zero = Duration::from_secs(0);
+        assert!(timings.total() >= timings.read);
+        assert_eq!(
+            timings.total(),
+            timings.read + timings.inflate + timings.unfilter + timings.expand
+        );
+        assert_eq!(timings.color_transform, zero);
+        assert_eq!(timings.filter, zero);
+        assert_eq!(timings.deflate, zero);
+    }
+
+    #[test]
+    fn chunk_inventory() {
+        use crate::chunk::{acTL, fcTL, tEXt, IHDR};
+
+        let png = damaged_text(&[0]);
+        let mut decoder = Decoder::new(&png[..]);
+        decoder.set_skip_recoverable(true);
+        decoder.set_chunk_inventory(true);
+        let reader = decoder.read_info().unwrap();
+        let inventory = &reader.info().chunk_inventory;
+        let types: Vec<_> = inventory.iter().map(|chunk| chunk.chunk_type).collect();
+        assert_eq!(types, [IHDR, acTL, tEXt, fcTL]);
+        assert!(inventory[1].crc_ok);
+        assert!(!inventory[2].crc_ok);
+
+        let mut decoder = Decoder::new(&png[..]);
+        decoder.set_skip_recoverable(true);
+        let reader = decoder.read_info().unwrap();
+        assert!(reader.info().chunk_inventory.is_empty());
+    }
+
+    #[test]
+    fn finish_reads_trailing_chunks() {
+        use crate::chunk::{fcTL, fdAT, tEXt, IDAT, IEND};
+
+        let mut png = Vec::new();
+        {
+            let mut encoder = crate::Encoder::new(&mut png, 1, 1);
+            encoder.set_animated(2, 0).unwrap();
+            let mut writer = encoder.write_header().unwrap();
+            writer
+                .add_trailing_text_chunk(&crate::text_metadata::TEXtChunk::new("Comment", "end"))
+                .unwrap();
+            writer.write_image_data(&[1]).unwrap();
+            writer.write_image_data(&[2]).unwrap();
+        }
+
+        // Finishing after the first frame skips the second one.
+        let mut decoder = Decoder::new(&png[..]);
+        decoder.set_chunk_inventory(true);
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = [0];
+        reader.next_frame(&mut buf).unwrap();
+        let types = |reader: &crate::Reader<&[u8]>| -> Vec<_> {
+            let inventory = &reader.info().chunk_inventory;
+            inventory.iter().map(|chunk| chunk.chunk_type).collect()
+        };
+        assert_eq!(types(&reader).last(), Some(&IDAT));
+        reader.finish().unwrap();
+        assert_eq!(types(&reader)[3..], [IDAT, fcTL, fdAT, tEXt, IEND]);
+        assert_eq!(reader.info().uncompressed_latin1_text[0].text, "end");
+        assert!(reader.next_frame(&mut buf).is_err());
+        reader.finish().unwrap();
+    }
+
+    #[test]
+    fn long_exif() {
+        let exif: Vec<u8> = (0..40_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let mut png = Vec::new();
+        {
+            let encoder = crate::Encoder::new(&mut png, 1, 1);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_chunk(crate::chunk::eXIf, &exif).unwrap();
+            writer.write_image_data(&[0]).unwrap();
+        }
+
+        let reader = Decoder::new(&png[..]).read_info().unwrap();
+        assert_eq!(reader.info().exif_metadata.as_deref(), Some(&exif[..]));
+        assert_eq!(crate::read_exif(&png[..]).unwrap(), Some(exif));
+    }
+
+    #[test]
+    fn invalid_stereo_layout() {
+        use crate::StereoLayout;
+
+        let layout = |chunks: &[&[u8]]| {
+            let chunks: Vec<_> = chunks
+                .iter()
+                .map(|&data| (crate::chunk::sTER, data))
+                .collect();
+            let png = with_chunks(&chunks);
+            let reader = Decoder::new(&png[..]).read_info().unwrap();
+            reader.info().stereo_layout
+        };
+        assert_eq!(layout(&[&[2]]), None);
+        assert_eq!(layout(&[&[255], &[1]]), Some(StereoLayout::DivergingFuse));
+        assert_eq!(layout(&[&[0], &[1]]), Some(StereoLayout::CrossFuse));
+    }
+
+    #[test]
+    fn invalid_coding_independent_code_points() {
+        let cicp = |chunks: &[&[u8]]| {
+            let chunks: Vec<_> = chunks
+                .iter()
+                .map(|&data| (crate::chunk::cICP, data))
+                .collect();
+            let png = with_chunks(&chunks);
+            let reader = Decoder::new(&png[..]).read_info().unwrap();
+            reader.info().coding_independent_code_points
+        };
+        let bt2100_pq = crate::CodingIndependentCodePoints::from_bytes(&[9, 16, 0, 1]);
+        assert!(bt2100_pq.is_some());
+        assert_eq!(cicp(&[&[9, 16, 0]]), None);
+        assert_eq!(cicp(&[&[9, 16, 0, 2]]), None);
+        assert_eq!(cicp(&[&[9, 16], &[9, 16, 0, 1]]), bt2100_pq);
+        assert_eq!(cicp(&[&[9, 16, 0, 1], &[1, 13, 0, 1]]), bt2100_pq);
+    }
+
+    #[test]
+    fn duplicate_exif() {
+        let png = with_chunks(&[
+            (crate::chunk::eXIf, b"first"),
+            (crate::chunk::eXIf, b"second"),
+        ]);
+        let reader = Decoder::new(&png[..]).read_info().unwrap();
+        assert_eq!(reader.info().exif_metadata.as_deref(), Some(&b"first"[..]));
+    }
 }
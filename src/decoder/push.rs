@@ -0,0 +1,394 @@
+//! A decoder that is fed with bytes as they arrive, instead of reading them itself.
+use std::mem;
+
+use super::stream::{Decoded, DecodingError, FormatErrorInner, StreamingDecoder};
+use super::{InterlaceInfo, InterlaceIter, Limits, SubframeInfo};
+use crate::chunk;
+use crate::common::{BytesPerPixel, Info};
+use crate::filter::{unfilter, FilterType};
+
+/// An event of a [`PushDecoder`].
+#[derive(Debug)]
+pub enum PushEvent<'a> {
+    /// The image header was read and [`PushDecoder::info`] is available.
+    Header,
+    /// The image data of a frame begins, with the size of the frame.
+    FrameBegin {
+        /// The width of the frame.
+        width: u32,
+        /// The height of the frame.
+        height: u32,
+    },
+    /// The next unfiltered row of the current frame, in the color type and bit depth of the image.
+    Row {
+        /// The samples of the row, without the filter type byte.
+        data: &'a [u8],
+        /// The pass and line of the row in an interlaced image.
+        interlace: InterlaceInfo,
+    },
+    /// All rows of the current frame were delivered.
+    FrameEnd,
+    /// The end of the image was reached.
+    End,
+}
+
+/// PNG decoder that is fed with byte slices of any size (push interface)
+///
+/// This suits hosts that receive the file in pieces, such as network streams in the browser or
+/// completion based IO, where a blocking `Read` is not available. Every call to
+/// [`PushDecoder::push`] consumes all given bytes and reports the rows that were completed by
+/// them. Rows are delivered as stored in the file, like [`Transformations::IDENTITY`] for a
+/// [`Reader`](crate::Reader): interlaced images yield the rows of each pass and animated images
+/// the rows of each frame.
+///
+/// [`Transformations::IDENTITY`]: crate::Transformations::IDENTITY
+///
+/// ```
+/// # fn receive() -> Vec<Vec<u8>> {
+/// #     vec![std::fs::read("tests/pngsuite/basn0g08.png").unwrap()]
+/// # }
+/// use png::{PushDecoder, PushEvent};
+///
+/// let mut decoder = PushDecoder::new();
+/// let mut rows = 0;
+/// for bytes in receive() {
+///     decoder.push(&bytes, |event| {
+///         if let PushEvent::Row { .. } = event {
+///             rows += 1;
+///         }
+///     })?;
+/// }
+/// assert_eq!(rows, 32);
+/// # Ok::<(), png::DecodingError>(())
+/// ```
+pub struct PushDecoder {
+    decoder: StreamingDecoder,
+    /// Decompressed image data that is not yet delivered.
+    current: Vec<u8>,
+    /// Start index of the current scan line.
+    scan_start: usize,
+    /// Previous unfiltered line, with its filter type byte.
+    prev: Vec<u8>,
+    bpp: BytesPerPixel,
+    /// The rows of the current frame, until all were delivered.
+    subframe: Option<SubframeInfo>,
+    /// The layout of the next row, once it is waiting for more data.
+    pending: Option<(usize, InterlaceInfo)>,
+    /// The pass of the last row.
+    pass: u8,
+    /// Whether consecutive image data chunks are being read.
+    in_image_data: bool,
+    at_end: bool,
+    limits: Limits,
+}
+
+impl PushDecoder {
+    /// Create a decoder with the defaults of a [`StreamingDecoder`].
+    pub fn new() -> Self {
+        PushDecoder::with_decoder(StreamingDecoder::new())
+    }
+
+    /// Create a decoder that parses with `decoder`, for example to set chunk limits.
+    pub fn with_decoder(decoder: StreamingDecoder) -> Self {
+        PushDecoder {
+            decoder,
+            current: Vec::new(),
+            scan_start: 0,
+            prev: Vec::new(),
+            bpp: BytesPerPixel::One,
+            subframe: None,
+            pending: None,
+            pass: 0,
+            in_image_data: false,
+            at_end: false,
+            limits: Limits::default(),
+        }
+    }
+
+    /// Limit the length of a single row, including its filter byte.
+    ///
+    /// Unlike [`Decoder::set_limits`] this does not bound the whole frame, as the push decoder
+    /// only keeps the current and the previous row. A frame with a longer row fails with
+    /// [`DecodingError::LimitsExceeded`] before its buffers are allocated.
+    ///
+    /// [`Decoder::set_limits`]: crate::Decoder::set_limits
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// The image info read so far, once [`PushEvent::Header`] was reported.
+    pub fn info(&self) -> Option<&Info<'static>> {
+        self.decoder.info()
+    }
+
+    /// Whether the end of the image was reached.
+    pub fn is_finished(&self) -> bool {
+        self.at_end
+    }
+
+    /// Consumes `data`, calling `on_event` for each event it completes.
+    ///
+    /// Bytes after the end of the image are ignored. After an error the decoder can not be used
+    /// any further.
+    pub fn push<F>(&mut self, mut data: &[u8], mut on_event: F) -> Result<(), DecodingError>
+    where
+        F: FnMut(PushEvent<'_>),
+    {
+        while !data.is_empty() && !self.at_end {
+            let (consumed, decoded) = self.decoder.update(data, &mut self.current)?;
+            data = &data[consumed..];
+            match decoded {
+                Decoded::Header(..) => on_event(PushEvent::Header),
+                Decoded::ChunkBegin(_, chunk::IDAT) | Decoded::ChunkBegin(_, chunk::fdAT)
+                    if !self.in_image_data =>
+                {
+                    self.in_image_data = true;
+                    self.begin_frame(&mut on_event)?;
+                }
+                Decoded::ImageData => self.deliver_rows(&mut on_event)?,
+                Decoded::ImageDataFlushed => {
+                    self.in_image_data = false;
+                    self.deliver_rows(&mut on_event)?;
+                    if self.subframe.is_some() {
+                        return Err(DecodingError::Format(
+                            FormatErrorInner::NoMoreImageData.into(),
+                        ));
+                    }
+                    self.current.clear();
+                    self.scan_start = 0;
+                }
+                Decoded::ImageEnd => {
+                    self.at_end = true;
+                    on_event(PushEvent::End);
+                }
+                Decoded::Nothing if consumed == 0 => break,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn begin_frame<F: FnMut(PushEvent<'_>)>(
+        &mut self,
+        on_event: &mut F,
+    ) -> Result<(), DecodingError> {
+        let info = match self.decoder.info() {
+            Some(info) => info,
+            None => return Ok(()),
+        };
+        let subframe = SubframeInfo::new(info);
+        if subframe.rowlen > self.limits.bytes {
+            debug!(
+                "row of width {} exceeds the limit of {} bytes",
+                subframe.width, self.limits.bytes
+            );
+            return Err(DecodingError::LimitsExceeded);
+        }
+        self.bpp = info.bpp_in_prediction();
+        self.pass = 0;
+        self.pending = None;
+        self.prev.clear();
+        self.prev.resize(subframe.rowlen, 0);
+        on_event(PushEvent::FrameBegin {
+            width: subframe.width,
+            height: subframe.height,
+        });
+        self.subframe = Some(subframe);
+        Ok(())
+    }
+
+    /// Delivers all rows of the current frame that are complete.
+    fn deliver_rows<F: FnMut(PushEvent<'_>)>(
+        &mut self,
+        on_event: &mut F,
+    ) -> Result<(), DecodingError> {
+        while self.subframe.is_some() {
+            let (rowlen, interlace) = match self.pending.take().or_else(|| self.next_layout()) {
+                Some(layout) => layout,
+                None => {
+                    self.subframe = None;
+                    on_event(PushEvent::FrameEnd);
+                    break;
+                }
+            };
+            if self.current.len() - self.scan_start < rowlen {
+                self.pending = Some((rowlen, interlace));
+                break;
+            }
+
+            let row = &mut self.current[self.scan_start..self.scan_start + rowlen];
+            let filter = FilterType::from_u8(row[0]).ok_or_else(|| {
+                DecodingError::Format(FormatErrorInner::UnknownFilterMethod(row[0]).into())
+            })?;
+            self.decoder.stats.filter_types[filter as usize] += 1;
+            unfilter(filter, self.bpp, &self.prev[1..rowlen], &mut row[1..]).map_err(
+                |message| DecodingError::Format(FormatErrorInner::BadFilter(message).into()),
+            )?;
+            self.prev[..rowlen].copy_from_slice(row);
+            self.scan_start += rowlen;
+
+            on_event(PushEvent::Row {
+                data: &self.prev[1..rowlen],
+                interlace,
+            });
+        }
+
+        if self.scan_start > 0 {
+            self.current.drain(..self.scan_start);
+            self.scan_start = 0;
+        }
+        Ok(())
+    }
+
+    /// The length and interlace information of the next row of the frame.
+    fn next_layout(&mut self) -> Option<(usize, InterlaceInfo)> {
+        let subframe = self.subframe.as_mut()?;
+        match subframe.interlace {
+            InterlaceIter::None(ref mut rows) => {
+                rows.next()?;
+                Some((subframe.rowlen, InterlaceInfo::Null))
+            }
+            InterlaceIter::Adam7(ref mut adam7) => {
+                let (pass, line, width) = adam7.next()?;
+                let rowlen = self.decoder.info()?.raw_row_length_from_width(width);
+                if mem::replace(&mut self.pass, pass) != pass {
+                    self.prev.clear();
+                    self.prev.resize(rowlen, 0);
+                }
+                Some((rowlen, InterlaceInfo::Adam7 { pass, line, width }))
+            }
+        }
+    }
+}
+
+impl Default for PushDecoder {
+    fn default() -> Self {
+        PushDecoder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Decoder, Transformations};
+
+    /// The rows of each frame, decoded by a `Reader`.
+    fn read_rows(png: &[u8]) -> Vec<Vec<Vec<u8>>> {
+        let mut decoder = Decoder::new(png);
+        decoder.set_transformations(Transformations::IDENTITY);
+        let mut reader = decoder.read_info().unwrap();
+        let frames = reader
+            .info()
+            .animation_control()
+            .map_or(1, |actl| actl.num_frames);
+        let mut all = Vec::new();
+        for _ in 0..frames {
+            let mut rows = Vec::new();
+            while let Some(row) = reader.next_row().unwrap() {
+                rows.push(row.data().to_vec());
+            }
+            all.push(rows);
+            // Advance to the next frame.
+            let mut buf = vec![0; reader.output_buffer_size()];
+            if all.len() < frames as usize {
+                let _ = reader.next_frame(&mut buf);
+            }
+        }
+        all
+    }
+
+    fn push_rows(png: &[u8], piece: usize) -> Vec<Vec<Vec<u8>>> {
+        let mut decoder = PushDecoder::new();
+        let mut frames = Vec::new();
+        for bytes in png.chunks(piece) {
+            decoder
+                .push(bytes, |event| match event {
+                    PushEvent::FrameBegin { .. } => frames.push(Vec::new()),
+                    PushEvent::Row { data, .. } => frames.last_mut().unwrap().push(data.to_vec()),
+                    _ => {}
+                })
+                .unwrap();
+        }
+        assert!(decoder.is_finished());
+        frames
+    }
+
+    #[test]
+    fn push_pieces() {
+        for name in &[
+            "pngsuite/basn0g01",
+            "pngsuite/basi3p02",
+            "pngsuite/basi6a16",
+        ] {
+            let png = std::fs::read(format!("tests/{}.png", name)).unwrap();
+            let expected = read_rows(&png);
+            for &piece in &[1, 7, 4096] {
+                assert_eq!(push_rows(&png, piece), expected, "{} in {}", name, piece);
+            }
+        }
+    }
+
+    #[test]
+    fn push_animation() {
+        let png = std::fs::read("tests/animated/basic_f20.png").unwrap();
+        let frames = push_rows(&png, 13);
+        let actl = Decoder::new(&*png)
+            .read_info()
+            .unwrap()
+            .info()
+            .animation_control;
+        assert_eq!(frames.len(), actl.unwrap().num_frames as usize);
+        assert!(frames.iter().all(|rows| !rows.is_empty()));
+    }
+
+    #[test]
+    fn truncated_image_data() {
+        let png = std::fs::read("tests/pngsuite/basn0g08.png").unwrap();
+        let idat = png.windows(4).position(|window| window == b"IDAT").unwrap();
+        // Stop in the middle of the image data, before `IEND`.
+        let end = idat + (png.len() - 12 - idat) / 2;
+        let mut decoder = PushDecoder::new();
+        let mut rows = 0;
+        decoder
+            .push(&png[..end], |event| {
+                if let PushEvent::Row { .. } = event {
+                    rows += 1;
+                }
+            })
+            .unwrap();
+        assert!(rows < 32);
+        assert!(!decoder.is_finished());
+    }
+
+    #[test]
+    fn limits() {
+        fn chunk(png: &mut Vec<u8>, chunk_type: chunk::ChunkType, data: &[u8]) {
+            png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            png.extend_from_slice(&chunk_type.0);
+            png.extend_from_slice(data);
+            png.extend_from_slice(&chunk::crc(chunk_type, data).to_be_bytes());
+        }
+
+        // A single RGBA row of 2^31 - 1 pixels takes 8 GiB.
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        chunk(
+            &mut png,
+            chunk::IHDR,
+            b"\x7f\xff\xff\xff\0\0\0\x01\x08\x06\0\0\0",
+        );
+        chunk(&mut png, chunk::IDAT, &[0x78, 0x9c]);
+        let mut decoder = PushDecoder::new();
+        let result = decoder.push(&png, |_| {});
+        assert!(matches!(result, Err(DecodingError::LimitsExceeded)));
+
+        let png = std::fs::read("tests/pngsuite/basn0g08.png").unwrap();
+        let mut decoder = PushDecoder::new();
+        decoder.set_limits(Limits { bytes: 32 });
+        let result = decoder.push(&png, |_| {});
+        assert!(matches!(result, Err(DecodingError::LimitsExceeded)));
+        decoder = PushDecoder::new();
+        decoder.set_limits(Limits { bytes: 33 });
+        decoder.push(&png, |_| {}).unwrap();
+        assert!(decoder.is_finished());
+    }
+}
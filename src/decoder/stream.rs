@@ -9,18 +9,27 @@ use std::{borrow::Cow, cmp::min};
 
 use crc32fast::Hasher as Crc32;
 
-use super::zlib::ZlibStream;
+use super::zlib::{MinizStream, ZlibStream};
 use crate::chunk::{self, ChunkType, IDAT, IEND, IHDR};
 use crate::common::{
-    AnimationControl, BitDepth, BlendOp, ColorType, DisposeOp, FrameControl, Info, ParameterError,
-    PixelDimensions, ScaledFloat, SourceChromaticities, Unit,
+    AnimationControl, BitDepth, BlendOp, ColorPrecedence, ColorType, DigitalSignature, DisposeOp,
+    FrameControl, Info, ParameterError, PhaseTimings, PixelDimensions, ScaledFloat,
+    SourceChromaticities, Unit,
+};
+use crate::filter::FilterType;
+use crate::text_metadata::{
+    check_keyword, ITXtChunk, KeywordCheck, KeywordError, Latin1Mode, TEXtChunk, TextDecodingError,
+    ZTXtChunk, DECOMPRESSION_LIMIT,
 };
-use crate::text_metadata::{ITXtChunk, TEXtChunk, TextDecodingError, ZTXtChunk};
 use crate::traits::ReadBytesExt;
+use crate::utils::{start_phase, stop_phase};
 
 /// TODO check if these size are reasonable
 pub const CHUNCK_BUFFER_SIZE: usize = 32 * 1024;
 
+/// The largest length of a chunk that is parsed, see `StreamingDecoder::retained_chunk_limit`.
+const RETAINED_CHUNK_LIMIT: usize = 8_000_000;
+
 /// Determines if checksum checks should be disabled globally.
 ///
 /// This is used only in fuzzing. `afl` automatically adds `--cfg fuzzing` to RUSTFLAGS which can
@@ -45,6 +54,8 @@ enum State {
     ReadChunk(ChunkType, bool),
     PartialChunk(ChunkType),
     DecodeData(ChunkType, usize),
+    /// A chunk was parsed after its CRC was verified, its `ChunkComplete` is still to be emitted.
+    Parsed(u32, ChunkType),
 }
 
 #[derive(Debug)]
@@ -68,6 +79,41 @@ pub enum Decoded {
     ImageEnd,
 }
 
+/// Statistics on the compressed image data read so far.
+///
+/// The counts cover the `IDAT` chunks and, in an animated image, the `fdAT` chunks of all frames
+/// decoded so far. They can be compared to the output of an optimizer without parsing the file
+/// a second time.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CompressionStats {
+    /// The number of `IDAT` and `fdAT` chunks.
+    pub chunks: u32,
+    /// The compressed bytes in these chunks, without the `fdAT` sequence numbers.
+    pub compressed_bytes: u64,
+    /// The bytes the data decompressed to, including the filter type byte of each row.
+    pub decompressed_bytes: u64,
+    /// The number of rows filtered with each filter type, indexed by the filter type byte.
+    ///
+    /// Rows are only counted once they are unfiltered by a [`Reader`](crate::Reader).
+    pub filter_types: [u64; 5],
+}
+
+impl CompressionStats {
+    /// The number of rows unfiltered with the given filter type.
+    pub fn filter_count(&self, filter: FilterType) -> u64 {
+        self.filter_types[filter as usize]
+    }
+
+    /// The ratio of decompressed to compressed bytes, if any data was read.
+    pub fn compression_ratio(&self) -> Option<f64> {
+        if self.compressed_bytes == 0 {
+            None
+        } else {
+            Some(self.decompressed_bytes as f64 / self.compressed_bytes as f64)
+        }
+    }
+}
+
 /// Any kind of error during PNG decoding.
 ///
 /// This enumeration provides a very rough analysis on the origin of the failure. That is, each
@@ -113,6 +159,57 @@ pub enum DecodingError {
 #[derive(Debug)]
 pub struct FormatError {
     inner: FormatErrorInner,
+    chunk: Option<ChunkContext>,
+    offset: Option<u64>,
+    recoverable: bool,
+}
+
+impl FormatError {
+    /// The chunk that was being decoded when the error occurred.
+    ///
+    /// This is `None` for errors in the signature or outside of the chunk stream, such as a filter
+    /// error in the image data.
+    pub fn chunk_context(&self) -> Option<ChunkContext> {
+        self.chunk
+    }
+
+    /// The offset from the start of the file of the byte at which the error was detected.
+    ///
+    /// This is the position in the input of [`StreamingDecoder::update`], for example the last
+    /// byte of a chunk with a CRC mismatch. Like [`FormatError::chunk_context`] it is `None` for
+    /// errors outside of the chunk stream.
+    pub fn offset(&self) -> Option<u64> {
+        self.offset
+    }
+}
+
+/// The position of a chunk in a file, attached to errors raised while decoding it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChunkContext {
+    /// The type of the chunk.
+    pub chunk_type: ChunkType,
+    /// The number of chunks of the same type before this one, `0` for the first chunk.
+    pub index: usize,
+    /// The offset of the length field of the chunk from the start of the file.
+    pub offset: u64,
+}
+
+impl fmt::Display for ChunkContext {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let nth = self.index + 1;
+        let suffix = match (nth % 10, nth % 100) {
+            (_, 11..=13) => "th",
+            (1, _) => "st",
+            (2, _) => "nd",
+            (3, _) => "rd",
+            _ => "th",
+        };
+        write!(
+            fmt,
+            "{}{} {} chunk at offset {:#x}",
+            nth, suffix, self.chunk_type, self.offset
+        )
+    }
 }
 
 #[derive(Debug)]
@@ -120,6 +217,7 @@ pub(crate) enum FormatErrorInner {
     /// Bad framing.
     CrcMismatch {
         /// bytes to skip to try to recover from this error
+        #[allow(dead_code)]
         recover: usize,
         /// Stored CRC32 value
         crc_val: u32,
@@ -207,6 +305,7 @@ pub(crate) enum FormatErrorInner {
     BadFilter(&'static str),
     /// Bad text encoding
     BadTextEncoding(TextDecodingError),
+    InvalidKeyword(KeywordError),
 }
 
 impl error::Error for DecodingError {
@@ -230,6 +329,51 @@ impl fmt::Display for DecodingError {
     }
 }
 
+/// Whether decoding can continue after an error, see [`DecodingError::severity`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorSeverity {
+    /// The stream is unusable, no more data can be decoded.
+    Fatal,
+    /// The chunk in which the error occurred can be skipped, for example with
+    /// [`Reader::skip_and_continue`](crate::Reader::skip_and_continue).
+    Recoverable,
+}
+
+impl DecodingError {
+    /// Whether the error is confined to a single chunk that can be skipped.
+    ///
+    /// Errors in ancillary chunks, such as a text chunk with a wrong CRC or an invalid `sRGB`
+    /// chunk, are recoverable. All other errors, including those in the header, the palette, the
+    /// image data and the animation chunks `acTL`, `fcTL` and `fdAT`, are fatal.
+    ///
+    /// A chunk is only parsed after its CRC was verified, so a skipped chunk leaves no trace in
+    /// the [`Info`](crate::Info).
+    pub fn severity(&self) -> ErrorSeverity {
+        match self {
+            DecodingError::Format(err) if err.recoverable => ErrorSeverity::Recoverable,
+            _ => ErrorSeverity::Fatal,
+        }
+    }
+
+    /// The chunk that was being decoded when a format error occurred, see
+    /// [`ChunkContext`].
+    pub fn chunk_context(&self) -> Option<ChunkContext> {
+        match self {
+            DecodingError::Format(err) => err.chunk_context(),
+            _ => None,
+        }
+    }
+
+    /// The offset in the file at which a format error was detected, see
+    /// [`StreamingDecoder::current_offset`].
+    pub fn offset(&self) -> Option<u64> {
+        match self {
+            DecodingError::Format(err) => err.offset(),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Display for FormatError {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         use FormatErrorInner::*;
@@ -315,6 +459,7 @@ impl fmt::Display for FormatError {
             }
             BadFilter(message) => write!(fmt, "{}.", message),
             // TODO: Wrap more info in the enum variant
+            InvalidKeyword(err) => write!(fmt, "Invalid text keyword, {}.", err),
             BadTextEncoding(tde) => {
                 match tde {
                     TextDecodingError::Unrepresentable => {
@@ -343,6 +488,10 @@ impl fmt::Display for FormatError {
                     }
                 }
             }
+        }?;
+        match &self.chunk {
+            Some(chunk) => write!(fmt, " (in the {})", chunk),
+            None => Ok(()),
         }
     }
 }
@@ -361,7 +510,12 @@ impl From<FormatError> for DecodingError {
 
 impl From<FormatErrorInner> for FormatError {
     fn from(inner: FormatErrorInner) -> Self {
-        FormatError { inner }
+        FormatError {
+            inner,
+            chunk: None,
+            offset: None,
+            recoverable: false,
+        }
     }
 }
 
@@ -378,6 +532,9 @@ impl From<TextDecodingError> for DecodingError {
     fn from(tbe: TextDecodingError) -> Self {
         DecodingError::Format(FormatError {
             inner: FormatErrorInner::BadTextEncoding(tbe),
+            chunk: None,
+            offset: None,
+            recoverable: false,
         })
     }
 }
@@ -387,7 +544,7 @@ pub struct StreamingDecoder {
     state: Option<State>,
     current_chunk: ChunkState,
     /// The inflater state handling consecutive `IDAT` and `fdAT` chunks.
-    inflater: ZlibStream,
+    pub(crate) inflater: Box<dyn ZlibStream>,
     /// The complete image info read from all prior chunks.
     pub(crate) info: Option<Info<'static>>,
     /// The animation chunk sequence number.
@@ -395,6 +552,32 @@ pub struct StreamingDecoder {
     /// Stores where in decoding an `fdAT` chunk we are.
     apng_seq_handled: bool,
     have_idat: bool,
+    /// The largest accepted data length of specific chunk types.
+    chunk_limits: Vec<(ChunkType, u32)>,
+    /// The handling of unrepresentable characters in `tEXt` and `zTXt` chunks.
+    latin1_mode: Latin1Mode,
+    /// The number of bytes that compressed text chunks may decompress to.
+    text_decompression_limit: usize,
+    /// The handling of text keywords that violate the specification.
+    keyword_check: KeywordCheck,
+    /// The precedence of the color space chunks of the decoded info.
+    color_precedence: ColorPrecedence,
+    /// The statistics on the image data chunks read so far.
+    pub(crate) stats: CompressionStats,
+    /// The number of bytes consumed since the start of the file.
+    offset: u64,
+    /// The offset of the length field of the chunk that is read next.
+    chunk_start: u64,
+    /// The number of chunks of each type seen so far.
+    chunk_counts: Vec<(ChunkType, usize)>,
+    /// The chunk that is currently decoded, attached to format errors.
+    chunk_context: Option<ChunkContext>,
+    /// The state after a recoverable error and the bytes consumed by the failed step.
+    recovery: Option<(State, usize)>,
+    /// The time spent in the phases of decoding, if it is measured.
+    pub(crate) timings: Option<PhaseTimings>,
+    /// Whether every chunk is recorded in `Info::chunk_inventory`.
+    chunk_inventory: bool,
 }
 
 struct ChunkState {
@@ -408,6 +591,9 @@ struct ChunkState {
     /// Remaining bytes to be read.
     remaining: u32,
 
+    /// The length of the chunk data.
+    length: u32,
+
     /// Non-decoded bytes in the chunk.
     raw_bytes: Vec<u8>,
 }
@@ -420,11 +606,24 @@ impl StreamingDecoder {
         StreamingDecoder {
             state: Some(State::Signature(0, [0; 7])),
             current_chunk: ChunkState::default(),
-            inflater: ZlibStream::new(),
+            inflater: Box::new(MinizStream::new()),
             info: None,
             current_seq_no: None,
             apng_seq_handled: false,
             have_idat: false,
+            chunk_limits: Vec::new(),
+            latin1_mode: Latin1Mode::default(),
+            text_decompression_limit: DECOMPRESSION_LIMIT,
+            keyword_check: KeywordCheck::default(),
+            color_precedence: ColorPrecedence::default(),
+            stats: CompressionStats::default(),
+            offset: 0,
+            chunk_start: 0,
+            chunk_counts: Vec::new(),
+            chunk_context: None,
+            recovery: None,
+            timings: None,
+            chunk_inventory: false,
         }
     }
 
@@ -434,11 +633,77 @@ impl StreamingDecoder {
         self.current_chunk.crc = Crc32::new();
         self.current_chunk.remaining = 0;
         self.current_chunk.raw_bytes.clear();
-        self.inflater = ZlibStream::new();
+        self.inflater.reset();
         self.info = None;
         self.current_seq_no = None;
         self.apng_seq_handled = false;
         self.have_idat = false;
+        self.stats = CompressionStats::default();
+        self.offset = 0;
+        self.chunk_start = 0;
+        self.chunk_counts.clear();
+        self.chunk_context = None;
+        self.recovery = None;
+        if let Some(timings) = &mut self.timings {
+            *timings = PhaseTimings::default();
+        }
+    }
+
+    /// Decompress the image data with `stream` instead of `miniz_oxide`, see [`ZlibStream`].
+    pub fn set_zlib_stream<Z: ZlibStream + 'static>(&mut self, stream: Z) {
+        self.inflater = Box::new(stream);
+    }
+
+    /// Limit the data length of chunks of the given type to `max_len` bytes.
+    ///
+    /// A longer chunk results in [`DecodingError::LimitsExceeded`] before any of its data is read.
+    /// This applies to the length stored in the file, compressed chunks may still decompress to
+    /// more data.
+    pub fn set_chunk_limit(&mut self, chunk_type: ChunkType, max_len: u32) {
+        match self
+            .chunk_limits
+            .iter_mut()
+            .find(|(type_, _)| *type_ == chunk_type)
+        {
+            Some(limit) => limit.1 = max_len,
+            None => self.chunk_limits.push((chunk_type, max_len)),
+        }
+    }
+
+    /// Set the handling of characters without meaning in the Latin-1 text of `tEXt` and `zTXt`
+    /// chunks.
+    ///
+    /// The keywords and the text of `tEXt` chunks are converted while decoding, the text of
+    /// `zTXt` chunks when it is decompressed.
+    pub fn set_latin1_mode(&mut self, mode: Latin1Mode) {
+        self.latin1_mode = mode;
+    }
+
+    /// Limit the number of bytes that each `zTXt` and `iTXt` chunk decompresses to.
+    ///
+    /// Text chunks are only decompressed on request, for example by [`ZTXtChunk::get_text`]. The
+    /// limit is independent of [`Limits`](crate::Limits) and defaults to
+    /// [`DECOMPRESSION_LIMIT`].
+    pub fn set_text_decompression_limit(&mut self, limit: usize) {
+        self.text_decompression_limit = limit;
+    }
+
+    /// Set the handling of text chunks whose keyword violates [`check_keyword`].
+    pub fn set_keyword_check(&mut self, check: KeywordCheck) {
+        self.keyword_check = check;
+    }
+
+    /// Record every chunk in [`Info::chunk_inventory`].
+    pub fn set_chunk_inventory(&mut self, enabled: bool) {
+        self.chunk_inventory = enabled;
+    }
+
+    /// Set the precedence of the color space chunks, see [`Info::source_color`].
+    pub fn set_color_precedence(&mut self, precedence: ColorPrecedence) {
+        self.color_precedence = precedence;
+        if let Some(info) = &mut self.info {
+            info.color_precedence = precedence;
+        }
     }
 
     /// Provides access to the inner `info` field
@@ -446,6 +711,35 @@ impl StreamingDecoder {
         self.info.as_ref()
     }
 
+    /// Statistics on the image data chunks read so far.
+    pub fn compression_stats(&self) -> &CompressionStats {
+        &self.stats
+    }
+
+    /// The number of bytes consumed by [`StreamingDecoder::update`] since the start of the file.
+    ///
+    /// This is the offset in the file of the next byte that is decoded.
+    pub fn current_offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Continue after a recoverable error by skipping the rest of the chunk in which it occurred.
+    ///
+    /// Returns `false` and leaves the decoder unchanged if the last error was fatal, see
+    /// [`DecodingError::severity`]. The data passed to [`StreamingDecoder::update`] afterwards
+    /// starts at [`StreamingDecoder::current_offset`], which includes the bytes consumed by the
+    /// failed call.
+    pub fn skip_and_continue(&mut self) -> bool {
+        match self.recovery.take() {
+            Some((state, consumed)) => {
+                self.state = Some(state);
+                self.offset += consumed as u64;
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Low level StreamingDecoder interface.
     ///
     /// Allows to stream partial data to the encoder. Returns a tuple containing the bytes that have
@@ -459,19 +753,38 @@ impl StreamingDecoder {
         let len = buf.len();
         while !buf.is_empty() && self.state.is_some() {
             match self.next_state(buf, image_data) {
-                Ok((bytes, Decoded::Nothing)) => buf = &buf[bytes..],
+                Ok((bytes, Decoded::Nothing)) => {
+                    buf = &buf[bytes..];
+                    self.offset += bytes as u64;
+                }
                 Ok((bytes, result)) => {
                     buf = &buf[bytes..];
+                    self.offset += bytes as u64;
                     return Ok((len - buf.len(), result));
                 }
-                Err(err) => return Err(err),
+                Err(DecodingError::Format(mut err)) => {
+                    if err.chunk.is_none() {
+                        err.chunk = self.chunk_context;
+                    }
+                    if err.offset.is_none() {
+                        err.offset = Some(self.offset);
+                    }
+                    err.recoverable = self.recovery.is_some();
+                    debug!("format error at offset {}: {}", self.offset, err);
+                    return Err(DecodingError::Format(err));
+                }
+                Err(err) => {
+                    // Only format errors are classified as recoverable.
+                    self.recovery = None;
+                    return Err(err);
+                }
             }
         }
         Ok((len - buf.len(), Decoded::Nothing))
     }
 
-    fn next_state<'a>(
-        &'a mut self,
+    fn next_state(
+        &mut self,
         buf: &[u8],
         image_data: &mut Vec<u8>,
     ) -> Result<(usize, Decoded), DecodingError> {
@@ -531,18 +844,70 @@ impl StreamingDecoder {
                                 || self.current_chunk.type_ == chunk::fdAT)
                         {
                             self.current_chunk.type_ = type_str;
-                            self.inflater.finish_compressed_chunks(image_data)?;
+                            let before = image_data.len();
+                            let timer = start_phase(&self.timings);
+                            let result = self.inflater.finish_compressed_chunks(image_data);
+                            stop_phase(&mut self.timings, timer, |t| &mut t.inflate);
+                            result?;
+                            self.stats.decompressed_bytes += (image_data.len() - before) as u64;
                             self.inflater.reset();
+                            debug!(
+                                "image data ended, {} bytes in {} chunks decompressed to {}",
+                                self.stats.compressed_bytes,
+                                self.stats.chunks,
+                                self.stats.decompressed_bytes
+                            );
                             return goto!(
                                 0,
                                 U32Byte3(Type(length), val & !0xff),
                                 emit Decoded::ImageDataFlushed
                             );
                         }
+                        let limit = self
+                            .chunk_limits
+                            .iter()
+                            .find(|(type_, _)| *type_ == type_str);
+                        if limit.map_or(false, |&(_, max_len)| length > max_len) {
+                            debug!("{} chunk of {} bytes exceeds its limit", type_str, length);
+                            return Err(DecodingError::LimitsExceeded);
+                        }
+                        if type_str == IDAT {
+                            self.stats.chunks += 1;
+                            self.stats.compressed_bytes += u64::from(length);
+                        } else if type_str == chunk::fdAT {
+                            self.stats.chunks += 1;
+                            self.stats.compressed_bytes += u64::from(length.saturating_sub(4));
+                        }
+                        let index = match self
+                            .chunk_counts
+                            .iter_mut()
+                            .find(|(type_, _)| *type_ == type_str)
+                        {
+                            Some((_, count)) => {
+                                *count += 1;
+                                *count - 1
+                            }
+                            None => {
+                                self.chunk_counts.push((type_str, 1));
+                                0
+                            }
+                        };
+                        trace!(
+                            "{} chunk of {} bytes at offset {}",
+                            type_str,
+                            length,
+                            self.chunk_start
+                        );
+                        self.chunk_context = Some(ChunkContext {
+                            chunk_type: type_str,
+                            index,
+                            offset: self.chunk_start,
+                        });
                         self.current_chunk.type_ = type_str;
                         self.current_chunk.crc.reset();
                         self.current_chunk.crc.update(&type_str.0);
                         self.current_chunk.remaining = length;
+                        self.current_chunk.length = length;
                         self.apng_seq_handled = false;
                         goto!(
                             ReadChunk(type_str, true),
@@ -551,16 +916,46 @@ impl StreamingDecoder {
                     }
                     Crc(type_str) => {
                         let sum = self.current_chunk.crc.clone().finalize();
-                        if CHECKSUM_DISABLED || val == sum {
-                            goto!(
-                                State::U32(U32Value::Length),
-                                emit if type_str == IEND {
-                                    Decoded::ImageEnd
-                                } else {
-                                    Decoded::ChunkComplete(val, type_str)
+                        let crc_ok = CHECKSUM_DISABLED || val == sum;
+                        // Chunks kept in full are parsed only now that their data is verified.
+                        // This comes before the inventory, which is part of the info of `IHDR`.
+                        let parsed = if crc_ok && self.retained_chunk_limit(type_str).is_some() {
+                            Some(self.parse_chunk(type_str))
+                        } else {
+                            None
+                        };
+                        if self.chunk_inventory {
+                            if let Some(info) = self.info.as_mut() {
+                                info.chunk_inventory.push(chunk::ChunkRecord {
+                                    chunk_type: type_str,
+                                    offset: self.chunk_start,
+                                    length: self.current_chunk.length,
+                                    crc: val,
+                                    crc_ok: val == sum,
+                                });
+                            }
+                        }
+                        if crc_ok {
+                            match parsed {
+                                _ if type_str == IEND => {
+                                    goto!(State::U32(U32Value::Length), emit Decoded::ImageEnd)
                                 }
-                            )
+                                None | Some(Ok(Decoded::Nothing)) => goto!(
+                                    State::U32(U32Value::Length),
+                                    emit Decoded::ChunkComplete(val, type_str)
+                                ),
+                                Some(Ok(decoded)) => goto!(Parsed(val, type_str), emit decoded),
+                                Some(Err(err)) => {
+                                    if is_recoverable(type_str) {
+                                        self.recovery = Some((State::U32(U32Value::Length), 1));
+                                    }
+                                    Err(err)
+                                }
+                            }
                         } else {
+                            if is_recoverable(type_str) {
+                                self.recovery = Some((State::U32(U32Value::Length), 1));
+                            }
                             Err(DecodingError::Format(
                                 FormatErrorInner::CrcMismatch {
                                     recover: 1,
@@ -576,7 +971,12 @@ impl StreamingDecoder {
             }
             U32Byte2(type_, val) => goto!(U32Byte3(type_, val | u32::from(current_byte) << 8)),
             U32Byte1(type_, val) => goto!(U32Byte2(type_, val | u32::from(current_byte) << 16)),
-            U32(type_) => goto!(U32Byte1(type_, u32::from(current_byte) << 24)),
+            U32(type_) => {
+                if let U32Value::Length = type_ {
+                    self.chunk_start = self.offset;
+                }
+                goto!(U32Byte1(type_, u32::from(current_byte) << 24))
+            }
             PartialChunk(type_str) => {
                 match type_str {
                     IDAT => {
@@ -622,8 +1022,41 @@ impl StreamingDecoder {
                     // Handle other chunks
                     _ => {
                         if self.current_chunk.remaining == 0 {
-                            // complete chunk
-                            Ok((0, self.parse_chunk(type_str)?))
+                            if self.retained_chunk_limit(type_str).is_some() {
+                                // The chunk is parsed once its CRC is verified, so that a
+                                // corrupt chunk does not end up in the info.
+                                goto!(0, U32(U32Value::Crc(type_str)))
+                            } else {
+                                // Complete chunk, only checked for its position.
+                                match self.parse_chunk(type_str) {
+                                    Ok(decoded) => {
+                                        goto!(0, U32(U32Value::Crc(type_str)), emit decoded)
+                                    }
+                                    Err(err) => {
+                                        // The data of the chunk has been read, the CRC follows.
+                                        if is_recoverable(type_str) {
+                                            self.recovery =
+                                                Some((State::U32(U32Value::Crc(type_str)), 0));
+                                        }
+                                        Err(err)
+                                    }
+                                }
+                            }
+                        } else if let Some(limit) = self.retained_chunk_limit(type_str) {
+                            // The chunk is parsed as a whole, keep all of its data.
+                            let length = self.current_chunk.length;
+                            if length as usize > limit {
+                                debug!(
+                                    "{} chunk of {} bytes is too long to be parsed",
+                                    type_str, length
+                                );
+                                return Err(DecodingError::LimitsExceeded);
+                            }
+                            let raw_bytes = &mut self.current_chunk.raw_bytes;
+                            let additional =
+                                min(self.current_chunk.remaining as usize, raw_bytes.len());
+                            raw_bytes.reserve(additional);
+                            goto!(0, ReadChunk(type_str, false))
                         } else {
                             goto!(
                                 0, ReadChunk(type_str, true),
@@ -635,7 +1068,12 @@ impl StreamingDecoder {
             }
             ReadChunk(type_str, clear) => {
                 if clear {
-                    self.current_chunk.raw_bytes.clear();
+                    if self.current_chunk.raw_bytes.capacity() > CHUNCK_BUFFER_SIZE {
+                        // Release the memory of a long chunk that was parsed as a whole.
+                        self.current_chunk.raw_bytes = Vec::with_capacity(CHUNCK_BUFFER_SIZE);
+                    } else {
+                        self.current_chunk.raw_bytes.clear();
+                    }
                 }
                 if self.current_chunk.remaining > 0 {
                     let ChunkState {
@@ -643,6 +1081,7 @@ impl StreamingDecoder {
                         remaining,
                         raw_bytes,
                         type_: _,
+                        length: _,
                     } = &mut self.current_chunk;
                     let buf_avail = raw_bytes.capacity() - raw_bytes.len();
                     let bytes_avail = min(buf.len(), buf_avail);
@@ -664,10 +1103,20 @@ impl StreamingDecoder {
                     goto!(0, U32(U32Value::Crc(type_str)))
                 }
             }
+            Parsed(crc, type_str) => goto!(
+                0,
+                U32(U32Value::Length),
+                emit Decoded::ChunkComplete(crc, type_str)
+            ),
             DecodeData(type_str, mut n) => {
                 let chunk_len = self.current_chunk.raw_bytes.len();
                 let chunk_data = &self.current_chunk.raw_bytes[n..];
-                let c = self.inflater.decompress(chunk_data, image_data)?;
+                let before = image_data.len();
+                let timer = start_phase(&self.timings);
+                let c = self.inflater.decompress(chunk_data, image_data);
+                stop_phase(&mut self.timings, timer, |t| &mut t.inflate);
+                let c = c?;
+                self.stats.decompressed_bytes += (image_data.len() - before) as u64;
                 n += c;
                 if n == chunk_len && c == 0 {
                     goto!(
@@ -686,14 +1135,46 @@ impl StreamingDecoder {
         }
     }
 
+    /// Returns the largest length of a chunk that `parse_chunk` parses, `None` otherwise.
+    ///
+    /// The data of such chunks is kept in full while reading, other chunks are passed on in
+    /// pieces of at most [`CHUNCK_BUFFER_SIZE`] bytes.
+    fn retained_chunk_limit(&self, type_str: ChunkType) -> Option<usize> {
+        match type_str {
+            chunk::tEXt | chunk::zTXt | chunk::iTXt => {
+                Some(RETAINED_CHUNK_LIMIT.max(self.text_decompression_limit))
+            }
+            IHDR
+            | chunk::PLTE
+            | chunk::tRNS
+            | chunk::pHYs
+            | chunk::gAMA
+            | chunk::acTL
+            | chunk::fcTL
+            | chunk::cHRM
+            | chunk::sRGB
+            | chunk::iCCP
+            | chunk::dSIG
+            | chunk::sTER
+            | chunk::cICP
+            | chunk::bKGD
+            | chunk::tIME
+            | chunk::eXIf
+            | chunk::gIFg
+            | chunk::gIFx
+            | chunk::fRAc => Some(RETAINED_CHUNK_LIMIT),
+            _ => None,
+        }
+    }
+
+    /// Parses the data of a complete chunk.
+    ///
+    /// The caller sets the next state, and on an error how to recover from it.
     fn parse_chunk(&mut self, type_str: ChunkType) -> Result<Decoded, DecodingError> {
-        self.state = Some(State::U32(U32Value::Crc(type_str)));
-        if self.info.is_none() && type_str != IHDR {
-            return Err(DecodingError::Format(
+        match type_str {
+            _ if self.info.is_none() && type_str != IHDR => Err(DecodingError::Format(
                 FormatErrorInner::ChunkBeforeIhdr { kind: type_str }.into(),
-            ));
-        }
-        match match type_str {
+            )),
             IHDR => self.parse_ihdr(),
             chunk::PLTE => self.parse_plte(),
             chunk::tRNS => self.parse_trns(),
@@ -707,14 +1188,14 @@ impl StreamingDecoder {
             chunk::tEXt => self.parse_text(),
             chunk::zTXt => self.parse_ztxt(),
             chunk::iTXt => self.parse_itxt(),
+            chunk::dSIG => self.parse_dsig(),
+            chunk::sTER => self.parse_ster(),
+            chunk::cICP => self.parse_cicp(),
+            chunk::bKGD => self.parse_bkgd(),
+            chunk::tIME => self.parse_time(),
+            chunk::eXIf => self.parse_exif(),
+            chunk::gIFg | chunk::gIFx | chunk::fRAc => self.parse_legacy(type_str),
             _ => Ok(Decoded::PartialChunk(type_str)),
-        } {
-            Err(err) => {
-                // Borrow of self ends here, because Decoding error does not borrow self.
-                self.state = None;
-                Err(err)
-            }
-            ok => ok,
         }
     }
 
@@ -746,7 +1227,7 @@ impl StreamingDecoder {
             }
             0
         });
-        self.inflater = ZlibStream::new();
+        self.inflater.reset();
         let fc = FrameControl {
             sequence_number: next_seq_no,
             width: buf.read_be()?,
@@ -803,9 +1284,9 @@ impl StreamingDecoder {
         let info = self.info.as_mut().unwrap();
         if info.palette.is_some() {
             // Only one palette is allowed
-            return Err(DecodingError::Format(
+            Err(DecodingError::Format(
                 FormatErrorInner::DuplicateChunk { kind: chunk::PLTE }.into(),
-            ));
+            ))
         } else {
             info.palette = Some(Cow::Owned(self.current_chunk.raw_bytes.clone()));
             Ok(Decoded::Nothing)
@@ -880,9 +1361,9 @@ impl StreamingDecoder {
                 FormatErrorInner::AfterIdat { kind: chunk::pHYs }.into(),
             ))
         } else if info.pixel_dims.is_some() {
-            return Err(DecodingError::Format(
+            Err(DecodingError::Format(
                 FormatErrorInner::DuplicateChunk { kind: chunk::pHYs }.into(),
-            ));
+            ))
         } else {
             let mut buf = &self.current_chunk.raw_bytes[..];
             let xppu = buf.read_be()?;
@@ -908,13 +1389,13 @@ impl StreamingDecoder {
             Err(DecodingError::Format(
                 FormatErrorInner::AfterIdat { kind: chunk::cHRM }.into(),
             ))
-        } else if info.source_chromaticities.is_some() {
-            return Err(DecodingError::Format(
-                FormatErrorInner::DuplicateChunk { kind: chunk::cHRM }.into(),
-            ));
         } else if info.srgb.is_some() {
-            // Ignore chromaticities if sRGB profile is used.
+            // Ignore chromaticities if sRGB profile is used, which already substituted them.
             Ok(Decoded::Nothing)
+        } else if info.source_chromaticities.is_some() {
+            Err(DecodingError::Format(
+                FormatErrorInner::DuplicateChunk { kind: chunk::cHRM }.into(),
+            ))
         } else {
             let mut buf = &self.current_chunk.raw_bytes[..];
             let white_x: u32 = buf.read_be()?;
@@ -956,13 +1437,13 @@ impl StreamingDecoder {
             Err(DecodingError::Format(
                 FormatErrorInner::AfterIdat { kind: chunk::gAMA }.into(),
             ))
-        } else if info.source_gamma.is_some() {
-            return Err(DecodingError::Format(
-                FormatErrorInner::DuplicateChunk { kind: chunk::gAMA }.into(),
-            ));
         } else if info.srgb.is_some() {
-            // Ignore chromaticities if sRGB profile is used.
+            // Ignore the gamma if sRGB profile is used, which already substituted it.
             Ok(Decoded::Nothing)
+        } else if info.source_gamma.is_some() {
+            Err(DecodingError::Format(
+                FormatErrorInner::DuplicateChunk { kind: chunk::gAMA }.into(),
+            ))
         } else {
             let mut buf = &self.current_chunk.raw_bytes[..];
             let source_gamma: u32 = buf.read_be()?;
@@ -975,12 +1456,12 @@ impl StreamingDecoder {
         let info = self.info.as_mut().unwrap();
         if self.have_idat {
             Err(DecodingError::Format(
-                FormatErrorInner::AfterIdat { kind: chunk::acTL }.into(),
+                FormatErrorInner::AfterIdat { kind: chunk::sRGB }.into(),
             ))
         } else if info.srgb.is_some() {
-            return Err(DecodingError::Format(
+            Err(DecodingError::Format(
                 FormatErrorInner::DuplicateChunk { kind: chunk::sRGB }.into(),
-            ));
+            ))
         } else {
             let mut buf = &self.current_chunk.raw_bytes[..];
             let raw: u8 = buf.read_be()?; // BE is is nonsense for single bytes, but this way the size is checked.
@@ -996,49 +1477,121 @@ impl StreamingDecoder {
         }
     }
 
-    fn parse_iccp(&mut self) -> Result<Decoded, DecodingError> {
+    fn parse_ster(&mut self) -> Result<Decoded, DecodingError> {
         let info = self.info.as_mut().unwrap();
         if self.have_idat {
             Err(DecodingError::Format(
-                FormatErrorInner::AfterIdat { kind: chunk::iCCP }.into(),
+                FormatErrorInner::AfterIdat { kind: chunk::sTER }.into(),
             ))
-        } else if info.icc_profile.is_some() {
-            return Err(DecodingError::Format(
-                FormatErrorInner::DuplicateChunk { kind: chunk::iCCP }.into(),
-            ));
+        } else if info.stereo_layout.is_some() {
+            // Like libpng, the first chunk is kept.
+            debug!("ignoring duplicate sTER chunk");
+            Ok(Decoded::Nothing)
         } else {
             let mut buf = &self.current_chunk.raw_bytes[..];
-
-            // read profile name
-            let _: u8 = buf.read_be()?;
-            for _ in 1..80 {
-                let raw: u8 = buf.read_be()?;
-                if raw == 0 {
-                    break;
-                }
+            let raw: u8 = buf.read_be()?;
+            // An unknown mode is ignored, the views can still be shown as one image.
+            if let Some(layout) = crate::StereoLayout::from_raw(raw) {
+                info.stereo_layout = Some(layout);
+            } else {
+                debug!("ignoring sTER chunk with unknown mode {}", raw);
             }
+            Ok(Decoded::Nothing)
+        }
+    }
 
-            match buf.read_be()? {
-                // compression method
-                0u8 => (),
-                n => {
-                    return Err(DecodingError::Format(
-                        FormatErrorInner::UnknownCompressionMethod(n).into(),
-                    ))
-                }
+    fn parse_cicp(&mut self) -> Result<Decoded, DecodingError> {
+        let info = self.info.as_mut().unwrap();
+        if self.have_idat {
+            Err(DecodingError::Format(
+                FormatErrorInner::AfterIdat { kind: chunk::cICP }.into(),
+            ))
+        } else if info.coding_independent_code_points.is_some() {
+            // Like libpng, the first chunk is kept.
+            debug!("ignoring duplicate cICP chunk");
+            Ok(Decoded::Nothing)
+        } else {
+            // A chunk of the wrong length or with an invalid range flag is ignored, the image is
+            // then decoded with the other color space chunks.
+            let data = &self.current_chunk.raw_bytes;
+            if let Some(cicp) = crate::CodingIndependentCodePoints::from_bytes(data) {
+                info.coding_independent_code_points = Some(cicp);
+            } else {
+                debug!("ignoring invalid cICP chunk");
             }
+            Ok(Decoded::Nothing)
+        }
+    }
 
-            let mut profile = Vec::new();
-            let mut inflater = ZlibStream::new();
-            while !buf.is_empty() {
-                let consumed_bytes = inflater.decompress(buf, &mut profile)?;
-                if profile.len() > 8000000 {
-                    // TODO: this should use Limits.bytes
-                    return Err(DecodingError::LimitsExceeded);
+    fn parse_bkgd(&mut self) -> Result<Decoded, DecodingError> {
+        let info = self.info.as_mut().unwrap();
+        if self.have_idat {
+            Err(DecodingError::Format(
+                FormatErrorInner::AfterIdat { kind: chunk::bKGD }.into(),
+            ))
+        } else if info.background_color.is_some() {
+            // Like libpng, the first chunk is kept.
+            debug!("ignoring duplicate bKGD chunk");
+            Ok(Decoded::Nothing)
+        } else {
+            let color =
+                crate::BackgroundColor::from_bytes(info.color_type, &self.current_chunk.raw_bytes);
+            // Like libpng, a chunk of the wrong length or an index outside of the palette is
+            // ignored instead of rejected.
+            let entries = info.palette.as_ref().map_or(0, |palette| palette.len() / 3);
+            match color {
+                Some(crate::BackgroundColor::Indexed(index)) if usize::from(index) >= entries => {}
+                Some(color) => info.background_color = Some(color),
+                None => {
+                    debug!("ignoring bKGD chunk of invalid length");
                 }
-                buf = &buf[consumed_bytes..];
             }
+            Ok(Decoded::Nothing)
+        }
+    }
 
+    fn parse_time(&mut self) -> Result<Decoded, DecodingError> {
+        let info = self.info.as_mut().unwrap();
+        if info.modification_time.is_some() {
+            // Like libpng, the first chunk is kept.
+            debug!("ignoring duplicate tIME chunk");
+            return Ok(Decoded::Nothing);
+        }
+        // A time of the wrong length or out of range is ignored.
+        info.modification_time = crate::ModificationTime::from_bytes(&self.current_chunk.raw_bytes);
+        Ok(Decoded::Nothing)
+    }
+
+    fn parse_exif(&mut self) -> Result<Decoded, DecodingError> {
+        let info = self.info.as_mut().unwrap();
+        if info.exif_metadata.is_some() {
+            // Like libpng, the first chunk is kept.
+            debug!("ignoring duplicate eXIf chunk");
+            return Ok(Decoded::Nothing);
+        }
+        info.exif_metadata = Some(Cow::Owned(self.current_chunk.raw_bytes.clone()));
+        Ok(Decoded::Nothing)
+    }
+
+    fn parse_legacy(&mut self, type_str: ChunkType) -> Result<Decoded, DecodingError> {
+        let info = self.info.as_mut().unwrap();
+        let data = self.current_chunk.raw_bytes.clone();
+        info.legacy_chunks.push((type_str, data));
+        Ok(Decoded::Nothing)
+    }
+
+    fn parse_iccp(&mut self) -> Result<Decoded, DecodingError> {
+        let info = self.info.as_mut().unwrap();
+        if self.have_idat {
+            Err(DecodingError::Format(
+                FormatErrorInner::AfterIdat { kind: chunk::iCCP }.into(),
+            ))
+        } else if info.icc_profile.is_some() {
+            Err(DecodingError::Format(
+                FormatErrorInner::DuplicateChunk { kind: chunk::iCCP }.into(),
+            ))
+        } else {
+            let profile = decompress_icc_profile(&self.current_chunk.raw_bytes)?;
             info.icc_profile = Some(Cow::Owned(profile));
             Ok(Decoded::Nothing)
         }
@@ -1065,7 +1618,7 @@ impl StreamingDecoder {
         let color_type = buf.read_be()?;
         let color_type = match ColorType::from_u8(color_type) {
             Some(color_type) => {
-                if color_type.is_combination_invalid(bit_depth) {
+                if !color_type.is_combination_allowed(bit_depth) {
                     return Err(DecodingError::Format(
                         FormatErrorInner::InvalidColorBitDepth {
                             color_type,
@@ -1117,6 +1670,7 @@ impl StreamingDecoder {
             bit_depth,
             color_type,
             interlaced,
+            color_precedence: self.color_precedence,
             ..Default::default()
         });
 
@@ -1138,16 +1692,56 @@ impl StreamingDecoder {
         Ok((&buf[..null_byte_index], &buf[null_byte_index + 1..]))
     }
 
+    fn parse_dsig(&mut self) -> Result<Decoded, DecodingError> {
+        let info = self.info.as_mut().unwrap();
+        let data = self.current_chunk.raw_bytes.clone();
+        if !self.have_idat {
+            info.signatures.push(DigitalSignature {
+                opening: data,
+                closing: None,
+            });
+        } else if let Some(signature) = info
+            .signatures
+            .iter_mut()
+            .rev()
+            .find(|signature| signature.closing.is_none())
+        {
+            // The innermost open pair is closed first.
+            signature.closing = Some(data);
+        }
+        Ok(Decoded::Nothing)
+    }
+
+    /// Applies the keyword check to a decoded text chunk.
+    fn check_text_keyword(&mut self, keyword: &str) -> Result<(), DecodingError> {
+        let err = match (self.keyword_check, check_keyword(keyword)) {
+            (KeywordCheck::Ignore, _) | (_, Ok(())) => return Ok(()),
+            (_, Err(err)) => err,
+        };
+        if self.keyword_check == KeywordCheck::Reject {
+            return Err(DecodingError::Format(
+                FormatErrorInner::InvalidKeyword(err).into(),
+            ));
+        }
+        let info = self.info.as_mut().unwrap();
+        info.keyword_warnings.push((keyword.to_owned(), err));
+        Ok(())
+    }
+
     fn parse_text(&mut self) -> Result<Decoded, DecodingError> {
         let buf = &self.current_chunk.raw_bytes[..];
 
         let (keyword_slice, value_slice) = Self::split_keyword(buf)?;
 
+        let text = TEXtChunk::decode(keyword_slice, value_slice, self.latin1_mode)
+            .map_err(DecodingError::from)?;
+        self.check_text_keyword(&text.keyword)?;
         self.info
             .as_mut()
             .unwrap()
             .uncompressed_latin1_text
-            .push(TEXtChunk::decode(keyword_slice, value_slice).map_err(DecodingError::from)?);
+            .push(text);
+        self.info.as_mut().unwrap().text_order.push(chunk::tEXt);
 
         Ok(Decoded::Nothing)
     }
@@ -1158,15 +1752,26 @@ impl StreamingDecoder {
         let (keyword_slice, value_slice) = Self::split_keyword(buf)?;
 
         let compression_method = *value_slice
-            .get(0)
+            .first()
             .ok_or_else(|| DecodingError::from(TextDecodingError::InvalidCompressionMethod))?;
 
         let text_slice = &value_slice[1..];
 
-        self.info.as_mut().unwrap().compressed_latin1_text.push(
-            ZTXtChunk::decode(keyword_slice, compression_method, text_slice)
-                .map_err(DecodingError::from)?,
-        );
+        let text = ZTXtChunk::decode(
+            keyword_slice,
+            compression_method,
+            text_slice,
+            self.latin1_mode,
+            self.text_decompression_limit,
+        )
+        .map_err(DecodingError::from)?;
+        self.check_text_keyword(&text.keyword)?;
+        self.info
+            .as_mut()
+            .unwrap()
+            .compressed_latin1_text
+            .push(text);
+        self.info.as_mut().unwrap().text_order.push(chunk::zTXt);
 
         Ok(Decoded::Nothing)
     }
@@ -1239,7 +1844,7 @@ impl StreamingDecoder {
         let (keyword_slice, value_slice) = Self::split_keyword(buf)?;
 
         let compression_flag = *value_slice
-            .get(0)
+            .first()
             .ok_or_else(|| DecodingError::from(TextDecodingError::MissingCompressionFlag))?;
 
         let compression_method = *value_slice
@@ -1265,17 +1870,19 @@ impl StreamingDecoder {
 
         let text_slice = &value_slice[third_null_byte_index + 1..];
 
-        self.info.as_mut().unwrap().utf8_text.push(
-            ITXtChunk::decode(
-                keyword_slice,
-                compression_flag,
-                compression_method,
-                language_tag_slice,
-                translated_keyword_slice,
-                text_slice,
-            )
-            .map_err(DecodingError::from)?,
-        );
+        let text = ITXtChunk::decode(
+            keyword_slice,
+            compression_flag,
+            compression_method,
+            language_tag_slice,
+            translated_keyword_slice,
+            text_slice,
+            self.text_decompression_limit,
+        )
+        .map_err(DecodingError::from)?;
+        self.check_text_keyword(&text.keyword)?;
+        self.info.as_mut().unwrap().utf8_text.push(text);
+        self.info.as_mut().unwrap().text_order.push(chunk::iTXt);
 
         Ok(Decoded::Nothing)
     }
@@ -1299,6 +1906,51 @@ impl Info<'_> {
     }
 }
 
+/// Whether decoding can continue after an error in a chunk of this type.
+///
+/// The animation chunks are ancillary, but skipping one would leave the frames out of sequence.
+fn is_recoverable(type_str: ChunkType) -> bool {
+    !chunk::is_critical(type_str) && !matches!(type_str, chunk::acTL | chunk::fcTL | chunk::fdAT)
+}
+
+/// Decompresses the profile in the data of an `iCCP` chunk.
+pub(crate) fn decompress_icc_profile(mut buf: &[u8]) -> Result<Vec<u8>, DecodingError> {
+    // read profile name
+    let _: u8 = buf.read_be()?;
+    for _ in 1..80 {
+        let raw: u8 = buf.read_be()?;
+        if raw == 0 {
+            break;
+        }
+    }
+
+    match buf.read_be()? {
+        // compression method
+        0u8 => (),
+        n => {
+            return Err(DecodingError::Format(
+                FormatErrorInner::UnknownCompressionMethod(n).into(),
+            ))
+        }
+    }
+
+    let mut profile = Vec::new();
+    let mut inflater = MinizStream::new();
+    while !buf.is_empty() {
+        let consumed_bytes = inflater.decompress(buf, &mut profile)?;
+        if profile.len() > 8000000 {
+            // TODO: this should use Limits.bytes
+            debug!("ICC profile exceeds the limit of 8000000 bytes");
+            return Err(DecodingError::LimitsExceeded);
+        }
+        buf = &buf[consumed_bytes..];
+    }
+    // The decompressor holds back the end of the profile until the stream is finished.
+    inflater.finish_compressed_chunks(&mut profile)?;
+
+    Ok(profile)
+}
+
 impl Default for StreamingDecoder {
     fn default() -> Self {
         Self::new()
@@ -1311,6 +1963,7 @@ impl Default for ChunkState {
             type_: ChunkType([0; 4]),
             crc: Crc32::new(),
             remaining: 0,
+            length: 0,
             raw_bytes: Vec::with_capacity(CHUNCK_BUFFER_SIZE),
         }
     }
@@ -1363,6 +2016,58 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn srgb_overrides_gamma_and_chromaticities() {
+        fn encode(before: &[(crate::chunk::ChunkType, &[u8])], after: bool) -> Vec<u8> {
+            let mut png = Vec::new();
+            {
+                let encoder = crate::Encoder::new(&mut png, 1, 1);
+                let mut writer = encoder.write_header().unwrap();
+                for &(kind, data) in before {
+                    writer.write_chunk(kind, data).unwrap();
+                }
+                writer.write_image_data(&[0]).unwrap();
+                if after {
+                    writer.write_chunk(crate::chunk::sRGB, &[0]).unwrap();
+                }
+            }
+            png
+        }
+
+        // gAMA and cHRM after sRGB are ignored instead of reported as duplicates.
+        let chrm = [0u8; 32];
+        let png = encode(
+            &[
+                (crate::chunk::sRGB, &[0]),
+                (crate::chunk::gAMA, &100_000u32.to_be_bytes()),
+                (crate::chunk::cHRM, &chrm),
+            ],
+            false,
+        );
+        let reader = crate::Decoder::new(&png[..]).read_info().unwrap();
+        assert_eq!(
+            reader.info().source_gamma,
+            Some(crate::srgb::substitute_gamma())
+        );
+        assert_eq!(
+            reader.info().source_chromaticities,
+            Some(crate::srgb::substitute_chromaticities())
+        );
+
+        // A late sRGB chunk is reported as such.
+        let png = encode(&[], true);
+        let mut decoder = super::StreamingDecoder::new();
+        let mut image_data = Vec::new();
+        let mut buf = &png[..];
+        let err = loop {
+            match decoder.update(buf, &mut image_data) {
+                Ok((consumed, _)) => buf = &buf[consumed..],
+                Err(err) => break err,
+            }
+        };
+        assert!(err.to_string().contains("sRGB"), "{}", err);
+    }
+
     #[test]
     fn image_source_chromaticities() -> Result<(), ()> {
         fn trial(path: &str, expected: Option<SourceChromaticities>) {
@@ -1549,4 +2254,111 @@ mod tests {
         trial("tests/pngsuite/z06n2c08.png", None);
         Ok(())
     }
+
+    #[test]
+    fn chunk_context() {
+        let mut png = Vec::new();
+        let mut encoder = crate::Encoder::new(&mut png, 1, 1);
+        encoder.add_text_chunk("a".into(), "1".into()).unwrap();
+        encoder.add_text_chunk("b".into(), "2".into()).unwrap();
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[0]).unwrap();
+        writer.finish().unwrap();
+
+        // Damage the CRC of the second tEXt chunk.
+        let pos = png
+            .windows(4)
+            .enumerate()
+            .filter(|(_, window)| window == b"tEXt")
+            .map(|(pos, _)| pos - 4)
+            .nth(1)
+            .unwrap();
+        png[pos + 14] ^= 1;
+
+        let err = crate::Decoder::new(&png[..]).read_info().err().unwrap();
+        let context = err.chunk_context().unwrap();
+        assert_eq!(context.chunk_type, crate::chunk::tEXt);
+        assert_eq!(context.index, 1);
+        assert_eq!(context.offset, pos as u64);
+        assert_eq!(err.offset(), Some(pos as u64 + 14));
+        let message = format!("in the 2nd tEXt chunk at offset {:#x})", pos);
+        assert!(err.to_string().ends_with(&message));
+    }
+
+    #[test]
+    fn current_offset() {
+        let mut png = Vec::new();
+        let mut writer = crate::Encoder::new(&mut png, 1, 1).write_header().unwrap();
+        writer.write_image_data(&[0]).unwrap();
+        writer.finish().unwrap();
+
+        let mut decoder = super::StreamingDecoder::new();
+        let mut buf = &png[..];
+        let mut image_data = Vec::new();
+        loop {
+            let (consumed, decoded) = decoder.update(buf, &mut image_data).unwrap();
+            buf = &buf[consumed..];
+            assert_eq!(decoder.current_offset(), (png.len() - buf.len()) as u64);
+            if let super::Decoded::ImageEnd = decoded {
+                break;
+            }
+        }
+        assert_eq!(decoder.current_offset(), png.len() as u64);
+        decoder.reset();
+        assert_eq!(decoder.current_offset(), 0);
+    }
+
+    #[test]
+    fn skip_and_continue() {
+        let mut png = Vec::new();
+        let mut writer = crate::Encoder::new(&mut png, 1, 1).write_header().unwrap();
+        writer.write_chunk(crate::chunk::sRGB, &[9]).unwrap();
+        writer.write_image_data(&[0]).unwrap();
+        writer.finish().unwrap();
+
+        let mut decoder = super::StreamingDecoder::new();
+        let mut image_data = Vec::new();
+        let mut errors = 0;
+        loop {
+            let buf = &png[decoder.current_offset() as usize..];
+            match decoder.update(buf, &mut image_data) {
+                Ok((_, super::Decoded::ImageEnd)) => break,
+                Ok(_) => {}
+                Err(err) => {
+                    assert_eq!(err.severity(), super::ErrorSeverity::Recoverable);
+                    assert!(decoder.skip_and_continue());
+                    errors += 1;
+                }
+            }
+        }
+        assert_eq!(errors, 1);
+        assert_eq!(decoder.info().unwrap().srgb, None);
+        assert!(!decoder.skip_and_continue());
+    }
+
+    #[test]
+    fn chunk_inventory() {
+        let png = std::fs::read("tests/pngsuite/ctzn0g04.png").unwrap();
+        let mut decoder = super::StreamingDecoder::new();
+        decoder.set_chunk_inventory(true);
+        let mut buf = &png[..];
+        loop {
+            let (consumed, decoded) = decoder.update(buf, &mut Vec::new()).unwrap();
+            buf = &buf[consumed..];
+            if let super::Decoded::ImageEnd = decoded {
+                break;
+            }
+        }
+
+        let index = crate::chunk::ChunkIndex::new(&png).unwrap();
+        let inventory = &decoder.info.as_ref().unwrap().chunk_inventory;
+        assert_eq!(inventory.len(), index.chunks().len());
+        for (record, chunk) in inventory.iter().zip(index.chunks()) {
+            assert_eq!(record.chunk_type, chunk.chunk_type);
+            assert_eq!(record.offset, chunk.offset as u64);
+            assert_eq!(record.length as usize, chunk.data.len());
+            assert_eq!(record.crc, chunk.crc);
+            assert!(record.crc_ok);
+        }
+    }
 }
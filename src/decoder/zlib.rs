@@ -3,8 +3,32 @@ use super::{stream::FormatErrorInner, DecodingError, CHUNCK_BUFFER_SIZE};
 use miniz_oxide::inflate::core::{decompress, inflate_flags, DecompressorOxide};
 use miniz_oxide::inflate::TINFLStatus;
 
+/// A decompressor of the zlib stream in the image data chunks of a frame.
+///
+/// The [`StreamingDecoder`](crate::StreamingDecoder) parses the PNG chunks and hands the
+/// contents of consecutive `IDAT` or `fdAT` chunks to the stream, which is reset for each frame.
+/// By default `miniz_oxide` decompresses, and other decompressors such as hardware inflate
+/// engines can be used with [`StreamingDecoder::set_zlib_stream`](crate::StreamingDecoder::set_zlib_stream).
+///
+/// Errors of an implementation are best reported as [`DecodingError::IoError`], for example with
+/// the kind [`std::io::ErrorKind::InvalidData`] for corrupt data.
+pub trait ZlibStream: Send {
+    /// Decompresses data of the stream, appending the decompressed bytes to `image_data`.
+    ///
+    /// Returns the number of consumed bytes of `data`. Bytes that are not consumed are passed
+    /// again, so an implementation buffers the input it can not use yet to make progress.
+    fn decompress(&mut self, data: &[u8], image_data: &mut Vec<u8>)
+        -> Result<usize, DecodingError>;
+
+    /// Decompresses all remaining buffered data once the image data chunks have ended.
+    fn finish_compressed_chunks(&mut self, image_data: &mut Vec<u8>) -> Result<(), DecodingError>;
+
+    /// Prepares for a new stream.
+    fn reset(&mut self);
+}
+
 /// Ergonomics wrapper around `miniz_oxide::inflate::stream` for zlib compressed data.
-pub(super) struct ZlibStream {
+pub(super) struct MinizStream {
     /// Current decoding state.
     state: Box<DecompressorOxide>,
     /// If there has been a call to decompress already.
@@ -32,9 +56,9 @@ pub(super) struct ZlibStream {
     out_pos: usize,
 }
 
-impl ZlibStream {
+impl MinizStream {
     pub(crate) fn new() -> Self {
-        ZlibStream {
+        MinizStream {
             state: Box::default(),
             started: false,
             in_buffer: Vec::with_capacity(CHUNCK_BUFFER_SIZE),
@@ -47,6 +71,7 @@ impl ZlibStream {
     pub(crate) fn reset(&mut self) {
         self.started = false;
         self.in_buffer.clear();
+        self.in_pos = 0;
         self.out_buffer.clear();
         self.out_pos = 0;
         *self.state = DecompressorOxide::default();
@@ -149,7 +174,7 @@ impl ZlibStream {
 
             match status {
                 TINFLStatus::Done => {
-                    self.out_buffer.truncate(self.out_pos as usize);
+                    self.out_buffer.truncate(self.out_pos);
                     image_data.append(&mut self.out_buffer);
                     return Ok(());
                 }
@@ -188,10 +213,10 @@ impl ZlibStream {
             .saturating_add(CHUNCK_BUFFER_SIZE.max(len))
             // Ensure all buffer indices are valid cursor positions.
             // Note: both cut off and zero extension give correct results.
-            .min(u64::max_value() as usize)
+            .min(u64::MAX as usize)
             // Ensure the allocation request is valid.
             // TODO: maximum allocation limits?
-            .min(isize::max_value() as usize)
+            .min(isize::MAX as usize)
     }
 
     fn transfer_finished_data(&mut self, image_data: &mut Vec<u8>) -> usize {
@@ -202,3 +227,21 @@ impl ZlibStream {
         safe
     }
 }
+
+impl ZlibStream for MinizStream {
+    fn decompress(
+        &mut self,
+        data: &[u8],
+        image_data: &mut Vec<u8>,
+    ) -> Result<usize, DecodingError> {
+        MinizStream::decompress(self, data, image_data)
+    }
+
+    fn finish_compressed_chunks(&mut self, image_data: &mut Vec<u8>) -> Result<(), DecodingError> {
+        MinizStream::finish_compressed_chunks(self, image_data)
+    }
+
+    fn reset(&mut self) {
+        MinizStream::reset(self)
+    }
+}
@@ -0,0 +1,646 @@
+//! Editing encoded images without recompressing their image data.
+use std::borrow::Cow;
+use std::io::Write;
+
+use crate::chunk::{self, ChunkType};
+use crate::common::{AnimationControl, BitDepth, ColorType, FrameControl, FrameData, Loops};
+use crate::encoder::{
+    encoded_chunks, image_data_of, malformed_encoded_image, validate_chunk_len, write_chunk,
+    EncodedHeader, EncodingError, FormatErrorKind, FrameOptions, Result,
+};
+
+/// The largest amount of compressed data written in a single `IDAT` or `fdAT` chunk.
+const MAX_DATA_LEN: usize = (u32::MAX >> 1) as usize - 4;
+
+/// An encoded animation whose frames can be inserted, removed, reordered and retimed.
+///
+/// Only the frame control and sequence numbers are rewritten, the compressed image data of each
+/// frame is copied. The other chunks of the image are kept, apart from the `acTL` chunk which is
+/// recreated.
+///
+/// ```
+/// # fn edit(apng: &[u8]) -> Result<Vec<u8>, png::EncodingError> {
+/// let mut editor = png::AnimationEditor::new(apng)?;
+/// let frames = editor.frames_mut();
+/// frames.truncate(3);
+/// frames.reverse();
+/// for frame in frames.iter_mut() {
+///     frame.control.delay_num = 10;
+///     frame.control.delay_den = 100;
+/// }
+///
+/// let mut out = Vec::new();
+/// editor.write(&mut out)?;
+/// # Ok(out)
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct AnimationEditor {
+    width: u32,
+    height: u32,
+    color_type: ColorType,
+    bit_depth: BitDepth,
+    num_plays: u32,
+    /// The chunks before the image data, starting with `IHDR` but without `acTL`.
+    leading: Vec<(ChunkType, Vec<u8>)>,
+    /// The chunks after the image data, without `IEND`.
+    trailing: Vec<(ChunkType, Vec<u8>)>,
+    /// The compressed default image, when it is not part of the animation.
+    default_image: Option<Vec<u8>>,
+    frames: Vec<AnimationFrame>,
+}
+
+/// A frame of an [`AnimationEditor`].
+#[derive(Clone, Debug)]
+pub struct AnimationFrame {
+    /// The size, position, timing and operations of the frame.
+    ///
+    /// The sequence number is ignored, as it is assigned again when writing.
+    pub control: FrameControl,
+    color_type: ColorType,
+    bit_depth: BitDepth,
+    colors: ColorTables,
+    zlib_encoded: Vec<u8>,
+}
+
+/// The `PLTE` and `tRNS` chunks that the image data of a frame refers to.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct ColorTables {
+    /// The palette of an indexed image, for other color types it is only a suggestion.
+    palette: Option<Vec<u8>>,
+    transparency: Option<Vec<u8>>,
+}
+
+impl ColorTables {
+    fn new<'a, I>(chunks: I, color_type: ColorType) -> Self
+    where
+        I: IntoIterator<Item = (ChunkType, &'a [u8])>,
+    {
+        let mut colors = ColorTables::default();
+        for (name, data) in chunks {
+            match name {
+                chunk::PLTE if color_type == ColorType::Indexed => {
+                    colors.palette.get_or_insert_with(|| data.to_vec());
+                }
+                chunk::tRNS => {
+                    colors.transparency.get_or_insert_with(|| data.to_vec());
+                }
+                _ => {}
+            }
+        }
+        colors
+    }
+}
+
+impl AnimationEditor {
+    /// Reads the frames of an encoded image.
+    ///
+    /// An image that is not animated is read as an animation with a single frame.
+    pub fn new(png: &[u8]) -> Result<Self> {
+        let chunks = encoded_chunks(png)?;
+        let header = EncodedHeader::parse(chunks[0].1)?;
+        let colors = ColorTables::new(chunks.iter().copied(), header.color_type);
+        let mut num_plays = None;
+        let mut leading = Vec::new();
+        let mut trailing = Vec::new();
+        let mut default_image: Option<Vec<u8>> = None;
+        let mut frames: Vec<AnimationFrame> = Vec::new();
+        let mut seen_image = false;
+
+        for (name, data) in chunks {
+            match name {
+                chunk::acTL if data.len() == 8 => {
+                    num_plays = AnimationControl::from_bytes(data).map(|actl| actl.num_plays);
+                }
+                chunk::fcTL => frames.push(AnimationFrame {
+                    control: FrameControl::from_bytes(data).ok_or_else(malformed_encoded_image)?,
+                    color_type: header.color_type,
+                    bit_depth: header.bit_depth,
+                    colors: colors.clone(),
+                    zlib_encoded: Vec::new(),
+                }),
+                chunk::IDAT => {
+                    seen_image = true;
+                    match frames.last_mut() {
+                        Some(frame) => frame.zlib_encoded.extend_from_slice(data),
+                        None => default_image
+                            .get_or_insert_with(Vec::new)
+                            .extend_from_slice(data),
+                    }
+                }
+                chunk::fdAT if seen_image => match (frames.last_mut(), FrameData::from_bytes(data))
+                {
+                    (Some(frame), Some(fdat)) => frame.zlib_encoded.extend_from_slice(fdat.data),
+                    _ => return Err(malformed_encoded_image()),
+                },
+                chunk::acTL | chunk::fdAT => return Err(malformed_encoded_image()),
+                // Any edit invalidates the signatures of the original file.
+                chunk::dSIG => {}
+                _ if seen_image => trailing.push((name, data.to_vec())),
+                _ => leading.push((name, data.to_vec())),
+            }
+        }
+
+        let num_plays = match num_plays {
+            Some(num_plays) => num_plays,
+            None => {
+                // Without animation control, decoders only show the default image.
+                let zlib_encoded = default_image.take().ok_or_else(malformed_encoded_image)?;
+                frames = vec![AnimationFrame {
+                    control: FrameControl {
+                        width: header.width,
+                        height: header.height,
+                        ..FrameControl::default()
+                    },
+                    color_type: header.color_type,
+                    bit_depth: header.bit_depth,
+                    colors,
+                    zlib_encoded,
+                }];
+                0
+            }
+        };
+
+        if frames.iter().any(|frame| frame.zlib_encoded.is_empty()) {
+            return Err(malformed_encoded_image());
+        }
+
+        Ok(AnimationEditor {
+            width: header.width,
+            height: header.height,
+            color_type: header.color_type,
+            bit_depth: header.bit_depth,
+            num_plays,
+            leading,
+            trailing,
+            default_image,
+            frames,
+        })
+    }
+
+    /// The frames of the animation.
+    pub fn frames(&self) -> &[AnimationFrame] {
+        &self.frames
+    }
+
+    /// The frames of the animation, which can be edited freely until they are written.
+    pub fn frames_mut(&mut self) -> &mut Vec<AnimationFrame> {
+        &mut self.frames
+    }
+
+    /// How many times the animation is played.
+    pub fn loops(&self) -> Loops {
+        Loops::from_num_plays(self.num_plays)
+    }
+
+    /// Set how many times the animation is played.
+    pub fn set_loops(&mut self, loops: Loops) {
+        self.num_plays = loops.num_plays();
+    }
+
+    /// Whether the default image is shown only by decoders without animation support.
+    ///
+    /// Otherwise the first frame is the default image, and must cover the whole image.
+    pub fn has_separate_default_image(&self) -> bool {
+        self.default_image.is_some()
+    }
+
+    /// Writes the edited animation.
+    ///
+    /// An error is returned if there are no frames, if a frame doesn't fit into the image or its
+    /// color type, bit depth, palette or transparency differ from those of the image, and if the
+    /// first frame is the default image but does not cover the whole image.
+    pub fn write<W: Write>(&self, mut w: W) -> Result<()> {
+        self.validate()?;
+
+        w.write_all(&[137, 80, 78, 71, 13, 10, 26, 10])?;
+        for (i, (name, data)) in self.leading.iter().enumerate() {
+            write_chunk(&mut w, *name, data)?;
+            if i == 0 {
+                AnimationControl {
+                    num_frames: self.frames.len() as u32,
+                    num_plays: self.num_plays,
+                }
+                .encode(&mut w)?;
+            }
+        }
+
+        if let Some(default_image) = &self.default_image {
+            write_idat(&mut w, default_image)?;
+        }
+
+        let mut sequence_number = 0u32;
+        for (i, frame) in self.frames.iter().enumerate() {
+            FrameControl {
+                sequence_number,
+                ..frame.control
+            }
+            .encode(&mut w)?;
+            sequence_number = sequence_number.wrapping_add(1);
+
+            if i == 0 && self.default_image.is_none() {
+                write_idat(&mut w, &frame.zlib_encoded)?;
+                continue;
+            }
+
+            for data in frame.zlib_encoded.chunks(MAX_DATA_LEN) {
+                FrameData {
+                    sequence_number,
+                    data,
+                }
+                .encode(&mut w)?;
+                sequence_number = sequence_number.wrapping_add(1);
+            }
+        }
+
+        for (name, data) in &self.trailing {
+            write_chunk(&mut w, *name, data)?;
+        }
+        write_chunk(&mut w, chunk::IEND, &[])
+    }
+
+    fn validate(&self) -> Result<()> {
+        let format = |kind: FormatErrorKind| Err(EncodingError::Format(kind.into()));
+        if self.frames.is_empty() {
+            return format(FormatErrorKind::ZeroFrames);
+        }
+
+        let leading = self.leading.iter().map(|(name, data)| (*name, &data[..]));
+        let colors = ColorTables::new(leading, self.color_type);
+        for (i, frame) in self.frames.iter().enumerate() {
+            let fctl = &frame.control;
+            if (frame.color_type, frame.bit_depth) != (self.color_type, self.bit_depth)
+                || frame.colors != colors
+            {
+                return format(FormatErrorKind::MismatchedEncodedFrame);
+            } else if fctl.width == 0 {
+                return format(FormatErrorKind::ZeroWidth);
+            } else if fctl.height == 0 {
+                return format(FormatErrorKind::ZeroHeight);
+            } else if Some(fctl.width) > self.width.checked_sub(fctl.x_offset)
+                || Some(fctl.height) > self.height.checked_sub(fctl.y_offset)
+            {
+                return format(FormatErrorKind::OutOfBounds);
+            } else if i == 0
+                && self.default_image.is_none()
+                && (fctl.width, fctl.height, fctl.x_offset, fctl.y_offset)
+                    != (self.width, self.height, 0, 0)
+            {
+                return format(FormatErrorKind::InvalidDefaultFrame);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl AnimationFrame {
+    /// Creates a frame from the compressed image data of an encoded PNG image.
+    ///
+    /// The size of the frame is that of `png`, and an error is returned if `frame.size` is set to
+    /// a different size. The image must not be interlaced, and only its image data is used. As
+    /// the data is not decoded, its `PLTE` and `tRNS` chunks must be the same as those of the
+    /// animation, otherwise [`AnimationEditor::write`] returns an error.
+    pub fn from_png(png: &[u8], frame: FrameOptions) -> Result<Self> {
+        let chunks = encoded_chunks(png)?;
+        let (header, zlib_encoded) = image_data_of(&chunks)?;
+        let size = (header.width, header.height);
+        if frame.size.map_or(false, |frame_size| frame_size != size) {
+            return Err(EncodingError::Format(
+                FormatErrorKind::MismatchedEncodedFrame.into(),
+            ));
+        }
+
+        Ok(AnimationFrame {
+            control: FrameControl {
+                sequence_number: 0,
+                width: header.width,
+                height: header.height,
+                x_offset: frame.x_offset,
+                y_offset: frame.y_offset,
+                delay_num: frame.delay_num,
+                delay_den: frame.delay_den,
+                dispose_op: frame.dispose_op,
+                blend_op: frame.blend_op,
+            },
+            color_type: header.color_type,
+            bit_depth: header.bit_depth,
+            colors: ColorTables::new(chunks, header.color_type),
+            zlib_encoded,
+        })
+    }
+}
+
+/// An encoded image whose ancillary chunks can be added, replaced and removed.
+///
+/// The critical chunks, and with them the image data, are copied unchanged, as are all ancillary
+/// chunks that are not edited. Only `dSIG` chunks are removed once anything is edited, as the
+/// signatures no longer match.
+///
+/// ```
+/// # fn edit(png: &[u8], profile: Vec<u8>) -> Result<Vec<u8>, png::EncodingError> {
+/// use png::chunk;
+///
+/// let mut editor = png::ChunkEditor::new(png)?;
+/// editor.retain(|chunk_type, _| ![chunk::tEXt, chunk::zTXt, chunk::iTXt].contains(&chunk_type));
+/// editor.replace(chunk::iCCP, profile)?;
+///
+/// let mut out = Vec::new();
+/// editor.write(&mut out)?;
+/// # Ok(out)
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct ChunkEditor<'a> {
+    /// The chunks of the image, starting with `IHDR` but without `IEND`.
+    chunks: Vec<(ChunkType, Cow<'a, [u8]>)>,
+    modified: bool,
+}
+
+impl<'a> ChunkEditor<'a> {
+    /// Reads the chunks of an encoded image, which are borrowed until they are edited.
+    pub fn new(png: &'a [u8]) -> Result<Self> {
+        let chunks = encoded_chunks(png)?
+            .into_iter()
+            .map(|(name, data)| (name, Cow::Borrowed(data)))
+            .collect();
+        Ok(ChunkEditor {
+            chunks,
+            modified: false,
+        })
+    }
+
+    /// The chunks of the image in their current order, without the `IEND` chunk.
+    pub fn chunks(&self) -> impl Iterator<Item = (ChunkType, &[u8])> {
+        self.chunks.iter().map(|(name, data)| (*name, &data[..]))
+    }
+
+    /// Removes all chunks of an ancillary chunk type, returning how many were removed.
+    pub fn remove(&mut self, chunk_type: ChunkType) -> Result<usize> {
+        check_ancillary(chunk_type)?;
+        let len = self.chunks.len();
+        self.retain(|name, _| name != chunk_type);
+        Ok(len - self.chunks.len())
+    }
+
+    /// Keeps only the ancillary chunks for which `keep` returns true.
+    ///
+    /// The critical chunks are always kept, `keep` is not called for them.
+    pub fn retain<F>(&mut self, mut keep: F)
+    where
+        F: FnMut(ChunkType, &[u8]) -> bool,
+    {
+        let len = self.chunks.len();
+        self.chunks
+            .retain(|(name, data)| chunk::is_critical(*name) || keep(*name, data));
+        self.modified |= len != self.chunks.len();
+    }
+
+    /// Replaces the data of the chunks with an ancillary chunk type by `data`.
+    ///
+    /// The first chunk of that type is kept in place with the new data and any further ones are
+    /// removed. Without a chunk of that type, it is inserted as by [`ChunkEditor::insert`].
+    pub fn replace(&mut self, chunk_type: ChunkType, data: Vec<u8>) -> Result<()> {
+        check_ancillary(chunk_type)?;
+        validate_chunk_len(&data)?;
+        match self.chunks.iter().position(|(name, _)| *name == chunk_type) {
+            Some(first) => {
+                self.chunks[first].1 = Cow::Owned(data);
+                let mut index = 0;
+                self.chunks.retain(|(name, _)| {
+                    index += 1;
+                    *name != chunk_type || index == first + 1
+                });
+                self.modified = true;
+                Ok(())
+            }
+            None => self.insert(chunk_type, data),
+        }
+    }
+
+    /// Adds a chunk with an ancillary chunk type.
+    ///
+    /// It is added after the last chunk of the same type. Otherwise, chunks that have to precede
+    /// the palette are added directly after the image header and all others directly before the
+    /// image data.
+    pub fn insert(&mut self, chunk_type: ChunkType, data: Vec<u8>) -> Result<()> {
+        check_ancillary(chunk_type)?;
+        validate_chunk_len(&data)?;
+        let position = match self
+            .chunks
+            .iter()
+            .rposition(|(name, _)| *name == chunk_type)
+        {
+            Some(last) => last + 1,
+            None if chunk::BEFORE_PALETTE.contains(&chunk_type) => 1,
+            None => self
+                .chunks
+                .iter()
+                .position(|(name, _)| *name == chunk::IDAT || *name == chunk::fcTL)
+                .unwrap_or(self.chunks.len()),
+        };
+        self.chunks.insert(position, (chunk_type, Cow::Owned(data)));
+        self.modified = true;
+        Ok(())
+    }
+
+    /// Writes the edited image.
+    pub fn write<W: Write>(&self, mut w: W) -> Result<()> {
+        w.write_all(&[137, 80, 78, 71, 13, 10, 26, 10])?;
+        for (name, data) in &self.chunks {
+            if !(self.modified && *name == chunk::dSIG) {
+                write_chunk(&mut w, *name, data)?;
+            }
+        }
+        write_chunk(&mut w, chunk::IEND, &[])
+    }
+}
+
+fn check_ancillary(chunk_type: ChunkType) -> Result<()> {
+    if !chunk_type.is_valid() {
+        let kind = FormatErrorKind::InvalidChunkType(chunk_type);
+        return Err(EncodingError::Format(kind.into()));
+    }
+    if chunk::is_critical(chunk_type) {
+        let kind = FormatErrorKind::CriticalChunk(chunk_type);
+        return Err(EncodingError::Format(kind.into()));
+    }
+    Ok(())
+}
+
+fn write_idat<W: Write>(mut w: W, zlib_encoded: &[u8]) -> Result<()> {
+    for data in zlib_encoded.chunks(MAX_DATA_LEN) {
+        write_chunk(&mut w, chunk::IDAT, data)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Decoder, Encoder};
+
+    fn animation(values: &[u8], sep_def_img: bool) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut encoder = Encoder::new(&mut out, 2, 2);
+        encoder.set_animated(values.len() as u32, 3).unwrap();
+        encoder.set_sep_def_img(sep_def_img).unwrap();
+        let mut writer = encoder.write_header().unwrap();
+        if sep_def_img {
+            writer.write_image_data(&[0xff; 4]).unwrap();
+        }
+        for &value in values {
+            writer
+                .add_frame(&[value; 4], FrameOptions::default())
+                .unwrap();
+        }
+        writer
+            .write_text_chunk(&crate::text_metadata::TEXtChunk::new("k", "v"))
+            .unwrap();
+        writer.finish().unwrap();
+        out
+    }
+
+    fn frame_values(png: &[u8]) -> Vec<(u8, u16)> {
+        let mut reader = Decoder::new(png).read_info().unwrap();
+        let num_frames = reader.info().animation_control().unwrap().num_frames;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        if reader.info().frame_control().is_none() {
+            reader.next_frame(&mut buf).unwrap();
+        }
+        (0..num_frames)
+            .map(|_| {
+                reader.next_frame(&mut buf).unwrap();
+                (buf[0], reader.info().frame_control().unwrap().delay_num)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn edit_frames() {
+        for &sep_def_img in &[false, true] {
+            let mut editor = AnimationEditor::new(&animation(&[1, 2, 3], sep_def_img)).unwrap();
+            assert_eq!(editor.has_separate_default_image(), sep_def_img);
+            assert_eq!(editor.loops(), Loops::from_num_plays(3));
+
+            let frames = editor.frames_mut();
+            frames.swap(0, 2);
+            frames.remove(1);
+            frames[0].control.delay_num = 7;
+            let mut png = Vec::new();
+            Encoder::new(&mut png, 2, 2)
+                .write_header()
+                .unwrap()
+                .write_image_data(&[4; 4])
+                .unwrap();
+            frames.push(AnimationFrame::from_png(&png, FrameOptions::default()).unwrap());
+
+            let mut out = Vec::new();
+            editor.write(&mut out).unwrap();
+            assert_eq!(frame_values(&out), [(3, 7), (1, 1), (4, 1)]);
+            let edited = AnimationEditor::new(&out).unwrap();
+            assert_eq!(edited.trailing, [(chunk::tEXt, b"k\0v".to_vec())]);
+        }
+    }
+
+    #[test]
+    fn edit_rejects_invalid_frames() {
+        let mut editor = AnimationEditor::new(&animation(&[1, 2], false)).unwrap();
+        editor.frames_mut()[0].control.x_offset = 1;
+        assert!(editor.write(std::io::sink()).is_err());
+
+        editor.frames_mut().clear();
+        assert!(editor.write(std::io::sink()).is_err());
+    }
+
+    #[test]
+    fn edit_rejects_other_palettes() {
+        let indexed = |palette: &[u8], trns: &[u8], frames: u32| {
+            let mut png = Vec::new();
+            let mut encoder = Encoder::new(&mut png, 2, 2);
+            encoder.set_color(ColorType::Indexed);
+            encoder.set_palette(palette.to_vec());
+            encoder.set_trns(trns.to_vec());
+            if frames > 0 {
+                encoder.set_animated(frames, 0).unwrap();
+            }
+            let mut writer = encoder.write_header().unwrap();
+            for _ in 0..frames.max(1) {
+                writer.write_image_data(&[0, 1, 1, 0]).unwrap();
+            }
+            writer.finish().unwrap();
+            png
+        };
+        let mut editor = AnimationEditor::new(&indexed(&[0; 6], &[0], 1)).unwrap();
+
+        for &(palette, trns, valid) in &[
+            (&[0; 6][..], &[0][..], true),
+            (&[0, 0, 0, 255, 255, 255][..], &[0][..], false),
+            (&[0; 6][..], &[255][..], false),
+        ] {
+            let png = indexed(palette, trns, 0);
+            let frame = AnimationFrame::from_png(&png, FrameOptions::default()).unwrap();
+            editor.frames_mut().push(frame);
+            assert_eq!(editor.write(std::io::sink()).is_ok(), valid);
+            editor.frames_mut().pop();
+        }
+    }
+
+    #[test]
+    fn edit_chunks() {
+        let mut png = Vec::new();
+        {
+            let mut encoder = Encoder::new(&mut png, 2, 2);
+            encoder.set_color(ColorType::Indexed);
+            encoder.set_palette(vec![0, 0, 0, 255, 255, 255]);
+            encoder.add_text_chunk("a".into(), "1".into()).unwrap();
+            encoder.add_text_chunk("b".into(), "2".into()).unwrap();
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_chunk(chunk::dSIG, b"signed").unwrap();
+            writer.write_image_data(&[0, 1, 1, 0]).unwrap();
+        }
+
+        let mut editor = ChunkEditor::new(&png).unwrap();
+        assert_eq!(editor.remove(chunk::tEXt).unwrap(), 2);
+        assert!(editor.remove(chunk::PLTE).is_err());
+        let mut iccp = b"profile\0\0".to_vec();
+        iccp.extend(deflate::deflate_bytes_zlib(b"icc"));
+        editor.replace(chunk::iCCP, iccp).unwrap();
+        editor.insert(chunk::cICP, vec![1, 13, 0, 1]).unwrap();
+        editor.insert(chunk::tEXt, b"k\0v".to_vec()).unwrap();
+        editor.insert(chunk::tEXt, b"k\0w".to_vec()).unwrap();
+        editor.replace(chunk::tEXt, b"k\0x".to_vec()).unwrap();
+        assert!(editor.insert(chunk::IDAT, vec![]).is_err());
+
+        let types: Vec<_> = editor.chunks().map(|(name, _)| name).collect();
+        assert_eq!(
+            types,
+            [
+                chunk::IHDR,
+                chunk::cICP,
+                chunk::iCCP,
+                chunk::PLTE,
+                chunk::dSIG,
+                chunk::tEXt,
+                chunk::IDAT
+            ]
+        );
+        let mut out = Vec::new();
+        editor.write(&mut out).unwrap();
+        let original: Vec<_> = encoded_chunks(&png).unwrap();
+        let edited: Vec<_> = encoded_chunks(&out).unwrap();
+        assert_eq!(edited.last(), original.last());
+        assert!(!edited.iter().any(|(name, _)| *name == chunk::dSIG));
+
+        let reader = Decoder::new(&out[..]).read_info().unwrap();
+        assert_eq!(reader.info().icc_profile.as_deref(), Some(&b"icc"[..]));
+        let text = &reader.info().uncompressed_latin1_text;
+        assert_eq!(text.len(), 1);
+        assert_eq!(text[0].text, "x");
+
+        // An unedited image is copied as is.
+        let mut copy = Vec::new();
+        ChunkEditor::new(&png).unwrap().write(&mut copy).unwrap();
+        assert_eq!(copy, png);
+    }
+}
@@ -1,21 +1,25 @@
 use borrow::Cow;
-use io::{Read, Write};
+use io::{Read, Seek, SeekFrom, Write};
 use ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
 use std::{borrow, error, fmt, io, mem, ops, result};
 
-use crc32fast::Hasher as Crc32;
-use deflate::write::ZlibEncoder;
-
 use crate::chunk::{self, ChunkType};
 use crate::common::{
-    AnimationControl, BitDepth, BlendOp, BytesPerPixel, ColorType, Compression, DisposeOp,
-    FrameControl, Info, ParameterError, ParameterErrorKind, ScaledFloat,
+    AnimationControl, BitDepth, BlendOp, BytesPerPixel, CodingIndependentCodePoints, ColorType,
+    Compression, DigitalSignature, DisposeOp, FrameControl, FrameData, Info, Loops,
+    ModificationTime, ParameterError, ParameterErrorKind, PhaseTimings, ScaledFloat,
+    SrgbRenderingIntent, StereoLayout,
 };
 use crate::filter::{filter, AdaptiveFilterType, FilterType};
 use crate::text_metadata::{
-    EncodableTextChunk, ITXtChunk, TEXtChunk, TextEncodingError, ZTXtChunk,
+    self, EncodableTextChunk, ITXtChunk, KeywordError, Latin1Mode, TEXtChunk, TextChunkKind,
+    TextEncodingError, ZTXtChunk,
 };
 use crate::traits::WriteBytesExt;
+use crate::utils::{start_timer, start_timer_if, stop_timer};
+use crate::zlib::ZlibEncoder;
 
 pub type Result<T> = result::Result<T, EncodingError>;
 
@@ -33,11 +37,37 @@ pub struct FormatError {
 }
 
 #[derive(Debug)]
-enum FormatErrorKind {
+pub(crate) enum FormatErrorKind {
     ZeroWidth,
     ZeroHeight,
     InvalidColorCombination(BitDepth, ColorType),
+    UnsupportedInputConversion {
+        input: (ColorType, BitDepth),
+        output: (ColorType, BitDepth),
+    },
+    NonOpaqueAlpha,
+    InvalidColorKey(BitDepth, ColorType),
+    InvalidSignificantBits(u8, BitDepth),
+    MissingDefaultImage,
+    NoSeparateDefaultImage,
+    InvalidDefaultFrame,
+    MalformedEncodedImage,
+    MismatchedEncodedFrame,
     NoPalette,
+    InvalidChunkType(ChunkType),
+    CriticalChunk(ChunkType),
+    InvalidKeyword(KeywordError),
+    InvalidPalette {
+        len: usize,
+        max_entries: usize,
+    },
+    PaletteNotAllowed(ColorType),
+    TrnsNotAllowed(ColorType),
+    TrnsTooLong {
+        len: usize,
+        entries: usize,
+    },
+    UnsupportedAnimation,
     // TODO: wait, what?
     WrittenTooMuch(usize),
     NotAnimated,
@@ -45,6 +75,7 @@ enum FormatErrorKind {
     EndReached,
     ZeroFrames,
     MissingFrames,
+    UnknownFrameCount,
     MissingData(usize),
     Unrecoverable,
     BadTextEncoding(TextEncodingError),
@@ -83,7 +114,62 @@ impl fmt::Display for FormatError {
                 "Invalid combination of bit-depth '{:?}' and color-type '{:?}'",
                 depth, color
             ),
+            UnsupportedInputConversion { input, output } => write!(
+                fmt,
+                "Can't convert input of color-type '{:?}' and bit-depth '{:?}' to color-type '{:?}' and bit-depth '{:?}'",
+                input.0, input.1, output.0, output.1
+            ),
+            NonOpaqueAlpha => write!(fmt, "can't strip alpha channel, the image is not opaque"),
+            InvalidSignificantBits(bits, depth) => write!(
+                fmt,
+                "Invalid number of significant bits '{}' for input of bit-depth '{:?}'",
+                bits, depth
+            ),
+            InvalidColorKey(depth, color) => write!(
+                fmt,
+                "Transparent color is not representable with bit-depth '{:?}' and color-type '{:?}'",
+                depth, color
+            ),
+            MissingDefaultImage => write!(
+                fmt,
+                "the separate default image must be written before the animation frames"
+            ),
+            NoSeparateDefaultImage => write!(
+                fmt,
+                "the default image is part of the animation or was already written"
+            ),
+            InvalidDefaultFrame => write!(
+                fmt,
+                "the first frame is the default image and must cover the whole image"
+            ),
+            MalformedEncodedImage => write!(fmt, "the encoded data is not a valid PNG image"),
+            MismatchedEncodedFrame => write!(
+                fmt,
+                "the encoded frame does not match the color type, bit depth, palette or frame size"
+            ),
             NoPalette => write!(fmt, "can't write indexed image without palette"),
+            InvalidChunkType(chunk_type) => write!(fmt, "invalid chunk type {:?}", chunk_type),
+            CriticalChunk(chunk_type) => {
+                write!(fmt, "the critical chunk {} can not be edited", chunk_type)
+            }
+            InvalidPalette { len, max_entries } => write!(
+                fmt,
+                "a palette of {} bytes is not a whole number of 1 to {} entries",
+                len, max_entries
+            ),
+            PaletteNotAllowed(color) => {
+                write!(fmt, "a palette is not allowed for color-type '{:?}'", color)
+            }
+            TrnsNotAllowed(color) => {
+                write!(fmt, "a tRNS chunk is not allowed for color-type '{:?}'", color)
+            }
+            TrnsTooLong { len, entries } => write!(
+                fmt,
+                "the tRNS chunk has {} entries but the palette only {}",
+                len, entries
+            ),
+            UnsupportedAnimation => write!(fmt, "animated images are not supported"),
+            InvalidKeyword(err) => write!(fmt, "invalid text keyword, {}", err),
             WrittenTooMuch(index) => write!(fmt, "wrong data size, got {} bytes too many", index),
             NotAnimated => write!(fmt, "not an animation"),
             OutOfBounds => write!(
@@ -92,6 +178,10 @@ impl fmt::Display for FormatError {
             ),
             EndReached => write!(fmt, "all the frames have been already written"),
             MissingFrames => write!(fmt, "there are still frames to be written"),
+            UnknownFrameCount => write!(
+                fmt,
+                "the number of frames must be written with `Writer::finish_with_frame_count`"
+            ),
             MissingData(n) => write!(fmt, "there are still {} bytes to be written", n),
             Unrecoverable => write!(
                 fmt,
@@ -149,6 +239,21 @@ pub struct Encoder<'a, W: Write> {
     w: W,
     info: Info<'a>,
     options: Options,
+    /// The transparent color, validated once the color type and depth are final.
+    color_key: Option<Vec<u16>>,
+}
+
+impl<W: Write + Seek> Encoder<'_, W> {
+    /// Specify that the image is an animation with a number of frames that is not known yet.
+    ///
+    /// This allows writing frames as they become available. The `acTL` chunk is written with a
+    /// placeholder that [`Writer::finish_with_frame_count`] replaces with the number of frames
+    /// written, by seeking back in the output. See [`Encoder::set_animated`] for `num_plays`.
+    pub fn set_animated_unknown_frames(&mut self, num_plays: u32) -> Result<()> {
+        self.set_animated(u32::MAX, num_plays)?;
+        self.options.frame_count_position = Some(self.w.seek(SeekFrom::Current(0))?);
+        Ok(())
+    }
 }
 
 /// Decoding options, internal type, forwarded to the Writer.
@@ -158,6 +263,334 @@ struct Options {
     adaptive_filter: AdaptiveFilterType,
     sep_def_img: bool,
     validate_sequence: bool,
+    palette_order: PaletteOrder,
+    premultiplied_alpha: bool,
+    linear_input: Option<LinearInput>,
+    input_depth: Option<BitDepth>,
+    depth_reduction: DepthReduction,
+    significant_bits: Option<u8>,
+    input_color: Option<ColorType>,
+    strip_alpha: StripAlpha,
+    frame_diffing: bool,
+    automatic_frame_ops: bool,
+    text_chunk_kind: TextChunkKind,
+    text_compression_threshold: Option<usize>,
+    /// The encoded text chunks written after the image data.
+    trailing_chunks: Vec<u8>,
+    strict_keywords: bool,
+    /// The stream position of the image, when the frame count is written at the end.
+    frame_count_position: Option<u64>,
+    /// Whether the input rows start with the bottom row of the image.
+    bottom_up: bool,
+    phase_timings: bool,
+}
+
+/// A builder of the [`Info`] of an image to encode, which checks the combination of its fields.
+///
+/// [`InfoBuilder::build`] reports the errors of an invalid header before any bytes are written,
+/// including those that only surface while writing the image data or that would otherwise
+/// produce a file that decoders reject:
+///
+/// * the bit depth must be allowed for the color type,
+/// * the palette must have whole entries, at most 256 and at most as many as the bit depth of
+///   an indexed image can address, and it is not allowed for gray images,
+/// * the `tRNS` chunk may have at most one entry for each entry of the palette, a color key
+///   must have a sample of two bytes within the bit depth for each color channel, and images
+///   with an alpha channel must not have one,
+/// * an animation must have at least one frame, and the frame control must lie within the
+///   image.
+///
+/// ```
+/// use png::{BitDepth, ColorType, Encoder, InfoBuilder};
+///
+/// let mut builder = InfoBuilder::new(2, 1);
+/// builder
+///     .set_color(ColorType::Indexed)
+///     .set_depth(BitDepth::One)
+///     .set_palette(vec![0, 0, 0, 255, 255, 255, 255, 0, 0]);
+/// // A bit depth of one can only address two entries.
+/// assert!(builder.build().is_err());
+///
+/// builder.set_depth(BitDepth::Two);
+/// let mut png = Vec::new();
+/// let encoder = Encoder::with_info(&mut png, builder.build()?)?;
+/// encoder.write_header()?.write_image_data(&[0b0010_0000])?;
+/// # Ok::<(), png::EncodingError>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct InfoBuilder<'a> {
+    info: Info<'a>,
+}
+
+impl<'a> InfoBuilder<'a> {
+    /// Start with the default info of an image of the given size.
+    pub fn new(width: u32, height: u32) -> InfoBuilder<'static> {
+        InfoBuilder {
+            info: Info::with_size(width, height),
+        }
+    }
+
+    /// Start with an existing info, for example to check one that was assembled by hand.
+    pub fn from_info(info: Info<'a>) -> Self {
+        InfoBuilder { info }
+    }
+
+    /// Set the color type of the image.
+    pub fn set_color(&mut self, color: ColorType) -> &mut Self {
+        self.info.color_type = color;
+        self
+    }
+
+    /// Set the bit depth of the image.
+    pub fn set_depth(&mut self, depth: BitDepth) -> &mut Self {
+        self.info.bit_depth = depth;
+        self
+    }
+
+    /// Set the raw RGB triples of the `PLTE` chunk.
+    pub fn set_palette<T: Into<Cow<'a, [u8]>>>(&mut self, palette: T) -> &mut Self {
+        self.info.palette = Some(palette.into());
+        self
+    }
+
+    /// Set the raw contents of the `tRNS` chunk.
+    pub fn set_trns<T: Into<Cow<'a, [u8]>>>(&mut self, trns: T) -> &mut Self {
+        self.info.trns = Some(trns.into());
+        self
+    }
+
+    /// Make the image an animation whose first frame covers the whole image.
+    ///
+    /// See [`Encoder::set_animated`] for `num_frames` and `num_plays`.
+    pub fn set_animated(&mut self, num_frames: u32, num_plays: u32) -> &mut Self {
+        self.info.animation_control = Some(AnimationControl {
+            num_frames,
+            num_plays,
+        });
+        self.info.frame_control = Some(FrameControl {
+            width: self.info.width,
+            height: self.info.height,
+            ..Default::default()
+        });
+        self
+    }
+
+    /// Set the frame control of the first frame of an animation.
+    pub fn set_frame_control(&mut self, frame_control: FrameControl) -> &mut Self {
+        self.info.frame_control = Some(frame_control);
+        self
+    }
+
+    /// The info as configured so far, to set other fields.
+    pub fn info_mut(&mut self) -> &mut Info<'a> {
+        &mut self.info
+    }
+
+    /// Check the info and return it.
+    pub fn build(&self) -> Result<Info<'a>> {
+        validate_info(&self.info)?;
+        Ok(self.info.clone())
+    }
+}
+
+/// Check the combination of the fields of an info, see [`InfoBuilder`].
+fn validate_info(info: &Info<'_>) -> Result<()> {
+    let error = |kind: FormatErrorKind| Err(EncodingError::Format(kind.into()));
+    let (color_type, bit_depth) = (info.color_type, info.bit_depth);
+    if info.width == 0 {
+        return error(FormatErrorKind::ZeroWidth);
+    } else if info.height == 0 {
+        return error(FormatErrorKind::ZeroHeight);
+    } else if !color_type.is_combination_allowed(bit_depth) {
+        return error(FormatErrorKind::InvalidColorCombination(
+            bit_depth, color_type,
+        ));
+    }
+
+    let entries = match &info.palette {
+        None if color_type == ColorType::Indexed => return error(FormatErrorKind::NoPalette),
+        None => 0,
+        Some(_) if matches!(color_type, ColorType::Grayscale | ColorType::GrayscaleAlpha) => {
+            return error(FormatErrorKind::PaletteNotAllowed(color_type));
+        }
+        Some(palette) => {
+            let max_entries = match color_type {
+                ColorType::Indexed => 1 << (bit_depth as u8),
+                _ => 256,
+            };
+            let len = palette.len();
+            if len == 0 || len % 3 != 0 || len / 3 > max_entries {
+                return error(FormatErrorKind::InvalidPalette { len, max_entries });
+            }
+            len / 3
+        }
+    };
+
+    if let Some(trns) = &info.trns {
+        let max = (1u32 << (bit_depth as u8)) - 1;
+        let key_fits = |samples: usize| {
+            trns.len() == 2 * samples
+                && trns
+                    .chunks(2)
+                    .all(|sample| u32::from(u16::from_be_bytes([sample[0], sample[1]])) <= max)
+        };
+        match color_type {
+            ColorType::Indexed if trns.len() > entries => {
+                return error(FormatErrorKind::TrnsTooLong {
+                    len: trns.len(),
+                    entries,
+                });
+            }
+            ColorType::Indexed => {}
+            ColorType::Grayscale | ColorType::Rgb if !key_fits(color_type.samples()) => {
+                return error(FormatErrorKind::InvalidColorKey(bit_depth, color_type));
+            }
+            ColorType::Grayscale | ColorType::Rgb => {}
+            ColorType::GrayscaleAlpha | ColorType::Rgba => {
+                return error(FormatErrorKind::TrnsNotAllowed(color_type));
+            }
+        }
+    }
+
+    match (&info.animation_control, &info.frame_control) {
+        (Some(actl), _) if actl.num_frames == 0 => return error(FormatErrorKind::ZeroFrames),
+        (None, Some(_)) => return error(FormatErrorKind::NotAnimated),
+        (_, Some(fctl)) => {
+            let fits = |offset: u32, size: u32, bound: u32| {
+                size > 0 && offset.checked_add(size).map_or(false, |end| end <= bound)
+            };
+            if !fits(fctl.x_offset, fctl.width, info.width)
+                || !fits(fctl.y_offset, fctl.height, info.height)
+            {
+                return error(FormatErrorKind::OutOfBounds);
+            }
+        }
+        (_, None) => {}
+    }
+    Ok(())
+}
+
+/// The order in which the entries of an indexed image's palette are written.
+///
+/// Reordering the palette does not change the decoded colors, the image data is remapped to the
+/// new indices before filtering. Placing similar colors at neighbouring indices gives the
+/// filters smaller differences to work with, which usually helps the deflate ratio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaletteOrder {
+    /// Keep the palette exactly as it was provided.
+    Unchanged,
+    /// Sort the entries by alpha and then by luminance.
+    ///
+    /// The fully opaque entries end up at the back of the palette, which also allows the `tRNS`
+    /// chunk to be shortened.
+    Luminance,
+    /// Sort the entries by how often they are used, most frequent first.
+    ///
+    /// The usage is counted on the first image passed to [`Writer::write_image_data`], so the
+    /// `PLTE` and `tRNS` chunks are only written at that point. Chunks written with
+    /// [`Writer::write_chunk`] before the first image will therefore precede the palette. When
+    /// the image data is supplied through a [`StreamWriter`] the palette is kept unchanged.
+    Frequency,
+}
+
+impl Default for PaletteOrder {
+    fn default() -> Self {
+        PaletteOrder::Unchanged
+    }
+}
+
+/// How 16-bit input samples are reduced when the image is written with 8 bits per sample.
+///
+/// See [`Encoder::set_input_depth`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthReduction {
+    /// Scale each sample to the nearest 8-bit value.
+    Round,
+    /// Scale each color sample with an ordered dither, which avoids banding in smooth gradients.
+    ///
+    /// Alpha samples are rounded to the nearest 8-bit value.
+    Dither,
+}
+
+impl Default for DepthReduction {
+    fn default() -> Self {
+        DepthReduction::Round
+    }
+}
+
+/// What to do with the alpha channel of the input when the image is written without one.
+///
+/// See [`Encoder::set_input_color`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StripAlpha {
+    /// Ignore the alpha samples.
+    Discard,
+    /// Return an error from writing the image data if any pixel is not fully opaque.
+    RequireOpaque,
+}
+
+impl Default for StripAlpha {
+    fn default() -> Self {
+        StripAlpha::Discard
+    }
+}
+
+/// The transfer function that encodes linear light input, see [`Encoder::set_linear_input`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinearInput {
+    /// Encode with the sRGB transfer function and write an `sRGB` chunk with the intent.
+    Srgb(SrgbRenderingIntent),
+    /// Encode with the power of a gamma, such as 1 / 2.2, and write it as `gAMA` chunk.
+    Gamma(ScaledFloat),
+}
+
+/// The placement and timing of a frame written with [`Writer::add_frame`].
+#[derive(Debug, Clone, Copy)]
+pub struct FrameOptions {
+    /// Width and height of the frame, or `None` to cover the whole image.
+    pub size: Option<(u32, u32)>,
+    /// X position at which to render the frame
+    pub x_offset: u32,
+    /// Y position at which to render the frame
+    pub y_offset: u32,
+    /// Frame delay fraction numerator
+    pub delay_num: u16,
+    /// Frame delay fraction denominator
+    pub delay_den: u16,
+    /// Type of frame area disposal to be done after rendering this frame
+    pub dispose_op: DisposeOp,
+    /// Type of frame area rendering for this frame
+    pub blend_op: BlendOp,
+}
+
+impl Default for FrameOptions {
+    fn default() -> Self {
+        let fctl = FrameControl::default();
+        FrameOptions {
+            size: None,
+            x_offset: fctl.x_offset,
+            y_offset: fctl.y_offset,
+            delay_num: fctl.delay_num,
+            delay_den: fctl.delay_den,
+            dispose_op: fctl.dispose_op,
+            blend_op: fctl.blend_op,
+        }
+    }
+}
+
+impl FrameOptions {
+    /// Set the delay of the frame from a duration.
+    ///
+    /// The delay is approximated by the fraction that is closest to `delay`, with a numerator and
+    /// denominator of at most `u16::MAX`. Delays of more than `u16::MAX` seconds are shortened.
+    pub fn delay_duration(self, delay: Duration) -> Self {
+        let (delay_num, delay_den) = rational_delay(delay);
+        FrameOptions {
+            delay_num,
+            delay_den,
+            ..self
+        }
+    }
 }
 
 impl<'a, W: Write> Encoder<'a, W> {
@@ -166,9 +599,21 @@ impl<'a, W: Write> Encoder<'a, W> {
             w,
             info: Info::with_size(width, height),
             options: Options::default(),
+            color_key: None,
         }
     }
 
+    /// Create an encoder for an image with the given info, checked like [`InfoBuilder::build`].
+    pub fn with_info(w: W, info: Info<'a>) -> Result<Encoder<'a, W>> {
+        validate_info(&info)?;
+        Ok(Encoder {
+            w,
+            info,
+            options: Options::default(),
+            color_key: None,
+        })
+    }
+
     /// Specify that the image is animated.
     ///
     /// `num_frames` controls how many frames the animation has, while
@@ -203,12 +648,27 @@ impl<'a, W: Write> Encoder<'a, W> {
         Ok(())
     }
 
+    /// Set how many times the animation is played.
+    ///
+    /// This overrides the `num_plays` passed to [`Encoder::set_animated`], and returns an error
+    /// when animation control was not configured.
+    pub fn set_loops(&mut self, loops: Loops) -> Result<()> {
+        match &mut self.info.animation_control {
+            Some(actl) => {
+                actl.num_plays = loops.num_plays();
+                Ok(())
+            }
+            None => Err(EncodingError::Format(FormatErrorKind::NotAnimated.into())),
+        }
+    }
+
     /// Mark the first animated frame as a 'separate default image'.
     ///
     /// In APNG each animated frame is preceded by a special control chunk, `fcTL`. It's up to the
     /// encoder to decide if the first image, the standard `IDAT` data, should be part of the
     /// animation by emitting this chunk or by not doing so. A default image that is _not_ part of
-    /// the animation is often interpreted as a thumbnail.
+    /// the animation is often interpreted as a thumbnail. It is written with
+    /// [`Writer::write_default_image`], followed by the frames of the animation.
     ///
     /// This method will return an error when animation control was not configured
     /// (which is done by calling [`Encoder::set_animated`]).
@@ -233,6 +693,17 @@ impl<'a, W: Write> Encoder<'a, W> {
         self.info.trns = Some(trns.into());
     }
 
+    /// Sets a single color that is to be treated as fully transparent.
+    ///
+    /// This is written as the tRNS chunk of [`ColorType::Grayscale`] and [`ColorType::Rgb`]
+    /// images, which takes one and three samples respectively. The samples are given in the
+    /// bit depth of the written image. [`Encoder::write_header`] returns an error if the number
+    /// of samples does not match the color type or a sample is too large for the bit depth.
+    /// This replaces any contents set with [`Encoder::set_trns`].
+    pub fn set_color_key(&mut self, samples: &[u16]) {
+        self.color_key = Some(samples.to_vec());
+    }
+
     /// Set the display gamma of the source system on which the image was generated or last edited.
     pub fn set_source_gamma(&mut self, source_gamma: ScaledFloat) {
         self.info.source_gamma = Some(source_gamma);
@@ -255,13 +726,81 @@ impl<'a, W: Write> Encoder<'a, W> {
         self.info.srgb = Some(rendering_intent);
     }
 
+    /// Set the time of the last modification of the image, written as `tIME` chunk.
+    pub fn set_modification_time(&mut self, time: ModificationTime) {
+        self.info.modification_time = Some(time);
+    }
+
+    /// Mark the image as a stereo image with the left and right eye views in the given layout.
+    ///
+    /// The image data has to contain both views side by side, see [`StereoLayout`].
+    pub fn set_stereo_layout(&mut self, layout: StereoLayout) {
+        self.info.stereo_layout = Some(layout);
+    }
+
+    /// Set the video signal type of the image, written as `cICP` chunk.
+    ///
+    /// This describes HDR images with the PQ or HLG transfer function, which decoders prefer
+    /// over the other color space chunks.
+    pub fn set_coding_independent_code_points(&mut self, cicp: CodingIndependentCodePoints) {
+        self.info.coding_independent_code_points = Some(cicp);
+    }
+
     /// Start encoding by writing the header data.
     ///
     /// The remaining data can be supplied by methods on the returned [`Writer`].
-    pub fn write_header(self) -> Result<Writer<W>> {
+    pub fn write_header(mut self) -> Result<Writer<W>> {
+        if let Some(key) = self.color_key.take() {
+            self.info.trns = Some(Cow::Owned(self.encode_color_key(&key)?));
+        }
+        if let Some(bits) = self.options.significant_bits {
+            self.info.significant_bits = Some(self.encode_significant_bits(bits)?);
+        }
+
         Writer::new(self.w, PartialInfo::new(&self.info), self.options).init(&self.info)
     }
 
+    /// Validates the significant bits of the input and encodes them as the contents of an sBIT
+    /// chunk, with one entry for each channel.
+    fn encode_significant_bits(&self, bits: u8) -> Result<Vec<u8>> {
+        let input_depth = self.options.input_depth.unwrap_or(self.info.bit_depth);
+        if input_depth != BitDepth::Sixteen || !(1..=16).contains(&bits) {
+            return Err(EncodingError::Format(
+                FormatErrorKind::InvalidSignificantBits(bits, input_depth).into(),
+            ));
+        }
+
+        let channels = match self.info.color_type {
+            ColorType::Grayscale => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb | ColorType::Indexed => 3,
+            ColorType::Rgba => 4,
+        };
+        Ok(vec![bits.min(self.info.bit_depth as u8); channels])
+    }
+
+    /// Validates a transparent color and encodes it as the contents of a tRNS chunk.
+    fn encode_color_key(&self, samples: &[u16]) -> Result<Vec<u8>> {
+        let (color, depth) = (self.info.color_type, self.info.bit_depth);
+        let expected = match color {
+            ColorType::Grayscale => 1,
+            ColorType::Rgb => 3,
+            _ => 0,
+        };
+        let max = u32::from(u16::MAX) >> (16 - depth as u32);
+
+        if samples.len() != expected || samples.iter().any(|&s| u32::from(s) > max) {
+            return Err(EncodingError::Format(
+                FormatErrorKind::InvalidColorKey(depth, color).into(),
+            ));
+        }
+
+        Ok(samples
+            .iter()
+            .flat_map(|s| s.to_be_bytes().to_vec())
+            .collect())
+    }
+
     /// Set the color of the encoded image.
     ///
     /// These correspond to the color types in the png IHDR data that will be written. The length
@@ -308,6 +847,155 @@ impl<'a, W: Write> Encoder<'a, W> {
         self.options.adaptive_filter = adaptive_filter;
     }
 
+    /// Set the order in which the palette entries are written.
+    ///
+    /// This only has an effect on images with [`ColorType::Indexed`]. The image data is always
+    /// supplied with indices into the palette as it was passed to [`Encoder::set_palette`], the
+    /// writer takes care of remapping them. The default is [`PaletteOrder::Unchanged`].
+    pub fn set_palette_order(&mut self, order: PaletteOrder) {
+        self.options.palette_order = order;
+    }
+
+    /// Declare that the color samples of the image data are premultiplied with alpha.
+    ///
+    /// PNG stores straight (non-premultiplied) alpha, so the writer divides the color samples of
+    /// each pixel by its alpha before filtering. Fully transparent pixels are written as black.
+    /// This only has an effect on images with [`ColorType::Rgba`] and
+    /// [`ColorType::GrayscaleAlpha`]. Note that premultiplication loses precision in the color
+    /// of mostly transparent pixels which can not be recovered.
+    pub fn set_premultiplied_alpha(&mut self, premultiplied: bool) {
+        self.options.premultiplied_alpha = premultiplied;
+    }
+
+    /// Declare that the color samples of the image data are linear light.
+    ///
+    /// The writer applies the transfer function to the color samples of each row, before reducing
+    /// their depth, and writes the matching `sRGB` or `gAMA` chunk instead of any set before.
+    /// Alpha samples stay linear as required by PNG. Linear input of 16 bits keeps more precision
+    /// in dark colors than linear input of 8 bits. This requires samples of 8 or 16 bits and is
+    /// not available for indexed images.
+    ///
+    /// ```
+    /// use png::{LinearInput, SrgbRenderingIntent};
+    ///
+    /// let mut png = Vec::new();
+    /// let mut encoder = png::Encoder::new(&mut png, 1, 1);
+    /// encoder.set_linear_input(LinearInput::Srgb(SrgbRenderingIntent::Perceptual));
+    /// // Half of the white light is encoded as 188 by sRGB.
+    /// encoder.write_header()?.write_image_data(&[128])?;
+    /// # Ok::<(), png::EncodingError>(())
+    /// ```
+    pub fn set_linear_input(&mut self, transfer: LinearInput) {
+        match transfer {
+            LinearInput::Srgb(intent) => {
+                self.info.srgb = Some(intent);
+                self.info.source_gamma = None;
+            }
+            LinearInput::Gamma(gamma) => {
+                self.info.srgb = None;
+                self.info.source_gamma = Some(gamma);
+            }
+        }
+        self.options.linear_input = Some(transfer);
+    }
+
+    /// Set the bit depth of the image data passed to the writer.
+    ///
+    /// By default the image data is expected in the depth given to [`Encoder::set_depth`]. The
+    /// only supported conversion is from [`BitDepth::Sixteen`] input to an image written with
+    /// [`BitDepth::Eight`], for any color type but [`ColorType::Indexed`]. Each sample is scaled
+    /// while the rows are processed as selected with [`Encoder::set_depth_reduction`]. Other
+    /// combinations are rejected by [`Encoder::write_header`].
+    pub fn set_input_depth(&mut self, depth: BitDepth) {
+        self.options.input_depth = Some(depth);
+    }
+
+    /// Set how 16-bit input samples are reduced to 8 bits.
+    ///
+    /// This only has an effect together with [`Encoder::set_input_depth`]. The default is
+    /// [`DepthReduction::Round`].
+    pub fn set_depth_reduction(&mut self, reduction: DepthReduction) {
+        self.options.depth_reduction = reduction;
+    }
+
+    /// Set how many of the low bits of each 16-bit input sample are significant.
+    ///
+    /// Camera and scanner data often has 10 or 12 bits in each 16-bit sample. Such samples are
+    /// scaled from their range to the full range of the image, also when it is written with 8
+    /// bits by [`Encoder::set_input_depth`]. An `sBIT` chunk records the significant bits of the
+    /// encoded samples, which is at most the bit depth of the image.
+    ///
+    /// The bits must be between 1 and 16, and the input must have 16 bits per sample. Otherwise
+    /// [`Encoder::write_header`] returns an error.
+    pub fn set_significant_bits(&mut self, bits: u8) {
+        self.options.significant_bits = Some(bits);
+    }
+
+    /// Set the color type of the image data passed to the writer.
+    ///
+    /// By default the image data is expected in the color type given to [`Encoder::set_color`].
+    /// This allows passing [`ColorType::Rgba`] data for an image written as [`ColorType::Rgb`],
+    /// or [`ColorType::GrayscaleAlpha`] data for an image written as [`ColorType::Grayscale`].
+    /// The alpha channel is stripped while the rows are processed, as selected with
+    /// [`Encoder::set_strip_alpha`]. Other combinations are rejected by
+    /// [`Encoder::write_header`].
+    pub fn set_input_color(&mut self, color: ColorType) {
+        self.options.input_color = Some(color);
+    }
+
+    /// Set how the alpha channel of the input is stripped.
+    ///
+    /// This only has an effect together with [`Encoder::set_input_color`]. The default is
+    /// [`StripAlpha::Discard`].
+    pub fn set_strip_alpha(&mut self, strip_alpha: StripAlpha) {
+        self.options.strip_alpha = strip_alpha;
+    }
+
+    /// Only encode the part of each animation frame that changed.
+    ///
+    /// With this enabled, every frame passed to [`Writer::add_frame`] is expected to contain the
+    /// complete canvas. The writer compares it to the canvas a decoder shows at that point,
+    /// taking the dispose operation of the previous frame into account, and encodes only the
+    /// bounding box of the changed pixels with [`BlendOp::Source`]. Frames with an explicit
+    /// [`FrameOptions::size`] are written unchanged, and the next diffed frame redraws the pixels
+    /// they covered.
+    pub fn set_frame_diffing(&mut self, diffing: bool) {
+        self.options.frame_diffing = diffing;
+    }
+
+    /// Accept image data whose first row is the bottom row of the image, as read back from
+    /// OpenGL.
+    ///
+    /// The rows are written in reverse without copying the input. This applies to
+    /// [`Writer::write_image_data`] and the frames of an animation, but not to a [`StreamWriter`]
+    /// which receives the rows in the order they are written.
+    pub fn set_bottom_up(&mut self, bottom_up: bool) {
+        self.options.bottom_up = bottom_up;
+    }
+
+    /// Measure the time spent filtering, compressing and writing the output, for
+    /// [`Writer::phase_timings`] and the times of the [`EncodingStats`].
+    pub fn set_phase_timings(&mut self, enabled: bool) {
+        self.options.phase_timings = enabled;
+    }
+
+    /// Choose the blend and dispose operation of diffed frames instead of using the
+    /// [`FrameOptions`].
+    ///
+    /// This only affects frames written with [`Encoder::set_frame_diffing`]. Images with an
+    /// alpha channel use [`BlendOp::Over`] when most pixels of the changed region are the same as
+    /// before, and these pixels are then written fully transparent, which compresses better.
+    /// Passing `false` restores the explicit operations of each frame.
+    ///
+    /// The dispose operation is chosen from the next frame: [`DisposeOp::Background`] or
+    /// [`DisposeOp::Previous`] is used when it leaves a smaller region to write, for example
+    /// after a frame that only shows something temporarily. A frame is therefore written once
+    /// the next one is added, or when anything else is written or the writer is finished. The
+    /// last frame, and a frame followed by one that isn't diffed, use [`DisposeOp::None`].
+    pub fn set_automatic_frame_ops(&mut self, automatic: bool) {
+        self.options.automatic_frame_ops = automatic;
+    }
+
     /// Set the fraction of time every frame is going to be displayed, in seconds.
     ///
     /// *Note that this parameter can be set for each individual frame after
@@ -428,6 +1116,122 @@ impl<'a, W: Write> Encoder<'a, W> {
         Ok(())
     }
 
+    /// Add a text chunk, choosing between `tEXt`, `zTXt` and `iTXt` chunks.
+    ///
+    /// Text that can be encoded as Latin-1 is written as `tEXt` or `zTXt`, other text as `iTXt`.
+    /// The text is compressed when it has at least as many bytes as the threshold set with
+    /// [`Encoder::set_text_compression_threshold`] and compressing it actually saves space. A
+    /// specific kind of chunk can be forced with [`Encoder::set_text_chunk_kind`].
+    ///
+    /// The keyword must be Latin-1 for all kinds of chunks, an error is returned otherwise or
+    /// when the forced kind can not represent the text.
+    pub fn add_text(&mut self, keyword: impl Into<String>, text: impl Into<String>) -> Result<()> {
+        let (keyword, text) = (keyword.into(), text.into());
+        text_metadata::encode_latin1(&keyword, Latin1Mode::Verbatim)?;
+        let latin1 = text_metadata::encode_latin1(&text, Latin1Mode::Verbatim);
+        let threshold = self
+            .options
+            .text_compression_threshold
+            .unwrap_or(text_metadata::COMPRESSION_THRESHOLD);
+        let compress = |raw: &[u8]| -> Result<bool> {
+            if raw.len() < threshold {
+                return Ok(false);
+            }
+            text_metadata::compression_saves(raw)
+        };
+
+        match (self.options.text_chunk_kind, latin1) {
+            (TextChunkKind::Uncompressed, latin1) => {
+                latin1?;
+                self.info
+                    .uncompressed_latin1_text
+                    .push(TEXtChunk::new(keyword, text));
+            }
+            (TextChunkKind::Compressed, latin1) => {
+                latin1?;
+                self.info
+                    .compressed_latin1_text
+                    .push(ZTXtChunk::new(keyword, text));
+            }
+            (TextChunkKind::Auto, Ok(raw)) => {
+                if compress(&raw)? {
+                    let chunk = ZTXtChunk::new(keyword, text);
+                    self.info.compressed_latin1_text.push(chunk);
+                } else {
+                    let chunk = TEXtChunk::new(keyword, text);
+                    self.info.uncompressed_latin1_text.push(chunk);
+                }
+            }
+            (TextChunkKind::Auto, Err(_)) | (TextChunkKind::International, _) => {
+                let compressed = compress(text.as_bytes())?;
+                let mut chunk = ITXtChunk::new(keyword, text);
+                chunk.compressed = compressed;
+                self.info.utf8_text.push(chunk);
+            }
+        }
+        Ok(())
+    }
+
+    /// Add a text chunk that is written after the image data, directly before the image trailer.
+    ///
+    /// The specification allows text chunks on either side of the image data. The chunk is
+    /// encoded immediately, which checks its keyword and text. See also
+    /// [`Writer::add_trailing_text_chunk`] for text that is only known while encoding.
+    pub fn add_trailing_text_chunk<T: EncodableTextChunk>(&mut self, text_chunk: &T) -> Result<()> {
+        check_text_keyword(self.options.strict_keywords, text_chunk)?;
+        text_chunk.encode(&mut self.options.trailing_chunks)
+    }
+
+    /// Reject text chunks whose keyword violates
+    /// [`check_keyword`](text_metadata::check_keyword) instead of writing them.
+    ///
+    /// Without this only the length of keywords is checked. This applies to the text chunks of the
+    /// header, which are checked by [`Encoder::write_header`], as well as those written later.
+    pub fn set_strict_keywords(&mut self, strict: bool) {
+        self.options.strict_keywords = strict;
+    }
+
+    /// Set the kind of chunk that [`Encoder::add_text`] writes, [`TextChunkKind::Auto`] by default.
+    pub fn set_text_chunk_kind(&mut self, kind: TextChunkKind) {
+        self.options.text_chunk_kind = kind;
+    }
+
+    /// Set the number of bytes from which [`Encoder::add_text`] tries to compress a text.
+    ///
+    /// The default is [`COMPRESSION_THRESHOLD`](text_metadata::COMPRESSION_THRESHOLD). Shorter
+    /// texts are always written uncompressed, as compression rarely pays off for them.
+    pub fn set_text_compression_threshold(&mut self, bytes: usize) {
+        self.options.text_compression_threshold = Some(bytes);
+    }
+
+    /// Add a raw `gIFg`, `gIFx` or `fRAc` chunk, as retained in [`Info::legacy_chunks`].
+    ///
+    /// These chunks are written with the other metadata before the image data. Other chunk types
+    /// are rejected, they can be written with [`Writer::write_chunk`] instead.
+    pub fn add_legacy_chunk(&mut self, chunk_type: ChunkType, data: Vec<u8>) -> Result<()> {
+        if ![chunk::gIFg, chunk::gIFx, chunk::fRAc].contains(&chunk_type) {
+            let kind = FormatErrorKind::InvalidChunkType(chunk_type);
+            return Err(EncodingError::Format(kind.into()));
+        }
+        validate_chunk_len(&data)?;
+        self.info.legacy_chunks.push((chunk_type, data));
+        Ok(())
+    }
+
+    /// Add a pair of `dSIG` chunks around the rest of the image.
+    ///
+    /// The `opening` chunk is written directly after the image header and the `closing` chunk
+    /// directly before the image trailer. Pairs added later are nested inside the earlier ones.
+    /// The data is not interpreted, so a signature must have been computed over exactly the
+    /// chunks that are written in between, for example when passing through a decoded
+    /// [`Info::signatures`] unchanged.
+    pub fn add_signature(&mut self, opening: Vec<u8>, closing: Vec<u8>) {
+        self.info.signatures.push(DigitalSignature {
+            opening,
+            closing: Some(closing),
+        });
+    }
+
     /// Validate the written image sequence.
     ///
     /// When validation is turned on (it's turned off by default) then attempts to write more than
@@ -446,14 +1250,14 @@ impl<'a, W: Write> Encoder<'a, W> {
 /// PNG writer
 ///
 /// Progresses through the image by writing images, frames, or raw individual chunks. This is
-/// constructed through [`Encoder::write_header()`].
+/// constructed through [`Encoder::write_header()`]. The writer is `Send` if `W` is.
 ///
 /// FIXME: Writing of animated chunks might be clearer if we had an _adapter_ that you would call
 /// to guarantee the next image to be prefaced with a fcTL-chunk, and all other chunks would be
 /// guaranteed to be `IDAT`/not affected by APNG's frame control.
 pub struct Writer<W: Write> {
     /// The underlying writer.
-    w: W,
+    w: ChunkCounter<W>,
     /// The local version of the `Info` struct.
     info: PartialInfo,
     /// Global encoding options.
@@ -465,19 +1269,192 @@ pub struct Writer<W: Write> {
     /// A flag to note when the IEND chunk was already added.
     /// This is only set on code paths that drop `Self` to control the destructor.
     iend_written: bool,
+    /// Conversion from the input scanlines to those that are encoded.
+    conversion: RowConversion,
+    /// The `PLTE` and `tRNS` contents, held back until the first image determines their order.
+    pending_palette: Option<(Vec<u8>, Option<Vec<u8>>)>,
+    /// The canvas that the next frame is compared to, when frame diffing is enabled.
+    frame_diff: Option<FrameDiff>,
+    /// The last diffed frame, encoded but held back until its dispose operation is chosen.
+    held_frame: Option<HeldFrame>,
+    /// The stream position of the `num_frames` field, if it must be written when finishing.
+    frame_count_offset: Option<u64>,
+    /// The data of the closing `dSIG` chunks, in the order they are written.
+    closing_signatures: Vec<Vec<u8>>,
+    /// The filtering and compression statistics, the chunks are counted by `w`.
+    stats: EncodingStats,
 }
 
-/// Contains the subset of attributes of [Info] needed for [Writer] to function
-struct PartialInfo {
-    width: u32,
-    height: u32,
-    bit_depth: BitDepth,
-    color_type: ColorType,
-    frame_control: Option<FrameControl>,
-    animation_control: Option<AnimationControl>,
-    compression: Compression,
-    has_palette: bool,
-}
+/// Statistics on an encoded image, see [`Writer::finish_with_stats`].
+///
+/// They show the effect of the filter and compression settings, for example whether an adaptive
+/// filter is worth its time for a kind of image.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EncodingStats {
+    /// The bytes written for each chunk type, including the length, type and CRC of each chunk.
+    ///
+    /// The types are in the order in which their first chunk was written.
+    pub chunk_bytes: Vec<(ChunkType, u64)>,
+    /// The number of rows filtered with each filter type, indexed by the filter type byte.
+    pub filter_types: [u64; 5],
+    /// The bytes passed to the compressor, including the filter type byte of each row.
+    pub filtered_bytes: u64,
+    /// The compressed image data in `IDAT` and `fdAT` chunks, without the sequence numbers.
+    pub compressed_bytes: u64,
+    /// The time spent filtering rows, zero unless enabled with [`Encoder::set_phase_timings`].
+    pub filter_time: Duration,
+    /// The time spent compressing the filtered rows, see [`EncodingStats::filter_time`].
+    pub compression_time: Duration,
+}
+
+impl EncodingStats {
+    /// The bytes written for chunks of the given type.
+    pub fn chunk_bytes(&self, chunk_type: ChunkType) -> u64 {
+        self.chunk_bytes
+            .iter()
+            .find(|(type_, _)| *type_ == chunk_type)
+            .map_or(0, |&(_, bytes)| bytes)
+    }
+
+    /// The number of rows filtered with the given filter type.
+    pub fn filter_count(&self, filter: FilterType) -> u64 {
+        self.filter_types[filter as usize]
+    }
+
+    /// The ratio of filtered to compressed bytes, if any image data was compressed.
+    ///
+    /// Frames copied as compressed data with [`Writer::add_encoded_frame`] add compressed bytes
+    /// but no filtered bytes.
+    pub fn compression_ratio(&self) -> Option<f64> {
+        if self.compressed_bytes == 0 {
+            None
+        } else {
+            Some(self.filtered_bytes as f64 / self.compressed_bytes as f64)
+        }
+    }
+
+    /// Adds the row statistics of a `StreamWriter`.
+    fn add_rows(&mut self, other: &EncodingStats) {
+        for (total, count) in self.filter_types.iter_mut().zip(&other.filter_types) {
+            *total += count;
+        }
+        self.filtered_bytes += other.filtered_bytes;
+        self.filter_time += other.filter_time;
+        self.compression_time += other.compression_time;
+    }
+}
+
+/// Forwards the output of a [`Writer`] and counts the bytes of each chunk type.
+///
+/// The chunks are recognized in the written stream, so that chunks encoded by any code path are
+/// counted, including the text chunks that are buffered until the end.
+struct ChunkCounter<W> {
+    inner: W,
+    /// The bytes of the signature that are still to come.
+    signature: usize,
+    /// The length and type of the next chunk, while they are written.
+    header: [u8; 8],
+    header_len: usize,
+    /// The type of the current chunk and its bytes still to come, including the CRC.
+    current: ChunkType,
+    remaining: u64,
+    chunks: Vec<(ChunkType, u64)>,
+    image_data: u64,
+    /// The time spent in `inner`, if it is measured.
+    write_time: Option<Duration>,
+}
+
+impl<W: Write> ChunkCounter<W> {
+    fn new(inner: W) -> Self {
+        ChunkCounter {
+            inner,
+            signature: 8,
+            header: [0; 8],
+            header_len: 0,
+            current: chunk::IEND,
+            remaining: 0,
+            chunks: Vec::new(),
+            image_data: 0,
+            write_time: None,
+        }
+    }
+
+    fn count(&mut self, mut buf: &[u8]) {
+        while !buf.is_empty() {
+            let n = if self.signature > 0 {
+                let n = self.signature.min(buf.len());
+                self.signature -= n;
+                n
+            } else if self.remaining == 0 {
+                let n = (8 - self.header_len).min(buf.len());
+                self.header[self.header_len..][..n].copy_from_slice(&buf[..n]);
+                self.header_len += n;
+                if self.header_len == 8 {
+                    let h = self.header;
+                    let length = u32::from_be_bytes([h[0], h[1], h[2], h[3]]);
+                    self.current = ChunkType([h[4], h[5], h[6], h[7]]);
+                    self.remaining = u64::from(length) + 4;
+                    self.header_len = 0;
+                    self.add(8);
+                    if self.current == chunk::IDAT {
+                        self.image_data += u64::from(length);
+                    } else if self.current == chunk::fdAT {
+                        self.image_data += u64::from(length.saturating_sub(4));
+                    }
+                }
+                n
+            } else {
+                let n = self.remaining.min(buf.len() as u64);
+                self.remaining -= n;
+                self.add(n);
+                n as usize
+            };
+            buf = &buf[n..];
+        }
+    }
+
+    fn add(&mut self, bytes: u64) {
+        let current = self.current;
+        match self.chunks.iter_mut().find(|(type_, _)| *type_ == current) {
+            Some((_, total)) => *total += bytes,
+            None => self.chunks.push((current, bytes)),
+        }
+    }
+}
+
+impl<W: Write> Write for ChunkCounter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let timer = self.write_time.and_then(|_| start_timer());
+        let written = self.inner.write(buf);
+        if let Some(write_time) = &mut self.write_time {
+            stop_timer(timer, write_time);
+        }
+        let written = written?;
+        self.count(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let timer = self.write_time.and_then(|_| start_timer());
+        let result = self.inner.flush();
+        if let Some(write_time) = &mut self.write_time {
+            stop_timer(timer, write_time);
+        }
+        result
+    }
+}
+
+/// Contains the subset of attributes of [Info] needed for [Writer] to function
+struct PartialInfo {
+    width: u32,
+    height: u32,
+    bit_depth: BitDepth,
+    color_type: ColorType,
+    frame_control: Option<FrameControl>,
+    animation_control: Option<AnimationControl>,
+    compression: Compression,
+    has_palette: bool,
+}
 
 impl PartialInfo {
     fn new(info: &Info) -> Self {
@@ -527,18 +1504,138 @@ impl PartialInfo {
 const DEFAULT_BUFFER_LENGTH: usize = 4 * 1024;
 
 pub(crate) fn write_chunk<W: Write>(mut w: W, name: chunk::ChunkType, data: &[u8]) -> Result<()> {
+    trace!("writing {} chunk of {} bytes", name, data.len());
     w.write_be(data.len() as u32)?;
     w.write_all(&name.0)?;
     w.write_all(data)?;
-    let mut crc = Crc32::new();
-    crc.update(&name.0);
-    crc.update(data);
-    w.write_be(crc.finalize())?;
+    w.write_be(chunk::crc(name, data))?;
+    Ok(())
+}
+
+/// Checks that the chunk `data` is short enough to have its length encoded.
+pub(crate) fn validate_chunk_len(data: &[u8]) -> Result<()> {
+    use std::convert::TryFrom;
+
+    if u32::try_from(data.len()).map_or(true, |length| length > i32::MAX as u32) {
+        let kind = FormatErrorKind::WrittenTooMuch(data.len() - i32::MAX as usize);
+        return Err(EncodingError::Format(kind.into()));
+    }
     Ok(())
 }
 
+/// Finds the start of the data of the first chunk named `name` in a sequence of chunks.
+fn chunk_data_offset(chunks: &[u8], name: ChunkType) -> Option<usize> {
+    let mut offset = 0;
+    while chunks.len().saturating_sub(offset) >= 12 {
+        let len = &chunks[offset..][..4];
+        let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+        if chunks[offset + 4..][..4] == name.0 {
+            return Some(offset + 8);
+        }
+        offset += 12 + len;
+    }
+    None
+}
+
+/// The parts of the `IHDR` chunk of an encoded image.
+pub(crate) struct EncodedHeader {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) bit_depth: BitDepth,
+    pub(crate) color_type: ColorType,
+}
+
+impl EncodedHeader {
+    /// Parses the contents of the `IHDR` chunk of a non-interlaced image.
+    pub(crate) fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() != 13 || data[10..] != [0, 0, 0] {
+            return Err(malformed_encoded_image());
+        }
+        Ok(EncodedHeader {
+            width: u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+            height: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            bit_depth: BitDepth::from_u8(data[8]).ok_or_else(malformed_encoded_image)?,
+            color_type: ColorType::from_u8(data[9]).ok_or_else(malformed_encoded_image)?,
+        })
+    }
+}
+
+pub(crate) fn malformed_encoded_image() -> EncodingError {
+    EncodingError::Format(FormatErrorKind::MalformedEncodedImage.into())
+}
+
+/// Checks the keyword of a text chunk that is about to be written, if asked to.
+fn check_text_keyword<T: EncodableTextChunk>(strict: bool, text_chunk: &T) -> Result<()> {
+    match text_chunk.keyword() {
+        Some(keyword) if strict => text_metadata::check_keyword(keyword)
+            .map_err(|err| EncodingError::Format(FormatErrorKind::InvalidKeyword(err).into())),
+        _ => Ok(()),
+    }
+}
+
+/// Splits an encoded PNG image into its chunks, up to but excluding `IEND`.
+///
+/// The signature, the chunk lengths and the checksums are validated, and the first chunk must be
+/// the only `IHDR` chunk.
+pub(crate) fn encoded_chunks(png: &[u8]) -> Result<Vec<(ChunkType, &[u8])>> {
+    if !png.starts_with(&[137, 80, 78, 71, 13, 10, 26, 10]) {
+        return Err(malformed_encoded_image());
+    }
+
+    let mut chunks = Vec::new();
+    let mut rest = &png[8..];
+    loop {
+        if rest.len() < 12 {
+            return Err(malformed_encoded_image());
+        }
+        let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        if rest.len() - 12 < len {
+            return Err(malformed_encoded_image());
+        }
+        let name = ChunkType([rest[4], rest[5], rest[6], rest[7]]);
+        let data = &rest[8..][..len];
+        let crc = &rest[8 + len..][..4];
+        rest = &rest[12 + len..];
+
+        let crc = u32::from_be_bytes([crc[0], crc[1], crc[2], crc[3]]);
+        if !chunk::verify_crc(name, data, crc) || (name == chunk::IHDR) != chunks.is_empty() {
+            return Err(malformed_encoded_image());
+        }
+
+        if name == chunk::IEND {
+            return Ok(chunks);
+        }
+        chunks.push((name, data));
+    }
+}
+
+/// Reads the header and the concatenated `IDAT` contents of an encoded PNG image.
+pub(crate) fn encoded_image_data(png: &[u8]) -> Result<(EncodedHeader, Vec<u8>)> {
+    image_data_of(&encoded_chunks(png)?)
+}
+
+/// The header and the concatenated image data of the chunks of an encoded image.
+pub(crate) fn image_data_of(chunks: &[(ChunkType, &[u8])]) -> Result<(EncodedHeader, Vec<u8>)> {
+    let header = EncodedHeader::parse(chunks[0].1)?;
+    let zlib_encoded: Vec<u8> = chunks
+        .iter()
+        .filter(|(name, _)| *name == chunk::IDAT)
+        .flat_map(|(_, data)| data.iter().copied())
+        .collect();
+
+    if zlib_encoded.is_empty() {
+        return Err(malformed_encoded_image());
+    }
+    Ok((header, zlib_encoded))
+}
+
 impl<W: Write> Writer<W> {
     fn new(w: W, info: PartialInfo, options: Options) -> Writer<W> {
+        let conversion = RowConversion::new(&info, &options);
+        let mut w = ChunkCounter::new(w);
+        if options.phase_timings {
+            w.write_time = Some(Duration::default());
+        }
         Writer {
             w,
             info,
@@ -546,6 +1643,13 @@ impl<W: Write> Writer<W> {
             images_written: 0,
             animation_written: 0,
             iend_written: false,
+            conversion,
+            pending_palette: None,
+            frame_diff: None,
+            held_frame: None,
+            frame_count_offset: None,
+            closing_signatures: Vec::new(),
+            stats: EncodingStats::default(),
         }
     }
 
@@ -558,10 +1662,10 @@ impl<W: Write> Writer<W> {
             return Err(EncodingError::Format(FormatErrorKind::ZeroHeight.into()));
         }
 
-        if self
+        if !self
             .info
             .color_type
-            .is_combination_invalid(self.info.bit_depth)
+            .is_combination_allowed(self.info.bit_depth)
         {
             return Err(EncodingError::Format(
                 FormatErrorKind::InvalidColorCombination(self.info.bit_depth, self.info.color_type)
@@ -569,31 +1673,134 @@ impl<W: Write> Writer<W> {
             ));
         }
 
+        if !self.conversion.is_supported() {
+            let conversion = &self.conversion;
+            return Err(EncodingError::Format(
+                FormatErrorKind::UnsupportedInputConversion {
+                    input: (conversion.input_color, conversion.input_depth),
+                    output: (conversion.color_type, conversion.bit_depth),
+                }
+                .into(),
+            ));
+        }
+
+        for text_chunk in info.text_chunks() {
+            check_text_keyword(self.options.strict_keywords, &text_chunk)?;
+        }
+
         self.w.write_all(&[137, 80, 78, 71, 13, 10, 26, 10])?; // PNG signature
-        info.encode(&mut self.w)?;
+
+        let mut header = Vec::new();
+        let order = match (self.info.color_type, &info.palette) {
+            (ColorType::Indexed, Some(_)) => self.options.palette_order,
+            _ => PaletteOrder::Unchanged,
+        };
+
+        match order {
+            PaletteOrder::Unchanged => info.encode(&mut header)?,
+            PaletteOrder::Luminance => {
+                let mut info = info.clone();
+                let (palette, trns, lut) = reorder_palette(
+                    order,
+                    self.info.bit_depth,
+                    info.palette.as_deref().unwrap_or_default(),
+                    info.trns.as_deref(),
+                    None,
+                );
+                info.palette = Some(Cow::Owned(palette));
+                info.trns = trns.map(Cow::Owned);
+                info.encode(&mut header)?;
+                self.conversion.palette_lut = Some(lut);
+            }
+            PaletteOrder::Frequency => {
+                let mut info = info.clone();
+                let palette = info.palette.take().map(Cow::into_owned);
+                let trns = info.trns.take().map(Cow::into_owned);
+                info.encode(&mut header)?;
+                self.pending_palette = palette.map(|palette| (palette, trns));
+            }
+        }
+
+        if let Some(position) = self.options.frame_count_position {
+            let offset = chunk_data_offset(&header, chunk::acTL).unwrap_or_default();
+            self.frame_count_offset = Some(position + 8 + offset as u64);
+        }
+        self.w.write_all(&header)?;
+
+        self.closing_signatures = info
+            .signatures
+            .iter()
+            .rev()
+            .map(|signature| signature.closing.clone().unwrap_or_default())
+            .collect();
 
         Ok(self)
     }
 
+    /// Write the `PLTE` and `tRNS` chunks if they were held back by [`PaletteOrder::Frequency`].
+    ///
+    /// The usage of each entry is counted in `image`, when it is available.
+    fn write_pending_palette(&mut self, image: Option<(&[u8], u32)>) -> Result<()> {
+        let (palette, trns) = match self.pending_palette.take() {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+
+        let (palette, trns) = match image {
+            Some((data, width)) => {
+                let mut counts = [0u64; 256];
+                let in_len = self.info.raw_row_length_from_width(width) - 1;
+                for line in data.chunks(in_len) {
+                    for idx in palette_indices(line, self.info.bit_depth, width as usize) {
+                        counts[usize::from(idx)] += 1;
+                    }
+                }
+
+                let (palette, trns, lut) = reorder_palette(
+                    PaletteOrder::Frequency,
+                    self.info.bit_depth,
+                    &palette,
+                    trns.as_deref(),
+                    Some(&counts),
+                );
+                self.conversion.palette_lut = Some(lut);
+                (palette, trns)
+            }
+            None => (palette, trns),
+        };
+
+        write_chunk(&mut self.w, chunk::PLTE, &palette)?;
+        if let Some(trns) = trns {
+            write_chunk(&mut self.w, chunk::tRNS, &trns)?;
+        }
+        Ok(())
+    }
+
     /// Write a raw chunk of PNG data.
     ///
     /// The chunk will have its CRC calculated and correctly. The data is not filtered in any way,
     /// but the chunk needs to be short enough to have its length encoded correctly.
     pub fn write_chunk(&mut self, name: ChunkType, data: &[u8]) -> Result<()> {
-        use std::convert::TryFrom;
-
-        if u32::try_from(data.len()).map_or(true, |length| length > i32::MAX as u32) {
-            let kind = FormatErrorKind::WrittenTooMuch(data.len() - i32::MAX as usize);
-            return Err(EncodingError::Format(kind.into()));
-        }
-
+        validate_chunk_len(data)?;
+        self.flush_held_frame()?;
         write_chunk(&mut self.w, name, data)
     }
 
     pub fn write_text_chunk<T: EncodableTextChunk>(&mut self, text_chunk: &T) -> Result<()> {
+        check_text_keyword(self.options.strict_keywords, text_chunk)?;
+        self.flush_held_frame()?;
         text_chunk.encode(&mut self.w)
     }
 
+    /// Add a text chunk that is written after the image data, directly before the image trailer.
+    ///
+    /// Unlike [`Writer::write_text_chunk`] this can be called before or while the image data is
+    /// written, for example with statistics gathered from the rows passed to a [`StreamWriter`].
+    pub fn add_trailing_text_chunk<T: EncodableTextChunk>(&mut self, text_chunk: &T) -> Result<()> {
+        check_text_keyword(self.options.strict_keywords, text_chunk)?;
+        text_chunk.encode(&mut self.options.trailing_chunks)
+    }
+
     /// Check if we should allow writing another image.
     fn validate_new_image(&self) -> Result<()> {
         if !self.options.validate_sequence {
@@ -632,29 +1839,51 @@ impl<W: Write> Writer<W> {
         }
     }
 
-    const MAX_IDAT_CHUNK_LEN: u32 = std::u32::MAX >> 1;
+    const MAX_IDAT_CHUNK_LEN: u32 = u32::MAX >> 1;
     #[allow(non_upper_case_globals)]
-    const MAX_fdAT_CHUNK_LEN: u32 = (std::u32::MAX >> 1) - 4;
+    const MAX_fdAT_CHUNK_LEN: u32 = (u32::MAX >> 1) - 4;
 
     /// Writes the next image data.
     pub fn write_image_data(&mut self, data: &[u8]) -> Result<()> {
+        self.flush_held_frame()?;
+        self.write_image_rows(data, self.options.bottom_up)
+    }
+
+    /// Writes the next image from typed pixels, see [`pixel`](crate::pixel).
+    ///
+    /// Samples of 16 bits are given in native endianness. The pixels must match the color type
+    /// and bit depth of the input, like the data of [`Writer::write_image_data`].
+    #[cfg(feature = "bytemuck")]
+    pub fn write_pixels<P: crate::pixel::Pixel>(&mut self, pixels: &[P]) -> Result<()> {
+        use crate::pixel::Sample;
+
+        if P::Sample::DEPTH == BitDepth::Sixteen && cfg!(target_endian = "little") {
+            let mut swapped = pixels.to_vec();
+            crate::pixel::swap_big_endian(&mut swapped);
+            self.write_image_data(bytemuck::cast_slice(&swapped))
+        } else {
+            self.write_image_data(bytemuck::cast_slice(pixels))
+        }
+    }
+
+    /// Writes the next image, with its rows in reverse if they are `bottom_up`.
+    fn write_image_rows(&mut self, data: &[u8], bottom_up: bool) -> Result<()> {
+        let zlib_encoded = self.encode_image_rows(data, bottom_up)?;
+        self.write_zlib_encoded_image(&zlib_encoded)
+    }
+
+    /// Compresses the next image, with its rows in reverse if they are `bottom_up`.
+    fn encode_image_rows(&mut self, data: &[u8], bottom_up: bool) -> Result<Vec<u8>> {
         if self.info.color_type == ColorType::Indexed && !self.info.has_palette {
             return Err(EncodingError::Format(FormatErrorKind::NoPalette.into()));
         }
 
         self.validate_new_image()?;
 
-        let width: usize;
-        let height: usize;
-        if let Some(ref mut fctl) = self.info.frame_control {
-            width = fctl.width as usize;
-            height = fctl.height as usize;
-        } else {
-            width = self.info.width as usize;
-            height = self.info.height as usize;
-        }
+        let (width, height) = self.next_image_size();
+        let (width, height) = (width as usize, height as usize);
 
-        let in_len = self.info.raw_row_length_from_width(width as u32) - 1;
+        let in_len = self.conversion.input_line_len(width);
         let data_size = in_len * height;
         if data_size != data.len() {
             return Err(EncodingError::Parameter(
@@ -666,31 +1895,55 @@ impl<W: Write> Writer<W> {
             ));
         }
 
-        let prev = vec![0; in_len];
-        let mut prev = prev.as_slice();
-        let mut current = vec![0; in_len];
+        self.write_pending_palette(Some((data, width as u32)))?;
 
-        let mut zlib =
-            deflate::write::ZlibEncoder::new(Vec::new(), self.info.compression.to_options());
+        let out_len = self.info.raw_row_length_from_width(width as u32) - 1;
+        let mut prev = vec![0; out_len];
+        let mut current = vec![0; out_len];
+        let mut filtered = vec![0; out_len];
+
+        let mut zlib = ZlibEncoder::new(Vec::new(), self.info.compression);
         let bpp = self.info.bpp_in_prediction();
         let filter_method = self.options.filter;
         let adaptive_method = self.options.adaptive_filter;
-
-        for line in data.chunks(in_len) {
-            current.copy_from_slice(line);
-            let filter_type = filter(filter_method, adaptive_method, bpp, prev, &mut current);
+        let timed = self.options.phase_timings;
+
+        for row in 0..height {
+            let start = if bottom_up { height - 1 - row } else { row } * in_len;
+            let line = &data[start..start + in_len];
+            self.conversion.convert(line, &mut current, width, row)?;
+            filtered.copy_from_slice(&current);
+            let timer = start_timer_if(timed);
+            let filter_type = filter(filter_method, adaptive_method, bpp, &prev, &mut filtered);
+            stop_timer(timer, &mut self.stats.filter_time);
+            self.stats.filter_types[filter_type as usize] += 1;
+            self.stats.filtered_bytes += 1 + filtered.len() as u64;
+            let timer = start_timer_if(timed);
             zlib.write_all(&[filter_type as u8])?;
-            zlib.write_all(&current)?;
-            prev = line;
+            zlib.write_all(&filtered)?;
+            stop_timer(timer, &mut self.stats.compression_time);
+            mem::swap(&mut prev, &mut current);
         }
+        let timer = start_timer_if(timed);
         let zlib_encoded = zlib.finish()?;
+        stop_timer(timer, &mut self.stats.compression_time);
+
+        Ok(zlib_encoded)
+    }
 
+    /// Writes compressed image data as the next image, with a frame control if it is animated.
+    fn write_zlib_encoded_image(&mut self, zlib_encoded: &[u8]) -> Result<()> {
+        debug!(
+            "writing image {} with {} bytes of compressed data",
+            self.images_written,
+            zlib_encoded.len()
+        );
         match self.info.frame_control {
             None => {
-                self.write_zlib_encoded_idat(&zlib_encoded)?;
+                self.write_zlib_encoded_idat(zlib_encoded)?;
             }
             Some(_) if self.should_skip_frame_control_on_default_image() => {
-                self.write_zlib_encoded_idat(&zlib_encoded)?;
+                self.write_zlib_encoded_idat(zlib_encoded)?;
             }
             Some(ref mut fctl) => {
                 fctl.encode(&mut self.w)?;
@@ -699,14 +1952,14 @@ impl<W: Write> Writer<W> {
 
                 // If the default image is the first frame of an animation, it's still an IDAT.
                 if self.images_written == 0 {
-                    self.write_zlib_encoded_idat(&zlib_encoded)?;
+                    self.write_zlib_encoded_idat(zlib_encoded)?;
                 } else {
-                    let buff_size = zlib_encoded.len().min(Self::MAX_fdAT_CHUNK_LEN as usize);
-                    let mut alldata = vec![0u8; 4 + buff_size];
-                    for chunk in zlib_encoded.chunks(Self::MAX_fdAT_CHUNK_LEN as usize) {
-                        alldata[..4].copy_from_slice(&fctl.sequence_number.to_be_bytes());
-                        alldata[4..][..chunk.len()].copy_from_slice(chunk);
-                        write_chunk(&mut self.w, chunk::fdAT, &alldata[..4 + chunk.len()])?;
+                    for data in zlib_encoded.chunks(Self::MAX_fdAT_CHUNK_LEN as usize) {
+                        FrameData {
+                            sequence_number: fctl.sequence_number,
+                            data,
+                        }
+                        .encode(&mut self.w)?;
                         fctl.sequence_number = fctl.sequence_number.wrapping_add(1);
                     }
                 }
@@ -718,129 +1971,566 @@ impl<W: Write> Writer<W> {
         Ok(())
     }
 
-    fn increment_images_written(&mut self) {
-        self.images_written = self.images_written.saturating_add(1);
-
-        if let Some(actl) = self.info.animation_control {
-            if actl.num_frames <= self.animation_written {
-                // If we've written all animation frames, all following will be normal image chunks.
-                self.info.frame_control = None;
-            }
-        }
-    }
-
-    fn write_iend(&mut self) -> Result<()> {
-        self.iend_written = true;
-        self.write_chunk(chunk::IEND, &[])
-    }
-
-    fn should_skip_frame_control_on_default_image(&self) -> bool {
-        self.options.sep_def_img && self.images_written == 0
-    }
-
-    fn write_zlib_encoded_idat(&mut self, zlib_encoded: &[u8]) -> Result<()> {
-        for chunk in zlib_encoded.chunks(Self::MAX_IDAT_CHUNK_LEN as usize) {
-            self.write_chunk(chunk::IDAT, chunk)?;
+    /// Writes the default image that is not part of the animation.
+    ///
+    /// This requires [`Encoder::set_sep_def_img`] and must be called before any of the animation
+    /// frames are written. The default image always covers the whole image, regardless of the
+    /// frame dimension and position that are set for the following frames, and is not counted
+    /// in the number of frames passed to [`Encoder::set_animated`].
+    pub fn write_default_image(&mut self, data: &[u8]) -> Result<()> {
+        if !self.should_skip_frame_control_on_default_image() {
+            return Err(EncodingError::Format(
+                FormatErrorKind::NoSeparateDefaultImage.into(),
+            ));
         }
-        Ok(())
+        self.write_image_data(data)
     }
 
-    /// Set the used filter type for the following frames.
-    ///
-    /// The default filter is [`FilterType::Sub`] which provides a basic prediction algorithm for
-    /// sample values based on the previous. For a potentially better compression ratio, at the
-    /// cost of more complex processing, try out [`FilterType::Paeth`].
+    /// Writes the next frame of an animated image.
     ///
-    /// [`FilterType::Sub`]: enum.FilterType.html#variant.Sub
-    /// [`FilterType::Paeth`]: enum.FilterType.html#variant.Paeth
-    pub fn set_filter(&mut self, filter: FilterType) {
-        self.options.filter = filter;
-    }
-
-    /// Set the adaptive filter type for the following frames.
+    /// This sets the frame control from `frame` and then writes `data` like
+    /// [`Writer::write_image_data`], taking care of the sequence numbers and of the choice between
+    /// `IDAT` and `fdAT` chunks. Unless [`Encoder::set_sep_def_img`] was used, the first frame
+    /// is also the default image and must therefore cover the whole image.
     ///
-    /// Adaptive filtering attempts to select the best filter for each line
-    /// based on heuristics which minimize the file size for compression rather
-    /// than use a single filter for the entire image. The default method is
-    /// [`AdaptiveFilterType::NonAdaptive`].
+    /// An error is returned if:
+    /// - The image is not animated;
     ///
-    /// [`AdaptiveFilterType::NonAdaptive`]: enum.AdaptiveFilterType.html
-    pub fn set_adaptive_filter(&mut self, adaptive_filter: AdaptiveFilterType) {
-        self.options.adaptive_filter = adaptive_filter;
-    }
-
-    /// Set the fraction of time the following frames are going to be displayed,
-    /// in seconds
+    /// - All frames announced in [`Encoder::set_animated`] have already been written;
     ///
-    /// If the denominator is 0, it is to be treated as if it were 100
-    /// (that is, the numerator then specifies 1/100ths of a second).
-    /// If the the value of the numerator is 0 the decoder should render the next frame
-    /// as quickly as possible, though viewers may impose a reasonable lower bound.
+    /// - A separate default image was requested but not written yet;
     ///
-    /// This method will return an error if the image is not animated.
-    pub fn set_frame_delay(&mut self, numerator: u16, denominator: u16) -> Result<()> {
-        if let Some(ref mut fctl) = self.info.frame_control {
-            fctl.delay_den = denominator;
-            fctl.delay_num = numerator;
-            Ok(())
-        } else {
-            Err(EncodingError::Format(FormatErrorKind::NotAnimated.into()))
+    /// - The frame is empty or goes outside the image boundaries.
+    pub fn add_frame(&mut self, data: &[u8], frame: FrameOptions) -> Result<()> {
+        let (width, height) = self.validate_frame(&frame)?;
+        let (image_width, image_height) = (self.info.width, self.info.height);
+        if !self.options.frame_diffing {
+            return self.write_frame(data, &frame, (width, height));
+        } else if (width, height) != (image_width, image_height) {
+            self.flush_held_frame()?;
+            let region = Region {
+                x: frame.x_offset,
+                y: frame.y_offset,
+                width,
+                height,
+            };
+            self.invalidate_frame_diff(region, frame.dispose_op);
+            return self.write_frame(data, &frame, (width, height));
         }
+
+        self.write_diffed_frame(data, frame)
     }
 
-    /// Set the dimension of the following frames.
-    ///
-    /// This function will return an error when:
-    /// - The image is not an animated;
-    ///
-    /// - The selected dimension, considering also the current frame position,
-    ///   goes outside the image boundaries;
+    /// Marks the pixels of `region` as unknown, for a frame that is written without diffing.
     ///
-    /// - One or both the width and height are 0;
-    ///
-    // ??? TODO ???
-    // - The next frame is the default image
-    pub fn set_frame_dimension(&mut self, width: u32, height: u32) -> Result<()> {
-        if let Some(ref mut fctl) = self.info.frame_control {
-            if Some(width) > self.info.width.checked_sub(fctl.x_offset)
-                || Some(height) > self.info.height.checked_sub(fctl.y_offset)
-            {
-                return Err(EncodingError::Format(FormatErrorKind::OutOfBounds.into()));
-            } else if width == 0 {
-                return Err(EncodingError::Format(FormatErrorKind::ZeroWidth.into()));
-            } else if height == 0 {
-                return Err(EncodingError::Format(FormatErrorKind::ZeroHeight.into()));
+    /// This must be called right before the frame is written, a state that does not account
+    /// for all written frames is ignored by the next diffed frame.
+    fn invalidate_frame_diff(&mut self, region: Region, dispose_op: DisposeOp) {
+        let bits_per_pixel = self.conversion.input_bits_per_pixel();
+        let image_width = self.info.width;
+        let animation_written = self.animation_written;
+        if let Some(state) = &mut self.frame_diff {
+            if state.frames != animation_written {
+                return;
+            }
+            state.frames += 1;
+            if dispose_op != DisposeOp::Previous {
+                let region = region.aligned(bits_per_pixel, image_width);
+                state.unknown = union_of(state.unknown, Some(region));
             }
-            fctl.width = width;
-            fctl.height = height;
-            Ok(())
-        } else {
-            Err(EncodingError::Format(FormatErrorKind::NotAnimated.into()))
         }
     }
 
-    /// Set the position of the following frames.
-    ///
-    /// An error will be returned if:
-    /// - The image is not animated;
+    /// Writes an animation frame from an already encoded PNG image, without recompressing it.
     ///
-    /// - The selected position, considering also the current frame dimension,
-    ///   goes outside the image boundaries;
+    /// The compressed image data of `png` is written as the next frame, in `IDAT` chunks for
+    /// the default image and in `fdAT` chunks otherwise. This allows assembling an animation from
+    /// a sequence of existing images, where the encoder is set up like the header of the first
+    /// one (see [`Decoder::read_info`](crate::Decoder::read_info)) and with
+    /// [`Encoder::set_animated`].
     ///
-    // ??? TODO ???
-    // - The next frame is the default image
-    pub fn set_frame_position(&mut self, x: u32, y: u32) -> Result<()> {
-        if let Some(ref mut fctl) = self.info.frame_control {
-            if Some(x) > self.info.width.checked_sub(fctl.width)
-                || Some(y) > self.info.height.checked_sub(fctl.height)
-            {
-                return Err(EncodingError::Format(FormatErrorKind::OutOfBounds.into()));
-            }
-            fctl.x_offset = x;
-            fctl.y_offset = y;
-            Ok(())
-        } else {
-            Err(EncodingError::Format(FormatErrorKind::NotAnimated.into()))
+    /// The image must have the bit depth and color type of this image, must not be interlaced
+    /// and determines the size of the frame, thus `frame.size` can be left at `None`. Ancillary
+    /// chunks of `png` are not copied, in particular indexed images must use the palette of this
+    /// image. Encoding options that convert the image data, such as [`Encoder::set_input_depth`]
+    /// or [`Encoder::set_palette_order`], can't be applied and result in an error.
+    pub fn add_encoded_frame(&mut self, png: &[u8], frame: FrameOptions) -> Result<()> {
+        let (header, zlib_encoded) = encoded_image_data(png)?;
+        let size = (header.width, header.height);
+        let matches = header.color_type == self.info.color_type
+            && header.bit_depth == self.info.bit_depth
+            && frame.size.map_or(true, |frame_size| frame_size == size);
+        if !matches {
+            return Err(EncodingError::Format(
+                FormatErrorKind::MismatchedEncodedFrame.into(),
+            ));
+        }
+
+        let frame = FrameOptions {
+            size: Some(size),
+            ..frame
+        };
+        self.validate_frame(&frame)?;
+
+        let converts = self.conversion.input_color != self.conversion.color_type
+            || self.conversion.input_depth != self.conversion.bit_depth
+            || self.conversion.premultiplied_alpha
+            || self.conversion.significant_bits.is_some()
+            || self.conversion.linear_lut.is_some()
+            || self.options.palette_order != PaletteOrder::Unchanged;
+        if converts {
+            return Err(EncodingError::Format(
+                FormatErrorKind::UnsupportedInputConversion {
+                    input: (self.conversion.input_color, self.conversion.input_depth),
+                    output: (self.info.color_type, self.info.bit_depth),
+                }
+                .into(),
+            ));
+        }
+
+        if self.info.color_type == ColorType::Indexed && !self.info.has_palette {
+            return Err(EncodingError::Format(FormatErrorKind::NoPalette.into()));
+        }
+
+        self.validate_new_image()?;
+        self.flush_held_frame()?;
+        let region = Region {
+            x: frame.x_offset,
+            y: frame.y_offset,
+            width: size.0,
+            height: size.1,
+        };
+        self.invalidate_frame_diff(region, frame.dispose_op);
+        self.set_frame_control(&frame, size);
+        self.write_zlib_encoded_image(&zlib_encoded)
+    }
+
+    /// Checks the frame options of the next frame and returns the size of the frame.
+    fn validate_frame(&self, frame: &FrameOptions) -> Result<(u32, u32)> {
+        if self.info.animation_control.is_none() {
+            return Err(EncodingError::Format(FormatErrorKind::NotAnimated.into()));
+        }
+
+        let is_default_image = self.images_written == 0 && self.held_frame.is_none();
+        if is_default_image && self.options.sep_def_img {
+            return Err(EncodingError::Format(
+                FormatErrorKind::MissingDefaultImage.into(),
+            ));
+        }
+
+        let (image_width, image_height) = (self.info.width, self.info.height);
+        let (width, height) = frame.size.unwrap_or((image_width, image_height));
+        if self.info.frame_control.is_none() {
+            return Err(EncodingError::Format(FormatErrorKind::EndReached.into()));
+        }
+
+        if width == 0 {
+            return Err(EncodingError::Format(FormatErrorKind::ZeroWidth.into()));
+        } else if height == 0 {
+            return Err(EncodingError::Format(FormatErrorKind::ZeroHeight.into()));
+        } else if Some(width) > image_width.checked_sub(frame.x_offset)
+            || Some(height) > image_height.checked_sub(frame.y_offset)
+        {
+            return Err(EncodingError::Format(FormatErrorKind::OutOfBounds.into()));
+        } else if is_default_image
+            && (width, height, frame.x_offset, frame.y_offset) != (image_width, image_height, 0, 0)
+        {
+            return Err(EncodingError::Format(
+                FormatErrorKind::InvalidDefaultFrame.into(),
+            ));
+        }
+
+        Ok((width, height))
+    }
+
+    /// Writes the changed region of a complete frame, see [`Encoder::set_frame_diffing`].
+    fn write_diffed_frame(&mut self, data: &[u8], frame: FrameOptions) -> Result<()> {
+        let (image_width, image_height) = (self.info.width, self.info.height);
+        let full = self.full_region();
+
+        let in_len = self.conversion.input_line_len(image_width as usize);
+        if data.len() != in_len * image_height as usize {
+            return Err(EncodingError::Parameter(
+                ParameterErrorKind::ImageBufferSize {
+                    expected: in_len * image_height as usize,
+                    actual: data.len(),
+                }
+                .into(),
+            ));
+        }
+
+        // The canvas is compared top-down.
+        let flipped;
+        let data = if self.options.bottom_up {
+            flipped = data
+                .chunks(in_len)
+                .rev()
+                .flatten()
+                .copied()
+                .collect::<Vec<_>>();
+            &flipped[..]
+        } else {
+            data
+        };
+
+        let automatic = self.options.automatic_frame_ops;
+        if let Some(held) = self.held_frame.take() {
+            let dispose_op = self.choose_dispose_op(&held, data);
+            self.write_held_frame(held, dispose_op)?;
+        }
+
+        let bits_per_pixel = self.conversion.input_bits_per_pixel();
+        let pixel_len = bits_per_pixel / 8;
+        let transparent = self.keeps_alpha();
+        let (region, cropped, over) = {
+            let shown = self
+                .valid_frame_diff()
+                .map(|state| state.shown(transparent, in_len, pixel_len));
+            let region = match &shown {
+                Some((canvas, unknown)) => {
+                    diff_region(canvas, *unknown, data, in_len, bits_per_pixel, full)
+                }
+                None => full,
+            };
+
+            let mut cropped = if region == full {
+                None
+            } else {
+                let start = region.x as usize * bits_per_pixel / 8;
+                let len = self.conversion.input_line_len(region.width as usize);
+                let cropped = data
+                    .chunks(in_len)
+                    .skip(region.y as usize)
+                    .take(region.height as usize)
+                    .flat_map(|line| &line[start..][..len])
+                    .copied()
+                    .collect::<Vec<_>>();
+                Some(cropped)
+            };
+
+            // Blending requires alpha in the written image, which the input then has as well.
+            let over = match &shown {
+                Some((canvas, unknown)) if automatic && transparent => {
+                    let cropped = cropped.get_or_insert_with(|| data.to_vec());
+                    let sample_len = self.conversion.input_depth as usize / 8;
+                    blend_over(
+                        cropped, canvas, *unknown, region, in_len, pixel_len, sample_len,
+                    )
+                }
+                _ => false,
+            };
+            (region, cropped, over)
+        };
+
+        let (blend_op, dispose_op) = if !automatic {
+            (BlendOp::Source, frame.dispose_op)
+        } else if over {
+            (BlendOp::Over, DisposeOp::None)
+        } else {
+            (BlendOp::Source, DisposeOp::None)
+        };
+
+        let diffed = FrameOptions {
+            x_offset: region.x,
+            y_offset: region.y,
+            dispose_op,
+            blend_op,
+            ..frame
+        };
+        let frame_data = cropped.as_deref().unwrap_or(data);
+        self.set_frame_control(&diffed, (region.width, region.height));
+
+        // The dispose operation is chosen once the next frame is known, unless this is the last.
+        let last = self
+            .info
+            .animation_control
+            .map_or(true, |actl| self.animation_written + 1 >= actl.num_frames);
+        if automatic && !last {
+            let zlib_encoded = self.encode_image_rows(frame_data, false)?;
+            self.held_frame = Some(HeldFrame {
+                frame: diffed,
+                region,
+                canvas: data.to_vec(),
+                zlib_encoded,
+            });
+            return Ok(());
+        }
+
+        let state = self.disposed_state(data.to_vec(), region, dispose_op);
+        self.write_image_rows(frame_data, false)?;
+        self.frame_diff = Some(FrameDiff {
+            frames: self.animation_written,
+            ..state
+        });
+
+        Ok(())
+    }
+
+    /// Chooses the dispose operation of `held` that leaves the smallest region to write for the
+    /// next frame `data`, preferring the operations in the order they are declared.
+    fn choose_dispose_op(&self, held: &HeldFrame, data: &[u8]) -> DisposeOp {
+        let full = self.full_region();
+        let in_len = self.conversion.input_line_len(full.width as usize);
+        let bits_per_pixel = self.conversion.input_bits_per_pixel();
+        let transparent = self.keeps_alpha();
+
+        let mut best = (DisposeOp::None, u64::MAX);
+        for &dispose_op in &[DisposeOp::None, DisposeOp::Background, DisposeOp::Previous] {
+            let state = self.disposed_state(held.canvas.clone(), held.region, dispose_op);
+            let (canvas, unknown) = state.shown(transparent, in_len, bits_per_pixel / 8);
+            let region = diff_region(&canvas, unknown, data, in_len, bits_per_pixel, full);
+            let area = u64::from(region.width) * u64::from(region.height);
+            if area < best.1 {
+                best = (dispose_op, area);
+            }
+        }
+        best.0
+    }
+
+    /// Writes the held frame, with the given dispose operation.
+    fn write_held_frame(&mut self, held: HeldFrame, dispose_op: DisposeOp) -> Result<()> {
+        let state = self.disposed_state(held.canvas, held.region, dispose_op);
+        let frame = FrameOptions {
+            dispose_op,
+            ..held.frame
+        };
+        self.set_frame_control(&frame, (held.region.width, held.region.height));
+        self.write_zlib_encoded_image(&held.zlib_encoded)?;
+        self.frame_diff = Some(FrameDiff {
+            frames: self.animation_written,
+            ..state
+        });
+        Ok(())
+    }
+
+    /// Writes the held frame, if any, before anything else is written.
+    fn flush_held_frame(&mut self) -> Result<()> {
+        match self.held_frame.take() {
+            Some(held) => self.write_held_frame(held, DisposeOp::None),
+            None => Ok(()),
+        }
+    }
+
+    /// The frame diff state after the next frame, with the complete `canvas` and covering
+    /// `region`, is disposed with `dispose_op`.
+    fn disposed_state(&self, canvas: Vec<u8>, region: Region, dispose_op: DisposeOp) -> FrameDiff {
+        let state = |cleared, unknown| FrameDiff {
+            canvas,
+            cleared,
+            unknown,
+            frames: 0,
+        };
+        match dispose_op {
+            DisposeOp::None => state(None, None),
+            DisposeOp::Background => state(Some(region), None),
+            // The first frame is disposed to the transparent canvas the animation starts with.
+            DisposeOp::Previous => match self.valid_frame_diff() {
+                Some(previous) => previous.clone(),
+                None if self.animation_written == 0 => state(Some(region), None),
+                None => state(None, Some(self.full_region())),
+            },
+        }
+    }
+
+    /// The frame diff state, if it accounts for all frames written so far.
+    fn valid_frame_diff(&self) -> Option<&FrameDiff> {
+        self.frame_diff
+            .as_ref()
+            .filter(|state| state.frames == self.animation_written)
+    }
+
+    /// Whether an input pixel of zeros is written as transparent black, the pixel that a decoder
+    /// shows after disposing to the background.
+    fn keeps_alpha(&self) -> bool {
+        self.conversion.input_color == self.conversion.color_type
+            && self.conversion.significant_bits.is_none()
+            && matches!(
+                self.conversion.color_type,
+                ColorType::Rgba | ColorType::GrayscaleAlpha
+            )
+    }
+
+    /// The region covering the whole image.
+    fn full_region(&self) -> Region {
+        Region {
+            x: 0,
+            y: 0,
+            width: self.info.width,
+            height: self.info.height,
+        }
+    }
+
+    /// Sets the frame control for a frame of the given size and writes its data.
+    fn write_frame(&mut self, data: &[u8], frame: &FrameOptions, size: (u32, u32)) -> Result<()> {
+        self.set_frame_control(frame, size);
+        self.write_image_data(data)
+    }
+
+    /// Sets the frame control of the next frame, keeping its sequence number.
+    fn set_frame_control(&mut self, frame: &FrameOptions, size: (u32, u32)) {
+        if let Some(fctl) = self.info.frame_control.as_mut() {
+            *fctl = FrameControl {
+                sequence_number: fctl.sequence_number,
+                width: size.0,
+                height: size.1,
+                x_offset: frame.x_offset,
+                y_offset: frame.y_offset,
+                delay_num: frame.delay_num,
+                delay_den: frame.delay_den,
+                dispose_op: frame.dispose_op,
+                blend_op: frame.blend_op,
+            };
+        }
+    }
+
+    fn increment_images_written(&mut self) {
+        self.images_written = self.images_written.saturating_add(1);
+
+        if let Some(actl) = self.info.animation_control {
+            if actl.num_frames <= self.animation_written {
+                // If we've written all animation frames, all following will be normal image chunks.
+                self.info.frame_control = None;
+            }
+        }
+    }
+
+    fn write_iend(&mut self) -> Result<()> {
+        self.iend_written = true;
+        let trailing_chunks = mem::take(&mut self.options.trailing_chunks);
+        self.w.write_all(&trailing_chunks)?;
+        for closing in mem::take(&mut self.closing_signatures) {
+            self.write_chunk(chunk::dSIG, &closing)?;
+        }
+        self.write_chunk(chunk::IEND, &[])
+    }
+
+    fn should_skip_frame_control_on_default_image(&self) -> bool {
+        self.options.sep_def_img && self.images_written == 0
+    }
+
+    /// The size of the next image, which for a separate default image is the whole image.
+    fn next_image_size(&self) -> (u32, u32) {
+        match self.info.frame_control {
+            Some(fctl) if !self.should_skip_frame_control_on_default_image() => {
+                (fctl.width, fctl.height)
+            }
+            _ => (self.info.width, self.info.height),
+        }
+    }
+
+    fn write_zlib_encoded_idat(&mut self, zlib_encoded: &[u8]) -> Result<()> {
+        for chunk in zlib_encoded.chunks(Self::MAX_IDAT_CHUNK_LEN as usize) {
+            self.write_chunk(chunk::IDAT, chunk)?;
+        }
+        Ok(())
+    }
+
+    /// Set the used filter type for the following frames.
+    ///
+    /// The default filter is [`FilterType::Sub`] which provides a basic prediction algorithm for
+    /// sample values based on the previous. For a potentially better compression ratio, at the
+    /// cost of more complex processing, try out [`FilterType::Paeth`].
+    ///
+    /// [`FilterType::Sub`]: enum.FilterType.html#variant.Sub
+    /// [`FilterType::Paeth`]: enum.FilterType.html#variant.Paeth
+    pub fn set_filter(&mut self, filter: FilterType) {
+        self.options.filter = filter;
+    }
+
+    /// Set the adaptive filter type for the following frames.
+    ///
+    /// Adaptive filtering attempts to select the best filter for each line
+    /// based on heuristics which minimize the file size for compression rather
+    /// than use a single filter for the entire image. The default method is
+    /// [`AdaptiveFilterType::NonAdaptive`].
+    ///
+    /// [`AdaptiveFilterType::NonAdaptive`]: enum.AdaptiveFilterType.html
+    pub fn set_adaptive_filter(&mut self, adaptive_filter: AdaptiveFilterType) {
+        self.options.adaptive_filter = adaptive_filter;
+    }
+
+    /// Set the compression parameters for the following frames.
+    ///
+    /// This allows, for example, compressing key frames well and small changes quickly.
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.info.compression = compression;
+    }
+
+    /// Set the fraction of time the following frames are going to be displayed,
+    /// in seconds
+    ///
+    /// If the denominator is 0, it is to be treated as if it were 100
+    /// (that is, the numerator then specifies 1/100ths of a second).
+    /// If the the value of the numerator is 0 the decoder should render the next frame
+    /// as quickly as possible, though viewers may impose a reasonable lower bound.
+    ///
+    /// This method will return an error if the image is not animated.
+    pub fn set_frame_delay(&mut self, numerator: u16, denominator: u16) -> Result<()> {
+        self.flush_held_frame()?;
+        if let Some(ref mut fctl) = self.info.frame_control {
+            fctl.delay_den = denominator;
+            fctl.delay_num = numerator;
+            Ok(())
+        } else {
+            Err(EncodingError::Format(FormatErrorKind::NotAnimated.into()))
+        }
+    }
+
+    /// Set the dimension of the following frames.
+    ///
+    /// This function will return an error when:
+    /// - The image is not an animated;
+    ///
+    /// - The selected dimension, considering also the current frame position,
+    ///   goes outside the image boundaries;
+    ///
+    /// - One or both the width and height are 0;
+    ///
+    // ??? TODO ???
+    // - The next frame is the default image
+    pub fn set_frame_dimension(&mut self, width: u32, height: u32) -> Result<()> {
+        self.flush_held_frame()?;
+        if let Some(ref mut fctl) = self.info.frame_control {
+            if Some(width) > self.info.width.checked_sub(fctl.x_offset)
+                || Some(height) > self.info.height.checked_sub(fctl.y_offset)
+            {
+                return Err(EncodingError::Format(FormatErrorKind::OutOfBounds.into()));
+            } else if width == 0 {
+                return Err(EncodingError::Format(FormatErrorKind::ZeroWidth.into()));
+            } else if height == 0 {
+                return Err(EncodingError::Format(FormatErrorKind::ZeroHeight.into()));
+            }
+            fctl.width = width;
+            fctl.height = height;
+            Ok(())
+        } else {
+            Err(EncodingError::Format(FormatErrorKind::NotAnimated.into()))
+        }
+    }
+
+    /// Set the position of the following frames.
+    ///
+    /// An error will be returned if:
+    /// - The image is not animated;
+    ///
+    /// - The selected position, considering also the current frame dimension,
+    ///   goes outside the image boundaries;
+    ///
+    // ??? TODO ???
+    // - The next frame is the default image
+    pub fn set_frame_position(&mut self, x: u32, y: u32) -> Result<()> {
+        self.flush_held_frame()?;
+        if let Some(ref mut fctl) = self.info.frame_control {
+            if Some(x) > self.info.width.checked_sub(fctl.width)
+                || Some(y) > self.info.height.checked_sub(fctl.height)
+            {
+                return Err(EncodingError::Format(FormatErrorKind::OutOfBounds.into()));
+            }
+            fctl.x_offset = x;
+            fctl.y_offset = y;
+            Ok(())
+        } else {
+            Err(EncodingError::Format(FormatErrorKind::NotAnimated.into()))
         }
     }
 
@@ -854,6 +2544,7 @@ impl<W: Write> Writer<W> {
     ///
     /// [`reset_frame_position`]: struct.Writer.html#method.reset_frame_position
     pub fn reset_frame_dimension(&mut self) -> Result<()> {
+        self.flush_held_frame()?;
         if let Some(ref mut fctl) = self.info.frame_control {
             fctl.width = self.info.width - fctl.x_offset;
             fctl.height = self.info.height - fctl.y_offset;
@@ -871,6 +2562,7 @@ impl<W: Write> Writer<W> {
     ///
     /// [`set_frame_position(0, 0)`]: struct.Writer.html#method.set_frame_position
     pub fn reset_frame_position(&mut self) -> Result<()> {
+        self.flush_held_frame()?;
         if let Some(ref mut fctl) = self.info.frame_control {
             fctl.x_offset = 0;
             fctl.y_offset = 0;
@@ -896,6 +2588,7 @@ impl<W: Write> Writer<W> {
     ///
     /// [`BlendOP`]: enum.BlendOp.html
     pub fn set_blend_op(&mut self, op: BlendOp) -> Result<()> {
+        self.flush_held_frame()?;
         if let Some(ref mut fctl) = self.info.frame_control {
             fctl.blend_op = op;
             Ok(())
@@ -920,6 +2613,7 @@ impl<W: Write> Writer<W> {
     /// [`DisposeOp::Previous`]: ../common/enum.BlendOp.html#variant.Previous
     /// [`DisposeOp::Background`]: ../common/enum.BlendOp.html#variant.Background
     pub fn set_dispose_op(&mut self, op: DisposeOp) -> Result<()> {
+        self.flush_held_frame()?;
         if let Some(ref mut fctl) = self.info.frame_control {
             fctl.dispose_op = op;
             Ok(())
@@ -936,7 +2630,7 @@ impl<W: Write> Writer<W> {
     ///
     /// This borrows the writer which allows for manually appending additional
     /// chunks after the image data has been written.
-    pub fn stream_writer(&mut self) -> Result<StreamWriter<W>> {
+    pub fn stream_writer(&mut self) -> Result<StreamWriter<'_, W>> {
         self.stream_writer_with_size(DEFAULT_BUFFER_LENGTH)
     }
 
@@ -945,7 +2639,8 @@ impl<W: Write> Writer<W> {
     /// See [`stream_writer`].
     ///
     /// [`stream_writer`]: #fn.stream_writer
-    pub fn stream_writer_with_size(&mut self, size: usize) -> Result<StreamWriter<W>> {
+    pub fn stream_writer_with_size(&mut self, size: usize) -> Result<StreamWriter<'_, W>> {
+        self.flush_held_frame()?;
         StreamWriter::new(ChunkOutput::Borrowed(self), size)
     }
 
@@ -963,7 +2658,8 @@ impl<W: Write> Writer<W> {
     /// See [`into_stream_writer`].
     ///
     /// [`into_stream_writer`]: #fn.into_stream_writer
-    pub fn into_stream_writer_with_size(self, size: usize) -> Result<StreamWriter<'static, W>> {
+    pub fn into_stream_writer_with_size(mut self, size: usize) -> Result<StreamWriter<'static, W>> {
+        self.flush_held_frame()?;
         StreamWriter::new(ChunkOutput::Owned(self), size)
     }
 
@@ -973,27 +2669,121 @@ impl<W: Write> Writer<W> {
     /// validation options (chunk sequencing) had been turned on in the configuration then it will
     /// also do a check on their correctness _before_ writing the final chunk.
     pub fn finish(mut self) -> Result<()> {
-        self.validate_sequence_done()?;
-        self.write_iend()?;
-        self.w.flush()?;
+        self.finish_image()?;
 
         // Explicitly drop `self` just for clarity.
         drop(self);
         Ok(())
     }
-}
 
-impl<W: Write> Drop for Writer<W> {
-    fn drop(&mut self) {
-        if !self.iend_written {
-            let _ = self.write_iend();
+    /// Consume the writer like [`Writer::finish`] and return statistics on the encoded image.
+    ///
+    /// The rows written with a [`StreamWriter`] are included if it was borrowed with
+    /// [`Writer::stream_writer`] and finished.
+    ///
+    /// ```
+    /// let mut writer = png::Encoder::new(Vec::new(), 2, 2).write_header()?;
+    /// writer.write_image_data(&[0; 4])?;
+    /// let stats = writer.finish_with_stats()?;
+    /// assert_eq!(stats.filter_count(png::FilterType::Sub), 2);
+    /// assert_eq!(stats.chunk_bytes(png::chunk::IEND), 12);
+    /// # Ok::<(), png::EncodingError>(())
+    /// ```
+    pub fn finish_with_stats(mut self) -> Result<EncodingStats> {
+        self.finish_image()?;
+        Ok(self.encoding_stats())
+    }
+
+    /// Statistics on the image data and chunks written so far, see [`Writer::finish_with_stats`].
+    pub fn encoding_stats(&self) -> EncodingStats {
+        EncodingStats {
+            chunk_bytes: self.w.chunks.clone(),
+            compressed_bytes: self.w.image_data,
+            ..self.stats.clone()
         }
     }
-}
 
-enum ChunkOutput<'a, W: Write> {
-    Borrowed(&'a mut Writer<W>),
-    Owned(Writer<W>),
+    /// The time spent filtering, compressing and writing so far, if enabled with
+    /// [`Encoder::set_phase_timings`].
+    ///
+    /// The rows of a [`StreamWriter`] are included once it is finished.
+    pub fn phase_timings(&self) -> Option<PhaseTimings> {
+        self.w.write_time.map(|write| PhaseTimings {
+            filter: self.stats.filter_time,
+            deflate: self.stats.compression_time,
+            write,
+            ..PhaseTimings::default()
+        })
+    }
+
+    fn finish_image(&mut self) -> Result<()> {
+        if self.frame_count_offset.is_some() {
+            return Err(EncodingError::Format(
+                FormatErrorKind::UnknownFrameCount.into(),
+            ));
+        }
+
+        self.flush_held_frame()?;
+        self.validate_sequence_done()?;
+        self.write_pending_palette(None)?;
+        self.write_iend()?;
+        self.w.flush()?;
+        Ok(())
+    }
+}
+
+impl<W: Write + Seek> Writer<W> {
+    /// Consume the writer of an animation started with [`Encoder::set_animated_unknown_frames`].
+    ///
+    /// This finishes the image like [`Writer::finish`] and then seeks back to write the number of
+    /// animation frames into the `acTL` chunk. An error is returned if no frame was written.
+    pub fn finish_with_frame_count(mut self) -> Result<()> {
+        let offset = match self.frame_count_offset.take() {
+            Some(offset) => offset,
+            None => return self.finish(),
+        };
+        self.flush_held_frame()?;
+        let mut actl = match self.info.animation_control {
+            Some(actl) => actl,
+            None => return Err(EncodingError::Format(FormatErrorKind::NotAnimated.into())),
+        };
+        if self.animation_written == 0 {
+            return Err(EncodingError::Format(FormatErrorKind::ZeroFrames.into()));
+        }
+        actl.num_frames = self.animation_written;
+        self.info.animation_control = Some(actl);
+        self.info.frame_control = None;
+
+        self.validate_sequence_done()?;
+        self.write_pending_palette(None)?;
+        self.write_iend()?;
+
+        let data = actl.to_bytes();
+        // The chunk is overwritten in place, past the counting of `w`.
+        let w = &mut self.w.inner;
+        let end = w.seek(SeekFrom::Current(0))?;
+        w.seek(SeekFrom::Start(offset))?;
+        w.write_all(&data)?;
+        w.write_be(chunk::crc(chunk::acTL, &data))?;
+        w.seek(SeekFrom::Start(end))?;
+        w.flush()?;
+        Ok(())
+    }
+}
+
+impl<W: Write> Drop for Writer<W> {
+    fn drop(&mut self) {
+        if !self.iend_written {
+            let _ = self.flush_held_frame();
+            let _ = self.write_iend();
+        }
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
+enum ChunkOutput<'a, W: Write> {
+    Borrowed(&'a mut Writer<W>),
+    Owned(Writer<W>),
 }
 
 // opted for deref for practical reasons
@@ -1047,13 +2837,12 @@ impl<'a, W: Write> ChunkWriter<'a, W> {
         //
         // TODO (maybe): find a way to hold two chunks at a time if `usize`
         //               is 64 bits.
-        const CAP: usize = std::u32::MAX as usize >> 1;
-        let curr_chunk;
-        if writer.images_written == 0 {
-            curr_chunk = chunk::IDAT;
+        const CAP: usize = u32::MAX as usize >> 1;
+        let curr_chunk = if writer.images_written == 0 {
+            chunk::IDAT
         } else {
-            curr_chunk = chunk::fdAT;
-        }
+            chunk::fdAT
+        };
         ChunkWriter {
             writer,
             buffer: vec![0; CAP.min(buf_len)],
@@ -1070,23 +2859,19 @@ impl<'a, W: Write> ChunkWriter<'a, W> {
     /// the next one
     fn next_frame_info(&self) -> (usize, usize) {
         let wrt = self.writer.deref();
+        let (width, height) = wrt.next_image_size();
 
-        let width: usize;
-        let height: usize;
-        if let Some(fctl) = wrt.info.frame_control {
-            width = fctl.width as usize;
-            height = fctl.height as usize;
-        } else {
-            width = wrt.info.width as usize;
-            height = wrt.info.height as usize;
-        }
-
-        let in_len = wrt.info.raw_row_length_from_width(width as u32) - 1;
-        let data_size = in_len * height;
+        let in_len = wrt.conversion.input_line_len(width as usize);
+        let data_size = in_len * height as usize;
 
         (in_len, data_size)
     }
 
+    /// Returns the number of pixels in each scanline of the next frame
+    fn next_frame_width(&self) -> usize {
+        self.writer.deref().next_image_size().0 as usize
+    }
+
     /// NOTE: this bypasses the internal buffer so the flush method should be called before this
     ///       in the case there is some data left in the buffer when this is called, it will panic
     fn write_header(&mut self) -> Result<()> {
@@ -1155,8 +2940,7 @@ impl<'a, W: Write> Write for ChunkWriter<'a, W> {
 
             // Prepare the next animated frame, if any.
             let no_fctl = wrt.should_skip_frame_control_on_default_image();
-            if wrt.info.frame_control.is_some() && !no_fctl {
-                let fctl = wrt.info.frame_control.as_mut().unwrap();
+            if let Some(fctl) = wrt.info.frame_control.as_mut().filter(|_| !no_fctl) {
                 self.buffer[0..4].copy_from_slice(&fctl.sequence_number.to_be_bytes());
                 fctl.sequence_number += 1;
                 self.index = 4;
@@ -1207,6 +2991,7 @@ impl<W: Write> Drop for ChunkWriter<'_, W> {
 /// If the `finish` function fails then there is nothing much to
 /// do as the `ChunkWriter` would get lost so the `Unrecoverable`
 /// variant is used to signal that.
+#[allow(clippy::large_enum_variant)]
 enum Wrapper<'a, W: Write> {
     Chunk(ChunkWriter<'a, W>),
     Zlib(ZlibEncoder<ChunkWriter<'a, W>>),
@@ -1235,12 +3020,18 @@ pub struct StreamWriter<'a, W: Write> {
     /// The option here is needed in order to access the inner `ChunkWriter` in-between
     /// each frame, which is needed for writing the fcTL chunks between each frame
     writer: Wrapper<'a, W>,
+    /// the scanline as it is passed in
+    input_buf: Vec<u8>,
+    /// the previous and current scanline after conversion but before filtering
     prev_buf: Vec<u8>,
     curr_buf: Vec<u8>,
+    filter_buf: Vec<u8>,
     /// Amount of data already written
     index: usize,
     /// length of the current scanline
     line_len: usize,
+    /// index of the current scanline in the frame
+    line_index: usize,
     /// size of the frame (width * height * sample_size)
     to_write: usize,
 
@@ -1252,10 +3043,23 @@ pub struct StreamWriter<'a, W: Write> {
     adaptive_filter: AdaptiveFilterType,
     fctl: Option<FrameControl>,
     compression: Compression,
+
+    /// number of pixels in the current scanline
+    line_width: usize,
+    conversion: RowConversion,
+    /// Encoded text chunks that are handed to the writer once the image data is complete.
+    trailing_chunks: Vec<u8>,
+    strict_keywords: bool,
+    /// Whether filtering and compression are timed, see [`Encoder::set_phase_timings`].
+    phase_timings: bool,
+    /// The row statistics that are added to those of the writer between frames.
+    stats: EncodingStats,
 }
 
 impl<'a, W: Write> StreamWriter<'a, W> {
-    fn new(writer: ChunkOutput<'a, W>, buf_len: usize) -> Result<StreamWriter<'a, W>> {
+    fn new(mut writer: ChunkOutput<'a, W>, buf_len: usize) -> Result<StreamWriter<'a, W>> {
+        writer.write_pending_palette(None)?;
+
         let PartialInfo {
             width,
             height,
@@ -1265,31 +3069,47 @@ impl<'a, W: Write> StreamWriter<'a, W> {
         } = writer.info;
 
         let bpp = writer.info.bpp_in_prediction();
-        let in_len = writer.info.raw_row_length() - 1;
+        let in_len = writer.conversion.input_line_len(width as usize);
+        let out_len = writer.info.raw_row_length() - 1;
         let filter = writer.options.filter;
         let adaptive_filter = writer.options.adaptive_filter;
-        let prev_buf = vec![0; in_len];
-        let curr_buf = vec![0; in_len];
+        let strict_keywords = writer.options.strict_keywords;
+        let phase_timings = writer.options.phase_timings;
+        let input_buf = vec![0; in_len];
+        let prev_buf = vec![0; out_len];
+        let curr_buf = vec![0; out_len];
+        let filter_buf = vec![0; out_len];
+        let conversion = writer.conversion.clone();
 
         let mut chunk_writer = ChunkWriter::new(writer, buf_len);
         let (line_len, to_write) = chunk_writer.next_frame_info();
+        let line_width = chunk_writer.next_frame_width();
         chunk_writer.write_header()?;
-        let zlib = ZlibEncoder::new(chunk_writer, compression.to_options());
+        let zlib = ZlibEncoder::new(chunk_writer, compression);
 
         Ok(StreamWriter {
             writer: Wrapper::Zlib(zlib),
             index: 0,
+            input_buf,
             prev_buf,
             curr_buf,
+            filter_buf,
             bpp,
             filter,
             width,
             height,
             adaptive_filter,
             line_len,
+            line_index: 0,
             to_write,
             fctl,
             compression,
+            line_width,
+            conversion,
+            trailing_chunks: Vec::new(),
+            strict_keywords,
+            phase_timings,
+            stats: EncodingStats::default(),
         })
     }
 
@@ -1317,6 +3137,13 @@ impl<'a, W: Write> StreamWriter<'a, W> {
         self.adaptive_filter = adaptive_filter;
     }
 
+    /// Set the compression parameters for the next frame.
+    ///
+    /// The frame that is currently being written keeps its compression.
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.compression = compression;
+    }
+
     /// Set the fraction of time the following frames are going to be displayed,
     /// in seconds
     ///
@@ -1472,6 +3299,13 @@ impl<'a, W: Write> StreamWriter<'a, W> {
         }
     }
 
+    /// Add a text chunk that is written after the image data, see
+    /// [`Writer::add_trailing_text_chunk`].
+    pub fn add_trailing_text_chunk<T: EncodableTextChunk>(&mut self, text_chunk: &T) -> Result<()> {
+        check_text_keyword(self.strict_keywords, text_chunk)?;
+        text_chunk.encode(&mut self.trailing_chunks)
+    }
+
     pub fn finish(mut self) -> Result<()> {
         if self.to_write > 0 {
             let err = FormatErrorKind::MissingData(self.to_write).into();
@@ -1480,14 +3314,53 @@ impl<'a, W: Write> StreamWriter<'a, W> {
 
         // TODO: call `writer.finish` somehow?
         self.flush()?;
+        self.hand_over_trailing_chunks()?;
+        self.finish_zlib()?;
 
-        if let Wrapper::Chunk(wrt) = self.writer.take() {
+        if let Wrapper::Chunk(mut wrt) = self.writer.take() {
+            wrt.flush()?;
+            wrt.writer.stats.add_rows(&self.stats);
             wrt.writer.validate_sequence_done()?;
         }
 
         Ok(())
     }
 
+    /// Moves the trailing text chunks to the writer, which writes them before the trailer.
+    ///
+    /// This finishes the compressed stream of the current frame.
+    fn hand_over_trailing_chunks(&mut self) -> Result<()> {
+        if self.trailing_chunks.is_empty() {
+            return Ok(());
+        }
+
+        self.finish_zlib()?;
+        if let Wrapper::Chunk(wrt) = &mut self.writer {
+            let trailing_chunks = &mut wrt.writer.options.trailing_chunks;
+            trailing_chunks.append(&mut self.trailing_chunks);
+        }
+        Ok(())
+    }
+
+    /// Finishes the compressed stream of the current frame, if it is not finished yet.
+    fn finish_zlib(&mut self) -> io::Result<()> {
+        if let Wrapper::Zlib(_) = self.writer {
+            let timer = start_timer_if(self.phase_timings);
+            match self.writer.take() {
+                Wrapper::Zlib(wrt) => match wrt.finish() {
+                    Ok(chunk) => self.writer = Wrapper::Chunk(chunk),
+                    Err(err) => {
+                        self.writer = Wrapper::Unrecoverable;
+                        return Err(err);
+                    }
+                },
+                _ => unreachable!(),
+            }
+            stop_timer(timer, &mut self.stats.compression_time);
+        }
+        Ok(())
+    }
+
     /// Flushes the buffered chunk, checks if it was the last frame,
     /// writes the next frame header and gets the next frame scanline size
     /// and image size.
@@ -1503,6 +3376,7 @@ impl<'a, W: Write> StreamWriter<'a, W> {
             Wrapper::None => unreachable!(),
         };
         wrt.flush()?;
+        wrt.writer.stats.add_rows(&mem::take(&mut self.stats));
         wrt.writer.validate_new_image()?;
 
         if let Some(fctl) = self.fctl {
@@ -1510,7 +3384,11 @@ impl<'a, W: Write> StreamWriter<'a, W> {
         }
         let (scansize, size) = wrt.next_frame_info();
         self.line_len = scansize;
+        self.line_index = 0;
         self.to_write = size;
+        self.line_width = wrt.next_frame_width();
+        // The first row of each frame is filtered against zeros.
+        self.prev_buf.iter_mut().for_each(|b| *b = 0);
 
         wrt.write_header()?;
         wrt.writer.increment_images_written();
@@ -1518,7 +3396,7 @@ impl<'a, W: Write> StreamWriter<'a, W> {
         // now it can be taken because the next statements cannot cause any errors
         match self.writer.take() {
             Wrapper::Chunk(wrt) => {
-                let encoder = ZlibEncoder::new(wrt, self.compression.to_options());
+                let encoder = ZlibEncoder::new(wrt, self.compression);
                 self.writer = Wrapper::Zlib(encoder);
             }
             _ => unreachable!(),
@@ -1540,221 +3418,2768 @@ impl<'a, W: Write> Write for StreamWriter<'a, W> {
         }
 
         if self.to_write == 0 {
-            match self.writer.take() {
-                Wrapper::Zlib(wrt) => match wrt.finish() {
-                    Ok(chunk) => self.writer = Wrapper::Chunk(chunk),
-                    Err(err) => {
-                        self.writer = Wrapper::Unrecoverable;
-                        return Err(err);
+            self.finish_zlib()?;
+
+            // Transition Wrapper::Chunk to Wrapper::Zlib.
+            self.new_frame()?;
+        }
+
+        let written = data.read(&mut self.input_buf[..self.line_len][self.index..])?;
+        self.index += written;
+        self.to_write -= written;
+
+        if self.index == self.line_len {
+            let out_len = self.conversion.output_line_len(self.line_width);
+            let current = &mut self.curr_buf[..out_len];
+            self.conversion.convert(
+                &self.input_buf[..self.line_len],
+                current,
+                self.line_width,
+                self.line_index,
+            )?;
+            let filtered = &mut self.filter_buf[..out_len];
+            filtered.copy_from_slice(current);
+            let timer = start_timer_if(self.phase_timings);
+            let filter_type = filter(
+                self.filter,
+                self.adaptive_filter,
+                self.bpp,
+                &self.prev_buf,
+                filtered,
+            );
+            stop_timer(timer, &mut self.stats.filter_time);
+            self.stats.filter_types[filter_type as usize] += 1;
+            self.stats.filtered_bytes += 1 + filtered.len() as u64;
+            // This can't fail as the other variant is used only to allow the zlib encoder to finish
+            let wrt = match &mut self.writer {
+                Wrapper::Zlib(wrt) => wrt,
+                _ => unreachable!(),
+            };
+
+            let timer = start_timer_if(self.phase_timings);
+            wrt.write_all(&[filter_type as u8])?;
+            wrt.write_all(filtered)?;
+            stop_timer(timer, &mut self.stats.compression_time);
+            mem::swap(&mut self.prev_buf, &mut self.curr_buf);
+            self.index = 0;
+            self.line_index += 1;
+        }
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.writer {
+            Wrapper::Zlib(wrt) => wrt.flush()?,
+            Wrapper::Chunk(wrt) => wrt.flush()?,
+            // This handles both the case where we entered an unrecoverable state after zlib
+            // decoding failure and after a panic while we had taken the chunk/zlib reader.
+            Wrapper::Unrecoverable | Wrapper::None => {
+                let err = FormatErrorKind::Unrecoverable.into();
+                return Err(EncodingError::Format(err).into());
+            }
+        }
+
+        if self.index > 0 {
+            let err = FormatErrorKind::WrittenTooMuch(self.index).into();
+            return Err(EncodingError::Format(err).into());
+        }
+
+        Ok(())
+    }
+}
+
+impl<W: Write> Drop for StreamWriter<'_, W> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+        let _ = self.hand_over_trailing_chunks();
+    }
+}
+
+/// An in-memory output whose written bytes can be taken while an encoder still writes to it.
+///
+/// This connects an encoder with sinks that are not a blocking [`Write`], such as the body of an
+/// HTTP response or an async writer. Handles are cheap to clone and share the same bytes, so one
+/// is given to the [`Encoder`] while the other drains the encoded bytes after writing each row
+/// or frame, without keeping the whole file in memory. The image data of a [`StreamWriter`]
+/// becomes available in chunks, whose size is set with [`Writer::stream_writer_with_size`].
+///
+/// There is no encoder for an `AsyncWrite` itself, as this crate does not depend on an async
+/// runtime. The encoder never waits for the sink, so the bytes are instead taken between writes
+/// and passed to the async writer by the caller.
+///
+/// ```
+/// use std::io::Write;
+/// use png::{Encoder, OutputBuffer};
+///
+/// let output = OutputBuffer::new();
+/// let mut encoder = Encoder::new(output.clone(), 16, 16);
+/// encoder.set_color(png::ColorType::Grayscale);
+/// let mut writer = encoder.write_header()?;
+/// let mut stream = writer.stream_writer_with_size(64)?;
+///
+/// let mut sent = Vec::new();
+/// for y in 0..16 {
+///     stream.write_all(&[y * 16; 16])?;
+///     // For example `response.write_all(&output.take()).await?` in an async task.
+///     sent.extend(output.take());
+/// }
+/// stream.finish()?;
+/// writer.finish()?;
+/// sent.extend(output.take());
+/// assert_eq!(&sent[1..4], b"PNG");
+/// # Ok::<(), png::EncodingError>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct OutputBuffer {
+    bytes: Arc<Mutex<Vec<u8>>>,
+}
+
+impl OutputBuffer {
+    /// Create an empty buffer.
+    pub fn new() -> Self {
+        OutputBuffer::default()
+    }
+
+    /// Removes and returns the bytes written since the last call.
+    pub fn take(&self) -> Vec<u8> {
+        mem::take(&mut *self.lock())
+    }
+
+    /// The number of bytes that can be taken.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Whether there are no bytes to take.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<u8>> {
+        // The bytes are valid even if a writer panicked while holding the lock.
+        self.bytes
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Write for OutputBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.lock().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// An output into a slice of the caller, for targets where the file can not be allocated.
+///
+/// Bytes that do not fit into the slice are counted and dropped, so encoding runs to the end and
+/// [`SliceWriter::finish`] reports the exact size the file needs.
+///
+/// ```
+/// use png::{Encoder, SliceWriter};
+///
+/// let encode = |buf: &mut [u8]| -> Result<usize, png::EncodingError> {
+///     let mut output = SliceWriter::new(buf);
+///     let mut encoder = Encoder::new(&mut output, 2, 1);
+///     encoder.set_color(png::ColorType::Grayscale);
+///     let mut writer = encoder.write_header()?;
+///     writer.write_image_data(&[0, 255])?;
+///     writer.finish()?;
+///     Ok(output.finish()?.len())
+/// };
+///
+/// let mut small = [0; 16];
+/// let err = encode(&mut small).unwrap_err();
+/// assert!(err.to_string().starts_with("output buffer too small, needed"));
+/// let mut buf = [0; 1024];
+/// assert!(encode(&mut buf)? > 16);
+/// # Ok::<(), png::EncodingError>(())
+/// ```
+#[derive(Debug)]
+pub struct SliceWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> SliceWriter<'a> {
+    /// Create an output writing to the start of `buf`.
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        SliceWriter { buf, len: 0 }
+    }
+
+    /// The number of bytes written, including those that did not fit into the slice.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether nothing was written yet.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the written part of the slice, or an error with the needed size if it is too small.
+    pub fn finish(self) -> Result<&'a mut [u8]> {
+        if self.len > self.buf.len() {
+            return Err(EncodingError::Parameter(
+                ParameterErrorKind::OutputBufferSize {
+                    needed: self.len,
+                    available: self.buf.len(),
+                }
+                .into(),
+            ));
+        }
+        Ok(&mut self.buf[..self.len])
+    }
+}
+
+impl Write for SliceWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(free) = self.buf.get_mut(self.len..) {
+            let fits = free.len().min(buf.len());
+            free[..fits].copy_from_slice(&buf[..fits]);
+        }
+        self.len = self.len.saturating_add(buf.len());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The conversions applied to each scanline of the input before it is filtered.
+#[derive(Clone)]
+struct RowConversion {
+    /// The color type of the input.
+    input_color: ColorType,
+    /// The color type of the encoded image.
+    color_type: ColorType,
+    strip_alpha: StripAlpha,
+    /// The depth of the input samples.
+    input_depth: BitDepth,
+    /// The depth of the encoded samples.
+    bit_depth: BitDepth,
+    depth_reduction: DepthReduction,
+    /// The significant low bits of the 16-bit input samples.
+    significant_bits: Option<u8>,
+    /// Divide the color samples by alpha.
+    premultiplied_alpha: bool,
+    /// Maps linear color samples of the input depth to encoded ones.
+    linear_lut: Option<Vec<u16>>,
+    /// Maps the palette indices of the input to those of the reordered palette.
+    palette_lut: Option<Vec<u8>>,
+    /// Holds the input while it is converted to a different layout.
+    scratch: Vec<u8>,
+}
+
+impl RowConversion {
+    fn new(info: &PartialInfo, options: &Options) -> Self {
+        let input_color = options.input_color.unwrap_or(info.color_type);
+        let has_alpha = matches!(input_color, ColorType::Rgba | ColorType::GrayscaleAlpha);
+        let input_depth = options.input_depth.unwrap_or(info.bit_depth);
+        RowConversion {
+            input_color,
+            color_type: info.color_type,
+            strip_alpha: options.strip_alpha,
+            input_depth,
+            bit_depth: info.bit_depth,
+            depth_reduction: options.depth_reduction,
+            significant_bits: options.significant_bits,
+            premultiplied_alpha: options.premultiplied_alpha && has_alpha,
+            linear_lut: options
+                .linear_input
+                .map(|transfer| linear_input_lut(transfer, input_depth)),
+            palette_lut: None,
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Checks if the input can be converted into the encoded format.
+    fn is_supported(&self) -> bool {
+        let color = match (self.input_color, self.color_type) {
+            (input, output) if input == output => true,
+            (ColorType::Rgba, ColorType::Rgb) => true,
+            (ColorType::GrayscaleAlpha, ColorType::Grayscale) => true,
+            _ => false,
+        };
+        let depth = match (self.input_depth, self.bit_depth) {
+            (input, output) if input == output => true,
+            (BitDepth::Sixteen, BitDepth::Eight) => self.color_type != ColorType::Indexed,
+            _ => false,
+        };
+        let linear = self.linear_lut.is_none()
+            || (self.input_color != ColorType::Indexed && self.input_depth as u8 >= 8);
+        color && depth && linear && self.input_color.is_combination_allowed(self.input_depth)
+    }
+
+    /// The number of bits of each pixel in the input.
+    fn input_bits_per_pixel(&self) -> usize {
+        self.input_color.samples() * self.input_depth as usize
+    }
+
+    /// The number of input bytes in a scanline of `width` pixels.
+    fn input_line_len(&self, width: usize) -> usize {
+        self.input_color
+            .raw_row_length_from_width(self.input_depth, width as u32)
+            - 1
+    }
+
+    /// The number of encoded bytes in a scanline of `width` pixels.
+    fn output_line_len(&self, width: usize) -> usize {
+        self.color_type
+            .raw_row_length_from_width(self.bit_depth, width as u32)
+            - 1
+    }
+
+    /// Converts one scanline of `width` pixels from `input` into `output`.
+    ///
+    /// The `row` within the frame selects the dither pattern.
+    fn convert(&mut self, input: &[u8], output: &mut [u8], width: usize, row: usize) -> Result<()> {
+        let in_samples = self.input_color.samples();
+
+        let has_alpha = matches!(
+            self.input_color,
+            ColorType::Rgba | ColorType::GrayscaleAlpha
+        );
+        if self.input_depth == self.bit_depth
+            && self.input_color == self.color_type
+            && self.significant_bits.is_none()
+        {
+            output.copy_from_slice(input);
+
+            if let Some(lut) = &self.palette_lut {
+                remap_palette_indices(output, self.bit_depth, width, lut);
+            }
+
+            if self.premultiplied_alpha {
+                unpremultiply_alpha(output, in_samples, self.bit_depth);
+            }
+            if let Some(lut) = &self.linear_lut {
+                encode_linear_samples(output, in_samples, has_alpha, self.bit_depth, lut);
+            }
+            return Ok(());
+        }
+
+        let line = if self.premultiplied_alpha
+            || self.linear_lut.is_some()
+            || self.significant_bits.is_some()
+        {
+            self.scratch.clear();
+            self.scratch.extend_from_slice(input);
+            if let Some(bits) = self.significant_bits {
+                scale_significant_bits(&mut self.scratch, bits);
+            }
+            if self.premultiplied_alpha {
+                unpremultiply_alpha(&mut self.scratch, in_samples, self.input_depth);
+            }
+            if let Some(lut) = &self.linear_lut {
+                encode_linear_samples(
+                    &mut self.scratch,
+                    in_samples,
+                    has_alpha,
+                    self.input_depth,
+                    lut,
+                );
+            }
+            &self.scratch[..]
+        } else {
+            input
+        };
+
+        let in_bytes = (self.input_depth as usize + 7) / 8;
+        let out_bytes = (self.bit_depth as usize + 7) / 8;
+        let out_samples = self.color_type.samples();
+
+        if self.strip_alpha == StripAlpha::RequireOpaque && in_samples > out_samples {
+            let opaque = line
+                .chunks_exact(in_bytes * in_samples)
+                .all(|pixel| pixel[in_bytes * out_samples..].iter().all(|&b| b == 0xff));
+            if !opaque {
+                return Err(EncodingError::Format(
+                    FormatErrorKind::NonOpaqueAlpha.into(),
+                ));
+            }
+        }
+
+        // The dither only applies to the color samples, the alpha sample comes last.
+        let color_samples = match self.color_type {
+            ColorType::Rgba | ColorType::GrayscaleAlpha => out_samples - 1,
+            _ => out_samples,
+        };
+        let pixels = line
+            .chunks_exact(in_bytes * in_samples)
+            .zip(output.chunks_exact_mut(out_bytes * out_samples));
+        for (x, (pixel, out_pixel)) in pixels.enumerate() {
+            let samples = pixel
+                .chunks_exact(in_bytes)
+                .zip(out_pixel.chunks_exact_mut(out_bytes));
+            for (i, (sample, out)) in samples.enumerate() {
+                if in_bytes == out_bytes {
+                    out.copy_from_slice(sample);
+                    continue;
+                }
+
+                let value = u16::from_be_bytes([sample[0], sample[1]]);
+                out[0] = match self.depth_reduction {
+                    DepthReduction::Dither if i < color_samples => {
+                        reduce_depth(value, dither_offset(x, row))
                     }
-                },
-                chunk @ Wrapper::Chunk(_) => self.writer = chunk,
-                Wrapper::Unrecoverable => unreachable!(),
-                Wrapper::None => unreachable!(),
+                    DepthReduction::Round | DepthReduction::Dither => reduce_depth(value, 16),
+                };
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Scales 16-bit samples of which only the low `bits` are significant to the full 16-bit range.
+fn scale_significant_bits(samples: &mut [u8], bits: u8) {
+    let max = (1u32 << bits) - 1;
+    for sample in samples.chunks_exact_mut(2) {
+        let value = u32::from(u16::from_be_bytes([sample[0], sample[1]])).min(max);
+        let scaled = (value * 0xffff + max / 2) / max;
+        sample.copy_from_slice(&(scaled as u16).to_be_bytes());
+    }
+}
+
+/// Scales a 16-bit sample to 8 bits.
+///
+/// The `offset` is added to the exact result, in units of 1/32, before truncating. With an
+/// offset of `16` this rounds to nearest.
+fn reduce_depth(value: u16, offset: u8) -> u8 {
+    let scaled = u64::from(value) * 0xff * 32 + u64::from(offset) * 0xffff;
+    (scaled / (0xffff * 32)).min(0xff) as u8
+}
+
+/// The ordered dither offset of a pixel, from a 4x4 Bayer matrix scaled to `1..32`.
+fn dither_offset(x: usize, y: usize) -> u8 {
+    const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+    2 * BAYER[y % 4][x % 4] + 1
+}
+
+/// Builds the table that applies the transfer function to linear samples of 8 or 16 bits.
+fn linear_input_lut(transfer: LinearInput, bit_depth: BitDepth) -> Vec<u16> {
+    let max = if bit_depth == BitDepth::Sixteen {
+        u16::MAX
+    } else {
+        u16::from(u8::MAX)
+    };
+    (0..=max)
+        .map(|sample| {
+            let linear = f64::from(sample) / f64::from(max);
+            let encoded = match transfer {
+                LinearInput::Srgb(_) => crate::srgb::oetf_f64(linear),
+                LinearInput::Gamma(gamma) => linear.powf(f64::from(gamma.into_value())),
             };
+            (encoded.max(0.0).min(1.0) * f64::from(max)).round() as u16
+        })
+        .collect()
+}
+
+/// Applies the table of [`linear_input_lut`] to the color samples of a line.
+fn encode_linear_samples(
+    line: &mut [u8],
+    samples: usize,
+    has_alpha: bool,
+    bit_depth: BitDepth,
+    lut: &[u16],
+) {
+    let colors = if has_alpha { samples - 1 } else { samples };
+    match bit_depth {
+        BitDepth::Sixteen => {
+            for pixel in line.chunks_exact_mut(2 * samples) {
+                for sample in pixel[..2 * colors].chunks_exact_mut(2) {
+                    let value = lut[usize::from(u16::from_be_bytes([sample[0], sample[1]]))];
+                    sample.copy_from_slice(&value.to_be_bytes());
+                }
+            }
+        }
+        _ => {
+            for pixel in line.chunks_exact_mut(samples) {
+                for sample in &mut pixel[..colors] {
+                    *sample = lut[usize::from(*sample)] as u8;
+                }
+            }
+        }
+    }
+}
+
+/// Converts premultiplied color samples to straight alpha, in place.
+///
+/// The alpha sample is expected to be the last of `samples` in each pixel.
+fn unpremultiply_alpha(line: &mut [u8], samples: usize, bit_depth: BitDepth) {
+    match bit_depth {
+        BitDepth::Eight => {
+            for pixel in line.chunks_exact_mut(samples) {
+                let (color, alpha) = pixel.split_at_mut(samples - 1);
+                let alpha = u32::from(alpha[0]);
+                for sample in color {
+                    *sample = match alpha {
+                        0 => 0,
+                        _ => ((u32::from(*sample) * 0xff + alpha / 2) / alpha).min(0xff) as u8,
+                    };
+                }
+            }
+        }
+        BitDepth::Sixteen => {
+            for pixel in line.chunks_exact_mut(2 * samples) {
+                let (color, alpha) = pixel.split_at_mut(2 * (samples - 1));
+                let alpha = u64::from(u16::from_be_bytes([alpha[0], alpha[1]]));
+                for sample in color.chunks_exact_mut(2) {
+                    let value = u64::from(u16::from_be_bytes([sample[0], sample[1]]));
+                    let value = match alpha {
+                        0 => 0,
+                        _ => ((value * 0xffff + alpha / 2) / alpha).min(0xffff) as u16,
+                    };
+                    sample.copy_from_slice(&value.to_be_bytes());
+                }
+            }
+        }
+        // Channels with alpha are only allowed at these depths.
+        _ => {}
+    }
+}
+
+/// Finds the fraction of seconds closest to `delay` that fits into the fields of `fcTL`.
+///
+/// This walks the continued fraction expansion of the delay until a convergent no longer fits,
+/// and then picks the better of the last convergent and the largest semiconvergent that fits.
+fn rational_delay(delay: Duration) -> (u16, u16) {
+    const MAX: u128 = u16::MAX as u128;
+    let (n, d) = (delay.as_nanos(), 1_000_000_000u128);
+    if n >= MAX * d {
+        return (u16::MAX, 1);
+    }
+
+    let (mut p0, mut q0, mut p1, mut q1) = (0u128, 1u128, 1u128, 0u128);
+    let (mut a, mut b) = (n, d);
+    loop {
+        let t = a / b;
+        let (p2, q2) = (p0 + t * p1, q0 + t * q1);
+        if p2 > MAX || q2 > MAX {
+            let (p, q) = match (MAX - p0).checked_div(p1) {
+                Some(k) => {
+                    let k = k.min((MAX - q0).checked_div(q1).unwrap_or(MAX));
+                    (p0 + k * p1, q0 + k * q1)
+                }
+                // The delay is shorter than any fraction with a numerator of 1 that fits, so the
+                // only candidates are 0 and the shortest possible delay.
+                None => (1, MAX),
+            };
+            // Compare the errors |p/q - n/d| of both candidates.
+            let error = |p: u128, q: u128| (p * d).max(n * q) - (p * d).min(n * q);
+            if q1 == 0 || error(p, q) * q1 < error(p1, q1) * q {
+                return (p as u16, q as u16);
+            }
+            return (p1 as u16, q1 as u16);
+        }
+
+        p0 = p1;
+        q0 = q1;
+        p1 = p2;
+        q1 = q2;
+        if a % b == 0 {
+            return (p1 as u16, q1 as u16);
+        }
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+}
+
+/// A rectangle of pixels on the canvas of an animation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Region {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl Region {
+    /// The smallest region containing both regions.
+    fn union(self, other: Region) -> Region {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        Region {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        }
+    }
+
+    /// Widens the region to whole bytes of the scanlines, for pixels smaller than a byte.
+    fn aligned(self, bits_per_pixel: usize, image_width: u32) -> Region {
+        let unit = (8 / bits_per_pixel).max(1) as u32;
+        let x = self.x / unit * unit;
+        let right = ((self.x + self.width + unit - 1) / unit * unit).min(image_width);
+        Region {
+            x,
+            width: right - x,
+            ..self
+        }
+    }
+
+    fn contains(self, x: u32, y: u32) -> bool {
+        (self.x..self.x + self.width).contains(&x) && (self.y..self.y + self.height).contains(&y)
+    }
+}
+
+/// The smallest region containing both regions, if any.
+fn union_of(a: Option<Region>, b: Option<Region>) -> Option<Region> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.union(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// The state kept between frames for [`Encoder::set_frame_diffing`].
+#[derive(Clone)]
+struct FrameDiff {
+    /// The last complete canvas, in the layout of the input.
+    canvas: Vec<u8>,
+    /// A region that a decoder shows as transparent black, as it was disposed.
+    cleared: Option<Region>,
+    /// A region in which the canvas a decoder shows is not known, as frames of other content
+    /// were written.
+    unknown: Option<Region>,
+    /// The number of frames written when this state was current.
+    frames: u32,
+}
+
+impl FrameDiff {
+    /// The canvas a decoder shows, and the region of it that is not known.
+    ///
+    /// Cleared pixels are the input pixels of zeros if the input is `transparent`, otherwise
+    /// they are not known.
+    fn shown(
+        &self,
+        transparent: bool,
+        line_len: usize,
+        pixel_len: usize,
+    ) -> (borrow::Cow<'_, [u8]>, Option<Region>) {
+        match self.cleared {
+            Some(cleared) if transparent => {
+                let mut canvas = self.canvas.clone();
+                let start = cleared.x as usize * pixel_len;
+                let len = cleared.width as usize * pixel_len;
+                let lines = canvas
+                    .chunks_mut(line_len)
+                    .skip(cleared.y as usize)
+                    .take(cleared.height as usize);
+                for line in lines {
+                    line[start..][..len].iter_mut().for_each(|b| *b = 0);
+                }
+                (borrow::Cow::Owned(canvas), self.unknown)
+            }
+            cleared => (
+                borrow::Cow::Borrowed(&self.canvas),
+                union_of(cleared, self.unknown),
+            ),
+        }
+    }
+}
+
+/// A diffed frame whose dispose operation is chosen once the next frame is known.
+struct HeldFrame {
+    /// The frame control, except for the dispose operation.
+    frame: FrameOptions,
+    region: Region,
+    /// The complete canvas of the frame, in the layout of the input.
+    canvas: Vec<u8>,
+    zlib_encoded: Vec<u8>,
+}
+
+/// Makes the pixels of `cropped` that a decoder already shows transparent, if blending the
+/// cropped `region` over the `shown` canvas then results in the same image.
+///
+/// Pixels in `unknown` must be opaque, as they replace whatever is shown. Returns `false` and
+/// leaves `cropped` unchanged when this is not possible, or when less than half of the pixels
+/// would become transparent.
+fn blend_over(
+    cropped: &mut [u8],
+    shown: &[u8],
+    unknown: Option<Region>,
+    region: Region,
+    line_len: usize,
+    pixel_len: usize,
+    sample_len: usize,
+) -> bool {
+    let is_unknown = |x: u32, y: u32| unknown.map_or(false, |unknown| unknown.contains(x, y));
+    let below = |x: u32, y: u32| {
+        let start = y as usize * line_len + x as usize * pixel_len;
+        &shown[start..][..pixel_len]
+    };
+    let pixels = || {
+        (0..region.height).flat_map(move |row| {
+            (0..region.width).map(move |column| (region.x + column, region.y + row))
+        })
+    };
+
+    let mut unchanged = 0;
+    for ((x, y), pixel) in pixels().zip(cropped.chunks(pixel_len)) {
+        let alpha = &pixel[pixel_len - sample_len..];
+        let opaque = alpha.iter().all(|&b| b == 0xff);
+        if is_unknown(x, y) {
+            if !opaque {
+                return false;
+            }
+        } else if pixel == below(x, y) {
+            unchanged += 1;
+        } else {
+            // Blending an opaque pixel, or a visible one onto a transparent one, replaces it.
+            let visible = alpha.iter().any(|&b| b != 0);
+            let cleared = below(x, y)[pixel_len - sample_len..]
+                .iter()
+                .all(|&b| b == 0);
+            let replaced = opaque || (visible && cleared);
+            if !replaced {
+                return false;
+            }
+        }
+    }
+
+    if unchanged * 2 <= region.width as usize * region.height as usize {
+        return false;
+    }
+
+    for ((x, y), pixel) in pixels().zip(cropped.chunks_mut(pixel_len)) {
+        if !is_unknown(x, y) && pixel == below(x, y) {
+            pixel.iter_mut().for_each(|b| *b = 0);
+        }
+    }
+
+    true
+}
+
+/// The region of `current` to write over the `shown` canvas, covering the changed pixels and
+/// those that are not known, or a single pixel if there are none.
+fn diff_region(
+    shown: &[u8],
+    unknown: Option<Region>,
+    current: &[u8],
+    line_len: usize,
+    bits_per_pixel: usize,
+    full: Region,
+) -> Region {
+    let changed = changed_region(shown, current, line_len, bits_per_pixel, full);
+    union_of(changed, unknown).unwrap_or(Region {
+        width: 1,
+        height: 1,
+        ..full
+    })
+}
+
+/// Finds the bounding box of the pixels that differ between two images of the size of `full`.
+///
+/// For pixels smaller than a byte the horizontal bounds are widened to whole bytes, so that the
+/// region can be cut out of the scanlines without shifting.
+fn changed_region(
+    previous: &[u8],
+    current: &[u8],
+    line_len: usize,
+    bits_per_pixel: usize,
+    full: Region,
+) -> Option<Region> {
+    let unit_bytes = (bits_per_pixel / 8).max(1);
+    let unit_pixels = (8 / bits_per_pixel).max(1);
+
+    let mut rows: Option<(usize, usize)> = None;
+    let mut units: Option<(usize, usize)> = None;
+    let lines = previous.chunks(line_len).zip(current.chunks(line_len));
+    for (y, (prev, curr)) in lines.enumerate() {
+        let first = match prev.iter().zip(curr).position(|(a, b)| a != b) {
+            Some(first) => first / unit_bytes,
+            None => continue,
+        };
+        let last = prev.iter().zip(curr).rposition(|(a, b)| a != b).unwrap() / unit_bytes;
+
+        rows = Some(rows.map_or((y, y), |(top, _)| (top, y)));
+        units = Some(units.map_or((first, last), |(left, right)| {
+            (left.min(first), right.max(last))
+        }));
+    }
+
+    let ((top, bottom), (left, right)) = (rows?, units?);
+    let x = (left * unit_pixels) as u32;
+    let right = (((right + 1) * unit_pixels) as u32).min(full.width);
+    Some(Region {
+        x,
+        y: top as u32,
+        width: right - x,
+        height: (bottom - top + 1) as u32,
+    })
+}
+
+/// Computes the reordered `PLTE` and `tRNS` contents, and the table mapping old to new indices.
+///
+/// Only the entries addressable with `bit_depth` take part in the reordering, any further ones
+/// keep their position at the end of the palette.
+fn reorder_palette(
+    order: PaletteOrder,
+    bit_depth: BitDepth,
+    palette: &[u8],
+    trns: Option<&[u8]>,
+    counts: Option<&[u64; 256]>,
+) -> (Vec<u8>, Option<Vec<u8>>, Vec<u8>) {
+    let entries = (palette.len() / 3).min(256);
+    let addressable = entries.min(1 << bit_depth as usize);
+    let alpha = |idx: usize| trns.and_then(|t| t.get(idx)).copied().unwrap_or(0xff);
+
+    let mut order_of: Vec<usize> = (0..entries).collect();
+    match order {
+        PaletteOrder::Unchanged => {}
+        PaletteOrder::Luminance => order_of[..addressable].sort_by_key(|&idx| {
+            let rgb = &palette[3 * idx..][..3];
+            let luma = 299 * u32::from(rgb[0]) + 587 * u32::from(rgb[1]) + 114 * u32::from(rgb[2]);
+            (alpha(idx), luma)
+        }),
+        PaletteOrder::Frequency => {
+            let counts = counts.expect("frequency order requires usage counts");
+            order_of[..addressable].sort_by_key(|&idx| std::cmp::Reverse(counts[idx]));
+        }
+    }
+
+    let mut lut: Vec<u8> = (0..=255).collect();
+    let mut new_palette = Vec::with_capacity(palette.len());
+    for (new_idx, &old_idx) in order_of.iter().enumerate() {
+        lut[old_idx] = new_idx as u8;
+        new_palette.extend_from_slice(&palette[3 * old_idx..][..3]);
+    }
+    // Keep any trailing bytes which do not form a full entry, they are not ours to fix.
+    new_palette.extend_from_slice(&palette[3 * entries..]);
+
+    let new_trns = trns.and_then(|_| {
+        let mut new_trns: Vec<u8> = order_of.iter().map(|&idx| alpha(idx)).collect();
+        // Entries past the end of the chunk are implicitly opaque.
+        while new_trns.last() == Some(&0xff) {
+            new_trns.pop();
+        }
+        Some(new_trns).filter(|t| !t.is_empty())
+    });
+
+    (new_palette, new_trns, lut)
+}
+
+/// Iterates over the palette indices of the first `width` pixels in a packed scanline.
+fn palette_indices(
+    line: &[u8],
+    bit_depth: BitDepth,
+    width: usize,
+) -> impl Iterator<Item = u8> + '_ {
+    let bits = bit_depth as usize;
+    let mask = ((1u16 << bits) - 1) as u8;
+    (0..width).map(move |px| {
+        let bit = px * bits;
+        let shift = 8 - bits - bit % 8;
+        (line[bit / 8] >> shift) & mask
+    })
+}
+
+/// Replaces the palette indices of the first `width` pixels in a packed scanline through `lut`.
+///
+/// The padding bits at the end of the line are left untouched.
+fn remap_palette_indices(line: &mut [u8], bit_depth: BitDepth, width: usize, lut: &[u8]) {
+    let bits = bit_depth as usize;
+    if bits == 8 {
+        for idx in &mut line[..width] {
+            *idx = lut[usize::from(*idx)];
+        }
+        return;
+    }
+
+    let mask = ((1u16 << bits) - 1) as u8;
+    for px in 0..width {
+        let bit = px * bits;
+        let shift = 8 - bits - bit % 8;
+        let byte = &mut line[bit / 8];
+        let idx = lut[usize::from((*byte >> shift) & mask)] & mask;
+        *byte = (*byte & !(mask << shift)) | (idx << shift);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Decoder;
+
+    use rand::{thread_rng, Rng};
+    use std::fs::File;
+    use std::io::{Cursor, Write};
+    use std::{cmp, io};
+
+    #[test]
+    fn roundtrip() {
+        // More loops = more random testing, but also more test wait time
+        for _ in 0..10 {
+            for path in glob::glob("tests/pngsuite/*.png")
+                .unwrap()
+                .map(|r| r.unwrap())
+            {
+                if path.file_name().unwrap().to_str().unwrap().starts_with('x') {
+                    // x* files are expected to fail to decode
+                    continue;
+                }
+                eprintln!("{}", path.display());
+                // Decode image
+                let decoder = Decoder::new(File::open(path).unwrap());
+                let mut reader = decoder.read_info().unwrap();
+                let mut buf = vec![0; reader.output_buffer_size()];
+                let info = reader.next_frame(&mut buf).unwrap();
+                // Encode decoded image
+                let mut out = Vec::new();
+                {
+                    let mut wrapper = RandomChunkWriter {
+                        rng: thread_rng(),
+                        w: &mut out,
+                    };
+
+                    let mut encoder = Encoder::new(&mut wrapper, info.width, info.height);
+                    encoder.set_color(info.color_type);
+                    encoder.set_depth(info.bit_depth);
+                    if let Some(palette) = &reader.info().palette {
+                        encoder.set_palette(palette.clone());
+                    }
+                    let mut encoder = encoder.write_header().unwrap();
+                    encoder.write_image_data(&buf).unwrap();
+                }
+                // Decode encoded decoded image
+                let decoder = Decoder::new(&*out);
+                let mut reader = decoder.read_info().unwrap();
+                let mut buf2 = vec![0; reader.output_buffer_size()];
+                reader.next_frame(&mut buf2).unwrap();
+                // check if the encoded image is ok:
+                assert_eq!(buf, buf2);
+            }
+        }
+    }
+
+    #[test]
+    fn roundtrip_stream() {
+        // More loops = more random testing, but also more test wait time
+        for _ in 0..10 {
+            for path in glob::glob("tests/pngsuite/*.png")
+                .unwrap()
+                .map(|r| r.unwrap())
+            {
+                if path.file_name().unwrap().to_str().unwrap().starts_with('x') {
+                    // x* files are expected to fail to decode
+                    continue;
+                }
+                // Decode image
+                let decoder = Decoder::new(File::open(path).unwrap());
+                let mut reader = decoder.read_info().unwrap();
+                let mut buf = vec![0; reader.output_buffer_size()];
+                let info = reader.next_frame(&mut buf).unwrap();
+                // Encode decoded image
+                let mut out = Vec::new();
+                {
+                    let mut wrapper = RandomChunkWriter {
+                        rng: thread_rng(),
+                        w: &mut out,
+                    };
+
+                    let mut encoder = Encoder::new(&mut wrapper, info.width, info.height);
+                    encoder.set_color(info.color_type);
+                    encoder.set_depth(info.bit_depth);
+                    if let Some(palette) = &reader.info().palette {
+                        encoder.set_palette(palette.clone());
+                    }
+                    let mut encoder = encoder.write_header().unwrap();
+                    let mut stream_writer = encoder.stream_writer().unwrap();
+
+                    let mut outer_wrapper = RandomChunkWriter {
+                        rng: thread_rng(),
+                        w: &mut stream_writer,
+                    };
+
+                    outer_wrapper.write_all(&buf).unwrap();
+                }
+                // Decode encoded decoded image
+                let decoder = Decoder::new(&*out);
+                let mut reader = decoder.read_info().unwrap();
+                let mut buf2 = vec![0; reader.output_buffer_size()];
+                reader.next_frame(&mut buf2).unwrap();
+                // check if the encoded image is ok:
+                assert_eq!(buf, buf2);
+            }
+        }
+    }
+
+    #[test]
+    fn image_palette() -> Result<()> {
+        for &bit_depth in &[1u8, 2, 4, 8] {
+            // Do a reference decoding, choose a fitting palette image from pngsuite
+            let path = format!("tests/pngsuite/basn3p0{}.png", bit_depth);
+            let decoder = Decoder::new(File::open(&path).unwrap());
+            let mut reader = decoder.read_info().unwrap();
+
+            let mut decoded_pixels = vec![0; reader.output_buffer_size()];
+            let info = reader.info();
+            assert_eq!(
+                info.width as usize * info.height as usize * usize::from(bit_depth),
+                decoded_pixels.len() * 8
+            );
+            let info = reader.next_frame(&mut decoded_pixels).unwrap();
+            let indexed_data = decoded_pixels;
+
+            let palette = reader.info().palette.as_ref().unwrap();
+            let mut out = Vec::new();
+            {
+                let mut encoder = Encoder::new(&mut out, info.width, info.height);
+                encoder.set_depth(BitDepth::from_u8(bit_depth).unwrap());
+                encoder.set_color(ColorType::Indexed);
+                encoder.set_palette(palette.as_ref());
+
+                let mut writer = encoder.write_header().unwrap();
+                writer.write_image_data(&indexed_data).unwrap();
+            }
+
+            // Decode re-encoded image
+            let decoder = Decoder::new(&*out);
+            let mut reader = decoder.read_info().unwrap();
+            let mut redecoded = vec![0; reader.output_buffer_size()];
+            reader.next_frame(&mut redecoded).unwrap();
+            // check if the encoded image is ok:
+            assert_eq!(indexed_data, redecoded);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn image_palette_reordered() -> Result<()> {
+        fn expanded(png: &[u8]) -> Vec<u8> {
+            let mut decoder = Decoder::new(png);
+            decoder.set_transformations(crate::Transformations::EXPAND);
+            let mut reader = decoder.read_info().unwrap();
+            let mut buf = vec![0; reader.output_buffer_size()];
+            reader.next_frame(&mut buf).unwrap();
+            buf
+        }
+
+        for &bit_depth in &[1u8, 2, 4, 8] {
+            let path = format!("tests/pngsuite/basn3p0{}.png", bit_depth);
+            let original = std::fs::read(&path).unwrap();
+            let mut reader = Decoder::new(&*original).read_info().unwrap();
+            let mut indexed_data = vec![0; reader.output_buffer_size()];
+            let info = reader.next_frame(&mut indexed_data).unwrap();
+            let palette = reader.info().palette.clone().unwrap();
+            // A made-up transparency so that the chunk has to be reordered as well.
+            let trns: Vec<u8> = (0..palette.len() / 3).map(|i| (i * 37) as u8).collect();
+
+            let mut reference = Vec::new();
+            {
+                let mut encoder = Encoder::new(&mut reference, info.width, info.height);
+                encoder.set_depth(info.bit_depth);
+                encoder.set_color(ColorType::Indexed);
+                encoder.set_palette(palette.as_ref());
+                encoder.set_trns(trns.as_slice());
+                let mut writer = encoder.write_header()?;
+                writer.write_image_data(&indexed_data)?;
+            }
+            let reference = expanded(&reference);
+
+            for &order in &[PaletteOrder::Luminance, PaletteOrder::Frequency] {
+                for &streaming in &[false, true] {
+                    let mut out = Vec::new();
+                    {
+                        let mut encoder = Encoder::new(&mut out, info.width, info.height);
+                        encoder.set_depth(info.bit_depth);
+                        encoder.set_color(ColorType::Indexed);
+                        encoder.set_palette(palette.as_ref());
+                        encoder.set_trns(trns.as_slice());
+                        encoder.set_palette_order(order);
+                        let mut writer = encoder.write_header()?;
+                        if streaming {
+                            writer.stream_writer()?.write_all(&indexed_data)?;
+                        } else {
+                            writer.write_image_data(&indexed_data)?;
+                        }
+                        writer.finish()?;
+                    }
+
+                    assert_eq!(
+                        reference,
+                        expanded(&out),
+                        "{:?} order changed the colors of {}",
+                        order,
+                        path
+                    );
+
+                    if order == PaletteOrder::Luminance {
+                        let reader = Decoder::new(&*out).read_info().unwrap();
+                        let alpha = reader.info().trns.as_ref().unwrap();
+                        assert!(alpha.windows(2).all(|w| w[0] <= w[1]));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn linear_input() -> Result<()> {
+        fn encode(
+            transfer: LinearInput,
+            color: ColorType,
+            depth: BitDepth,
+            data: &[u8],
+        ) -> Vec<u8> {
+            let mut out = Vec::new();
+            {
+                let mut encoder = Encoder::new(&mut out, 2, 1);
+                encoder.set_color(color);
+                encoder.set_depth(depth);
+                encoder.set_source_gamma(ScaledFloat::new(1.0));
+                encoder.set_linear_input(transfer);
+                let mut writer = encoder.write_header().unwrap();
+                writer.write_image_data(data).unwrap();
+            }
+            out
+        }
+        fn decode(png: &[u8]) -> (Option<ScaledFloat>, Option<SrgbRenderingIntent>, Vec<u8>) {
+            let mut reader = Decoder::new(png).read_info().unwrap();
+            let mut buf = vec![0; reader.output_buffer_size()];
+            reader.next_frame(&mut buf).unwrap();
+            let info = reader.info();
+            let gamma = info.source_gamma.filter(|_| info.srgb.is_none());
+            (gamma, info.srgb, buf)
+        }
+
+        let srgb = LinearInput::Srgb(SrgbRenderingIntent::RelativeColorimetric);
+        let png = encode(
+            srgb,
+            ColorType::GrayscaleAlpha,
+            BitDepth::Eight,
+            &[128, 128, 0, 255],
+        );
+        let expected = (
+            None,
+            Some(SrgbRenderingIntent::RelativeColorimetric),
+            vec![188, 128, 0, 255],
+        );
+        assert_eq!(decode(&png), expected);
+
+        let gamma = ScaledFloat::new(0.5);
+        let png = encode(
+            LinearInput::Gamma(gamma),
+            ColorType::Rgb,
+            BitDepth::Sixteen,
+            &[0x40, 0, 0xff, 0xff, 0, 0, 0x10, 0, 0, 0, 0, 0],
+        );
+        let expected = (
+            Some(gamma),
+            None,
+            vec![0x80, 0, 0xff, 0xff, 0, 0, 0x40, 0, 0, 0, 0, 0],
+        );
+        assert_eq!(decode(&png), expected);
+
+        // Linear input of 16 bits is encoded before its depth is reduced.
+        let mut out = Vec::new();
+        {
+            let mut encoder = Encoder::new(&mut out, 1, 1);
+            encoder.set_input_depth(BitDepth::Sixteen);
+            encoder.set_linear_input(srgb);
+            encoder.write_header()?.write_image_data(&[0x00, 0x80])?;
+        }
+        assert_eq!(decode(&out).2, [6]);
+
+        let mut encoder = Encoder::new(Vec::new(), 1, 1);
+        encoder.set_color(ColorType::Indexed);
+        encoder.set_palette(vec![0; 3]);
+        encoder.set_linear_input(srgb);
+        assert!(encoder.write_header().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn premultiplied_alpha() -> Result<()> {
+        fn roundtrip(color: ColorType, depth: BitDepth, data: &[u8], streaming: bool) -> Vec<u8> {
+            let mut out = Vec::new();
+            {
+                let mut encoder = Encoder::new(&mut out, 4, 1);
+                encoder.set_color(color);
+                encoder.set_depth(depth);
+                encoder.set_premultiplied_alpha(true);
+                let mut writer = encoder.write_header().unwrap();
+                if streaming {
+                    writer.stream_writer().unwrap().write_all(data).unwrap();
+                } else {
+                    writer.write_image_data(data).unwrap();
+                }
+            }
+            let mut reader = Decoder::new(&*out).read_info().unwrap();
+            let mut buf = vec![0; reader.output_buffer_size()];
+            reader.next_frame(&mut buf).unwrap();
+            buf
+        }
+
+        for &streaming in &[false, true] {
+            let rgba = [
+                10, 20, 30, 255, //
+                64, 32, 0, 128, //
+                9, 9, 9, 0, //
+                200, 1, 100, 100,
+            ];
+            let straight = [
+                10, 20, 30, 255, //
+                128, 64, 0, 128, //
+                0, 0, 0, 0, //
+                255, 3, 255, 100,
+            ];
+            let result = roundtrip(ColorType::Rgba, BitDepth::Eight, &rgba, streaming);
+            assert_eq!(result, straight);
+
+            let gray_alpha16 = [
+                0x80, 0x00, 0xff, 0xff, //
+                0x40, 0x00, 0x80, 0x00, //
+                0x12, 0x34, 0x00, 0x00, //
+                0x00, 0x01, 0x00, 0x02,
+            ];
+            let straight16 = [
+                0x80, 0x00, 0xff, 0xff, //
+                0x80, 0x00, 0x80, 0x00, //
+                0x00, 0x00, 0x00, 0x00, //
+                0x80, 0x00, 0x00, 0x02,
+            ];
+            let result = roundtrip(
+                ColorType::GrayscaleAlpha,
+                BitDepth::Sixteen,
+                &gray_alpha16,
+                streaming,
+            );
+            assert_eq!(result, straight16);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn sixteen_bit_input() -> Result<()> {
+        fn encode(reduction: DepthReduction, data: &[u8], streaming: bool) -> Vec<u8> {
+            let mut out = Vec::new();
+            {
+                let mut encoder = Encoder::new(&mut out, 4, 4);
+                encoder.set_color(ColorType::GrayscaleAlpha);
+                encoder.set_depth(BitDepth::Eight);
+                encoder.set_input_depth(BitDepth::Sixteen);
+                encoder.set_depth_reduction(reduction);
+                let mut writer = encoder.write_header().unwrap();
+                if streaming {
+                    writer.stream_writer().unwrap().write_all(data).unwrap();
+                } else {
+                    writer.write_image_data(data).unwrap();
+                }
+            }
+            let mut reader = Decoder::new(&*out).read_info().unwrap();
+            assert_eq!(reader.info().bit_depth, BitDepth::Eight);
+            let mut buf = vec![0; reader.output_buffer_size()];
+            reader.next_frame(&mut buf).unwrap();
+            buf
+        }
+
+        let values: Vec<u16> = (0..32).map(|i| i * 2047 + 300).collect();
+        let data: Vec<u8> = values
+            .iter()
+            .flat_map(|v| v.to_be_bytes().to_vec())
+            .collect();
+
+        for &streaming in &[false, true] {
+            let rounded = encode(DepthReduction::Round, &data, streaming);
+            for (&value, &out) in values.iter().zip(&rounded) {
+                assert_eq!(u32::from(out), (u32::from(value) * 255 + 32767) / 65535);
+            }
+
+            let dithered = encode(DepthReduction::Dither, &data, streaming);
+            for (&value, &out) in values.iter().zip(&dithered) {
+                let exact = f64::from(value) * 255.0 / 65535.0;
+                assert!(f64::from(out) >= exact.floor() && f64::from(out) <= exact.ceil());
+            }
+            // The alpha samples are rounded.
+            let alpha = |samples: &[u8]| samples.iter().skip(1).step_by(2).copied().collect();
+            let rounded_alpha: Vec<u8> = alpha(&rounded);
+            assert_eq!(alpha(&dithered), rounded_alpha);
+        }
+
+        // A constant area is dithered to the exact value on average.
+        let value = 0x4321u16;
+        let data: Vec<u8> = std::iter::repeat(value.to_be_bytes().to_vec())
+            .take(32)
+            .flatten()
+            .collect();
+        let dithered = encode(DepthReduction::Dither, &data, false);
+        let gray = dithered.iter().step_by(2);
+        let mean = gray.map(|&v| f64::from(v)).sum::<f64>() / 16.0;
+        assert!((mean - f64::from(value) * 255.0 / 65535.0).abs() <= 1.0 / 16.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn significant_bits() -> Result<()> {
+        fn encode(depth: BitDepth, bits: u8, data: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+            let mut png = Vec::new();
+            {
+                let mut encoder = Encoder::new(&mut png, 2, 1);
+                encoder.set_color(ColorType::GrayscaleAlpha);
+                encoder.set_depth(depth);
+                encoder.set_input_depth(BitDepth::Sixteen);
+                encoder.set_significant_bits(bits);
+                encoder.write_header()?.write_image_data(data)?;
+            }
+            let index = chunk::ChunkIndex::new(&png).unwrap();
+            let sbit = index.find(chunk::sBIT).unwrap().data.to_vec();
+            let mut reader = Decoder::new(&png[..]).read_info().unwrap();
+            let mut buf = vec![0; reader.output_buffer_size()];
+            reader.next_frame(&mut buf).unwrap();
+            Ok((sbit, buf))
+        }
+
+        // Samples of 12 bits, the last one out of range.
+        let data = [0x00, 0x00, 0x0f, 0xff, 0x08, 0x00, 0xff, 0xff];
+        let (sbit, buf) = encode(BitDepth::Sixteen, 12, &data)?;
+        assert_eq!(sbit, [12, 12]);
+        assert_eq!(buf, [0x00, 0x00, 0xff, 0xff, 0x80, 0x08, 0xff, 0xff]);
+        let (sbit, buf) = encode(BitDepth::Eight, 12, &data)?;
+        assert_eq!(sbit, [8, 8]);
+        assert_eq!(buf, [0x00, 0xff, 0x80, 0xff]);
+        let (sbit, _) = encode(BitDepth::Eight, 5, &data)?;
+        assert_eq!(sbit, [5, 5]);
+
+        assert!(encode(BitDepth::Sixteen, 0, &data).is_err());
+        assert!(encode(BitDepth::Sixteen, 17, &data).is_err());
+        let mut encoder = Encoder::new(io::sink(), 1, 1);
+        encoder.set_significant_bits(4);
+        assert!(encoder.write_header().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn expect_error_on_unsupported_input_depth() -> Result<()> {
+        let combinations = [
+            (BitDepth::Sixteen, BitDepth::Eight, ColorType::Indexed),
+            (BitDepth::Eight, BitDepth::Sixteen, ColorType::Rgb),
+            (BitDepth::Sixteen, BitDepth::Four, ColorType::Grayscale),
+        ];
+
+        for &(input, output, color) in combinations.iter() {
+            let mut encoder = Encoder::new(io::sink(), 1, 1);
+            encoder.set_color(color);
+            encoder.set_depth(output);
+            encoder.set_input_depth(input);
+            assert!(encoder.write_header().is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn strip_alpha_input() -> Result<()> {
+        fn encode(
+            input: (ColorType, BitDepth),
+            output: (ColorType, BitDepth),
+            strip_alpha: StripAlpha,
+            data: &[u8],
+            streaming: bool,
+        ) -> Result<Vec<u8>> {
+            let mut out = Vec::new();
+            {
+                let mut encoder = Encoder::new(&mut out, 2, 1);
+                encoder.set_color(output.0);
+                encoder.set_depth(output.1);
+                encoder.set_input_color(input.0);
+                encoder.set_input_depth(input.1);
+                encoder.set_strip_alpha(strip_alpha);
+                let mut writer = encoder.write_header()?;
+                if streaming {
+                    writer.stream_writer()?.write_all(data)?;
+                } else {
+                    writer.write_image_data(data)?;
+                }
+            }
+            let mut reader = Decoder::new(&*out).read_info().unwrap();
+            assert_eq!(reader.info().color_type, output.0);
+            let mut buf = vec![0; reader.output_buffer_size()];
+            reader.next_frame(&mut buf).unwrap();
+            Ok(buf)
+        }
+
+        use BitDepth::{Eight, Sixteen};
+        use ColorType::{Grayscale, GrayscaleAlpha, Rgb, Rgba};
+
+        for &streaming in &[false, true] {
+            let rgba = [1, 2, 3, 255, 4, 5, 6, 7];
+            let rgb = encode(
+                (Rgba, Eight),
+                (Rgb, Eight),
+                StripAlpha::Discard,
+                &rgba,
+                streaming,
+            )?;
+            assert_eq!(rgb, [1, 2, 3, 4, 5, 6]);
+            assert!(encode(
+                (Rgba, Eight),
+                (Rgb, Eight),
+                StripAlpha::RequireOpaque,
+                &rgba,
+                streaming
+            )
+            .is_err());
+
+            let ga16 = [0x12, 0x34, 0xff, 0xff, 0xab, 0xcd, 0xff, 0xff];
+            let gray16 = encode(
+                (GrayscaleAlpha, Sixteen),
+                (Grayscale, Sixteen),
+                StripAlpha::RequireOpaque,
+                &ga16,
+                streaming,
+            )?;
+            assert_eq!(gray16, [0x12, 0x34, 0xab, 0xcd]);
+
+            let gray8 = encode(
+                (GrayscaleAlpha, Sixteen),
+                (Grayscale, Eight),
+                StripAlpha::Discard,
+                &ga16,
+                streaming,
+            )?;
+            assert_eq!(gray8, [0x12, 0xab]);
+        }
+
+        for &(input, output) in [(Rgb, Rgba), (Rgba, Grayscale), (GrayscaleAlpha, Rgb)].iter() {
+            let mut encoder = Encoder::new(io::sink(), 1, 1);
+            encoder.set_color(output);
+            encoder.set_input_color(input);
+            assert!(encoder.write_header().is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn color_key() -> Result<()> {
+        let mut out = Vec::new();
+        {
+            let mut encoder = Encoder::new(&mut out, 2, 1);
+            encoder.set_color(ColorType::Rgb);
+            encoder.set_color_key(&[1, 2, 3]);
+            let mut writer = encoder.write_header()?;
+            writer.write_image_data(&[1, 2, 3, 4, 5, 6])?;
+        }
+
+        let mut decoder = Decoder::new(&*out);
+        decoder.set_transformations(crate::Transformations::EXPAND);
+        let mut reader = decoder.read_info().unwrap();
+        // The decoder stores the samples of 8-bit images as single bytes.
+        assert_eq!(reader.info().trns.as_deref(), Some(&[1, 2, 3][..]));
+        let mut buf = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut buf).unwrap();
+        assert_eq!(buf, [1, 2, 3, 0, 4, 5, 6, 255]);
+
+        let invalid: [(ColorType, BitDepth, &[u16]); 4] = [
+            (ColorType::Grayscale, BitDepth::Four, &[16]),
+            (ColorType::Grayscale, BitDepth::Eight, &[1, 2]),
+            (ColorType::Rgb, BitDepth::Eight, &[1, 2, 256]),
+            (ColorType::Rgba, BitDepth::Eight, &[1, 2, 3]),
+        ];
+        for &(color, depth, key) in invalid.iter() {
+            let mut encoder = Encoder::new(io::sink(), 1, 1);
+            encoder.set_color(color);
+            encoder.set_depth(depth);
+            encoder.set_color_key(key);
+            assert!(encoder.write_header().is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn add_frames() -> Result<()> {
+        let mut out = Vec::new();
+        {
+            let mut encoder = Encoder::new(&mut out, 4, 4);
+            encoder.set_color(ColorType::Grayscale);
+            encoder.set_animated(3, 0)?;
+            let mut writer = encoder.write_header()?;
+
+            let partial = FrameOptions {
+                size: Some((2, 2)),
+                x_offset: 1,
+                y_offset: 1,
+                ..FrameOptions::default()
+            };
+            // The default image has to cover the whole image.
+            assert!(writer.add_frame(&[0; 4], partial).is_err());
+
+            writer.add_frame(&[1; 16], FrameOptions::default())?;
+            writer.add_frame(
+                &[2; 4],
+                FrameOptions {
+                    delay_num: 1,
+                    delay_den: 2,
+                    dispose_op: DisposeOp::Background,
+                    blend_op: BlendOp::Over,
+                    ..partial
+                },
+            )?;
+            let out_of_bounds = FrameOptions {
+                x_offset: 3,
+                ..partial
+            };
+            assert!(writer.add_frame(&[3; 4], out_of_bounds).is_err());
+            writer.add_frame(&[3; 16], FrameOptions::default())?;
+            assert!(writer.add_frame(&[4; 16], FrameOptions::default()).is_err());
+            writer.finish()?;
+        }
+
+        let mut reader = Decoder::new(&*out).read_info().unwrap();
+        assert_eq!(reader.info().animation_control().unwrap().num_frames, 3);
+        let expected = [(1, 4, 0), (2, 2, 1), (3, 4, 0)];
+        for (i, &(value, size, offset)) in expected.iter().enumerate() {
+            let mut buf = vec![0; reader.output_buffer_size()];
+            let info = reader.next_frame(&mut buf).unwrap();
+            let fctl = *reader.info().frame_control().unwrap();
+            assert_eq!(fctl.sequence_number, [0, 1, 3][i]);
+            assert_eq!((fctl.width, fctl.height), (size, size));
+            assert_eq!((fctl.x_offset, fctl.y_offset), (offset, offset));
+            assert!(buf[..info.buffer_size()].iter().all(|&b| b == value));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn frame_diffing() -> Result<()> {
+        let mut frames = [[0u8; 16]; 4];
+        frames[1][6] = 9;
+        frames[2] = frames[1];
+        frames[2][12] = 5;
+        frames[3] = frames[2];
+        let dispose = [
+            DisposeOp::None,
+            DisposeOp::Background,
+            DisposeOp::None,
+            DisposeOp::None,
+        ];
+
+        let mut out = Vec::new();
+        {
+            let mut encoder = Encoder::new(&mut out, 4, 4);
+            encoder.set_color(ColorType::Grayscale);
+            encoder.set_animated(4, 0)?;
+            encoder.set_frame_diffing(true);
+            let mut writer = encoder.write_header()?;
+            for (frame, &dispose_op) in frames.iter().zip(&dispose) {
+                let options = FrameOptions {
+                    dispose_op,
+                    blend_op: BlendOp::Over,
+                    ..FrameOptions::default()
+                };
+                writer.add_frame(frame, options)?;
+            }
+            writer.finish()?;
+        }
+
+        let mut reader = Decoder::new(&*out).read_info().unwrap();
+        // The region disposed by the second frame is redrawn by the third.
+        let expected = [(0, 0, 4, 4), (2, 1, 1, 1), (0, 1, 3, 3), (0, 0, 1, 1)];
+        for (frame, &(x, y, width, height)) in frames.iter().zip(&expected) {
+            let mut buf = vec![0; reader.output_buffer_size()];
+            let info = reader.next_frame(&mut buf).unwrap();
+            let fctl = *reader.info().frame_control().unwrap();
+            assert_eq!((fctl.x_offset, fctl.y_offset), (x, y));
+            assert_eq!((fctl.width, fctl.height), (width, height));
+            assert_eq!(fctl.blend_op, BlendOp::Source);
+            for (row, line) in buf[..info.buffer_size()].chunks(width as usize).enumerate() {
+                let start = (y as usize + row) * 4 + x as usize;
+                assert_eq!(line, &frame[start..][..width as usize]);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn bottom_up() -> Result<()> {
+        let top_down: Vec<u8> = (0..12).collect();
+        let bottom_up: Vec<u8> = top_down.chunks(3).rev().flatten().copied().collect();
+
+        let mut out = Vec::new();
+        {
+            let mut encoder = Encoder::new(&mut out, 3, 4);
+            encoder.set_color(ColorType::Grayscale);
+            encoder.set_bottom_up(true);
+            encoder.write_header()?.write_image_data(&bottom_up)?;
+        }
+        let mut reader = Decoder::new(&*out).read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut buf).unwrap();
+        assert_eq!(buf, top_down);
+
+        // Frame diffing finds the changed region in the flipped rows.
+        let mut changed = bottom_up.clone();
+        changed[1] = 0xff;
+        let mut out = Vec::new();
+        {
+            let mut encoder = Encoder::new(&mut out, 3, 4);
+            encoder.set_color(ColorType::Grayscale);
+            encoder.set_animated(2, 0)?;
+            encoder.set_frame_diffing(true);
+            encoder.set_bottom_up(true);
+            let mut writer = encoder.write_header()?;
+            writer.add_frame(&bottom_up, FrameOptions::default())?;
+            writer.add_frame(&changed, FrameOptions::default())?;
+            writer.finish()?;
+        }
+        let mut reader = Decoder::new(&*out).read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut buf).unwrap();
+        assert_eq!(buf, top_down);
+        reader.next_frame(&mut buf).unwrap();
+        let fctl = *reader.info().frame_control().unwrap();
+        assert_eq!(
+            (fctl.x_offset, fctl.y_offset, fctl.width, fctl.height),
+            (1, 3, 1, 1)
+        );
+        assert_eq!(buf[0], 0xff);
+
+        Ok(())
+    }
+
+    #[test]
+    fn automatic_frame_ops() -> Result<()> {
+        let mut frames = [[0xff, 0, 0, 0xff].repeat(9), Vec::new(), Vec::new()];
+        frames[1] = frames[0].clone();
+        frames[1][..4].copy_from_slice(&[0, 0xff, 0, 0xff]);
+        frames[1][32..].copy_from_slice(&[0, 0, 0xff, 0xff]);
+        frames[2] = frames[1].clone();
+        frames[2][19] = 0x80;
+
+        let mut out = Vec::new();
+        {
+            let mut encoder = Encoder::new(&mut out, 3, 3);
+            encoder.set_color(ColorType::Rgba);
+            encoder.set_animated(3, 0)?;
+            encoder.set_frame_diffing(true);
+            encoder.set_automatic_frame_ops(true);
+            let mut writer = encoder.write_header()?;
+            for frame in &frames {
+                let options = FrameOptions {
+                    dispose_op: DisposeOp::Background,
+                    ..FrameOptions::default()
+                };
+                writer.add_frame(frame, options)?;
+            }
+            writer.finish()?;
+        }
+
+        let mut reader = Decoder::new(&*out).read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let expected = [
+            (3, BlendOp::Source),
+            (3, BlendOp::Over),
+            (1, BlendOp::Source),
+        ];
+        for &(size, blend_op) in &expected {
+            let info = reader.next_frame(&mut buf).unwrap();
+            let fctl = *reader.info().frame_control().unwrap();
+            assert_eq!((fctl.width, fctl.height), (size, size));
+            assert_eq!(fctl.dispose_op, DisposeOp::None);
+            assert_eq!(fctl.blend_op, blend_op);
+            if blend_op == BlendOp::Over {
+                let cleared = buf[..info.buffer_size()].chunks(4).filter(|p| p == &[0; 4]);
+                assert_eq!(cleared.count(), 7);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Decodes the frames of an 8-bit animation and composites them like a viewer, returning the
+    /// frame control and the shown canvas of every frame.
+    fn composite_frames(png: &[u8]) -> Vec<(FrameControl, Vec<u8>)> {
+        let mut reader = Decoder::new(png).read_info().unwrap();
+        let width = reader.info().width as usize;
+        let samples = reader.info().color_type.samples();
+        let alpha = samples % 2 == 0;
+        let num_frames = reader.info().animation_control().unwrap().num_frames;
+        let mut canvas = vec![0; width * reader.info().height as usize * samples];
+        let mut buf = vec![0; reader.output_buffer_size()];
+
+        let mut shown = Vec::new();
+        for _ in 0..num_frames {
+            let info = reader.next_frame(&mut buf).unwrap();
+            let fctl = *reader.info().frame_control().unwrap();
+            let (x, y) = (fctl.x_offset as usize, fctl.y_offset as usize);
+            let line_len = fctl.width as usize * samples;
+            let previous = canvas.clone();
+            for (row, line) in buf[..info.buffer_size()].chunks(line_len).enumerate() {
+                let start = ((y + row) * width + x) * samples;
+                let pixels = canvas[start..][..line_len].chunks_mut(samples);
+                for (dst, src) in pixels.zip(line.chunks(samples)) {
+                    let (src_a, dst_a) = (u32::from(src[samples - 1]), u32::from(dst[samples - 1]));
+                    if fctl.blend_op == BlendOp::Source || !alpha || src_a == 0xff || dst_a == 0 {
+                        dst.copy_from_slice(src);
+                    } else if src_a != 0 {
+                        let out_a = src_a * 0xff + dst_a * (0xff - src_a);
+                        for (d, &s) in dst.iter_mut().zip(src).take(samples - 1) {
+                            let sum = u32::from(s) * src_a * 0xff
+                                + u32::from(*d) * dst_a * (0xff - src_a);
+                            *d = ((sum + out_a / 2) / out_a) as u8;
+                        }
+                        dst[samples - 1] = ((out_a + 0x7f) / 0xff) as u8;
+                    }
+                }
+            }
+            shown.push((fctl, canvas.clone()));
+
+            match fctl.dispose_op {
+                DisposeOp::None => {}
+                DisposeOp::Background => {
+                    let lines = canvas.chunks_mut(width * samples).skip(y);
+                    for line in lines.take(fctl.height as usize) {
+                        line[x * samples..][..line_len]
+                            .iter_mut()
+                            .for_each(|b| *b = 0);
+                    }
+                }
+                DisposeOp::Previous => canvas = previous,
+            }
+        }
+
+        shown
+    }
+
+    #[test]
+    fn automatic_dispose_ops() -> Result<()> {
+        let at = |x: usize, y: usize| (y * 4 + x) * 4;
+        let block = |x: usize, y: usize, rgba: [u8; 4]| {
+            let mut frame = vec![0; 64];
+            for &(x, y) in &[(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)] {
+                frame[at(x, y)..][..4].copy_from_slice(&rgba);
+            }
+            frame
+        };
+        let blue = block(2, 2, [0, 0, 0xff, 0xff]);
+        let overlay = [0x10, 0x20, 0x30, 0x80];
+        let mut last = blue.clone();
+        last[at(3, 0)..][..4].copy_from_slice(&[9, 9, 9, 0x80]);
+        let frames = [
+            block(0, 0, [0xff, 0, 0, 0xff]),
+            blue.clone(),
+            block(2, 2, [0, 0xff, 0, 0xff]),
+            blue.clone(),
+            overlay.repeat(4),
+            last,
+        ];
+
+        let mut out = Vec::new();
+        {
+            let mut encoder = Encoder::new(&mut out, 4, 4);
+            encoder.set_color(ColorType::Rgba);
+            encoder.set_animated(6, 0)?;
+            encoder.set_frame_diffing(true);
+            encoder.set_automatic_frame_ops(true);
+            let mut writer = encoder.write_header()?;
+            for (index, frame) in frames.iter().enumerate() {
+                // A translucent frame of its own size on top of the content of the diffed frames.
+                let size = if index == 4 { Some((2, 2)) } else { None };
+                let options = FrameOptions {
+                    size,
+                    ..FrameOptions::default()
+                };
+                writer.add_frame(frame, options)?;
+            }
+            writer.finish()?;
+        }
+
+        let mut expected = frames.to_vec();
+        expected[4] = blue;
+        for &(x, y) in &[(0, 0), (1, 0), (0, 1), (1, 1)] {
+            expected[4][at(x, y)..][..4].copy_from_slice(&overlay);
+        }
+        let dispose = [
+            DisposeOp::Background,
+            DisposeOp::None,
+            DisposeOp::Previous,
+            DisposeOp::None,
+            DisposeOp::None,
+            DisposeOp::None,
+        ];
+        let shown = composite_frames(&out);
+        assert_eq!(shown.len(), 6);
+        for ((fctl, canvas), (expected, &dispose_op)) in
+            shown.iter().zip(expected.iter().zip(&dispose))
+        {
+            assert_eq!(canvas, expected);
+            assert_eq!(fctl.dispose_op, dispose_op);
+        }
+        // The translucent pixels of the explicit frame are written again by the next frame.
+        assert_eq!((shown[5].0.width, shown[5].0.height), (4, 2));
+        assert_eq!(shown[5].0.blend_op, BlendOp::Source);
+
+        // Without alpha, disposed pixels are written again, which makes restoring cheaper.
+        let mut frames = [[0u8; 16]; 3];
+        for &i in &[0, 1, 4, 5] {
+            frames[0][i] = 200;
+        }
+        frames[1] = frames[0];
+        for &i in &[10, 11, 14, 15] {
+            frames[1][i] = 7;
+        }
+        frames[2] = frames[0];
+        frames[2][3] = 5;
+
+        let mut out = Vec::new();
+        {
+            let mut encoder = Encoder::new(&mut out, 4, 4);
+            encoder.set_color(ColorType::Grayscale);
+            encoder.set_animated(3, 0)?;
+            encoder.set_frame_diffing(true);
+            encoder.set_automatic_frame_ops(true);
+            let mut writer = encoder.write_header()?;
+            for frame in &frames {
+                writer.add_frame(frame, FrameOptions::default())?;
+            }
+            writer.finish()?;
+        }
+
+        let shown = composite_frames(&out);
+        let expected = [
+            ((0, 0, 4, 4), DisposeOp::None),
+            ((2, 2, 2, 2), DisposeOp::Previous),
+            ((3, 0, 1, 1), DisposeOp::None),
+        ];
+        for ((fctl, canvas), (frame, &(region, dispose_op))) in
+            shown.iter().zip(frames.iter().zip(&expected))
+        {
+            assert_eq!(&canvas[..], &frame[..]);
+            assert_eq!(
+                (fctl.x_offset, fctl.y_offset, fctl.width, fctl.height),
+                region
+            );
+            assert_eq!(fctl.dispose_op, dispose_op);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn loops() -> Result<()> {
+        let mut encoder = Encoder::new(io::sink(), 1, 1);
+        assert!(encoder.set_loops(Loops::Infinite).is_err());
+
+        let three = Loops::Count(std::num::NonZeroU32::new(3).unwrap());
+        for &loops in &[Loops::Infinite, three] {
+            let mut out = Vec::new();
+            let mut encoder = Encoder::new(&mut out, 1, 1);
+            encoder.set_animated(1, 5)?;
+            encoder.set_loops(loops)?;
+            encoder.write_header()?.write_image_data(&[0])?;
+
+            let decoder = Decoder::new(&*out).read_info().unwrap();
+            assert_eq!(decoder.info().animation_control().unwrap().loops(), loops);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn add_encoded_frames() -> Result<()> {
+        let encode = |color, data: &[u8]| -> Result<Vec<u8>> {
+            let mut png = Vec::new();
+            let mut encoder = Encoder::new(&mut png, 2, 2);
+            encoder.set_color(color);
+            encoder.write_header()?.write_image_data(data)?;
+            Ok(png)
+        };
+        let pngs = [
+            encode(ColorType::Grayscale, &[1; 4])?,
+            encode(ColorType::Grayscale, &[2; 4])?,
+            encode(ColorType::Grayscale, &[3; 4])?,
+        ];
+
+        let mut out = Vec::new();
+        {
+            let mut encoder = Encoder::new(&mut out, 2, 2);
+            encoder.set_color(ColorType::Grayscale);
+            encoder.set_animated(3, 0)?;
+            let mut writer = encoder.write_header()?;
+            let rgb = encode(ColorType::Rgb, &[0; 12])?;
+            assert!(writer
+                .add_encoded_frame(&rgb, FrameOptions::default())
+                .is_err());
+            assert!(writer
+                .add_encoded_frame(&pngs[0][..40], FrameOptions::default())
+                .is_err());
+            for png in &pngs {
+                writer.add_encoded_frame(png, FrameOptions::default())?;
+            }
+            writer.finish()?;
+        }
+
+        let mut reader = Decoder::new(&*out).read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        for (value, &sequence_number) in (1..=3).zip(&[0, 1, 3]) {
+            reader.next_frame(&mut buf).unwrap();
+            let fctl = reader.info().frame_control().unwrap();
+            assert_eq!(fctl.sequence_number, sequence_number);
+            assert!(buf.iter().all(|&b| b == value));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_frame_count() -> Result<()> {
+        let mut out = Cursor::new(b"prefix".to_vec());
+        out.set_position(6);
+        {
+            let mut encoder = Encoder::new(&mut out, 1, 1);
+            encoder.set_animated_unknown_frames(0)?;
+            encoder.set_source_gamma(ScaledFloat::new(0.5));
+            let mut writer = encoder.write_header()?;
+            for value in 0..3 {
+                writer.add_frame(&[value], FrameOptions::default())?;
+            }
+            writer.finish_with_frame_count()?;
+        }
+
+        let mut reader = Decoder::new(&out.get_ref()[6..]).read_info().unwrap();
+        assert_eq!(reader.info().animation_control().unwrap().num_frames, 3);
+        let mut buf = [0; 1];
+        for value in 0..3 {
+            reader.next_frame(&mut buf).unwrap();
+            assert_eq!(buf, [value]);
+        }
+
+        let mut encoder = Encoder::new(Cursor::new(Vec::new()), 1, 1);
+        encoder.set_animated_unknown_frames(0)?;
+        let mut writer = encoder.write_header()?;
+        writer.add_frame(&[0], FrameOptions::default())?;
+        assert!(writer.finish().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn per_frame_compression() -> Result<()> {
+        let data: Vec<u8> = (0..64).map(|i| (i * i) as u8).collect();
+        let mut out = Vec::new();
+        {
+            let mut encoder = Encoder::new(&mut out, 8, 8);
+            encoder.set_animated(4, 0)?;
+            encoder.set_compression(Compression::Best);
+            let mut writer = encoder.write_header()?;
+            writer.add_frame(&data, FrameOptions::default())?;
+            writer.set_compression(Compression::Fast);
+            writer.set_filter(FilterType::Paeth);
+            writer.add_frame(&data, FrameOptions::default())?;
+
+            let mut stream = writer.stream_writer()?;
+            stream.set_compression(Compression::Huffman);
+            stream.write_all(&data)?;
+            stream.set_compression(Compression::Rle);
+            stream.write_all(&data)?;
+            stream.finish()?;
+            writer.finish()?;
+        }
+
+        let mut reader = Decoder::new(&*out).read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        for _ in 0..4 {
+            reader.next_frame(&mut buf).unwrap();
+            assert_eq!(buf, data);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn separate_default_image() -> Result<()> {
+        let mut out = Vec::new();
+        {
+            let mut encoder = Encoder::new(&mut out, 2, 2);
+            encoder.set_animated(2, 0)?;
+            let mut writer = encoder.write_header()?;
+            assert!(writer.write_default_image(&[0; 4]).is_err());
+        }
+
+        out.clear();
+        {
+            let mut encoder = Encoder::new(&mut out, 2, 2);
+            encoder.set_animated(2, 0)?;
+            encoder.set_sep_def_img(true)?;
+            let mut writer = encoder.write_header()?;
+            writer.set_frame_dimension(1, 1)?;
+            writer.write_default_image(&[9; 4])?;
+            assert!(writer.write_default_image(&[9; 4]).is_err());
+            writer.write_image_data(&[1])?;
+            writer.write_image_data(&[2])?;
+            writer.finish()?;
+        }
+
+        let mut reader = Decoder::new(&*out).read_info().unwrap();
+        assert_eq!(reader.info().animation_control().unwrap().num_frames, 2);
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        assert_eq!((info.width, info.height), (2, 2));
+        assert_eq!(buf, [9; 4]);
+        for value in 1..=2 {
+            let info = reader.next_frame(&mut buf).unwrap();
+            assert_eq!((info.width, info.height), (1, 1));
+            assert_eq!(buf[0], value);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn frame_delay_from_duration() {
+        let delay = |duration| {
+            let frame = FrameOptions::default().delay_duration(duration);
+            (frame.delay_num, frame.delay_den)
+        };
+        assert_eq!(delay(Duration::from_secs(0)), (0, 1));
+        assert_eq!(delay(Duration::from_millis(40)), (1, 25));
+        assert_eq!(delay(Duration::from_millis(1500)), (3, 2));
+        assert_eq!(delay(Duration::from_nanos(33_333_333)), (1, 30));
+        assert_eq!(delay(Duration::from_nanos(16_683_350)), (50, 2997));
+        assert_eq!(delay(Duration::from_secs(70_000)), (u16::MAX, 1));
+        // Only approximations with a small enough numerator and denominator are possible.
+        assert_eq!(delay(Duration::from_nanos(12_345_678_901)), (1000, 81));
+        // Delays below 1/65535 seconds round to zero or to that shortest delay.
+        assert_eq!(delay(Duration::from_nanos(1)), (0, 1));
+        assert_eq!(delay(Duration::from_nanos(999)), (0, 1));
+        assert_eq!(delay(Duration::from_micros(5)), (0, 1));
+        assert_eq!(delay(Duration::from_micros(10)), (1, u16::MAX));
+        assert_eq!(delay(Duration::from_micros(15)), (1, u16::MAX));
+        assert_eq!(delay(Duration::from_micros(16)), (1, 62500));
+        assert_eq!(delay(Duration::from_micros(20)), (1, 50000));
+        assert_eq!(delay(Duration::from_micros(100)), (1, 10000));
+    }
+
+    #[test]
+    fn signatures() -> result::Result<(), Box<dyn error::Error>> {
+        use crate::{Decoded, StreamingDecoder};
+
+        let mut png = Vec::new();
+        let mut encoder = Encoder::new(&mut png, 1, 1);
+        encoder.add_signature(b"outer".to_vec(), b"/outer".to_vec());
+        encoder.add_signature(b"inner".to_vec(), b"/inner".to_vec());
+        encoder.add_text_chunk("k".into(), "v".into())?;
+        encoder.write_header()?.write_image_data(&[0])?;
+
+        let chunks: Vec<_> = crate::chunk::ChunkReader::new(&png[..])
+            .map(|raw| raw.map(|raw| (raw.chunk_type, raw.data)))
+            .collect::<result::Result<_, _>>()?;
+        let dsig = |data: &[u8]| (chunk::dSIG, data.to_vec());
+        assert_eq!(chunks[1..3], [dsig(b"outer"), dsig(b"inner")]);
+        assert_eq!(
+            chunks[chunks.len() - 3..chunks.len() - 1],
+            [dsig(b"/inner"), dsig(b"/outer")]
+        );
+
+        let reader = Decoder::new(&png[..]).read_info()?;
+        let openings: Vec<_> = reader
+            .info()
+            .signatures
+            .iter()
+            .map(|s| &s.opening)
+            .collect();
+        assert_eq!(openings, [b"outer", b"inner"]);
+        assert!(reader.info().signatures.iter().all(|s| s.closing.is_none()));
+
+        let mut decoder = StreamingDecoder::new();
+        let mut buf = &png[..];
+        loop {
+            let (consumed, decoded) = decoder.update(buf, &mut Vec::new())?;
+            buf = &buf[consumed..];
+            if let Decoded::ImageEnd = decoded {
+                break;
+            }
+        }
+        let signatures = &decoder.info().unwrap().signatures;
+        assert_eq!(signatures[0].closing.as_deref(), Some(&b"/outer"[..]));
+        assert_eq!(signatures[1].closing.as_deref(), Some(&b"/inner"[..]));
+
+        // Signatures longer than the read buffer of the decoder are kept in full.
+        let long: Vec<u8> = (0..40_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let mut png = Vec::new();
+        let mut encoder = Encoder::new(&mut png, 1, 1);
+        encoder.add_signature(long.clone(), b"/long".to_vec());
+        encoder.write_header()?.write_image_data(&[0])?;
+        let reader = Decoder::new(&png[..]).read_info()?;
+        assert_eq!(reader.info().signatures[0].opening, long);
+
+        Ok(())
+    }
+
+    #[test]
+    fn animation_chunk_bytes() -> Result<()> {
+        let mut png = Vec::new();
+        let mut encoder = Encoder::new(&mut png, 2, 2);
+        encoder.set_animated(2, 0)?;
+        encoder.set_dispose_op(DisposeOp::Background)?;
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&[0; 4])?;
+        writer.set_frame_dimension(1, 2)?;
+        writer.set_frame_position(1, 0)?;
+        writer.write_image_data(&[255; 2])?;
+        writer.finish()?;
+
+        let chunks: Vec<_> = crate::chunk::ChunkReader::new(&png[..])
+            .map(|raw| raw.unwrap())
+            .collect();
+        let actl = chunks
+            .iter()
+            .find(|raw| raw.chunk_type == chunk::acTL)
+            .unwrap();
+        let actl = AnimationControl::from_bytes(&actl.data).unwrap();
+        assert_eq!(actl.to_bytes(), [0, 0, 0, 2, 0, 0, 0, 0]);
+
+        let fctls: Vec<_> = chunks
+            .iter()
+            .filter(|raw| raw.chunk_type == chunk::fcTL)
+            .map(|raw| (FrameControl::from_bytes(&raw.data).unwrap(), &raw.data))
+            .collect();
+        assert_eq!(fctls[1].0.sequence_number, 1);
+        assert_eq!((fctls[1].0.width, fctls[1].0.x_offset), (1, 1));
+        assert_eq!(fctls[1].0.dispose_op, DisposeOp::Background);
+        assert!(fctls
+            .iter()
+            .all(|(fctl, data)| fctl.to_bytes()[..] == data[..]));
+
+        let fdat = chunks
+            .iter()
+            .find(|raw| raw.chunk_type == chunk::fdAT)
+            .unwrap();
+        let frame_data = FrameData::from_bytes(&fdat.data).unwrap();
+        assert_eq!(frame_data.sequence_number, 2);
+        assert_eq!(frame_data.to_bytes(), fdat.data);
+
+        assert!(AnimationControl::from_bytes(&[0; 7]).is_none());
+        assert!(FrameControl::from_bytes(&[0; 26]).is_some());
+        assert!(FrameControl::from_bytes(&[9; 26]).is_none());
+        assert!(FrameData::from_bytes(&[0; 3]).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn text_lookup() -> Result<()> {
+        let mut png = Vec::new();
+        let mut encoder = Encoder::new(&mut png, 1, 1);
+        encoder.add_ztxt_chunk("Comment".into(), "compressed".into())?;
+        encoder.add_itxt_chunk("Comment".into(), "ütf-8".into())?;
+        encoder.add_text_chunk("Comment".into(), "plain".into())?;
+        encoder.add_text_chunk("Title".into(), "title".into())?;
+        encoder.write_header()?.write_image_data(&[0])?;
+
+        let reader = Decoder::new(&png[..]).read_info().unwrap();
+        let info = reader.info();
+        assert_eq!(info.text_value("Title").unwrap().as_deref(), Some("title"));
+        assert_eq!(
+            info.text_value("Comment").unwrap().as_deref(),
+            Some("plain")
+        );
+        assert_eq!(info.text_value("Author").unwrap(), None);
+        assert_eq!(
+            info.texts_matching("Comment").unwrap(),
+            ["plain", "compressed", "ütf-8"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn automatic_text_chunks() -> Result<()> {
+        let long = "a repetitive text ".repeat(100);
+        let mut png = Vec::new();
+        let mut encoder = Encoder::new(&mut png, 1, 1);
+        encoder.add_text("Short", "Café")?;
+        encoder.add_text("Long", long.as_str())?;
+        encoder.add_text("Hindi", "हिंदी")?;
+        encoder.set_text_compression_threshold(usize::MAX);
+        encoder.add_text("Threshold", long.as_str())?;
+        encoder.set_text_chunk_kind(TextChunkKind::Compressed);
+        encoder.add_text("Forced", "short")?;
+        assert!(encoder.add_text("Forced", "हिंदी").is_err());
+        assert!(encoder.add_text("Ωmega", "text").is_err());
+        encoder.write_header()?.write_image_data(&[0])?;
+
+        let reader = Decoder::new(&png[..]).read_info().unwrap();
+        let info = reader.info();
+        let text = &info.uncompressed_latin1_text;
+        assert_eq!((text.len(), &*text[1].keyword), (2, "Threshold"));
+        let ztxt = &info.compressed_latin1_text;
+        assert_eq!((ztxt.len(), &*ztxt[0].keyword), (2, "Long"));
+        assert_eq!(info.utf8_text.len(), 1);
+        assert!(!info.utf8_text[0].compressed);
+        assert_eq!(info.text_value("Long").unwrap(), Some(long));
+
+        Ok(())
+    }
+
+    #[test]
+    fn standard_keywords() -> Result<()> {
+        use crate::text_metadata::CreationTime;
+
+        let mut png = Vec::new();
+        let mut encoder = Encoder::new(&mut png, 1, 1);
+        encoder.add_text("Author", "Jane Doe")?;
+        encoder.add_text("Creation Time", "Sun, 29 Feb 2004 23:59:60 EST")?;
+        encoder.add_itxt_chunk("Software".into(), "png ⚙".into())?;
+        encoder.write_header()?.write_image_data(&[0])?;
+
+        let reader = Decoder::new(&png[..]).read_info().unwrap();
+        let keywords = reader.info().standard_keywords();
+        assert_eq!(keywords.author().unwrap().as_deref(), Some("Jane Doe"));
+        assert_eq!(keywords.software().unwrap().as_deref(), Some("png ⚙"));
+        assert_eq!(keywords.title().unwrap(), None);
+        let time = keywords.parsed_creation_time().unwrap().unwrap();
+        let expected = CreationTime {
+            year: 2004,
+            month: 2,
+            day: 29,
+            hour: 23,
+            minute: 59,
+            second: 60,
+            utc_offset: -300,
+        };
+        assert_eq!(time, expected);
+        assert_eq!(time.to_string(), "Sun, 29 Feb 2004 23:59:60 -0500");
+        assert_eq!(CreationTime::parse("29 Feb 2003 12:00 GMT"), None);
+        assert_eq!(
+            CreationTime::parse("1 jan 1970 00:00 gmt")
+                .unwrap()
+                .to_string(),
+            "Thu, 01 Jan 1970 00:00:00 GMT"
+        );
+        // The fields are public, so invalid dates are formatted without panicking.
+        let invalid = CreationTime {
+            month: 250,
+            day: 255,
+            ..expected
+        };
+        assert!(invalid.to_string().contains(", 255 Dec 2004 "));
+
+        Ok(())
+    }
+
+    #[test]
+    fn trailing_text_chunks() -> Result<()> {
+        let chunk_types = |png: &[u8]| -> Vec<ChunkType> {
+            chunk::ChunkReader::new(png)
+                .map(|raw| raw.unwrap().chunk_type)
+                .collect()
+        };
+
+        let mut png = Vec::new();
+        let mut encoder = Encoder::new(&mut png, 4, 4);
+        encoder.add_trailing_text_chunk(&TEXtChunk::new("Comment", "after"))?;
+        encoder.add_text_chunk("Title".into(), "before".into())?;
+        let mut writer = encoder.write_header()?;
+        let mut stream = writer.stream_writer()?;
+        stream.write_all(&[0; 8])?;
+        stream.add_trailing_text_chunk(&ZTXtChunk::new("Rows", "2 of 4 written"))?;
+        stream.write_all(&[0; 8])?;
+        assert!(stream
+            .add_trailing_text_chunk(&TEXtChunk::new("", "no keyword"))
+            .is_err());
+        stream.finish()?;
+        writer.finish()?;
+
+        let expected = [
+            chunk::IHDR,
+            chunk::tEXt,
+            chunk::IDAT,
+            chunk::tEXt,
+            chunk::zTXt,
+            chunk::IEND,
+        ];
+        assert_eq!(chunk_types(&png), expected);
+
+        let mut png = Vec::new();
+        let mut writer = Encoder::new(&mut png, 1, 1).write_header()?;
+        writer.add_trailing_text_chunk(&ITXtChunk::new("Comment", "after"))?;
+        writer.write_image_data(&[0])?;
+        writer.finish()?;
+        assert_eq!(chunk_types(&png)[2], chunk::iTXt);
+
+        Ok(())
+    }
+
+    #[test]
+    fn text_chunk_order() -> Result<()> {
+        let chunks = |png: &[u8]| -> Vec<(ChunkType, Vec<u8>)> {
+            chunk::ChunkReader::new(png)
+                .map(|raw| raw.unwrap())
+                .map(|raw| (raw.chunk_type, raw.data))
+                .filter(|&(chunk_type, _)| !chunk::is_critical(chunk_type))
+                .collect()
+        };
 
-            // Transition Wrapper::Chunk to Wrapper::Zlib.
-            self.new_frame()?;
+        let mut png = Vec::new();
+        let mut writer = Encoder::new(&mut png, 1, 1).write_header()?;
+        writer.write_text_chunk(&ITXtChunk::new("first", "iTXt"))?;
+        writer.write_text_chunk(&ZTXtChunk::new("second", "zTXt"))?;
+        writer.write_text_chunk(&TEXtChunk::new("third", "tEXt"))?;
+        writer.write_image_data(&[0])?;
+        writer.finish()?;
+
+        let reader = Decoder::new(&png[..]).read_info().unwrap();
+        let mut info = reader.info().clone();
+        let keywords: Vec<_> = info.text_chunks().map(|c| c.keyword().to_owned()).collect();
+        assert_eq!(keywords, ["first", "second", "third"]);
+        let ztxt = &chunks(&png)[1].1;
+        let compressed = info.compressed_latin1_text[0].compressed_text().unwrap();
+        assert_eq!(compressed, &ztxt[b"second\0\0".len()..]);
+
+        info.uncompressed_latin1_text
+            .push(TEXtChunk::new("added", "tEXt"));
+        let mut encoded = vec![137, 80, 78, 71, 13, 10, 26, 10];
+        info.encode(&mut encoded)?;
+        write_chunk(&mut encoded, chunk::IEND, &[])?;
+        let encoded = chunks(&encoded);
+        let order: Vec<_> = encoded.iter().map(|chunk| chunk.0).collect();
+        assert_eq!(order, [chunk::iTXt, chunk::zTXt, chunk::tEXt, chunk::tEXt]);
+        assert_eq!(encoded[..3], chunks(&png)[..3]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn localized_text() -> Result<()> {
+        let mut png = Vec::new();
+        let mut encoder = Encoder::new(&mut png, 1, 1);
+        encoder.add_itxt_chunk("Title".into(), "untagged".into())?;
+        let mut writer = encoder.write_header()?;
+        for &(tag, text) in &[("en-GB", "colour"), ("de", "Farbe"), ("DE-ch", "Farbe CH")] {
+            let mut chunk = ITXtChunk::new("Title", text);
+            chunk.language_tag = tag.into();
+            writer.write_text_chunk(&chunk)?;
         }
+        writer.write_image_data(&[0])?;
+        writer.finish()?;
+
+        let reader = Decoder::new(&png[..]).read_info().unwrap();
+        let info = reader.info();
+        let text = |tag| {
+            info.localized_text("Title", tag)
+                .map(|chunk| chunk.get_text().unwrap())
+        };
+        assert_eq!(text("de-CH").as_deref(), Some("Farbe CH"));
+        assert_eq!(text("de-AT").as_deref(), Some("Farbe"));
+        assert_eq!(text("en-US").as_deref(), Some("colour"));
+        assert_eq!(text("fr").as_deref(), Some("untagged"));
+        assert_eq!(info.localized_text("Author", "en"), None);
 
-        let written = data.read(&mut self.curr_buf[..self.line_len][self.index..])?;
-        self.index += written;
-        self.to_write -= written;
+        Ok(())
+    }
 
-        if self.index == self.line_len {
-            let filter_type = filter(
-                self.filter,
-                self.adaptive_filter,
-                self.bpp,
-                &self.prev_buf,
-                &mut self.curr_buf,
-            );
-            // This can't fail as the other variant is used only to allow the zlib encoder to finish
-            let wrt = match &mut self.writer {
-                Wrapper::Zlib(wrt) => wrt,
-                _ => unreachable!(),
-            };
+    #[test]
+    fn latin1_modes() -> Result<()> {
+        use crate::text_metadata::Latin1Mode;
+
+        let mut png = Vec::new();
+        let mut encoder = Encoder::new(&mut png, 1, 1);
+        encoder.add_text_chunk("Comment".into(), "Café\u{7}".into())?;
+        encoder.add_ztxt_chunk("Title".into(), "Bell\u{7}".into())?;
+        encoder.write_header()?.write_image_data(&[0])?;
+
+        let reader = Decoder::new(&png[..]).read_info().unwrap();
+        assert_eq!(reader.info().uncompressed_latin1_text[0].text, "Café\u{7}");
+
+        let mut decoder = Decoder::new(&png[..]);
+        decoder.set_latin1_mode(Latin1Mode::Lossy);
+        let reader = decoder.read_info().unwrap();
+        let info = reader.info();
+        assert_eq!(info.uncompressed_latin1_text[0].text, "Café\u{fffd}");
+        assert_eq!(
+            info.compressed_latin1_text[0].get_text().unwrap(),
+            "Bell\u{fffd}"
+        );
+
+        let mut decoder = Decoder::new(&png[..]);
+        decoder.set_latin1_mode(Latin1Mode::Strict);
+        assert!(decoder.read_info().is_err());
 
-            wrt.write_all(&[filter_type as u8])?;
-            wrt.write_all(&self.curr_buf)?;
-            mem::swap(&mut self.prev_buf, &mut self.curr_buf);
-            self.index = 0;
-        }
+        Ok(())
+    }
 
-        Ok(written)
+    #[test]
+    fn legacy_chunks() -> Result<()> {
+        let control = vec![2, 0, 10, 0];
+        let mut png = Vec::new();
+        let mut encoder = Encoder::new(&mut png, 1, 1);
+        encoder.add_legacy_chunk(chunk::gIFg, control.clone())?;
+        encoder.add_legacy_chunk(chunk::gIFx, b"NETSCAPE2.0\x01\0\0".to_vec())?;
+        assert!(encoder.add_legacy_chunk(chunk::tEXt, vec![]).is_err());
+        encoder.write_header()?.write_image_data(&[0])?;
+
+        let reader = Decoder::new(&png[..]).read_info().unwrap();
+        let legacy = &reader.info().legacy_chunks;
+        assert_eq!(legacy.len(), 2);
+        assert_eq!(legacy[0], (chunk::gIFg, control));
+        assert_eq!(legacy[1].0, chunk::gIFx);
+
+        // Chunks longer than the read buffer of the decoder are kept in full.
+        let long: Vec<u8> = (0..40_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let mut png = Vec::new();
+        let mut encoder = Encoder::new(&mut png, 1, 1);
+        encoder.add_legacy_chunk(chunk::fRAc, long.clone())?;
+        encoder.write_header()?.write_image_data(&[0])?;
+        let reader = Decoder::new(&png[..]).read_info().unwrap();
+        assert_eq!(reader.info().legacy_chunks, [(chunk::fRAc, long)]);
+
+        Ok(())
     }
 
-    fn flush(&mut self) -> io::Result<()> {
-        match &mut self.writer {
-            Wrapper::Zlib(wrt) => wrt.flush()?,
-            Wrapper::Chunk(wrt) => wrt.flush()?,
-            // This handles both the case where we entered an unrecoverable state after zlib
-            // decoding failure and after a panic while we had taken the chunk/zlib reader.
-            Wrapper::Unrecoverable | Wrapper::None => {
-                let err = FormatErrorKind::Unrecoverable.into();
-                return Err(EncodingError::Format(err).into());
-            }
-        }
+    #[test]
+    fn source_color() -> Result<()> {
+        use crate::{
+            CodingIndependentCodePoints, ColorChunk, ColorPrecedence, SourceColor,
+            SrgbRenderingIntent,
+        };
 
-        if self.index > 0 {
-            let err = FormatErrorKind::WrittenTooMuch(self.index).into();
-            return Err(EncodingError::Format(err).into());
-        }
+        let decode = |encoder: &dyn Fn(&mut Encoder<&mut Vec<u8>>)| {
+            let mut png = Vec::new();
+            let mut enc = Encoder::new(&mut png, 1, 1);
+            encoder(&mut enc);
+            enc.write_header().unwrap().write_image_data(&[0]).unwrap();
+            Decoder::new(std::io::Cursor::new(png)).read_info().unwrap()
+        };
 
+        let reader = decode(&|_| {});
+        assert_eq!(reader.info().source_color(), SourceColor::Unknown);
+
+        let gamma = ScaledFloat::new(0.5);
+        let reader = decode(&|encoder| encoder.set_source_gamma(gamma));
+        let expected = SourceColor::Calibrated {
+            gamma: Some(gamma),
+            chromaticities: None,
+        };
+        assert_eq!(reader.info().source_color(), expected);
+
+        let intent = SrgbRenderingIntent::Saturation;
+        let reader = decode(&|encoder| {
+            encoder.set_source_gamma(gamma);
+            encoder.set_srgb(intent);
+        });
+        assert_eq!(reader.info().source_color(), SourceColor::Srgb(intent));
+
+        let mut info = reader.info().clone();
+        info.icc_profile = Some(Cow::Borrowed(b"profile"));
+        assert_eq!(info.source_color(), SourceColor::IccProfile(b"profile"));
+
+        let cicp = CodingIndependentCodePoints {
+            color_primaries: 1,
+            transfer_function: 13,
+            matrix_coefficients: 0,
+            is_video_full_range_image: true,
+        };
+        info.coding_independent_code_points = Some(cicp);
+        assert_eq!(info.source_color(), SourceColor::Cicp(cicp));
+
+        info.color_precedence = ColorPrecedence::ICC_FIRST;
+        assert_eq!(info.source_color(), SourceColor::IccProfile(b"profile"));
+        info.color_precedence = ColorPrecedence::new(&[ColorChunk::Calibrated, ColorChunk::Cicp]);
+        let expected = SourceColor::Calibrated {
+            gamma: Some(crate::srgb::substitute_gamma()),
+            chromaticities: Some(crate::srgb::substitute_chromaticities()),
+        };
+        assert_eq!(info.source_color(), expected);
+
+        let mut png = Vec::new();
+        let mut encoder = Encoder::new(&mut png, 1, 1);
+        encoder.set_srgb(intent);
+        encoder.write_header()?.write_image_data(&[0])?;
+        let mut decoder = Decoder::new(&png[..]);
+        decoder.set_color_precedence(ColorPrecedence::new(&[]));
+        let reader = decoder.read_info().unwrap();
+        assert_eq!(reader.info().source_color(), SourceColor::Unknown);
         Ok(())
     }
-}
 
-impl<W: Write> Drop for StreamWriter<'_, W> {
-    fn drop(&mut self) {
-        let _ = self.flush();
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_info() -> Result<()> {
+        let mut png = Vec::new();
+        let mut encoder = Encoder::new(&mut png, 1, 1);
+        encoder.set_animated(1, 0)?;
+        encoder.set_source_chromaticities(crate::srgb::substitute_chromaticities());
+        encoder.add_text_chunk("Title".to_string(), "Serde".to_string())?;
+        encoder.add_ztxt_chunk("Comment".to_string(), "Compressed".to_string())?;
+        encoder.write_header()?.write_image_data(&[0])?;
+        let reader = Decoder::new(&png[..]).read_info().unwrap();
+
+        let json = serde_json::to_string(reader.info()).unwrap();
+        let info: Info = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&info).unwrap(), json);
+        assert_eq!(info.animation_control, reader.info().animation_control);
+        assert_eq!(info.frame_control, reader.info().frame_control);
+        assert_eq!(info.uncompressed_latin1_text[0].text, "Serde");
+        assert_eq!(
+            info.compressed_latin1_text,
+            reader.info().compressed_latin1_text
+        );
+        assert_eq!(info.color_precedence, reader.info().color_precedence);
+        let chromaticities = info.source_chromaticities;
+        assert_eq!(chromaticities, reader.info().source_chromaticities);
+
+        // Fields that are missing keep their default.
+        let info: Info = serde_json::from_str(r#"{"width": 3, "height": 2}"#).unwrap();
+        assert_eq!(info.size(), (3, 2));
+        assert_eq!(info.color_type, ColorType::Grayscale);
+
+        let chunk: chunk::ChunkType = serde_json::from_str("[73, 68, 65, 84]").unwrap();
+        assert_eq!(chunk, chunk::IDAT);
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::Decoder;
+    #[test]
+    fn info_builder() -> Result<()> {
+        let invalid = |builder: &InfoBuilder| match builder.build() {
+            Err(EncodingError::Format(err)) => err.inner,
+            other => panic!("expected a format error, got {:?}", other.map(|_| ())),
+        };
 
-    use rand::{thread_rng, Rng};
-    use std::fs::File;
-    use std::io::{Cursor, Write};
-    use std::{cmp, io};
+        let mut builder = InfoBuilder::new(4, 4);
+        builder.set_color(ColorType::Rgba).set_depth(BitDepth::Four);
+        assert!(matches!(
+            invalid(&builder),
+            FormatErrorKind::InvalidColorCombination(BitDepth::Four, ColorType::Rgba)
+        ));
+        builder.set_depth(BitDepth::Eight).set_trns(vec![0, 0]);
+        assert!(matches!(
+            invalid(&builder),
+            FormatErrorKind::TrnsNotAllowed(ColorType::Rgba)
+        ));
+
+        let mut builder = InfoBuilder::new(4, 4);
+        builder.set_color(ColorType::Indexed);
+        assert!(matches!(invalid(&builder), FormatErrorKind::NoPalette));
+        builder.set_palette(vec![0; 7]);
+        assert!(matches!(
+            invalid(&builder),
+            FormatErrorKind::InvalidPalette {
+                len: 7,
+                max_entries: 256
+            }
+        ));
+        builder.set_palette(vec![0; 6]).set_trns(vec![0; 3]);
+        assert!(matches!(
+            invalid(&builder),
+            FormatErrorKind::TrnsTooLong { len: 3, entries: 2 }
+        ));
+        builder.set_trns(vec![0; 2]);
+        builder.build()?;
+
+        let mut builder = InfoBuilder::new(4, 4);
+        builder.set_palette(vec![0; 3]);
+        assert!(matches!(
+            invalid(&builder),
+            FormatErrorKind::PaletteNotAllowed(ColorType::Grayscale)
+        ));
+        let mut builder = InfoBuilder::new(4, 4);
+        builder.set_depth(BitDepth::Two).set_trns(vec![0, 4]);
+        assert!(matches!(
+            invalid(&builder),
+            FormatErrorKind::InvalidColorKey(BitDepth::Two, ColorType::Grayscale)
+        ));
+        builder.set_trns(vec![0, 3]);
+        builder.build()?;
+
+        let mut builder = InfoBuilder::new(4, 4);
+        builder.set_animated(0, 0);
+        assert!(matches!(invalid(&builder), FormatErrorKind::ZeroFrames));
+        builder.set_animated(2, 0).set_frame_control(FrameControl {
+            x_offset: 1,
+            width: 4,
+            height: 4,
+            ..FrameControl::default()
+        });
+        assert!(matches!(invalid(&builder), FormatErrorKind::OutOfBounds));
+        builder.info_mut().animation_control = None;
+        assert!(matches!(invalid(&builder), FormatErrorKind::NotAnimated));
+        assert!(matches!(
+            invalid(&InfoBuilder::new(0, 1)),
+            FormatErrorKind::ZeroWidth
+        ));
+
+        // A valid animation is written as configured.
+        let mut builder = InfoBuilder::new(2, 2);
+        builder.set_color(ColorType::Rgb).set_animated(1, 3);
+        let mut png = Vec::new();
+        let encoder = Encoder::with_info(&mut png, builder.build()?)?;
+        encoder.write_header()?.write_image_data(&[0; 12])?;
+        let reader = Decoder::new(&png[..]).read_info().unwrap();
+        assert_eq!(reader.info().animation_control.unwrap().num_plays, 3);
+        assert_eq!(reader.info().color_type, ColorType::Rgb);
+
+        let mut info = Info::with_size(1, 1);
+        info.color_type = ColorType::Indexed;
+        assert!(Encoder::with_info(Vec::new(), info).is_err());
+        Ok(())
+    }
 
     #[test]
-    fn roundtrip() {
-        // More loops = more random testing, but also more test wait time
-        for _ in 0..10 {
-            for path in glob::glob("tests/pngsuite/*.png")
-                .unwrap()
-                .map(|r| r.unwrap())
-            {
-                if path.file_name().unwrap().to_str().unwrap().starts_with('x') {
-                    // x* files are expected to fail to decode
-                    continue;
-                }
-                eprintln!("{}", path.display());
-                // Decode image
-                let decoder = Decoder::new(File::open(path).unwrap());
-                let mut reader = decoder.read_info().unwrap();
-                let mut buf = vec![0; reader.output_buffer_size()];
-                let info = reader.next_frame(&mut buf).unwrap();
-                // Encode decoded image
-                let mut out = Vec::new();
-                {
-                    let mut wrapper = RandomChunkWriter {
-                        rng: thread_rng(),
-                        w: &mut out,
-                    };
+    fn clone_metadata() -> Result<()> {
+        let mut png = Vec::new();
+        let mut encoder = Encoder::new(&mut png, 2, 1);
+        encoder.set_color(ColorType::Indexed);
+        encoder.set_depth(BitDepth::One);
+        encoder.set_palette(vec![0, 0, 0, 255, 255, 255]);
+        encoder.set_trns(vec![0]);
+        encoder.add_itxt_chunk("Description".to_string(), "text".repeat(100))?;
+        encoder.set_source_gamma(ScaledFloat::new(0.5));
+        encoder.write_header()?.write_image_data(&[0b0100_0000])?;
+        let mut reader = Decoder::new(&png[..]).read_info().unwrap();
+        let mut info = reader.info().clone();
+        info.icc_profile = Some(Cow::Borrowed(&[0; 1024]));
+
+        let metadata = std::thread::spawn({
+            let metadata = info.clone_metadata();
+            move || metadata
+        })
+        .join()
+        .unwrap();
+        assert_eq!(metadata.size(), (2, 1));
+        assert_eq!(metadata.palette, info.palette);
+        assert_eq!(metadata.trns.as_deref(), Some(&[0][..]));
+        assert_eq!(metadata.source_gamma, Some(ScaledFloat::new(0.5)));
+        assert!(metadata.icc_profile.is_none() && metadata.utf8_text.is_empty());
+        assert_eq!(info.utf8_text.len(), 1);
+
+        // The metadata still describes the image data.
+        let mut buf = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut buf).unwrap();
+        assert_eq!(metadata.raw_row_length() - 1, buf.len());
+        Ok(())
+    }
 
-                    let mut encoder = Encoder::new(&mut wrapper, info.width, info.height);
-                    encoder.set_color(info.color_type);
-                    encoder.set_depth(info.bit_depth);
-                    if let Some(palette) = &reader.info().palette {
-                        encoder.set_palette(palette.clone());
-                    }
-                    let mut encoder = encoder.write_header().unwrap();
-                    encoder.write_image_data(&buf).unwrap();
-                }
-                // Decode encoded decoded image
-                let decoder = Decoder::new(&*out);
-                let mut reader = decoder.read_info().unwrap();
-                let mut buf2 = vec![0; reader.output_buffer_size()];
-                reader.next_frame(&mut buf2).unwrap();
-                // check if the encoded image is ok:
-                assert_eq!(buf, buf2);
+    #[test]
+    fn wide_gamut_and_hdr() {
+        use crate::{CodingIndependentCodePoints, SourceChromaticities};
+
+        let mut info = Info::default();
+        assert!(!info.is_wide_gamut() && !info.is_hdr());
+
+        let white = (0.3127, 0.3290);
+        info.source_chromaticities = Some(crate::srgb::substitute_chromaticities());
+        assert!(!info.is_wide_gamut());
+        let rec2020 =
+            SourceChromaticities::new(white, (0.708, 0.292), (0.170, 0.797), (0.131, 0.046));
+        info.source_chromaticities = Some(rec2020);
+        assert!(info.is_wide_gamut() && !info.is_hdr());
+
+        // A profile with the Display P3 colorants and a cicp tag for PQ.
+        let colorants: [(&[u8; 4], [f64; 3]); 3] = [
+            (b"rXYZ", [0.5151, 0.2412, -0.0011]),
+            (b"gXYZ", [0.2920, 0.6922, 0.0419]),
+            (b"bXYZ", [0.1571, 0.0666, 0.7841]),
+        ];
+        let mut profile = vec![0; 128];
+        profile[16..20].copy_from_slice(b"RGB ");
+        profile.extend_from_slice(&4u32.to_be_bytes());
+        let data_start = 132 + 4 * 12;
+        let mut data = Vec::new();
+        let mut table = |signature: &[u8; 4], tag: Vec<u8>| {
+            profile.extend_from_slice(signature);
+            profile.extend_from_slice(&((data_start + data.len()) as u32).to_be_bytes());
+            profile.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+            data.extend_from_slice(&tag);
+        };
+        for (signature, xyz) in colorants.iter() {
+            let mut tag = b"XYZ \0\0\0\0".to_vec();
+            for value in xyz.iter() {
+                tag.extend_from_slice(&((value.max(0.0) * 65536.0) as i32).to_be_bytes());
             }
+            table(signature, tag);
         }
+        table(b"cicp", b"cicp\0\0\0\0\x0c\x10\0\x01".to_vec());
+        profile.extend_from_slice(&data);
+
+        info.icc_profile = Some(Cow::Owned(profile));
+        assert!(info.is_wide_gamut() && info.is_hdr());
+        info.icc_profile = Some(Cow::Borrowed(&[0; 16]));
+        assert!(!info.is_wide_gamut() && !info.is_hdr());
+
+        info.coding_independent_code_points = Some(CodingIndependentCodePoints {
+            color_primaries: 9,
+            transfer_function: CodingIndependentCodePoints::HLG,
+            matrix_coefficients: 0,
+            is_video_full_range_image: true,
+        });
+        assert!(info.is_wide_gamut() && info.is_hdr());
     }
 
     #[test]
-    fn roundtrip_stream() {
-        // More loops = more random testing, but also more test wait time
-        for _ in 0..10 {
-            for path in glob::glob("tests/pngsuite/*.png")
-                .unwrap()
-                .map(|r| r.unwrap())
-            {
-                if path.file_name().unwrap().to_str().unwrap().starts_with('x') {
-                    // x* files are expected to fail to decode
-                    continue;
-                }
-                // Decode image
-                let decoder = Decoder::new(File::open(path).unwrap());
-                let mut reader = decoder.read_info().unwrap();
-                let mut buf = vec![0; reader.output_buffer_size()];
-                let info = reader.next_frame(&mut buf).unwrap();
-                // Encode decoded image
-                let mut out = Vec::new();
-                {
-                    let mut wrapper = RandomChunkWriter {
-                        rng: thread_rng(),
-                        w: &mut out,
-                    };
-
-                    let mut encoder = Encoder::new(&mut wrapper, info.width, info.height);
-                    encoder.set_color(info.color_type);
-                    encoder.set_depth(info.bit_depth);
-                    if let Some(palette) = &reader.info().palette {
-                        encoder.set_palette(palette.clone());
-                    }
-                    let mut encoder = encoder.write_header().unwrap();
-                    let mut stream_writer = encoder.stream_writer().unwrap();
+    fn stereo_layout() -> Result<()> {
+        for &layout in &[StereoLayout::CrossFuse, StereoLayout::DivergingFuse] {
+            let mut png = Vec::new();
+            let mut encoder = Encoder::new(&mut png, 2, 1);
+            encoder.set_stereo_layout(layout);
+            encoder.write_header()?.write_image_data(&[0, 0])?;
+
+            let reader = Decoder::new(&png[..]).read_info().unwrap();
+            assert_eq!(reader.info().stereo_layout, Some(layout));
+        }
 
-                    let mut outer_wrapper = RandomChunkWriter {
-                        rng: thread_rng(),
-                        w: &mut stream_writer,
-                    };
+        let mut png = Vec::new();
+        let mut writer = Encoder::new(&mut png, 2, 1).write_header()?;
+        writer.write_chunk(chunk::sTER, &[2])?;
+        writer.write_image_data(&[0, 0])?;
+        writer.finish()?;
+        let reader = Decoder::new(&png[..]).read_info().unwrap();
+        assert_eq!(reader.info().stereo_layout, None);
 
-                    outer_wrapper.write_all(&buf).unwrap();
-                }
-                // Decode encoded decoded image
-                let decoder = Decoder::new(&*out);
-                let mut reader = decoder.read_info().unwrap();
-                let mut buf2 = vec![0; reader.output_buffer_size()];
-                reader.next_frame(&mut buf2).unwrap();
-                // check if the encoded image is ok:
-                assert_eq!(buf, buf2);
-            }
-        }
+        Ok(())
     }
 
     #[test]
-    fn image_palette() -> Result<()> {
-        for &bit_depth in &[1u8, 2, 4, 8] {
-            // Do a reference decoding, choose a fitting palette image from pngsuite
-            let path = format!("tests/pngsuite/basn3p0{}.png", bit_depth);
-            let decoder = Decoder::new(File::open(&path).unwrap());
-            let mut reader = decoder.read_info().unwrap();
-
-            let mut decoded_pixels = vec![0; reader.output_buffer_size()];
-            let info = reader.info();
-            assert_eq!(
-                info.width as usize * info.height as usize * usize::from(bit_depth),
-                decoded_pixels.len() * 8
-            );
-            let info = reader.next_frame(&mut decoded_pixels).unwrap();
-            let indexed_data = decoded_pixels;
+    fn add_frame_requires_default_image() -> Result<()> {
+        let encoder = Encoder::new(io::sink(), 1, 1);
+        assert!(encoder
+            .write_header()?
+            .add_frame(&[0], FrameOptions::default())
+            .is_err());
+
+        let mut encoder = Encoder::new(io::sink(), 1, 1);
+        encoder.set_animated(1, 0)?;
+        encoder.set_sep_def_img(true)?;
+        let mut writer = encoder.write_header()?;
+        assert!(writer.add_frame(&[0], FrameOptions::default()).is_err());
+        writer.write_image_data(&[0])?;
+        writer.add_frame(&[0], FrameOptions::default())?;
+        writer.finish()
+    }
 
-            let palette = reader.info().palette.as_ref().unwrap();
-            let mut out = Vec::new();
+    #[test]
+    fn stream_writer_filters_against_unfiltered_rows() -> Result<()> {
+        let data: Vec<u8> = (0..64u8).map(|i| i.wrapping_mul(37)).collect();
+        for &filter in &[FilterType::Up, FilterType::Avg, FilterType::Paeth] {
+            let mut png = Vec::new();
             {
-                let mut encoder = Encoder::new(&mut out, info.width, info.height);
-                encoder.set_depth(BitDepth::from_u8(bit_depth).unwrap());
-                encoder.set_color(ColorType::Indexed);
-                encoder.set_palette(palette.as_ref());
-
-                let mut writer = encoder.write_header().unwrap();
-                writer.write_image_data(&indexed_data).unwrap();
+                let mut encoder = Encoder::new(&mut png, 8, 8);
+                encoder.set_filter(filter);
+                let mut writer = encoder.write_header()?;
+                writer.stream_writer()?.write_all(&data)?;
             }
-
-            // Decode re-encoded image
-            let decoder = Decoder::new(&*out);
-            let mut reader = decoder.read_info().unwrap();
-            let mut redecoded = vec![0; reader.output_buffer_size()];
-            reader.next_frame(&mut redecoded).unwrap();
-            // check if the encoded image is ok:
-            assert_eq!(indexed_data, redecoded);
+            let mut reader = Decoder::new(&png[..]).read_info().unwrap();
+            let mut buf = vec![0; reader.output_buffer_size()];
+            reader.next_frame(&mut buf).unwrap();
+            assert_eq!(buf, data, "{:?}", filter);
         }
         Ok(())
     }
@@ -2256,7 +6681,7 @@ mod tests {
             encoder.set_color(ColorType::Grayscale);
 
             let mut writer = encoder.write_header()?;
-            writer.write_image_data(&vec![0; 100])?;
+            writer.write_image_data(&[0; 100])?;
             writer.finish()?;
         }
 
@@ -2264,6 +6689,88 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn output_buffer() -> Result<()> {
+        let mut rng = thread_rng();
+        let data: Vec<u8> = (0..256 * 256 * 3).map(|_| rng.gen()).collect();
+        let encode = |out: &mut dyn FnMut(&OutputBuffer)| -> Result<()> {
+            let output = OutputBuffer::new();
+            let mut encoder = Encoder::new(output.clone(), 256, 256);
+            encoder.set_color(ColorType::Rgb);
+            let mut writer = encoder.write_header()?;
+            out(&output);
+            let mut stream = writer.stream_writer_with_size(1024)?;
+            for row in data.chunks(256 * 3) {
+                stream.write_all(row)?;
+                out(&output);
+            }
+            stream.finish()?;
+            writer.finish()?;
+            out(&output);
+            Ok(())
+        };
+
+        let mut pieces = Vec::new();
+        encode(&mut |output| {
+            let len = output.len();
+            pieces.push(output.take());
+            assert_eq!(pieces.last().unwrap().len(), len);
+            assert!(output.is_empty());
+        })?;
+        assert!(pieces.iter().filter(|piece| !piece.is_empty()).count() > 2);
+
+        let png = pieces.concat();
+        let mut reader = Decoder::new(&*png).read_info().unwrap();
+        let mut decoded = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut decoded).unwrap();
+        assert_eq!(decoded, data);
+        Ok(())
+    }
+
+    #[test]
+    fn slice_writer() -> Result<()> {
+        let encode = |w: &mut dyn Write| -> Result<()> {
+            let mut encoder = Encoder::new(w, 4, 4);
+            encoder.set_color(ColorType::Rgba);
+            let mut writer = encoder.write_header()?;
+            writer.write_image_data(&[7; 64])?;
+            writer.finish()
+        };
+        let mut expected = Vec::new();
+        encode(&mut expected)?;
+
+        let mut buf = vec![0; expected.len() + 1];
+        let mut output = SliceWriter::new(&mut buf);
+        encode(&mut output)?;
+        assert_eq!(output.len(), expected.len());
+        assert_eq!(output.finish()?, &expected[..]);
+
+        let mut buf = vec![0; expected.len() - 1];
+        let mut output = SliceWriter::new(&mut buf);
+        encode(&mut output)?;
+        match output.finish() {
+            Err(EncodingError::Parameter(err)) => assert_eq!(
+                err.to_string(),
+                format!(
+                    "output buffer too small, needed {} bytes but got {}",
+                    expected.len(),
+                    expected.len() - 1
+                )
+            ),
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(buf[..], expected[..buf.len()]);
+        Ok(())
+    }
+
+    #[test]
+    fn send_types() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Encoder<'static, std::fs::File>>();
+        assert_send::<Writer<std::fs::File>>();
+        assert_send::<StreamWriter<'static, std::fs::File>>();
+    }
+
     /// A Writer that only writes a few bytes at a time
     struct RandomChunkWriter<R: Rng, W: Write> {
         rng: R,
@@ -2282,20 +6789,118 @@ mod tests {
             self.w.flush()
         }
     }
-}
 
-/// Mod to encapsulate the converters depending on the `deflate` crate.
-///
-/// Since this only contains trait impls, there is no need to make this public, they are simply
-/// available when the mod is compiled as well.
-impl Compression {
-    fn to_options(self) -> deflate::CompressionOptions {
-        match self {
-            Compression::Default => deflate::CompressionOptions::default(),
-            Compression::Fast => deflate::CompressionOptions::fast(),
-            Compression::Best => deflate::CompressionOptions::high(),
-            Compression::Huffman => deflate::CompressionOptions::huffman_only(),
-            Compression::Rle => deflate::CompressionOptions::rle(),
+    #[test]
+    fn encoding_stats() -> Result<()> {
+        let mut file = Vec::new();
+        let mut encoder = Encoder::new(&mut file, 4, 3);
+        encoder.set_color(ColorType::Rgb);
+        encoder.set_adaptive_filter(AdaptiveFilterType::Adaptive);
+        encoder.add_text("Title", "Stats")?;
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&[7; 36])?;
+        let stats = writer.finish_with_stats()?;
+
+        let total: u64 = stats.chunk_bytes.iter().map(|&(_, bytes)| bytes).sum();
+        assert_eq!(total, file.len() as u64 - 8);
+        assert_eq!(stats.chunk_bytes[0], (chunk::IHDR, 25));
+        assert_eq!(stats.chunk_bytes(chunk::tEXt), 12 + 11);
+        assert_eq!(stats.filter_types.iter().sum::<u64>(), 3);
+        assert_eq!(stats.filtered_bytes, 3 * 13);
+        let idat = stats.chunk_bytes(chunk::IDAT);
+        assert_eq!(stats.compressed_bytes, idat - 12);
+        assert!(stats.compression_ratio().unwrap() > 0.0);
+        // The rows are only timed with phase timings.
+        assert_eq!(stats.filter_time, Duration::from_secs(0));
+        assert_eq!(stats.compression_time, Duration::from_secs(0));
+
+        // The rows of a stream writer are added once it is finished.
+        let mut writer = Encoder::new(Vec::new(), 4, 3).write_header()?;
+        let mut stream = writer.stream_writer()?;
+        stream.write_all(&[0; 12])?;
+        stream.finish()?;
+        let stats = writer.finish_with_stats()?;
+        assert_eq!(stats.filtered_bytes, 3 * 5);
+        assert_eq!(stats.compressed_bytes, stats.chunk_bytes(chunk::IDAT) - 12);
+        Ok(())
+    }
+
+    /// The combinations of options explored by the `options` fuzz target, on fixed pixels.
+    #[test]
+    fn option_combinations() -> Result<()> {
+        use crate::{Limits, Transformations};
+
+        let pixels: Vec<u8> = (0..=255).collect();
+        for color_type in (0..7).filter_map(ColorType::from_u8) {
+            for &bit_depth in color_type.allowed_bit_depths() {
+                for filter in (0..5).filter_map(FilterType::from_u8) {
+                    for &(adaptive, bottom_up) in &[(false, false), (true, true)] {
+                        let mut png = Vec::new();
+                        let mut encoder = Encoder::new(&mut png, 5, 3);
+                        encoder.set_color(color_type);
+                        encoder.set_depth(bit_depth);
+                        encoder.set_palette(vec![0; 3 << bit_depth as u8]);
+                        encoder.set_filter(filter);
+                        if adaptive {
+                            encoder.set_adaptive_filter(AdaptiveFilterType::Adaptive);
+                        }
+                        encoder.set_compression(Compression::Rle);
+                        encoder.set_bottom_up(bottom_up);
+                        let mut info = Info::with_size(5, 3);
+                        info.color_type = color_type;
+                        info.bit_depth = bit_depth;
+                        // Without the filter type byte of each row.
+                        let len = info.raw_bytes() - 3;
+                        let mut writer = encoder.write_header()?;
+                        writer.write_image_data(&pixels[..len])?;
+                        writer.finish()?;
+
+                        let decode = |transformations, bytes| {
+                            let limits = Limits { bytes };
+                            let mut decoder = Decoder::new_with_limits(&png[..], limits);
+                            decoder.set_transformations(transformations);
+                            decoder.set_bottom_up(bottom_up);
+                            let mut reader = decoder.read_info()?;
+                            let mut buf = vec![0; reader.output_buffer_size()];
+                            reader.next_frame(&mut buf)?;
+                            Ok::<_, crate::DecodingError>(buf)
+                        };
+                        let identity = decode(Transformations::IDENTITY, 1 << 20).unwrap();
+                        assert_eq!(identity, &pixels[..len]);
+                        let expanded = Transformations::EXPAND | Transformations::STRIP_16;
+                        decode(expanded, 1 << 20).unwrap();
+                        // A small limit either suffices or is reported, but does not break decoding.
+                        assert!(matches!(
+                            decode(expanded, 16),
+                            Ok(_) | Err(crate::DecodingError::LimitsExceeded)
+                        ));
+                    }
+                }
+            }
         }
+        Ok(())
+    }
+
+    #[test]
+    fn phase_timings() -> Result<()> {
+        let writer = Encoder::new(Vec::new(), 4, 3).write_header()?;
+        assert_eq!(writer.phase_timings(), None);
+        writer.finish()?;
+
+        let mut encoder = Encoder::new(Vec::new(), 64, 64);
+        encoder.set_phase_timings(true);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&[7; 64 * 64])?;
+        let timings = writer.phase_timings().unwrap();
+        let stats = writer.encoding_stats();
+        assert_eq!(timings.filter, stats.filter_time);
+        assert_eq!(timings.deflate, stats.compression_time);
+        assert!(timings.total() >= timings.write);
+        assert_eq!(timings.read, Duration::from_secs(0));
+        assert_eq!(
+            timings.total(),
+            timings.filter + timings.deflate + timings.write
+        );
+        Ok(())
     }
 }
@@ -0,0 +1,356 @@
+//! The most common fields of the Exif data of the `eXIf` chunk.
+//!
+//! This module is only built with the `exif` feature. It reads the orientation, the timestamps and
+//! the GPS position from the TIFF structure of the data in [`Info::exif_metadata`], other tags are
+//! skipped. The raw data remains available for a complete Exif library.
+//!
+//! The reader is part of this crate instead of going through `kamadak-exif`. The few tags read
+//! only need the TIFF header and two image file directories, so the feature adds no dependency.
+//!
+//! [`Info::exif_metadata`]: crate::Info::exif_metadata
+use std::fmt;
+
+/// The fields read from Exif data by [`Exif::parse`].
+#[derive(Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct Exif {
+    /// The `Orientation` tag, from 1 for upright to 8, as in the TIFF specification.
+    pub orientation: Option<u16>,
+    /// The `DateTime` tag of the last change, formatted as `YYYY:MM:DD HH:MM:SS`.
+    pub date_time: Option<String>,
+    /// The `DateTimeOriginal` tag of when the image was taken.
+    pub date_time_original: Option<String>,
+    /// The `DateTimeDigitized` tag of when the image was stored digitally.
+    pub date_time_digitized: Option<String>,
+    /// The position of the GPS tags, if they contain a latitude and a longitude.
+    pub gps: Option<GpsPosition>,
+}
+
+/// A position on the earth from the GPS tags of Exif data.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GpsPosition {
+    /// The latitude in degrees, negative in the south.
+    pub latitude: f64,
+    /// The longitude in degrees, negative in the west.
+    pub longitude: f64,
+    /// The altitude in meters, negative below sea level.
+    pub altitude: Option<f64>,
+}
+
+/// Exif data that could not be read by [`Exif::parse`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ExifError {
+    /// The data does not start with a TIFF header.
+    InvalidHeader,
+    /// A directory or a value ends after the data.
+    Truncated,
+}
+
+impl fmt::Display for ExifError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExifError::InvalidHeader => write!(fmt, "the Exif data has no TIFF header"),
+            ExifError::Truncated => write!(fmt, "the Exif data is truncated"),
+        }
+    }
+}
+
+impl std::error::Error for ExifError {}
+
+const ORIENTATION: u16 = 0x0112;
+const DATE_TIME: u16 = 0x0132;
+const EXIF_IFD: u16 = 0x8769;
+const GPS_IFD: u16 = 0x8825;
+const DATE_TIME_ORIGINAL: u16 = 0x9003;
+const DATE_TIME_DIGITIZED: u16 = 0x9004;
+const GPS_LATITUDE_REF: u16 = 1;
+const GPS_LATITUDE: u16 = 2;
+const GPS_LONGITUDE_REF: u16 = 3;
+const GPS_LONGITUDE: u16 = 4;
+const GPS_ALTITUDE_REF: u16 = 5;
+const GPS_ALTITUDE: u16 = 6;
+
+impl Exif {
+    /// Reads the fields from Exif data, which starts with the byte order mark of a TIFF header.
+    ///
+    /// ```
+    /// // A big endian header and a directory with the orientation 6.
+    /// let data = b"MM\0\x2a\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01\0\x06\0\0\0\0\0\0";
+    /// let exif = png::exif::Exif::parse(data).unwrap();
+    /// assert_eq!(exif.orientation, Some(6));
+    /// ```
+    pub fn parse(data: &[u8]) -> Result<Exif, ExifError> {
+        let tiff = Tiff::new(data)?;
+        let mut exif = Exif::default();
+        let mut exif_ifd = None;
+        let mut gps_ifd = None;
+        for entry in tiff.directory(tiff.u32(4)?)? {
+            match entry.tag {
+                ORIENTATION => exif.orientation = tiff.unsigned(&entry)?.map(|v| v as u16),
+                DATE_TIME => exif.date_time = tiff.ascii(&entry)?,
+                EXIF_IFD => exif_ifd = tiff.unsigned(&entry)?,
+                GPS_IFD => gps_ifd = tiff.unsigned(&entry)?,
+                _ => {}
+            }
+        }
+
+        if let Some(offset) = exif_ifd {
+            for entry in tiff.directory(offset)? {
+                match entry.tag {
+                    DATE_TIME_ORIGINAL => exif.date_time_original = tiff.ascii(&entry)?,
+                    DATE_TIME_DIGITIZED => exif.date_time_digitized = tiff.ascii(&entry)?,
+                    _ => {}
+                }
+            }
+        }
+
+        if let Some(offset) = gps_ifd {
+            let (mut latitude, mut longitude, mut altitude) = (None, None, None);
+            let (mut south, mut west, mut below) = (false, false, false);
+            for entry in tiff.directory(offset)? {
+                match entry.tag {
+                    GPS_LATITUDE_REF => south = tiff.ascii(&entry)?.as_deref() == Some("S"),
+                    GPS_LATITUDE => latitude = tiff.degrees(&entry)?,
+                    GPS_LONGITUDE_REF => west = tiff.ascii(&entry)?.as_deref() == Some("W"),
+                    GPS_LONGITUDE => longitude = tiff.degrees(&entry)?,
+                    GPS_ALTITUDE_REF => below = tiff.unsigned(&entry)? == Some(1),
+                    GPS_ALTITUDE => altitude = tiff.rationals(&entry)?.first().copied(),
+                    _ => {}
+                }
+            }
+            let sign = |negative: bool, value: f64| if negative { -value } else { value };
+            if let (Some(latitude), Some(longitude)) = (latitude, longitude) {
+                exif.gps = Some(GpsPosition {
+                    latitude: sign(south, latitude),
+                    longitude: sign(west, longitude),
+                    altitude: altitude.map(|altitude| sign(below, altitude)),
+                });
+            }
+        }
+        Ok(exif)
+    }
+}
+
+/// An entry of a TIFF directory.
+struct Entry {
+    tag: u16,
+    kind: u16,
+    count: u32,
+    /// The position of the value, or of its offset if it does not fit into four bytes.
+    pos: usize,
+}
+
+/// The TIFF structure of Exif data.
+struct Tiff<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Result<Self, ExifError> {
+        let big_endian = match data.get(..4) {
+            Some(b"MM\0\x2a") => true,
+            Some(b"II\x2a\0") => false,
+            _ => return Err(ExifError::InvalidHeader),
+        };
+        Ok(Tiff { data, big_endian })
+    }
+
+    fn bytes(&self, pos: usize, len: usize) -> Result<&'a [u8], ExifError> {
+        pos.checked_add(len)
+            .and_then(|end| self.data.get(pos..end))
+            .ok_or(ExifError::Truncated)
+    }
+
+    fn u16(&self, pos: usize) -> Result<u16, ExifError> {
+        let bytes = self.bytes(pos, 2)?;
+        let bytes = [bytes[0], bytes[1]];
+        Ok(if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, pos: usize) -> Result<u32, ExifError> {
+        let bytes = self.bytes(pos, 4)?;
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        Ok(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    /// The entries of the directory at `offset`, the link to a next directory is ignored.
+    fn directory(&self, offset: u32) -> Result<Vec<Entry>, ExifError> {
+        let offset = offset as usize;
+        let count = usize::from(self.u16(offset)?);
+        // Check the whole directory at once.
+        self.bytes(offset + 2, count * 12)?;
+        (0..count)
+            .map(|i| {
+                let pos = offset + 2 + i * 12;
+                Ok(Entry {
+                    tag: self.u16(pos)?,
+                    kind: self.u16(pos + 2)?,
+                    count: self.u32(pos + 4)?,
+                    pos: pos + 8,
+                })
+            })
+            .collect()
+    }
+
+    /// The position and length of the value of an entry, or `None` if its type is unknown.
+    fn value(&self, entry: &Entry) -> Result<Option<(usize, usize)>, ExifError> {
+        let size = match entry.kind {
+            // BYTE, ASCII, SBYTE and UNDEFINED.
+            1 | 2 | 6 | 7 => 1,
+            // SHORT and SSHORT.
+            3 | 8 => 2,
+            // LONG, SLONG and FLOAT.
+            4 | 9 | 11 => 4,
+            // RATIONAL, SRATIONAL and DOUBLE.
+            5 | 10 | 12 => 8,
+            _ => return Ok(None),
+        };
+        let len = (entry.count as usize)
+            .checked_mul(size)
+            .ok_or(ExifError::Truncated)?;
+        let pos = if len <= 4 {
+            entry.pos
+        } else {
+            self.u32(entry.pos)? as usize
+        };
+        self.bytes(pos, len)?;
+        Ok(Some((pos, len)))
+    }
+
+    /// The first value of a BYTE, SHORT or LONG entry.
+    fn unsigned(&self, entry: &Entry) -> Result<Option<u32>, ExifError> {
+        if entry.count == 0 {
+            return Ok(None);
+        }
+        match entry.kind {
+            1 => Ok(Some(u32::from(self.bytes(entry.pos, 1)?[0]))),
+            3 => Ok(Some(u32::from(self.u16(entry.pos)?))),
+            4 => self.u32(entry.pos).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// The text of an ASCII entry, up to its first NUL.
+    fn ascii(&self, entry: &Entry) -> Result<Option<String>, ExifError> {
+        if entry.kind != 2 {
+            return Ok(None);
+        }
+        let bytes = match self.value(entry)? {
+            Some((pos, len)) => &self.data[pos..pos + len],
+            None => &[],
+        };
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        Ok(Some(String::from_utf8_lossy(&bytes[..end]).into_owned()))
+    }
+
+    /// The values of a RATIONAL entry, skipping those with a zero denominator.
+    fn rationals(&self, entry: &Entry) -> Result<Vec<f64>, ExifError> {
+        if entry.kind != 5 {
+            return Ok(Vec::new());
+        }
+        let (start, len) = self.value(entry)?.unwrap_or((0, 0));
+        let mut values = Vec::with_capacity(len / 8);
+        for i in 0..len / 8 {
+            let numerator = self.u32(start + i * 8)?;
+            let denominator = self.u32(start + i * 8 + 4)?;
+            if denominator != 0 {
+                values.push(f64::from(numerator) / f64::from(denominator));
+            }
+        }
+        Ok(values)
+    }
+
+    /// An angle of degrees, minutes and seconds in a RATIONAL entry.
+    fn degrees(&self, entry: &Entry) -> Result<Option<f64>, ExifError> {
+        match self.rationals(entry)?[..] {
+            [degrees, minutes, seconds] => Ok(Some(degrees + minutes / 60.0 + seconds / 3600.0)),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds little endian Exif data of an orientation, a time and a GPS position.
+    fn exif_data() -> Vec<u8> {
+        let mut data = b"II\x2a\0\x08\0\0\0".to_vec();
+        let entry = |data: &mut Vec<u8>, tag: u16, kind: u16, count: u32, value: u32| {
+            data.extend_from_slice(&tag.to_le_bytes());
+            data.extend_from_slice(&kind.to_le_bytes());
+            data.extend_from_slice(&count.to_le_bytes());
+            data.extend_from_slice(&value.to_le_bytes());
+        };
+        // IFD0 at 8 with four entries, ending at 8 + 2 + 48 + 4 = 62.
+        data.extend_from_slice(&4u16.to_le_bytes());
+        entry(&mut data, ORIENTATION, 3, 1, 8);
+        entry(&mut data, DATE_TIME, 2, 20, 62);
+        entry(&mut data, EXIF_IFD, 4, 1, 82);
+        entry(&mut data, GPS_IFD, 4, 1, 120);
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(b"2024:02:29 12:30:00\0");
+        // Exif IFD at 82 with one entry, its value at 100.
+        data.extend_from_slice(&1u16.to_le_bytes());
+        entry(&mut data, DATE_TIME_ORIGINAL, 2, 20, 100);
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(b"2024:02:28 08:00:00\0");
+        // GPS IFD at 120 with five entries, its values at 186.
+        data.extend_from_slice(&5u16.to_le_bytes());
+        entry(&mut data, GPS_LATITUDE_REF, 2, 2, u32::from(b'S'));
+        entry(&mut data, GPS_LATITUDE, 5, 3, 186);
+        entry(&mut data, GPS_LONGITUDE_REF, 2, 2, u32::from(b'E'));
+        entry(&mut data, GPS_LONGITUDE, 5, 3, 210);
+        entry(&mut data, GPS_ALTITUDE, 5, 1, 234);
+        data.extend_from_slice(&[0; 4]);
+        for &(numerator, denominator) in &[
+            (33, 1),
+            (51, 1),
+            (3240, 100),
+            (151, 1),
+            (12, 1),
+            (0, 1),
+            (58, 2),
+        ] {
+            data.extend_from_slice(&u32::to_le_bytes(numerator));
+            data.extend_from_slice(&u32::to_le_bytes(denominator));
+        }
+        data
+    }
+
+    #[test]
+    fn parse_fields() {
+        let exif = Exif::parse(&exif_data()).unwrap();
+        assert_eq!(exif.orientation, Some(8));
+        assert_eq!(exif.date_time.as_deref(), Some("2024:02:29 12:30:00"));
+        assert_eq!(
+            exif.date_time_original.as_deref(),
+            Some("2024:02:28 08:00:00")
+        );
+        assert_eq!(exif.date_time_digitized, None);
+        let gps = exif.gps.unwrap();
+        assert!((gps.latitude + 33.859).abs() < 1e-9);
+        assert!((gps.longitude - 151.2).abs() < 1e-9);
+        assert_eq!(gps.altitude, Some(29.0));
+    }
+
+    #[test]
+    fn invalid_data() {
+        assert_eq!(Exif::parse(b"MM\0\x2b"), Err(ExifError::InvalidHeader));
+        let data = exif_data();
+        assert_eq!(Exif::parse(&data[..40]), Err(ExifError::Truncated));
+        assert_eq!(Exif::parse(&data[..200]), Err(ExifError::Truncated));
+        // An empty directory.
+        assert_eq!(Exif::parse(b"MM\0\x2a\0\0\0\x08\0\0"), Ok(Exif::default()));
+    }
+}
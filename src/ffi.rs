@@ -0,0 +1,284 @@
+//! A C interface to decode and encode images in memory.
+//!
+//! This module is only built with the `ffi` feature. A shared library is built with
+//! `cargo rustc --release --lib --features ffi --crate-type cdylib`, and the header with
+//! `cbindgen --config cbindgen.toml --output png.h` from the root of the crate.
+//!
+//! All functions return [`PNGRS_OK`] on success or one of the negative error codes. They never
+//! unwind into the caller. Buffers returned by [`pngrs_encode`] belong to the caller and must be
+//! released with [`pngrs_free`].
+#![allow(unsafe_code)]
+
+use std::os::raw::c_int;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::{ptr, slice};
+
+use crate::common::{BitDepth, ColorType, Info, Transformations};
+use crate::decoder::{Decoder, Reader};
+use crate::encoder::Encoder;
+
+/// The call succeeded.
+pub const PNGRS_OK: c_int = 0;
+/// A pointer was null or an argument was out of range.
+pub const PNGRS_INVALID_ARGUMENT: c_int = -1;
+/// The input could not be decoded.
+pub const PNGRS_DECODING_ERROR: c_int = -2;
+/// The pixels could not be encoded.
+pub const PNGRS_ENCODING_ERROR: c_int = -3;
+/// The output buffer is smaller than the decoded image.
+pub const PNGRS_BUFFER_TOO_SMALL: c_int = -4;
+/// An internal error occurred.
+pub const PNGRS_INTERNAL_ERROR: c_int = -5;
+
+/// The header and the main metadata of an image.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PngrsInfo {
+    /// The width of the image in pixels.
+    pub width: u32,
+    /// The height of the image in pixels.
+    pub height: u32,
+    /// The color type of the decoded pixels, as in the `IHDR` chunk.
+    pub color_type: u8,
+    /// The bit depth of the decoded samples.
+    pub bit_depth: u8,
+    /// Whether the image is interlaced.
+    pub interlaced: u8,
+    /// The rendering intent of the `sRGB` chunk, or `-1` without one.
+    pub srgb_intent: i8,
+    /// The gamma of the `gAMA` chunk, scaled by 100000, or `0` without one.
+    pub gamma: u32,
+    /// The number of animation frames, or `0` for a still image.
+    pub frames: u32,
+    /// The number of bytes of a decoded row.
+    pub line_size: usize,
+    /// The number of bytes of the decoded image.
+    pub buffer_size: usize,
+}
+
+impl PngrsInfo {
+    fn new<R: std::io::Read>(reader: &Reader<R>) -> Self {
+        let info: &Info = reader.info();
+        let (color_type, bit_depth) = reader.output_color_type();
+        PngrsInfo {
+            width: info.width,
+            height: info.height,
+            color_type: color_type as u8,
+            bit_depth: bit_depth as u8,
+            interlaced: info.interlaced as u8,
+            srgb_intent: info.srgb.map_or(-1, |intent| u8::from(intent) as i8),
+            gamma: info.source_gamma.map_or(0, |gamma| gamma.into_scaled()),
+            frames: info.animation_control.map_or(0, |actl| actl.num_frames),
+            line_size: reader.output_line_size(info.width),
+            buffer_size: reader.output_buffer_size(),
+        }
+    }
+}
+
+/// Runs `f`, turning a panic into an error code.
+fn guard(f: impl FnOnce() -> c_int) -> c_int {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or(PNGRS_INTERNAL_ERROR)
+}
+
+/// Reads the header and metadata of the PNG file in `data` into `info`.
+///
+/// `transformations` are the bits of [`Transformations`] and determine the color type, bit depth
+/// and buffer size reported for decoding.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes and `info` to a writable `PngrsInfo`.
+#[no_mangle]
+pub unsafe extern "C" fn pngrs_read_info(
+    data: *const u8,
+    len: usize,
+    transformations: u32,
+    info: *mut PngrsInfo,
+) -> c_int {
+    if data.is_null() || info.is_null() {
+        return PNGRS_INVALID_ARGUMENT;
+    }
+    let data = slice::from_raw_parts(data, len);
+    guard(|| match reader(data, transformations) {
+        Ok(reader) => {
+            ptr::write(info, PngrsInfo::new(&reader));
+            PNGRS_OK
+        }
+        Err(code) => code,
+    })
+}
+
+/// Decodes the first frame of the PNG file in `data` into `buf` and fills `info`.
+///
+/// The buffer must hold at least the `buffer_size` reported by [`pngrs_read_info`] for the same
+/// `transformations`. `info` may be null.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes, `buf` to `buf_len` writable bytes and `info` to a
+/// writable `PngrsInfo` unless it is null.
+#[no_mangle]
+pub unsafe extern "C" fn pngrs_decode(
+    data: *const u8,
+    len: usize,
+    transformations: u32,
+    buf: *mut u8,
+    buf_len: usize,
+    info: *mut PngrsInfo,
+) -> c_int {
+    if data.is_null() || buf.is_null() {
+        return PNGRS_INVALID_ARGUMENT;
+    }
+    let data = slice::from_raw_parts(data, len);
+    let buf = slice::from_raw_parts_mut(buf, buf_len);
+    guard(|| {
+        let mut reader = match reader(data, transformations) {
+            Ok(reader) => reader,
+            Err(code) => return code,
+        };
+        let decoded = PngrsInfo::new(&reader);
+        if buf.len() < decoded.buffer_size {
+            return PNGRS_BUFFER_TOO_SMALL;
+        }
+        if reader.next_frame(buf).is_err() {
+            return PNGRS_DECODING_ERROR;
+        }
+        if !info.is_null() {
+            ptr::write(info, decoded);
+        }
+        PNGRS_OK
+    })
+}
+
+/// Encodes `width` by `height` pixels of the given color type and bit depth as a PNG file.
+///
+/// On success `out` and `out_len` receive the encoded file, which must be released with
+/// [`pngrs_free`]. Indexed images are not supported as they need a palette.
+///
+/// # Safety
+///
+/// `pixels` must point to `len` readable bytes, and `out` and `out_len` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn pngrs_encode(
+    pixels: *const u8,
+    len: usize,
+    width: u32,
+    height: u32,
+    color_type: u8,
+    bit_depth: u8,
+    out: *mut *mut u8,
+    out_len: *mut usize,
+) -> c_int {
+    if pixels.is_null() || out.is_null() || out_len.is_null() {
+        return PNGRS_INVALID_ARGUMENT;
+    }
+    let (color_type, bit_depth) =
+        match (ColorType::from_u8(color_type), BitDepth::from_u8(bit_depth)) {
+            (Some(ColorType::Indexed), _) | (None, _) | (_, None) => return PNGRS_INVALID_ARGUMENT,
+            (Some(color_type), Some(bit_depth)) => (color_type, bit_depth),
+        };
+    let pixels = slice::from_raw_parts(pixels, len);
+    guard(|| {
+        let mut png = Vec::new();
+        let mut encoder = Encoder::new(&mut png, width, height);
+        encoder.set_color(color_type);
+        encoder.set_depth(bit_depth);
+        let written = encoder.write_header().and_then(|mut writer| {
+            writer
+                .write_image_data(pixels)
+                .and_then(|_| writer.finish())
+        });
+        if written.is_err() {
+            return PNGRS_ENCODING_ERROR;
+        }
+
+        let png = png.into_boxed_slice();
+        ptr::write(out_len, png.len());
+        ptr::write(out, Box::into_raw(png) as *mut u8);
+        PNGRS_OK
+    })
+}
+
+/// Releases a buffer returned by [`pngrs_encode`]. Null pointers are ignored.
+///
+/// # Safety
+///
+/// `buf` and `len` must have been returned by [`pngrs_encode`] and not been released before.
+#[no_mangle]
+pub unsafe extern "C" fn pngrs_free(buf: *mut u8, len: usize) {
+    if !buf.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buf, len)));
+    }
+}
+
+fn reader(data: &[u8], transformations: u32) -> Result<Reader<&[u8]>, c_int> {
+    let transformations =
+        Transformations::from_bits(transformations).ok_or(PNGRS_INVALID_ARGUMENT)?;
+    let mut decoder = Decoder::new(data);
+    decoder.set_transformations(transformations);
+    decoder.read_info().map_err(|_| PNGRS_DECODING_ERROR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let pixels: Vec<u8> = (0..48).collect();
+        let (mut out, mut out_len) = (ptr::null_mut(), 0);
+        let code = unsafe { pngrs_encode(pixels.as_ptr(), 48, 4, 4, 2, 8, &mut out, &mut out_len) };
+        assert_eq!(code, PNGRS_OK);
+        let png = unsafe { slice::from_raw_parts(out, out_len) };
+
+        let mut info = PngrsInfo::default();
+        let code = unsafe { pngrs_read_info(png.as_ptr(), png.len(), 0, &mut info) };
+        assert_eq!(code, PNGRS_OK);
+        assert_eq!(
+            (info.width, info.height, info.color_type, info.bit_depth),
+            (4, 4, 2, 8)
+        );
+        assert_eq!((info.line_size, info.buffer_size), (12, 48));
+
+        let mut buf = vec![0; info.buffer_size];
+        let code = unsafe {
+            pngrs_decode(
+                png.as_ptr(),
+                png.len(),
+                0,
+                buf.as_mut_ptr(),
+                47,
+                ptr::null_mut(),
+            )
+        };
+        assert_eq!(code, PNGRS_BUFFER_TOO_SMALL);
+        let code = unsafe {
+            pngrs_decode(
+                png.as_ptr(),
+                png.len(),
+                0,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut info,
+            )
+        };
+        assert_eq!(code, PNGRS_OK);
+        assert_eq!(buf, pixels);
+        unsafe { pngrs_free(out, out_len) };
+    }
+
+    #[test]
+    fn errors() {
+        let code = unsafe { pngrs_read_info(b"GIF89a".as_ptr(), 6, 0, &mut PngrsInfo::default()) };
+        assert_eq!(code, PNGRS_DECODING_ERROR);
+        let code = unsafe { pngrs_read_info(ptr::null(), 0, 0, &mut PngrsInfo::default()) };
+        assert_eq!(code, PNGRS_INVALID_ARGUMENT);
+
+        let (mut out, mut out_len) = (ptr::null_mut(), 0);
+        let code = unsafe { pngrs_encode([0].as_ptr(), 1, 1, 1, 3, 8, &mut out, &mut out_len) };
+        assert_eq!(code, PNGRS_INVALID_ARGUMENT);
+        let code = unsafe { pngrs_encode([0].as_ptr(), 1, 2, 1, 0, 8, &mut out, &mut out_len) };
+        assert_eq!(code, PNGRS_ENCODING_ERROR);
+        assert!(out.is_null());
+    }
+}
@@ -1,3 +1,22 @@
+//! The scanline filters of PNG.
+//!
+//! The functions work on single rows of raw image data, without the leading filter type byte.
+//! The previous row is the unfiltered row above, or all zeros for the first row of an image or
+//! interlacing pass.
+//!
+//! ```
+//! use png::filter::{filter, unfilter, AdaptiveFilterType, FilterType};
+//! use png::BytesPerPixel;
+//!
+//! let previous = [10, 20, 30, 40, 50, 60];
+//! let row = [11, 22, 33, 44, 55, 66];
+//! let mut current = row;
+//! let bpp = BytesPerPixel::Three;
+//! let kind = filter(FilterType::Paeth, AdaptiveFilterType::NonAdaptive, bpp, &previous, &mut current);
+//! assert_eq!(kind, FilterType::Paeth);
+//! unfilter(kind, bpp, &previous, &mut current).unwrap();
+//! assert_eq!(current, row);
+//! ```
 use crate::common::BytesPerPixel;
 
 /// The byte level filter applied to scanlines to prepare them for compression.
@@ -74,7 +93,11 @@ fn filter_paeth(a: u8, b: u8, c: u8) -> u8 {
     }
 }
 
-pub(crate) fn unfilter(
+/// Reverses the filter of a row in place.
+///
+/// Fails if `previous` is shorter than `current` for filters that refer to it, or if the row is
+/// shorter than a pixel for the average filter.
+pub fn unfilter(
     filter: FilterType,
     tbpp: BytesPerPixel,
     previous: &[u8],
@@ -256,7 +279,16 @@ fn filter_internal(
     }
 }
 
-pub(crate) fn filter(
+/// Filters a row in place and returns the filter type that was applied.
+///
+/// With [`AdaptiveFilterType::Adaptive`] the filter type that is expected to compress best is
+/// chosen, otherwise `method` is applied.
+///
+/// # Panics
+///
+/// If `previous` is shorter than `current` and the filter refers to it, or if the row is shorter
+/// than a pixel.
+pub fn filter(
     method: FilterType,
     adaptive: AdaptiveFilterType,
     bpp: BytesPerPixel,
@@ -293,6 +325,11 @@ pub(crate) fn filter(
             }
 
             current.copy_from_slice(&filtered_buffer);
+            trace!(
+                "adaptive filter chose {:?} with sum {}",
+                filter_choice,
+                min_sum
+            );
 
             filter_choice
         }
@@ -397,6 +434,49 @@ mod test {
         }
     }
 
+    #[test]
+    fn adaptive_roundtrip() {
+        let previous: Vec<u8> = (0..96).map(|i| (i * 7) as u8).collect();
+        let row: Vec<u8> = (0..96).map(|i| (i * i) as u8).collect();
+        for &len in &[1, 2, 3, 4, 6, 8] {
+            let bpp = BytesPerPixel::from_usize(len).unwrap();
+            assert_eq!(bpp.into_usize(), len);
+            let mut current = row.clone();
+            let kind = filter(
+                FilterType::NoFilter,
+                AdaptiveFilterType::Adaptive,
+                bpp,
+                &previous,
+                &mut current,
+            );
+            unfilter(kind, bpp, &previous, &mut current).unwrap();
+            assert_eq!(current, row, "{:?} with {:?}", kind, bpp);
+        }
+        for &len in &[0, 5, 7, 9] {
+            assert_eq!(BytesPerPixel::from_usize(len), None);
+        }
+    }
+
+    #[test]
+    fn unfilter_short_rows() {
+        let mut current = [1, 2, 3, 4];
+        let bpp = BytesPerPixel::Two;
+        for &kind in &[FilterType::Up, FilterType::Avg, FilterType::Paeth] {
+            assert!(unfilter(kind, bpp, &[0; 3], &mut current).is_err());
+        }
+        // The filters without the previous row don't need it.
+        unfilter(FilterType::NoFilter, bpp, &[], &mut current).unwrap();
+        unfilter(FilterType::Sub, bpp, &[], &mut current).unwrap();
+        assert_eq!(current, [1, 2, 4, 6]);
+        assert!(unfilter(
+            FilterType::Avg,
+            BytesPerPixel::Four,
+            &[0; 2],
+            &mut current[..2]
+        )
+        .is_err());
+    }
+
     #[test]
     // This tests that converting u8 to i8 doesn't overflow when taking the
     // absolute value for adaptive filtering: -128_i8.abs() will panic in debug
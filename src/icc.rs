@@ -0,0 +1,193 @@
+//! A summary of the header and description of an ICC profile.
+//!
+//! This reads just enough of the profile in [`Info::icc_profile`](crate::Info::icc_profile) to
+//! show which color space an image is in, for example "Display P3". Converting colors with the
+//! profile requires a color management system.
+use crate::common::SrgbRenderingIntent;
+
+/// The fields of an ICC profile read by [`IccSummary::parse`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct IccSummary {
+    /// The major and minor version of the profile format, such as `(4, 3)`.
+    pub version: (u8, u8),
+    /// The class of the device of the profile, such as `*b"mntr"` for displays.
+    pub device_class: [u8; 4],
+    /// The color space of the image data.
+    pub color_space: IccColorSpace,
+    /// The profile connection space, [`IccColorSpace::Xyz`] or [`IccColorSpace::Lab`].
+    pub connection_space: IccColorSpace,
+    /// The rendering intent of the header, `None` for an undefined value.
+    pub rendering_intent: Option<SrgbRenderingIntent>,
+    /// The text of the `desc` tag, in English if the profile has several languages.
+    pub description: Option<String>,
+}
+
+/// A color space signature of an ICC profile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum IccColorSpace {
+    /// `GRAY`.
+    Gray,
+    /// `RGB `.
+    Rgb,
+    /// `CMYK`.
+    Cmyk,
+    /// `Lab `, CIE L\*a\*b\*.
+    Lab,
+    /// `XYZ `, CIE XYZ.
+    Xyz,
+    /// Any other signature.
+    Other([u8; 4]),
+}
+
+impl IccColorSpace {
+    fn from_signature(signature: [u8; 4]) -> Self {
+        match &signature {
+            b"GRAY" => IccColorSpace::Gray,
+            b"RGB " => IccColorSpace::Rgb,
+            b"CMYK" => IccColorSpace::Cmyk,
+            b"Lab " => IccColorSpace::Lab,
+            b"XYZ " => IccColorSpace::Xyz,
+            _ => IccColorSpace::Other(signature),
+        }
+    }
+}
+
+const HEADER_LEN: usize = 128;
+
+impl IccSummary {
+    /// Reads the summary of a profile, or returns `None` if it has no valid header.
+    ///
+    /// A description tag that is truncated or of an unknown type is left out.
+    pub fn parse(profile: &[u8]) -> Option<IccSummary> {
+        if profile.len() < HEADER_LEN || &profile[36..40] != b"acsp" {
+            return None;
+        }
+        let intent = read_u32(profile, 64)?;
+        Some(IccSummary {
+            version: (profile[8], profile[9] >> 4),
+            device_class: signature(profile, 12)?,
+            color_space: IccColorSpace::from_signature(signature(profile, 16)?),
+            connection_space: IccColorSpace::from_signature(signature(profile, 20)?),
+            rendering_intent: if intent <= 3 {
+                SrgbRenderingIntent::from_raw(intent as u8)
+            } else {
+                None
+            },
+            description: find_tag(profile, *b"desc").and_then(description),
+        })
+    }
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    let bytes = data.get(pos..pos.checked_add(4)?)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn signature(data: &[u8], pos: usize) -> Option<[u8; 4]> {
+    read_u32(data, pos).map(u32::to_be_bytes)
+}
+
+/// The data of a tag in the tag table after the header.
+fn find_tag(profile: &[u8], tag: [u8; 4]) -> Option<&[u8]> {
+    let count = read_u32(profile, HEADER_LEN)? as usize;
+    (0..count.min(profile.len() / 12))
+        .map(|i| HEADER_LEN + 4 + i * 12)
+        .find(|&pos| signature(profile, pos) == Some(tag))
+        .and_then(|pos| {
+            let offset = read_u32(profile, pos + 4)? as usize;
+            let size = read_u32(profile, pos + 8)? as usize;
+            profile.get(offset..offset.checked_add(size)?)
+        })
+}
+
+/// The text of a `textDescriptionType` or `multiLocalizedUnicodeType` tag.
+fn description(tag: &[u8]) -> Option<String> {
+    match &signature(tag, 0)? {
+        // ICC v2: an ASCII count including the NUL, and the text.
+        b"desc" => {
+            let len = read_u32(tag, 8)? as usize;
+            let text = tag.get(12..12usize.checked_add(len)?)?;
+            let end = text.iter().position(|&b| b == 0).unwrap_or(text.len());
+            Some(String::from_utf8_lossy(&text[..end]).into_owned())
+        }
+        // ICC v4: records of a language, a country, and the length and offset of UTF-16 text.
+        b"mluc" => {
+            let count = read_u32(tag, 8)? as usize;
+            let record_size = read_u32(tag, 12)? as usize;
+            let records: Vec<usize> = (0..count.min(tag.len() / 12))
+                .filter_map(|i| i.checked_mul(record_size)?.checked_add(16))
+                .filter(|&pos| pos < tag.len())
+                .collect();
+            let record = records
+                .iter()
+                .copied()
+                .find(|&pos| tag[pos..].starts_with(b"en"))
+                .or_else(|| records.first().copied())?;
+            let len = read_u32(tag, record + 4)? as usize;
+            let offset = read_u32(tag, record + 8)? as usize;
+            let text = tag.get(offset..offset.checked_add(len)?)?;
+            let units: Vec<u16> = text
+                .chunks_exact(2)
+                .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+                .collect();
+            Some(String::from_utf16_lossy(&units))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(version: u8, desc: &[u8]) -> Vec<u8> {
+        let mut profile = vec![0; HEADER_LEN];
+        profile[8] = version;
+        profile[9] = 0x30;
+        profile[12..16].copy_from_slice(b"mntr");
+        profile[16..20].copy_from_slice(b"RGB ");
+        profile[20..24].copy_from_slice(b"XYZ ");
+        profile[36..40].copy_from_slice(b"acsp");
+        profile[67] = 1;
+        // A tag table of one tag, directly followed by its data.
+        profile.extend_from_slice(&1u32.to_be_bytes());
+        profile.extend_from_slice(b"desc");
+        profile.extend_from_slice(&(HEADER_LEN as u32 + 16).to_be_bytes());
+        profile.extend_from_slice(&(desc.len() as u32).to_be_bytes());
+        profile.extend_from_slice(desc);
+        profile
+    }
+
+    #[test]
+    fn summaries() {
+        let mut desc = b"desc\0\0\0\0\0\0\0\x0cDisplay P3\0\0".to_vec();
+        let summary = IccSummary::parse(&profile(2, &desc)).unwrap();
+        assert_eq!(summary.version, (2, 3));
+        assert_eq!(&summary.device_class, b"mntr");
+        assert_eq!(summary.color_space, IccColorSpace::Rgb);
+        assert_eq!(summary.connection_space, IccColorSpace::Xyz);
+        assert_eq!(
+            summary.rendering_intent,
+            Some(SrgbRenderingIntent::RelativeColorimetric)
+        );
+        assert_eq!(summary.description.as_deref(), Some("Display P3"));
+
+        // A German and an English record, the English one is preferred.
+        let mut mluc = b"mluc\0\0\0\0\0\0\0\x02\0\0\0\x0c".to_vec();
+        mluc.extend_from_slice(b"deDE\0\0\0\x04\0\0\0\x28");
+        mluc.extend_from_slice(b"enUS\0\0\0\x04\0\0\0\x2c");
+        mluc.extend_from_slice(b"\0G\0r\0E\0n");
+        let summary = IccSummary::parse(&profile(4, &mluc)).unwrap();
+        assert_eq!(summary.description.as_deref(), Some("En"));
+
+        // A truncated description is left out.
+        desc.truncate(14);
+        let summary = IccSummary::parse(&profile(2, &desc)).unwrap();
+        assert_eq!(summary.description, None);
+
+        assert_eq!(IccSummary::parse(&[0; HEADER_LEN]), None);
+        assert_eq!(IccSummary::parse(&profile(2, &desc)[..100]), None);
+    }
+}
@@ -58,25 +58,70 @@
 //! writer.write_image_data(&data).unwrap(); // Save
 //! ```
 //!
+//! ## Compression backends
+//!
+//! Image data is decompressed with `miniz_oxide` and compressed with the `deflate` crate by
+//! default. The `miniz-encoder` feature compresses with `miniz_oxide` as well, so a build has a
+//! single zlib implementation. Other backends such as `fdeflate`, `zlib-rs` or system zlib are
+//! not offered as features.
+//!
+//! Decompression has no feature to select a backend, as `miniz_oxide` is the only one the crate
+//! depends on. Another decompressor is plugged in at run time with [`Decoder::set_zlib_stream`],
+//! by implementing [`ZlibStream`] on top of it.
+//!
 
-#![forbid(unsafe_code)]
+#![cfg_attr(not(any(feature = "ffi", feature = "bytemuck")), forbid(unsafe_code))]
+#![cfg_attr(any(feature = "ffi", feature = "bytemuck"), deny(unsafe_code))]
 
 #[macro_use]
 extern crate bitflags;
 
+#[macro_use]
+mod trace;
+
+pub mod adam7;
 pub mod chunk;
 mod common;
+pub mod convert;
+#[cfg(feature = "data-url")]
+pub mod data_url;
 mod decoder;
+mod editor;
 mod encoder;
-mod filter;
-mod srgb;
+#[cfg(feature = "exif")]
+pub mod exif;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod filter;
+pub mod icc;
+pub mod pack;
+#[cfg(feature = "parallel")]
+pub mod parallel;
+#[cfg(feature = "bytemuck")]
+pub mod pixel;
+pub mod srgb;
 pub mod text_metadata;
 mod traits;
+mod transcode;
+pub mod unpack;
 mod utils;
+pub mod validate;
+mod zlib;
 
 pub use crate::{
     common::*,
-    decoder::{Decoded, Decoder, DecodingError, Limits, OutputInfo, Reader, StreamingDecoder},
-    encoder::{Encoder, EncodingError, StreamWriter, Writer},
+    decoder::{
+        read_exif, read_icc_profile, BufferProvider, ChunkContext, ColorDestination, ColorSource,
+        ColorTransform, Composite, CompressionStats, Decoded, Decoder, DecodingError,
+        ErrorSeverity, InterlaceInfo, Limits, LinearScale, OutputInfo, PushDecoder, PushEvent,
+        Reader, StreamingDecoder, ZlibStream,
+    },
+    editor::{AnimationEditor, AnimationFrame, ChunkEditor},
+    encoder::{
+        DepthReduction, Encoder, EncodingError, EncodingStats, FrameOptions, InfoBuilder,
+        LinearInput, OutputBuffer, PaletteOrder, SliceWriter, StreamWriter, StripAlpha, Writer,
+    },
     filter::{AdaptiveFilterType, FilterType},
+    transcode::{Transcoder, TranscodingError},
+    validate::validate,
 };
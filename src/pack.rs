@@ -0,0 +1,127 @@
+//! Packing samples of one byte each into the rows of low bit depths.
+//!
+//! This is the inverse of the [`unpack`](crate::unpack) module, to prepare input for the
+//! [`Encoder`](crate::Encoder) with a bit depth below eight from ordinary byte buffers.
+
+use crate::common::BitDepth;
+
+/// Packs the samples of `samples` into `out`, starting in the high-order bits of each byte.
+///
+/// Only the low bits of each sample are used, the values must already fit into the bit depth.
+/// The last byte is padded with zero bits. This packs as many samples as fit into `out` and
+/// returns the number of bytes written.
+///
+/// # Panics
+///
+/// If `bit_depth` is [`BitDepth::Sixteen`].
+///
+/// ```
+/// use png::pack::pack_bits;
+/// let mut out = [0; 2];
+/// assert_eq!(pack_bits(&[3, 1, 0, 2, 3], png::BitDepth::Two, &mut out), 2);
+/// assert_eq!(out, [0b1101_0010, 0b1100_0000]);
+/// ```
+pub fn pack_bits(samples: &[u8], bit_depth: BitDepth, out: &mut [u8]) -> usize {
+    pack_with(samples, bit_depth, out, |sample| sample)
+}
+
+/// Packs the samples of `samples` into `out` like [`pack_bits`], scaling them down from the full
+/// range of a byte.
+///
+/// Each sample is rounded to the nearest value of the bit depth, which for a bit depth of one is
+/// a threshold at `128`.
+///
+/// ```
+/// use png::pack::pack_bits_scaled;
+/// let mut out = [0; 1];
+/// pack_bits_scaled(&[255, 90, 20, 170], png::BitDepth::Two, &mut out);
+/// assert_eq!(out, [0b1101_0010]);
+/// ```
+pub fn pack_bits_scaled(samples: &[u8], bit_depth: BitDepth, out: &mut [u8]) -> usize {
+    let max = (1u32 << (bit_depth as u32).min(8)) - 1;
+    pack_with(samples, bit_depth, out, |sample| {
+        ((u32::from(sample) * max + 127) / 255) as u8
+    })
+}
+
+/// Packs the samples of `samples` into a row of one bit per sample, set for all samples of at
+/// least `threshold`.
+///
+/// ```
+/// use png::pack::pack_bits_threshold;
+/// let mut out = [0; 1];
+/// pack_bits_threshold(&[10, 200, 60, 80], 64, &mut out);
+/// assert_eq!(out, [0b0101_0000]);
+/// ```
+pub fn pack_bits_threshold(samples: &[u8], threshold: u8, out: &mut [u8]) -> usize {
+    pack_with(samples, BitDepth::One, out, |sample| {
+        (sample >= threshold) as u8
+    })
+}
+
+fn pack_with(samples: &[u8], bit_depth: BitDepth, out: &mut [u8], f: impl Fn(u8) -> u8) -> usize {
+    let bits = match bit_depth {
+        BitDepth::Eight => {
+            let len = samples.len().min(out.len());
+            for (out, &sample) in out.iter_mut().zip(samples) {
+                *out = f(sample);
+            }
+            return len;
+        }
+        BitDepth::Sixteen => panic!("Sixteen bit samples can not be packed from bytes"),
+        other => other as usize,
+    };
+    let mask = (1u8 << bits) - 1;
+    out.iter_mut()
+        .zip(samples.chunks(8 / bits))
+        .map(|(out, chunk)| {
+            *out = chunk.iter().enumerate().fold(0, |byte, (i, &sample)| {
+                byte | (f(sample) & mask) << (8 - bits * (i + 1))
+            });
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unpack::{unpack_bits, unpack_bits_scaled};
+
+    #[test]
+    fn roundtrip_unpack() {
+        let samples: Vec<u8> = (0..=255).collect();
+        for &bit_depth in &[
+            BitDepth::One,
+            BitDepth::Two,
+            BitDepth::Four,
+            BitDepth::Eight,
+        ] {
+            let max = (1u16 << bit_depth as u8) - 1;
+            let levels: Vec<u8> = samples
+                .iter()
+                .map(|&s| (u16::from(s) % (max + 1)) as u8)
+                .collect();
+            let mut packed = vec![0; 256 * bit_depth as usize / 8];
+            assert_eq!(pack_bits(&levels, bit_depth, &mut packed), packed.len());
+            let mut unpacked = vec![0; 256];
+            unpack_bits(&packed, bit_depth, &mut unpacked);
+            assert_eq!(unpacked, levels);
+
+            // Scaled samples of the bit depth survive being scaled down again.
+            let mut scaled = vec![0; 256];
+            unpack_bits_scaled(&packed, bit_depth, &mut scaled);
+            let mut repacked = vec![0; packed.len()];
+            pack_bits_scaled(&scaled, bit_depth, &mut repacked);
+            assert_eq!(repacked, packed);
+        }
+    }
+
+    #[test]
+    fn partial_bytes() {
+        let mut out = [0xff; 2];
+        assert_eq!(pack_bits(&[1, 0, 1], BitDepth::One, &mut out), 1);
+        assert_eq!(out, [0b1010_0000, 0xff]);
+        assert_eq!(pack_bits_threshold(&[0, 255], 128, &mut out[1..]), 1);
+        assert_eq!(out, [0b1010_0000, 0b0100_0000]);
+    }
+}
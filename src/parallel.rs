@@ -0,0 +1,132 @@
+//! The threads used for work that can be done concurrently.
+//!
+//! This module is only built with the `parallel` feature. Every concurrent part of the crate runs
+//! on an [`Executor`], which is chosen per decoder with
+//! [`Decoder::set_executor`](crate::Decoder::set_executor). An embedder can thereby pass one
+//! policy to all decoders, run everything on the calling thread, or cap the number of threads.
+//!
+//! Currently the passes of interlaced frames are unfiltered concurrently by
+//! [`Reader::next_frame`](crate::Reader::next_frame).
+use std::panic;
+use std::thread;
+
+/// How independent jobs are spread over threads.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Executor {
+    /// Run all jobs on the calling thread.
+    Sequential,
+    /// Spawn at most the given number of threads for each batch of jobs.
+    ///
+    /// A limit of `0` or `1` runs all jobs on the calling thread.
+    Threads(usize),
+    /// Run the jobs on the current rayon thread pool.
+    ///
+    /// This is the global pool, unless the decoder is called within `ThreadPool::install`. A
+    /// pool with fewer threads caps the threads used. Requires the `rayon` feature.
+    #[cfg(feature = "rayon")]
+    Rayon,
+}
+
+impl Executor {
+    /// The number of threads spawned by default without rayon.
+    pub const DEFAULT_THREADS: usize = 4;
+
+    /// Applies `f` to each job and returns the results in the order of the jobs.
+    ///
+    /// A panic of a job is resumed on the calling thread.
+    pub(crate) fn map<T, R>(self, jobs: Vec<T>, f: fn(T) -> R) -> Vec<R>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+    {
+        match self {
+            Executor::Sequential => jobs.into_iter().map(f).collect(),
+            Executor::Threads(max) => map_threads(max, jobs, f),
+            #[cfg(feature = "rayon")]
+            Executor::Rayon => {
+                use rayon::prelude::*;
+                jobs.into_par_iter().map(f).collect()
+            }
+        }
+    }
+}
+
+impl Default for Executor {
+    /// The rayon pool with the `rayon` feature, otherwise [`Executor::DEFAULT_THREADS`] threads.
+    fn default() -> Self {
+        #[cfg(feature = "rayon")]
+        {
+            Executor::Rayon
+        }
+        #[cfg(not(feature = "rayon"))]
+        {
+            Executor::Threads(Executor::DEFAULT_THREADS)
+        }
+    }
+}
+
+fn map_threads<T, R>(max: usize, jobs: Vec<T>, f: fn(T) -> R) -> Vec<R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+{
+    let threads = max.min(jobs.len());
+    if threads <= 1 {
+        return jobs.into_iter().map(f).collect();
+    }
+
+    let len = jobs.len();
+    let mut batches: Vec<Vec<(usize, T)>> = (0..threads).map(|_| Vec::new()).collect();
+    for (index, job) in jobs.into_iter().enumerate() {
+        batches[index % threads].push((index, job));
+    }
+    let handles: Vec<_> = batches
+        .into_iter()
+        .map(|batch| {
+            thread::spawn(move || {
+                batch
+                    .into_iter()
+                    .map(|(index, job)| (index, f(job)))
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+
+    let mut results: Vec<Option<R>> = (0..len).map(|_| None).collect();
+    for handle in handles {
+        match handle.join() {
+            Ok(done) => {
+                for (index, result) in done {
+                    results[index] = Some(result);
+                }
+            }
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+    results
+        .into_iter()
+        .map(|result| result.expect("every job has a result"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_in_order() {
+        let jobs: Vec<u32> = (0..23).collect();
+        let expected: Vec<u32> = jobs.iter().map(|job| job * job).collect();
+        let mut executors = vec![
+            Executor::Sequential,
+            Executor::Threads(0),
+            Executor::Threads(3),
+            Executor::Threads(64),
+        ];
+        executors.push(Executor::default());
+        for executor in executors {
+            assert_eq!(executor.map(jobs.clone(), |job| job * job), expected);
+        }
+    }
+}
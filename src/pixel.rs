@@ -0,0 +1,157 @@
+//! Typed pixels for image buffers, based on `bytemuck`.
+//!
+//! This module is only built with the `bytemuck` feature. The pixel types are [`bytemuck::Pod`],
+//! so byte buffers can be reinterpreted with `bytemuck::cast_slice` without copying. Samples of
+//! 16 bits are stored big-endian in PNG buffers, so a cast gives byte-swapped values on little
+//! endian targets. [`Reader::next_frame_pixels`](crate::Reader::next_frame_pixels) and
+//! [`Writer::write_pixels`](crate::Writer::write_pixels) convert them to and from native
+//! endianness.
+//!
+//! ```
+//! use png::pixel::Rgb;
+//!
+//! let decoder = png::Decoder::new(std::fs::File::open("tests/pngsuite/basn2c16.png").unwrap());
+//! let mut reader = decoder.read_info().unwrap();
+//! let mut pixels = vec![Rgb::<u16>::default(); 32 * 32];
+//! reader.next_frame_pixels(&mut pixels).unwrap();
+//! assert_eq!(pixels[0], Rgb { r: 0xffff, g: 0xffff, b: 0 });
+//! ```
+use bytemuck::{Pod, Zeroable};
+
+use crate::common::{BitDepth, ColorType};
+
+/// A sample type of pixels.
+pub trait Sample: Pod {
+    /// The bit depth of the sample.
+    const DEPTH: BitDepth;
+
+    /// Converts big-endian samples to native endianness in place, or back.
+    fn swap_big_endian(samples: &mut [Self]);
+}
+
+impl Sample for u8 {
+    const DEPTH: BitDepth = BitDepth::Eight;
+
+    fn swap_big_endian(_: &mut [u8]) {}
+}
+
+impl Sample for u16 {
+    const DEPTH: BitDepth = BitDepth::Sixteen;
+
+    fn swap_big_endian(samples: &mut [u16]) {
+        for sample in samples {
+            *sample = u16::from_be(*sample);
+        }
+    }
+}
+
+/// A pixel of a color type with samples of type `Sample`.
+pub trait Pixel: Pod {
+    /// The color type of the pixel.
+    const COLOR_TYPE: ColorType;
+    /// The type of each sample.
+    type Sample: Sample;
+}
+
+macro_rules! pixel {
+    ($(#[$attr:meta])* $name:ident, $color:ident, $($field:ident),+) => {
+        $(#[$attr])*
+        #[repr(C)]
+        #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+        pub struct $name<T> {
+            $(
+                #[allow(missing_docs)]
+                pub $field: T,
+            )+
+        }
+
+        // Safety: the struct is `repr(C)` with fields of a single `Pod` type, so it has no
+        // padding and every bit pattern is valid.
+        #[allow(unsafe_code)]
+        unsafe impl<T: Zeroable> Zeroable for $name<T> {}
+        #[allow(unsafe_code)]
+        unsafe impl<T: Pod> Pod for $name<T> {}
+
+        impl<T: Sample> Pixel for $name<T> {
+            const COLOR_TYPE: ColorType = ColorType::$color;
+            type Sample = T;
+        }
+    };
+}
+
+pixel!(
+    /// A grayscale pixel.
+    Gray,
+    Grayscale,
+    v
+);
+pixel!(
+    /// A grayscale pixel with alpha.
+    GrayAlpha,
+    GrayscaleAlpha,
+    v,
+    a
+);
+pixel!(
+    /// An RGB pixel.
+    Rgb,
+    Rgb,
+    r,
+    g,
+    b
+);
+pixel!(
+    /// An RGB pixel with alpha.
+    Rgba,
+    Rgba,
+    r,
+    g,
+    b,
+    a
+);
+
+/// Swaps the samples of `pixels` between big-endian and native endianness.
+pub(crate) fn swap_big_endian<P: Pixel>(pixels: &mut [P]) {
+    P::Sample::swap_big_endian(bytemuck::cast_slice_mut(pixels));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Decoder, Encoder};
+
+    #[test]
+    fn pixels_roundtrip() {
+        let pixels: Vec<Rgba<u16>> = (0..12u16)
+            .map(|i| Rgba {
+                r: i * 5000,
+                g: i,
+                b: 0xff00 | i,
+                a: 0xffff - i,
+            })
+            .collect();
+        let mut png = Vec::new();
+        {
+            let mut encoder = Encoder::new(&mut png, 4, 3);
+            encoder.set_color(ColorType::Rgba);
+            encoder.set_depth(BitDepth::Sixteen);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_pixels(&pixels).unwrap();
+        }
+
+        let mut reader = Decoder::new(&*png).read_info().unwrap();
+        let mut bytes = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut bytes).unwrap();
+        assert_eq!(&bytes[..4], &[0, 0, 0, 0]);
+        assert_eq!(&bytes[8..12], &[0x13, 0x88, 0, 1]);
+
+        let mut reader = Decoder::new(&*png).read_info().unwrap();
+        let mut decoded = vec![Rgba::default(); pixels.len()];
+        reader.next_frame_pixels(&mut decoded).unwrap();
+        assert_eq!(decoded, pixels);
+
+        let mut reader = Decoder::new(&*png).read_info().unwrap();
+        let mut wrong = vec![Rgba::<u8>::default(); pixels.len() * 2];
+        assert!(reader.next_frame_pixels(&mut wrong).is_err());
+    }
+}
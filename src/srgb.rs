@@ -1,3 +1,8 @@
+//! The transfer functions and substitute values of the sRGB color space.
+//!
+//! The functions convert between sRGB encoded values and linear light with the piecewise curve of
+//! IEC 61966-2-1, which differs noticeably from a plain gamma of 2.2 close to black.
+
 use crate::{ScaledFloat, SourceChromaticities};
 
 /// Get the gamma that should be substituted for images conforming to the sRGB color space.
@@ -28,3 +33,231 @@ pub fn substitute_chromaticities() -> SourceChromaticities {
         ),
     }
 }
+
+/// Converts an sRGB encoded value in `0.0..=1.0` to linear light, the electro-optical transfer
+/// function of the sRGB color space.
+///
+/// ```
+/// assert_eq!(png::srgb::eotf(0.0), 0.0);
+/// assert!((png::srgb::eotf(0.5) - 0.214).abs() < 0.001);
+/// ```
+pub fn eotf(encoded: f32) -> f32 {
+    eotf_f64(f64::from(encoded)) as f32
+}
+
+/// Converts a linear light value in `0.0..=1.0` to its sRGB encoding, the opto-electronic transfer
+/// function of the sRGB color space and the inverse of [`eotf`].
+pub fn oetf(linear: f32) -> f32 {
+    oetf_f64(f64::from(linear)) as f32
+}
+
+/// Converts all sRGB encoded values of a slice to linear light in place, see [`eotf`].
+pub fn decode_slice(values: &mut [f32]) {
+    values.iter_mut().for_each(|value| *value = eotf(*value));
+}
+
+/// Converts all linear light values of a slice to their sRGB encoding in place, see [`oetf`].
+pub fn encode_slice(values: &mut [f32]) {
+    values.iter_mut().for_each(|value| *value = oetf(*value));
+}
+
+pub(crate) fn eotf_f64(encoded: f64) -> f64 {
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub(crate) fn oetf_f64(linear: f64) -> f64 {
+    if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Maps the integer samples `0..=from_max` through a transfer function onto `0..=to_max`.
+fn table(from_max: u16, to_max: u16, transfer: fn(f64) -> f64) -> Vec<u16> {
+    (0..=from_max)
+        .map(|sample| {
+            let value = transfer(f64::from(sample) / f64::from(from_max));
+            (value * f64::from(to_max)).round() as u16
+        })
+        .collect()
+}
+
+/// Lookup tables between 8-bit sRGB encoded samples and 16-bit linear samples.
+#[derive(Clone, Debug)]
+pub struct Lut8 {
+    linear: Vec<u16>,
+    encoded: Vec<u8>,
+}
+
+impl Lut8 {
+    /// Compute both tables.
+    pub fn new() -> Self {
+        let encoded = table(u16::MAX, 255, oetf_f64);
+        Lut8 {
+            linear: table(255, u16::MAX, eotf_f64),
+            encoded: encoded.into_iter().map(|sample| sample as u8).collect(),
+        }
+    }
+
+    /// The linear sample of an encoded sample.
+    pub fn to_linear(&self, encoded: u8) -> u16 {
+        self.linear[usize::from(encoded)]
+    }
+
+    /// The encoded sample closest to a linear sample.
+    pub fn to_encoded(&self, linear: u16) -> u8 {
+        self.encoded[usize::from(linear)]
+    }
+
+    /// The table of the 256 linear samples, indexed by the encoded sample.
+    pub fn linear_table(&self) -> &[u16] {
+        &self.linear
+    }
+
+    /// The table of the 65536 encoded samples, indexed by the linear sample.
+    pub fn encoded_table(&self) -> &[u8] {
+        &self.encoded
+    }
+
+    /// Convert encoded samples to linear samples.
+    ///
+    /// # Panics
+    ///
+    /// If the slices have different lengths.
+    pub fn decode(&self, encoded: &[u8], linear: &mut [u16]) {
+        assert_eq!(encoded.len(), linear.len());
+        for (&sample, out) in encoded.iter().zip(linear) {
+            *out = self.to_linear(sample);
+        }
+    }
+
+    /// Convert linear samples to encoded samples.
+    ///
+    /// # Panics
+    ///
+    /// If the slices have different lengths.
+    pub fn encode(&self, linear: &[u16], encoded: &mut [u8]) {
+        assert_eq!(linear.len(), encoded.len());
+        for (&sample, out) in linear.iter().zip(encoded) {
+            *out = self.to_encoded(sample);
+        }
+    }
+}
+
+impl Default for Lut8 {
+    fn default() -> Self {
+        Lut8::new()
+    }
+}
+
+/// Lookup tables between 16-bit sRGB encoded samples and 16-bit linear samples.
+#[derive(Clone, Debug)]
+pub struct Lut16 {
+    linear: Vec<u16>,
+    encoded: Vec<u16>,
+}
+
+impl Lut16 {
+    /// Compute both tables.
+    pub fn new() -> Self {
+        Lut16 {
+            linear: table(u16::MAX, u16::MAX, eotf_f64),
+            encoded: table(u16::MAX, u16::MAX, oetf_f64),
+        }
+    }
+
+    /// The linear sample of an encoded sample.
+    pub fn to_linear(&self, encoded: u16) -> u16 {
+        self.linear[usize::from(encoded)]
+    }
+
+    /// The encoded sample closest to a linear sample.
+    pub fn to_encoded(&self, linear: u16) -> u16 {
+        self.encoded[usize::from(linear)]
+    }
+
+    /// The table of the 65536 linear samples, indexed by the encoded sample.
+    pub fn linear_table(&self) -> &[u16] {
+        &self.linear
+    }
+
+    /// The table of the 65536 encoded samples, indexed by the linear sample.
+    pub fn encoded_table(&self) -> &[u16] {
+        &self.encoded
+    }
+
+    /// Convert encoded samples to linear samples in place.
+    pub fn decode(&self, samples: &mut [u16]) {
+        samples
+            .iter_mut()
+            .for_each(|sample| *sample = self.to_linear(*sample));
+    }
+
+    /// Convert linear samples to encoded samples in place.
+    pub fn encode(&self, samples: &mut [u16]) {
+        samples
+            .iter_mut()
+            .for_each(|sample| *sample = self.to_encoded(*sample));
+    }
+}
+
+impl Default for Lut16 {
+    fn default() -> Self {
+        Lut16::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transfer_functions() {
+        for &(encoded, linear) in &[
+            (0.0, 0.0),
+            (0.04045, 0.003_130_8),
+            (0.5, 0.214_04),
+            (1.0, 1.0),
+        ] {
+            assert!((eotf(encoded) - linear).abs() < 1e-5);
+            assert!((oetf(linear) - encoded).abs() < 1e-5);
+        }
+
+        let mut values = [0.25, 0.75];
+        decode_slice(&mut values);
+        encode_slice(&mut values);
+        assert!((values[0] - 0.25).abs() < 1e-6 && (values[1] - 0.75).abs() < 1e-6);
+    }
+
+    #[test]
+    fn lookup_tables() {
+        let lut = Lut8::new();
+        assert_eq!(lut.linear_table().len(), 256);
+        assert_eq!(lut.encoded_table().len(), 1 << 16);
+        assert_eq!((lut.to_linear(0), lut.to_linear(255)), (0, u16::MAX));
+        assert_eq!(lut.to_linear(128), 14_146);
+        for encoded in 0..=255 {
+            assert_eq!(lut.to_encoded(lut.to_linear(encoded)), encoded);
+        }
+
+        let mut linear = [0; 3];
+        lut.decode(&[0, 128, 255], &mut linear);
+        let mut encoded = [0; 3];
+        lut.encode(&linear, &mut encoded);
+        assert_eq!(encoded, [0, 128, 255]);
+
+        let lut = Lut16::new();
+        let mut samples = [0, 1000, 40_000, u16::MAX];
+        lut.decode(&mut samples);
+        assert!(samples[1] < 1000 && samples[2] < 40_000);
+        lut.encode(&mut samples);
+        assert_eq!(samples[0], 0);
+        assert_eq!(samples[3], u16::MAX);
+        assert!((i32::from(samples[2]) - 40_000).abs() <= 2);
+    }
+}
@@ -5,15 +5,17 @@
 //! chunks. There are three kinds of text chunks.
 //!  -   `tEXt`: This has a `keyword` and `text` field, and is ISO 8859-1 encoded.
 //!  -   `zTXt`: This is semantically the same as `tEXt`, i.e. it has the same fields and
-//!       encoding, but the `text` field is compressed before being written into the PNG file.
+//!      encoding, but the `text` field is compressed before being written into the PNG file.
 //!  -   `iTXt`: This chunk allows for its `text` field to be any valid UTF-8, and supports
-//!        compression of the text field as well.
+//!      compression of the text field as well.
 //!
 //!  The `ISO 8859-1` encoding technically doesn't allow any control characters
 //!  to be used, but in practice these values are encountered anyway. This can
 //!  either be the extended `ISO-8859-1` encoding with control characters or the
 //!  `Windows-1252` encoding. This crate assumes the `ISO-8859-1` encoding is
-//!  used.
+//!  used. A stricter handling of these characters can be chosen with a [`Latin1Mode`], both for
+//!  the conversion functions [`decode_latin1`] and [`encode_latin1`] and while decoding, with
+//!  [`Decoder::set_latin1_mode`](crate::Decoder::set_latin1_mode).
 //!
 //!  ## Reading text chunks
 //!
@@ -102,14 +104,228 @@
 
 #![warn(missing_docs)]
 
-use crate::{chunk, encoder, DecodingError, EncodingError};
-use deflate::{write::ZlibEncoder, Compression};
-use miniz_oxide::inflate::{decompress_to_vec_zlib, decompress_to_vec_zlib_with_limit};
-use std::{convert::TryFrom, io::Write};
+use crate::zlib::ZlibEncoder;
+use crate::{chunk, encoder, Compression, DecodingError, EncodingError, Info};
+use miniz_oxide::inflate::decompress_to_vec_zlib_with_limit;
+use miniz_oxide::inflate::stream::{inflate, InflateState};
+use miniz_oxide::{DataFormat, MZError, MZFlush, MZStatus};
+use std::borrow::Cow;
+use std::io::{self, Cursor, Read, Write};
+use std::{convert::TryFrom, fmt};
 
 /// Default decompression limit for compressed text chunks.
 pub const DECOMPRESSION_LIMIT: usize = 2097152; // 2 MiB
 
+/// Default number of bytes from which [`Encoder::add_text`](crate::Encoder::add_text) considers
+/// compressing a text.
+pub const COMPRESSION_THRESHOLD: usize = 1024;
+
+/// The kind of chunk that [`Encoder::add_text`](crate::Encoder::add_text) writes a text as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextChunkKind {
+    /// A `tEXt` or `zTXt` chunk for Latin-1 text and an `iTXt` chunk for any other text. The text
+    /// is compressed if it is at least as long as the threshold and compression makes it smaller.
+    Auto,
+    /// Always a `tEXt` chunk.
+    Uncompressed,
+    /// Always a `zTXt` chunk.
+    Compressed,
+    /// Always an `iTXt` chunk, compressed by the same rule as for [`TextChunkKind::Auto`].
+    International,
+}
+
+impl Default for TextChunkKind {
+    fn default() -> Self {
+        TextChunkKind::Auto
+    }
+}
+
+/// The handling of characters that have no meaning in Latin-1 text.
+///
+/// The specification only defines the printable Latin-1 characters and the linefeed for `tEXt`
+/// and `zTXt` chunks, the other control characters and all characters above `U+00FF` are
+/// unrepresentable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Latin1Mode {
+    /// Every byte is decoded to the character of the same value, including control characters.
+    /// Only characters above `U+00FF` fail to encode. This is the default.
+    Verbatim,
+    /// Unrepresentable characters are an error.
+    Strict,
+    /// Unrepresentable characters are replaced, by `U+FFFD` when decoding and by `?` when
+    /// encoding.
+    Lossy,
+}
+
+impl Default for Latin1Mode {
+    fn default() -> Self {
+        Latin1Mode::Verbatim
+    }
+}
+
+/// Decodes the Latin-1 bytes of a `tEXt` or `zTXt` chunk.
+///
+/// ```
+/// use png::text_metadata::{decode_latin1, Latin1Mode};
+///
+/// assert_eq!(decode_latin1(b"Caf\xe9", Latin1Mode::Strict).unwrap(), "Café");
+/// assert!(decode_latin1(b"\x07", Latin1Mode::Strict).is_err());
+/// assert_eq!(decode_latin1(b"\x07", Latin1Mode::Lossy).unwrap(), "\u{fffd}");
+/// ```
+pub fn decode_latin1(bytes: &[u8], mode: Latin1Mode) -> Result<String, DecodingError> {
+    decode_latin1_with(bytes, mode).map_err(DecodingError::from)
+}
+
+/// Encodes a string to the Latin-1 bytes of a `tEXt` or `zTXt` chunk.
+///
+/// ```
+/// use png::text_metadata::{encode_latin1, Latin1Mode};
+///
+/// assert_eq!(encode_latin1("Café", Latin1Mode::Strict).unwrap(), b"Caf\xe9");
+/// assert!(encode_latin1("€", Latin1Mode::Verbatim).is_err());
+/// assert_eq!(encode_latin1("5 €", Latin1Mode::Lossy).unwrap(), b"5 ?");
+/// ```
+pub fn encode_latin1(text: &str, mode: Latin1Mode) -> Result<Vec<u8>, EncodingError> {
+    text.chars()
+        .map(|c| match u8::try_from(c as u32) {
+            Ok(b) if mode == Latin1Mode::Verbatim || is_latin1_text(b) => Ok(b),
+            _ if mode == Latin1Mode::Lossy => Ok(b'?'),
+            _ => Err(TextEncodingError::Unrepresentable.into()),
+        })
+        .collect()
+}
+
+/// Returns whether the compressed text would be shorter than `raw`, counting the compression
+/// method byte of a `zTXt` chunk.
+pub(crate) fn compression_saves(raw: &[u8]) -> Result<bool, EncodingError> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::Fast);
+    encoder
+        .write_all(raw)
+        .map_err(|_| EncodingError::from(TextEncodingError::CompressionError))?;
+    let compressed = encoder
+        .finish()
+        .map_err(|_| EncodingError::from(TextEncodingError::CompressionError))?;
+    Ok(compressed.len() + 1 < raw.len())
+}
+
+fn is_latin1_text(b: u8) -> bool {
+    b == b'\n' || !(b as char).is_control()
+}
+
+fn decode_latin1_with(bytes: &[u8], mode: Latin1Mode) -> Result<String, TextDecodingError> {
+    match mode {
+        Latin1Mode::Verbatim => Ok(decode_iso_8859_1(bytes)),
+        Latin1Mode::Strict if bytes.iter().all(|&b| is_latin1_text(b)) => {
+            Ok(decode_iso_8859_1(bytes))
+        }
+        Latin1Mode::Strict => Err(TextDecodingError::Unrepresentable),
+        Latin1Mode::Lossy => Ok(bytes
+            .iter()
+            .map(|&b| match b {
+                b if is_latin1_text(b) => b as char,
+                _ => '\u{fffd}',
+            })
+            .collect()),
+    }
+}
+
+/// A violation of the rules for the keyword of a text chunk, see [`check_keyword`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum KeywordError {
+    /// The keyword is empty.
+    Empty,
+    /// The keyword is longer than 79 characters.
+    TooLong,
+    /// The keyword contains a character that is not printable Latin-1 or a space.
+    Unprintable(char),
+    /// The keyword starts with a space.
+    LeadingSpace,
+    /// The keyword ends with a space.
+    TrailingSpace,
+    /// The keyword contains two spaces in a row.
+    ConsecutiveSpaces,
+}
+
+impl fmt::Display for KeywordError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeywordError::Empty => write!(fmt, "the keyword is empty"),
+            KeywordError::TooLong => write!(fmt, "the keyword is longer than 79 characters"),
+            KeywordError::Unprintable(c) => {
+                write!(
+                    fmt,
+                    "the keyword contains the unprintable character {:?}",
+                    c
+                )
+            }
+            KeywordError::LeadingSpace => write!(fmt, "the keyword starts with a space"),
+            KeywordError::TrailingSpace => write!(fmt, "the keyword ends with a space"),
+            KeywordError::ConsecutiveSpaces => {
+                write!(fmt, "the keyword contains consecutive spaces")
+            }
+        }
+    }
+}
+
+impl std::error::Error for KeywordError {}
+
+/// Checks a keyword against the rules of the specification.
+///
+/// A keyword has 1 to 79 characters, which are printable Latin-1 characters (`U+0020` to `U+007E`
+/// and `U+00A1` to `U+00FF`), and must not start or end with a space or contain two spaces in a
+/// row. The encoder and decoder only enforce the length unless asked to, see
+/// [`Encoder::set_strict_keywords`](crate::Encoder::set_strict_keywords) and
+/// [`Decoder::set_keyword_check`](crate::Decoder::set_keyword_check).
+///
+/// ```
+/// use png::text_metadata::{check_keyword, KeywordError};
+///
+/// assert_eq!(check_keyword("Creation Time"), Ok(()));
+/// assert_eq!(check_keyword("Creation  Time"), Err(KeywordError::ConsecutiveSpaces));
+/// assert_eq!(check_keyword("Tab\t"), Err(KeywordError::Unprintable('\t')));
+/// ```
+pub fn check_keyword(keyword: &str) -> Result<(), KeywordError> {
+    if keyword.is_empty() {
+        return Err(KeywordError::Empty);
+    }
+    let printable = |c: char| matches!(c as u32, 0x20..=0x7e | 0xa1..=0xff);
+    if let Some(c) = keyword.chars().find(|&c| !printable(c)) {
+        return Err(KeywordError::Unprintable(c));
+    }
+    if keyword.chars().count() > 79 {
+        Err(KeywordError::TooLong)
+    } else if keyword.starts_with(' ') {
+        Err(KeywordError::LeadingSpace)
+    } else if keyword.ends_with(' ') {
+        Err(KeywordError::TrailingSpace)
+    } else if keyword.contains("  ") {
+        Err(KeywordError::ConsecutiveSpaces)
+    } else {
+        Ok(())
+    }
+}
+
+/// The handling of decoded text chunks whose keyword violates [`check_keyword`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeywordCheck {
+    /// Accept any keyword of the right length. This is the default.
+    Ignore,
+    /// Accept the chunk but record the violation in
+    /// [`Info::keyword_warnings`](crate::Info::keyword_warnings).
+    Warn,
+    /// Reject the chunk with an error.
+    Reject,
+}
+
+impl Default for KeywordCheck {
+    fn default() -> Self {
+        KeywordCheck::Ignore
+    }
+}
+
 /// Text encoding errors that is wrapped by the standard EncodingError type
 #[derive(Debug, Clone, Copy)]
 pub(crate) enum TextEncodingError {
@@ -146,10 +362,16 @@ pub(crate) enum TextDecodingError {
 pub trait EncodableTextChunk {
     /// Encode text chunk as Vec<u8> to a `Write`
     fn encode<W: Write>(&self, w: &mut W) -> Result<(), EncodingError>;
+
+    /// The keyword of the chunk, which the encoder checks if asked to, see [`check_keyword`].
+    fn keyword(&self) -> Option<&str> {
+        None
+    }
 }
 
 /// Struct representing a tEXt chunk
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TEXtChunk {
     /// Keyword field of the tEXt chunk. Needs to be between 1-79 bytes when encoded as Latin-1.
     pub keyword: String,
@@ -157,6 +379,16 @@ pub struct TEXtChunk {
     pub text: String,
 }
 
+fn decompress_with_limit(compressed: &[u8], limit: usize) -> Result<Vec<u8>, DecodingError> {
+    match decompress_to_vec_zlib_with_limit(compressed, limit) {
+        Ok(uncompressed) => Ok(uncompressed),
+        Err(miniz_oxide::inflate::TINFLStatus::HasMoreOutput) => Err(DecodingError::from(
+            TextDecodingError::OutOfDecompressionSpace,
+        )),
+        Err(_) => Err(DecodingError::from(TextDecodingError::InflationError)),
+    }
+}
+
 fn decode_iso_8859_1(text: &[u8]) -> String {
     text.iter().map(|&b| b as char).collect()
 }
@@ -202,14 +434,15 @@ impl TEXtChunk {
     pub(crate) fn decode(
         keyword_slice: &[u8],
         text_slice: &[u8],
+        mode: Latin1Mode,
     ) -> Result<Self, TextDecodingError> {
         if keyword_slice.is_empty() || keyword_slice.len() > 79 {
             return Err(TextDecodingError::InvalidKeywordSize);
         }
 
         Ok(Self {
-            keyword: decode_iso_8859_1(keyword_slice),
-            text: decode_iso_8859_1(text_slice),
+            keyword: decode_latin1_with(keyword_slice, mode)?,
+            text: decode_latin1_with(text_slice, mode)?,
         })
     }
 }
@@ -229,19 +462,74 @@ impl EncodableTextChunk for TEXtChunk {
 
         encoder::write_chunk(w, chunk::tEXt, &data)
     }
+
+    fn keyword(&self) -> Option<&str> {
+        Some(&self.keyword)
+    }
+}
+
+/// A reference to any kind of text chunk, see [`Info::text_chunks`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextChunkRef<'a> {
+    /// A `tEXt` chunk.
+    Uncompressed(&'a TEXtChunk),
+    /// A `zTXt` chunk.
+    Compressed(&'a ZTXtChunk),
+    /// An `iTXt` chunk.
+    International(&'a ITXtChunk),
+}
+
+impl TextChunkRef<'_> {
+    /// The keyword of the chunk.
+    pub fn keyword(&self) -> &str {
+        match self {
+            TextChunkRef::Uncompressed(chunk) => &chunk.keyword,
+            TextChunkRef::Compressed(chunk) => &chunk.keyword,
+            TextChunkRef::International(chunk) => &chunk.keyword,
+        }
+    }
+
+    /// The text of the chunk, decompressing it if necessary.
+    pub fn text(&self) -> Result<String, DecodingError> {
+        match self {
+            TextChunkRef::Uncompressed(chunk) => Ok(chunk.text.clone()),
+            TextChunkRef::Compressed(chunk) => chunk.get_text(),
+            TextChunkRef::International(chunk) => chunk.get_text(),
+        }
+    }
+}
+
+impl EncodableTextChunk for TextChunkRef<'_> {
+    fn encode<W: Write>(&self, w: &mut W) -> Result<(), EncodingError> {
+        match self {
+            TextChunkRef::Uncompressed(chunk) => chunk.encode(w),
+            TextChunkRef::Compressed(chunk) => chunk.encode(w),
+            TextChunkRef::International(chunk) => chunk.encode(w),
+        }
+    }
+
+    fn keyword(&self) -> Option<&str> {
+        Some(TextChunkRef::keyword(self))
+    }
 }
 
 /// Struct representing a zTXt chunk
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ZTXtChunk {
     /// Keyword field of the tEXt chunk. Needs to be between 1-79 bytes when encoded as Latin-1.
     pub keyword: String,
     /// Text field of zTXt chunk. It is compressed by default, but can be uncompressed if necessary.
     text: OptCompressed,
+    /// The handling of unrepresentable characters when the text is decompressed.
+    mode: Latin1Mode,
+    /// The number of bytes the text may decompress to.
+    limit: usize,
 }
 
 /// Private enum encoding the compressed and uncompressed states of zTXt/iTXt text field.
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 enum OptCompressed {
     /// Compressed version of text field. Can be at most 2GB.
     Compressed(Vec<u8>),
@@ -249,12 +537,88 @@ enum OptCompressed {
     Uncompressed(String),
 }
 
+impl OptCompressed {
+    fn compressed(&self) -> Option<&[u8]> {
+        match self {
+            OptCompressed::Compressed(v) => Some(v),
+            OptCompressed::Uncompressed(_) => None,
+        }
+    }
+
+    fn reader(&self, encoded: impl FnOnce(&str) -> Cow<[u8]>) -> TextReader<'_> {
+        let inner = match self {
+            OptCompressed::Compressed(v) => TextReaderInner::Compressed {
+                input: v,
+                state: InflateState::new_boxed(DataFormat::Zlib),
+                done: false,
+            },
+            OptCompressed::Uncompressed(s) => {
+                TextReaderInner::Uncompressed(Cursor::new(encoded(s)))
+            }
+        };
+        TextReader { inner }
+    }
+}
+
+/// Reads the text of a `zTXt` or `iTXt` chunk, decompressing it on the fly.
+///
+/// This avoids holding very large texts in memory at once. The bytes are the encoded text as in
+/// the chunk, Latin-1 for `zTXt` and UTF-8 for `iTXt` chunks, and are not checked. Created by
+/// [`ZTXtChunk::text_reader`] and [`ITXtChunk::text_reader`].
+pub struct TextReader<'a> {
+    inner: TextReaderInner<'a>,
+}
+
+enum TextReaderInner<'a> {
+    Compressed {
+        input: &'a [u8],
+        state: Box<InflateState>,
+        done: bool,
+    },
+    Uncompressed(Cursor<Cow<'a, [u8]>>),
+}
+
+impl Read for TextReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let (input, state, done) = match &mut self.inner {
+            TextReaderInner::Uncompressed(text) => return text.read(buf),
+            TextReaderInner::Compressed { input, state, done } => (input, state, done),
+        };
+        if *done || buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let result = inflate(state, input, buf, MZFlush::None);
+            *input = &input[result.bytes_consumed..];
+            match result.status {
+                Ok(MZStatus::StreamEnd) => {
+                    *done = true;
+                    return Ok(result.bytes_written);
+                }
+                Ok(_) if result.bytes_written > 0 => return Ok(result.bytes_written),
+                Ok(_) if result.bytes_consumed > 0 => {}
+                Ok(_) | Err(MZError::Buf) => {
+                    let err = "the compressed text ends unexpectedly";
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, err));
+                }
+                Err(_) => {
+                    let err = "the compressed text is corrupt";
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, err));
+                }
+            }
+        }
+    }
+}
+
 impl ZTXtChunk {
     /// Creates a new ZTXt chunk.
     pub fn new(keyword: impl Into<String>, text: impl Into<String>) -> Self {
         Self {
             keyword: keyword.into(),
             text: OptCompressed::Uncompressed(text.into()),
+            mode: Latin1Mode::default(),
+            limit: DECOMPRESSION_LIMIT,
         }
     }
 
@@ -262,6 +626,8 @@ impl ZTXtChunk {
         keyword_slice: &[u8],
         compression_method: u8,
         text_slice: &[u8],
+        mode: Latin1Mode,
+        limit: usize,
     ) -> Result<Self, TextDecodingError> {
         if keyword_slice.is_empty() || keyword_slice.len() > 79 {
             return Err(TextDecodingError::InvalidKeywordSize);
@@ -272,51 +638,78 @@ impl ZTXtChunk {
         }
 
         Ok(Self {
-            keyword: decode_iso_8859_1(keyword_slice),
+            keyword: decode_latin1_with(keyword_slice, mode)?,
             text: OptCompressed::Compressed(text_slice.to_vec()),
+            mode,
+            limit,
         })
     }
 
-    /// Decompresses the inner text, mutating its own state. Can only handle decompressed text up to `DECOMPRESSION_LIMIT` bytes.
+    /// Decompresses the inner text, mutating its own state.
+    ///
+    /// Can only handle decompressed text up to [`DECOMPRESSION_LIMIT`] bytes, or the limit set
+    /// with [`Decoder::set_text_decompression_limit`](crate::Decoder::set_text_decompression_limit)
+    /// for decoded chunks.
     pub fn decompress_text(&mut self) -> Result<(), DecodingError> {
-        self.decompress_text_with_limit(DECOMPRESSION_LIMIT)
+        self.decompress_text_with_limit(self.limit)
     }
 
     /// Decompresses the inner text, mutating its own state. Can only handle decompressed text up to `limit` bytes.
     pub fn decompress_text_with_limit(&mut self, limit: usize) -> Result<(), DecodingError> {
-        match &self.text {
-            OptCompressed::Compressed(v) => {
-                let uncompressed_raw = match decompress_to_vec_zlib_with_limit(&v[..], limit) {
-                    Ok(s) => s,
-                    Err(miniz_oxide::inflate::TINFLStatus::HasMoreOutput) => {
-                        return Err(DecodingError::from(
-                            TextDecodingError::OutOfDecompressionSpace,
-                        ));
-                    }
-                    Err(_) => {
-                        return Err(DecodingError::from(TextDecodingError::InflationError));
-                    }
-                };
-                self.text = OptCompressed::Uncompressed(decode_iso_8859_1(&uncompressed_raw));
-            }
-            OptCompressed::Uncompressed(_) => {}
-        };
+        if let OptCompressed::Compressed(_) = &self.text {
+            self.text = OptCompressed::Uncompressed(self.text_with_limit(limit)?);
+        }
         Ok(())
     }
 
-    /// Decompresses the inner text, and returns it as a `String`.
-    /// If decompression uses more the 2MiB, first call decompress with limit, and then this method.
-    pub fn get_text(&self) -> Result<String, DecodingError> {
+    /// Returns the compressed text as it was decoded, unless it has been decompressed.
+    ///
+    /// This compressed text is written again when the chunk is encoded.
+    pub fn compressed_text(&self) -> Option<&[u8]> {
+        self.text.compressed()
+    }
+
+    /// Returns a reader of the Latin-1 bytes of the text, which is decompressed while reading.
+    ///
+    /// No decompression limit applies. A text that was decompressed or created from a string is
+    /// encoded to Latin-1, replacing unrepresentable characters by `?`.
+    ///
+    /// ```
+    /// use png::text_metadata::ZTXtChunk;
+    /// use std::io::Read;
+    ///
+    /// let mut chunk = ZTXtChunk::new("XML:com.adobe.xmp", "<x:xmpmeta/>");
+    /// chunk.compress_text().unwrap();
+    /// let mut xmp = Vec::new();
+    /// chunk.text_reader().read_to_end(&mut xmp).unwrap();
+    /// assert_eq!(xmp, b"<x:xmpmeta/>");
+    /// ```
+    pub fn text_reader(&self) -> TextReader<'_> {
+        self.text.reader(|text| {
+            let latin1 = encode_latin1(text, Latin1Mode::Lossy);
+            Cow::Owned(latin1.unwrap_or_default())
+        })
+    }
+
+    /// Returns the text, decompressing at most `limit` bytes without changing the state.
+    fn text_with_limit(&self, limit: usize) -> Result<String, DecodingError> {
         match &self.text {
             OptCompressed::Compressed(v) => {
-                let uncompressed_raw = decompress_to_vec_zlib(&v[..])
-                    .map_err(|_| DecodingError::from(TextDecodingError::InflationError))?;
-                Ok(decode_iso_8859_1(&uncompressed_raw))
+                let uncompressed_raw = decompress_with_limit(v, limit)?;
+                Ok(decode_latin1_with(&uncompressed_raw, self.mode)?)
             }
             OptCompressed::Uncompressed(s) => Ok(s.clone()),
         }
     }
 
+    /// Decompresses the inner text, and returns it as a `String`.
+    ///
+    /// The decompression is limited as in [`ZTXtChunk::decompress_text`]. For a longer text, first
+    /// call `decompress_text_with_limit`, and then this method.
+    pub fn get_text(&self) -> Result<String, DecodingError> {
+        self.text_with_limit(self.limit)
+    }
+
     /// Compresses the inner text, mutating its own state.
     pub fn compress_text(&mut self) -> Result<(), EncodingError> {
         match &self.text {
@@ -372,10 +765,15 @@ impl EncodableTextChunk for ZTXtChunk {
 
         encoder::write_chunk(w, chunk::zTXt, &data)
     }
+
+    fn keyword(&self) -> Option<&str> {
+        Some(&self.keyword)
+    }
 }
 
 /// Struct encoding an iTXt chunk
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ITXtChunk {
     /// The keyword field. This needs to be between 1-79 bytes when encoded as Latin-1.
     pub keyword: String,
@@ -387,6 +785,8 @@ pub struct ITXtChunk {
     pub translated_keyword: String,
     /// Text field of iTXt chunk. It is compressed by default, but can be uncompressed if necessary.
     text: OptCompressed,
+    /// The number of bytes the text may decompress to.
+    limit: usize,
 }
 
 impl ITXtChunk {
@@ -398,6 +798,7 @@ impl ITXtChunk {
             language_tag: "".to_string(),
             translated_keyword: "".to_string(),
             text: OptCompressed::Uncompressed(text.into()),
+            limit: DECOMPRESSION_LIMIT,
         }
     }
 
@@ -408,6 +809,7 @@ impl ITXtChunk {
         language_tag_slice: &[u8],
         translated_keyword_slice: &[u8],
         text_slice: &[u8],
+        limit: usize,
     ) -> Result<Self, TextDecodingError> {
         if keyword_slice.is_empty() || keyword_slice.len() > 79 {
             return Err(TextDecodingError::InvalidKeywordSize);
@@ -444,53 +846,59 @@ impl ITXtChunk {
             language_tag,
             translated_keyword,
             text,
+            limit,
         })
     }
 
-    /// Decompresses the inner text, mutating its own state. Can only handle decompressed text up to `DECOMPRESSION_LIMIT` bytes.
+    /// Decompresses the inner text, mutating its own state.
+    ///
+    /// Can only handle decompressed text up to [`DECOMPRESSION_LIMIT`] bytes, or the limit set
+    /// with [`Decoder::set_text_decompression_limit`](crate::Decoder::set_text_decompression_limit)
+    /// for decoded chunks.
     pub fn decompress_text(&mut self) -> Result<(), DecodingError> {
-        self.decompress_text_with_limit(DECOMPRESSION_LIMIT)
+        self.decompress_text_with_limit(self.limit)
     }
 
     /// Decompresses the inner text, mutating its own state. Can only handle decompressed text up to `limit` bytes.
     pub fn decompress_text_with_limit(&mut self, limit: usize) -> Result<(), DecodingError> {
-        match &self.text {
-            OptCompressed::Compressed(v) => {
-                let uncompressed_raw = match decompress_to_vec_zlib_with_limit(&v[..], limit) {
-                    Ok(s) => s,
-                    Err(miniz_oxide::inflate::TINFLStatus::HasMoreOutput) => {
-                        return Err(DecodingError::from(
-                            TextDecodingError::OutOfDecompressionSpace,
-                        ));
-                    }
-                    Err(_) => {
-                        return Err(DecodingError::from(TextDecodingError::InflationError));
-                    }
-                };
-                self.text = OptCompressed::Uncompressed(
-                    String::from_utf8(uncompressed_raw)
-                        .map_err(|_| TextDecodingError::Unrepresentable)?,
-                );
-            }
-            OptCompressed::Uncompressed(_) => {}
-        };
+        if let OptCompressed::Compressed(_) = &self.text {
+            self.text = OptCompressed::Uncompressed(self.text_with_limit(limit)?);
+        }
         Ok(())
     }
 
-    /// Decompresses the inner text, and returns it as a `String`.
-    /// If decompression takes more than 2 MiB, try `decompress_text_with_limit` followed by this method.
-    pub fn get_text(&self) -> Result<String, DecodingError> {
+    /// Returns the compressed text as it was decoded, unless it has been decompressed.
+    ///
+    /// This compressed text is written again when the chunk is encoded with `compressed` set.
+    pub fn compressed_text(&self) -> Option<&[u8]> {
+        self.text.compressed()
+    }
+
+    /// Returns a reader of the UTF-8 bytes of the text, which is decompressed while reading.
+    ///
+    /// No decompression limit applies, and the bytes are not checked to be valid UTF-8. A
+    /// character may be split between two reads.
+    pub fn text_reader(&self) -> TextReader<'_> {
+        self.text.reader(|text| Cow::Borrowed(text.as_bytes()))
+    }
+
+    /// Returns the text, decompressing at most `limit` bytes without changing the state.
+    fn text_with_limit(&self, limit: usize) -> Result<String, DecodingError> {
         match &self.text {
-            OptCompressed::Compressed(v) => {
-                let uncompressed_raw = decompress_to_vec_zlib(&v[..])
-                    .map_err(|_| DecodingError::from(TextDecodingError::InflationError))?;
-                String::from_utf8(uncompressed_raw)
-                    .map_err(|_| TextDecodingError::Unrepresentable.into())
-            }
+            OptCompressed::Compressed(v) => String::from_utf8(decompress_with_limit(v, limit)?)
+                .map_err(|_| TextDecodingError::Unrepresentable.into()),
             OptCompressed::Uncompressed(s) => Ok(s.clone()),
         }
     }
 
+    /// Decompresses the inner text, and returns it as a `String`.
+    ///
+    /// The decompression is limited as in [`ITXtChunk::decompress_text`]. For a longer text, try
+    /// `decompress_text_with_limit` followed by this method.
+    pub fn get_text(&self) -> Result<String, DecodingError> {
+        self.text_with_limit(self.limit)
+    }
+
     /// Compresses the inner text, mutating its own state.
     pub fn compress_text(&mut self) -> Result<(), EncodingError> {
         match &self.text {
@@ -570,7 +978,7 @@ impl EncodableTextChunk for ITXtChunk {
         } else {
             match &self.text {
                 OptCompressed::Compressed(v) => {
-                    let uncompressed_raw = decompress_to_vec_zlib(&v[..])
+                    let uncompressed_raw = decompress_to_vec_zlib_with_limit(v, self.limit)
                         .map_err(|_| EncodingError::from(TextEncodingError::CompressionError))?;
                     data.extend_from_slice(&uncompressed_raw[..]);
                 }
@@ -582,4 +990,239 @@ impl EncodableTextChunk for ITXtChunk {
 
         encoder::write_chunk(w, chunk::iTXt, &data)
     }
+
+    fn keyword(&self) -> Option<&str> {
+        Some(&self.keyword)
+    }
+}
+
+/// The text chunks with the keywords registered by the specification.
+///
+/// Each accessor returns the first text with its keyword, as [`Info::text_value`] does.
+#[derive(Clone, Copy, Debug)]
+pub struct StandardKeywords<'a> {
+    info: &'a Info<'a>,
+}
+
+macro_rules! standard_keywords {
+    ($($(#[$doc:meta])* $name:ident => $keyword:expr,)*) => {
+        impl<'a> StandardKeywords<'a> {
+            $(
+                $(#[$doc])*
+                pub fn $name(&self) -> Result<Option<String>, DecodingError> {
+                    self.info.text_value($keyword)
+                }
+            )*
+        }
+    };
+}
+
+standard_keywords! {
+    /// The `Title`, a short title or caption.
+    title => "Title",
+    /// The `Author`, the name of the creator of the image.
+    author => "Author",
+    /// The `Description`, possibly long.
+    description => "Description",
+    /// The `Copyright` notice.
+    copyright => "Copyright",
+    /// The `Creation Time` of the original image, see [`StandardKeywords::parsed_creation_time`].
+    creation_time => "Creation Time",
+    /// The `Software` used to create the image.
+    software => "Software",
+    /// The legal `Disclaimer`.
+    disclaimer => "Disclaimer",
+    /// The `Warning` of the nature of the content.
+    warning => "Warning",
+    /// The `Source`, the device used to create the image.
+    source => "Source",
+    /// A miscellaneous `Comment`.
+    comment => "Comment",
+}
+
+impl<'a> StandardKeywords<'a> {
+    pub(crate) fn new(info: &'a Info<'a>) -> Self {
+        StandardKeywords { info }
+    }
+
+    /// The `Creation Time` parsed in the RFC 1123 format that the specification recommends.
+    ///
+    /// Returns `None` when there is no such text or it is in another format, which the
+    /// specification allows. The text itself is returned by [`StandardKeywords::creation_time`].
+    pub fn parsed_creation_time(&self) -> Result<Option<CreationTime>, DecodingError> {
+        Ok(self
+            .creation_time()?
+            .and_then(|text| CreationTime::parse(&text)))
+    }
+}
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+/// A date and time in the RFC 1123 format of the `Creation Time` keyword.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CreationTime {
+    /// The year, with all its digits.
+    pub year: u16,
+    /// The month, from 1 to 12.
+    pub month: u8,
+    /// The day of the month, starting at 1.
+    pub day: u8,
+    /// The hour, from 0 to 23.
+    pub hour: u8,
+    /// The minute, from 0 to 59.
+    pub minute: u8,
+    /// The second, from 0 to 60 for a leap second.
+    pub second: u8,
+    /// The offset of the local time from UTC, in minutes.
+    pub utc_offset: i16,
+}
+
+impl CreationTime {
+    /// Parses a date and time such as `Sun, 06 Nov 1994 08:49:37 GMT`.
+    ///
+    /// The day of the week and the seconds are optional. The time zone is either a name from
+    /// RFC 822, such as `GMT` or `PST`, or an offset such as `+0100`.
+    ///
+    /// ```
+    /// use png::text_metadata::CreationTime;
+    ///
+    /// let time = CreationTime::parse("Mon, 14 Oct 2024 17:30 +0200").unwrap();
+    /// assert_eq!((time.year, time.month, time.day), (2024, 10, 14));
+    /// assert_eq!((time.hour, time.minute, time.utc_offset), (17, 30, 120));
+    /// assert_eq!(time.to_string(), "Mon, 14 Oct 2024 17:30:00 +0200");
+    /// assert_eq!(CreationTime::parse("2024-10-14"), None);
+    /// ```
+    pub fn parse(text: &str) -> Option<Self> {
+        fn number(digits: &str) -> Option<u16> {
+            if digits.is_empty() || digits.len() > 4 || !digits.bytes().all(|b| b.is_ascii_digit())
+            {
+                return None;
+            }
+            digits.parse().ok()
+        }
+
+        // The day of the week is redundant and not checked.
+        let text = match text.find(',') {
+            Some(comma) => &text[comma + 1..],
+            None => text,
+        };
+        let mut parts = text.split_whitespace();
+        let day = number(parts.next()?)?;
+        let month_name = parts.next()?;
+        let month = MONTHS
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(month_name))?;
+        let year = number(parts.next()?)?;
+
+        let mut clock = parts.next()?.split(':');
+        let hour = number(clock.next()?)?;
+        let minute = number(clock.next()?)?;
+        let second = match clock.next() {
+            Some(second) => number(second)?,
+            None => 0,
+        };
+        let utc_offset = Self::parse_zone(parts.next()?)?;
+        if clock.next().is_some() || parts.next().is_some() {
+            return None;
+        }
+
+        let month = month as u8 + 1;
+        if day == 0 || day > u16::from(days_in_month(year, month)) {
+            return None;
+        }
+        if hour > 23 || minute > 59 || second > 60 {
+            return None;
+        }
+
+        Some(CreationTime {
+            year,
+            month,
+            day: day as u8,
+            hour: hour as u8,
+            minute: minute as u8,
+            second: second as u8,
+            utc_offset,
+        })
+    }
+
+    fn parse_zone(zone: &str) -> Option<i16> {
+        let hours = match zone.to_ascii_uppercase().as_str() {
+            "UT" | "UTC" | "GMT" | "Z" => 0,
+            "EDT" => -4,
+            "EST" | "CDT" => -5,
+            "CST" | "MDT" => -6,
+            "MST" | "PDT" => -7,
+            "PST" => -8,
+            _ => {
+                let sign = match zone.as_bytes().first()? {
+                    b'+' => 1,
+                    b'-' => -1,
+                    _ => return None,
+                };
+                let digits = &zone[1..];
+                if digits.len() != 4 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                    return None;
+                }
+                let (hours, minutes) = (
+                    digits[..2].parse::<i16>().ok()?,
+                    digits[2..].parse::<i16>().ok()?,
+                );
+                if minutes > 59 {
+                    return None;
+                }
+                return Some(sign * (hours * 60 + minutes));
+            }
+        };
+        Some(hours * 60)
+    }
+
+    /// The day of the week, from 0 for Sunday to 6 for Saturday.
+    fn weekday(&self) -> usize {
+        // Days since 1970-01-01, a Thursday, with the leap years counted from March 1st.
+        let year = i64::from(self.year) - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let month = (i64::from(self.month) + 9) % 12;
+        let day_of_year = (153 * month + 2) / 5 + i64::from(self.day) - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146_097 + day_of_era - 719_468;
+        (days + 4).rem_euclid(7) as usize
+    }
+}
+
+pub(crate) fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl fmt::Display for CreationTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}, {:02} {} {:04} {:02}:{:02}:{:02} ",
+            WEEKDAYS[self.weekday()],
+            self.day,
+            MONTHS[usize::from(self.month.max(1).min(12)) - 1],
+            self.year,
+            self.hour,
+            self.minute,
+            self.second,
+        )?;
+        match self.utc_offset {
+            0 => write!(f, "GMT"),
+            offset => {
+                let sign = if offset < 0 { '-' } else { '+' };
+                let offset = offset.abs();
+                write!(f, "{}{:02}{:02}", sign, offset / 60, offset % 60)
+            }
+        }
+    }
 }
@@ -0,0 +1,80 @@
+//! Log events for debugging files that fail to decode or are slow.
+//!
+//! With the `trace` feature the macros forward to the `log` crate, otherwise they expand to
+//! nothing. Their arguments must not have side effects.
+
+/// Logs frequent events, such as each chunk and the filter of each row.
+macro_rules! trace {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "trace")]
+        log::trace!($($arg)+);
+    };
+}
+
+/// Logs milestones, such as the end of the image data, and rejected limits.
+macro_rules! debug {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "trace")]
+        log::debug!($($arg)+);
+    };
+}
+
+#[cfg(all(test, feature = "trace"))]
+mod tests {
+    use std::sync::Mutex;
+    use std::thread::{self, ThreadId};
+
+    /// Collects the messages of all threads, as other tests may log concurrently.
+    struct Collect(Mutex<Vec<(ThreadId, log::Level, String)>>);
+
+    impl log::Log for Collect {
+        fn enabled(&self, _: &log::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record<'_>) {
+            let message = (
+                thread::current().id(),
+                record.level(),
+                record.args().to_string(),
+            );
+            self.0.lock().unwrap().push(message);
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn logs_events() {
+        use crate::chunk;
+        use log::Level::{Debug, Trace};
+
+        let logger: &'static Collect = Box::leak(Box::new(Collect(Mutex::new(Vec::new()))));
+        log::set_logger(logger).unwrap();
+        log::set_max_level(log::LevelFilter::Trace);
+
+        let mut png = Vec::new();
+        {
+            let mut encoder = crate::Encoder::new(&mut png, 8, 8);
+            encoder.set_adaptive_filter(crate::AdaptiveFilterType::Adaptive);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_chunk(chunk::bKGD, &[0, 1]).unwrap();
+            writer.write_chunk(chunk::bKGD, &[0, 2]).unwrap();
+            writer.write_image_data(&[0x55; 64]).unwrap();
+        }
+        let mut reader = crate::Decoder::new(&png[..]).read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut buf).unwrap();
+
+        let id = thread::current().id();
+        let logged = |level, prefix: &str| {
+            logger.0.lock().unwrap().iter().any(|(thread, l, message)| {
+                *thread == id && *l == level && message.starts_with(prefix)
+            })
+        };
+        assert!(logged(Trace, "writing IHDR chunk of 13 bytes"));
+        assert!(logged(Trace, "adaptive filter chose"));
+        assert!(logged(Trace, "IHDR chunk of 13 bytes at offset 8"));
+        assert!(logged(Debug, "ignoring duplicate bKGD chunk"));
+    }
+}
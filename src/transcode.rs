@@ -0,0 +1,392 @@
+//! Re-encoding the image data of a PNG file while keeping its other chunks.
+use std::io::Write;
+use std::{error, fmt, mem};
+
+use crate::adam7;
+use crate::chunk::{self, ChunkReader, ChunkType, ChunkWriter};
+use crate::common::{BitDepth, ColorType, Compression};
+use crate::decoder::{Decoder, DecodingError};
+use crate::encoder::{
+    encoded_image_data, malformed_encoded_image, Encoder, EncodingError, FormatErrorKind,
+};
+use crate::filter::{filter, AdaptiveFilterType, FilterType};
+use crate::zlib::ZlibEncoder;
+
+/// Decodes and re-encodes the image data of a PNG file, copying its other chunks.
+///
+/// The image is written without interlacing, unless [`Transcoder::set_interlaced`] is used, and
+/// with the filter and compression configured here, while the palette and the ancillary chunks
+/// keep their place before or after the image data.
+/// As the image data changes, unknown chunks that are not safe to copy are removed, unless
+/// [`Transcoder::set_copy_unsafe_chunks`] is used.
+///
+/// ```
+/// # fn recompress(png: &[u8]) -> Result<Vec<u8>, png::TranscodingError> {
+/// let mut transcoder = png::Transcoder::new();
+/// transcoder.set_compression(png::Compression::Best);
+/// transcoder.set_adaptive_filter(png::AdaptiveFilterType::Adaptive);
+///
+/// let mut out = Vec::new();
+/// transcoder.transcode(png, &mut out)?;
+/// # Ok(out)
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Transcoder {
+    compression: Compression,
+    filter: FilterType,
+    adaptive_filter: AdaptiveFilterType,
+    copy_unsafe_chunks: bool,
+    interlaced: bool,
+}
+
+/// An error while transcoding with a [`Transcoder`].
+#[derive(Debug)]
+pub enum TranscodingError {
+    /// The input could not be decoded.
+    Decoding(DecodingError),
+    /// The output could not be encoded.
+    Encoding(EncodingError),
+}
+
+impl Default for Transcoder {
+    fn default() -> Self {
+        Transcoder {
+            compression: Compression::Default,
+            filter: FilterType::default(),
+            adaptive_filter: AdaptiveFilterType::default(),
+            copy_unsafe_chunks: false,
+            interlaced: false,
+        }
+    }
+}
+
+impl Transcoder {
+    /// Create a transcoder with the default options of the [`Encoder`].
+    pub fn new() -> Self {
+        Transcoder::default()
+    }
+
+    /// Set the compression of the re-encoded image data.
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.compression = compression;
+    }
+
+    /// Set the filter type of the re-encoded image data, see [`Encoder::set_filter`].
+    pub fn set_filter(&mut self, filter: FilterType) {
+        self.filter = filter;
+    }
+
+    /// Set the adaptive filter type, see [`Encoder::set_adaptive_filter`].
+    pub fn set_adaptive_filter(&mut self, adaptive_filter: AdaptiveFilterType) {
+        self.adaptive_filter = adaptive_filter;
+    }
+
+    /// Also copy unknown chunks that are not marked as safe to copy.
+    ///
+    /// Such chunks may depend on the image data, which is only re-encoded and not changed. They
+    /// are removed by default as the specification requires from editors of critical chunks.
+    pub fn set_copy_unsafe_chunks(&mut self, copy: bool) {
+        self.copy_unsafe_chunks = copy;
+    }
+
+    /// Write the image data with Adam7 interlacing.
+    ///
+    /// Interlaced and non-interlaced input can be converted either way, as all input is
+    /// deinterlaced while decoding. Only the `IHDR` and the image data chunks change.
+    pub fn set_interlaced(&mut self, interlaced: bool) {
+        self.interlaced = interlaced;
+    }
+
+    /// Transcodes the PNG file in `png` and writes the result to `w`.
+    ///
+    /// Animated images are not supported and result in an error.
+    pub fn transcode<W: Write>(&self, png: &[u8], w: W) -> Result<(), TranscodingError> {
+        let chunks = ChunkReader::new(png).collect::<Result<Vec<_>, _>>()?;
+        if chunks.iter().any(|raw| raw.chunk_type == chunk::acTL) {
+            let kind = FormatErrorKind::UnsupportedAnimation;
+            return Err(EncodingError::Format(kind.into()).into());
+        }
+
+        let mut reader = Decoder::new(png).read_info()?;
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut pixels)?;
+        let zlib_encoded = if self.interlaced {
+            self.encode_interlaced(&pixels, reader.info())?
+        } else {
+            self.encode(&pixels, reader.info())?
+        };
+
+        let mut writer = ChunkWriter::new(w);
+        let mut written_image = false;
+        for raw in &chunks {
+            match raw.chunk_type {
+                chunk::IHDR => {
+                    let mut header = raw.data.clone();
+                    *header.last_mut().ok_or_else(malformed_encoded_image)? = self.interlaced as u8;
+                    writer.write_chunk(chunk::IHDR, &header)?;
+                }
+                chunk::IDAT if !written_image => {
+                    written_image = true;
+                    let max_len = (u32::MAX >> 1) as usize;
+                    for data in zlib_encoded.chunks(max_len) {
+                        writer.write_chunk(chunk::IDAT, data)?;
+                    }
+                }
+                chunk::IDAT | chunk::IEND => {}
+                chunk::PLTE => writer.write_chunk(chunk::PLTE, &raw.data)?,
+                chunk_type if self.should_copy(chunk_type) => {
+                    writer.write_chunk(chunk_type, &raw.data)?
+                }
+                _ => {}
+            }
+        }
+        writer.write_chunk(chunk::IEND, &[])?;
+
+        Ok(())
+    }
+
+    fn should_copy(&self, chunk_type: ChunkType) -> bool {
+        // A signature never matches the re-encoded image data, the meaning of the other known
+        // chunks does not depend on its encoding.
+        chunk_type != chunk::dSIG
+            && !chunk::is_critical(chunk_type)
+            && (chunk::KNOWN_ANCILLARY.contains(&chunk_type)
+                || chunk::safe_to_copy(chunk_type)
+                || self.copy_unsafe_chunks)
+    }
+
+    /// Compresses the decoded image data in its original color type and bit depth.
+    fn encode(&self, pixels: &[u8], info: &crate::Info) -> Result<Vec<u8>, EncodingError> {
+        let mut png = Vec::new();
+        {
+            let mut encoder = Encoder::new(&mut png, info.width, info.height);
+            encoder.set_color(info.color_type);
+            encoder.set_depth(info.bit_depth);
+            encoder.set_compression(self.compression);
+            encoder.set_filter(self.filter);
+            encoder.set_adaptive_filter(self.adaptive_filter);
+            if info.color_type == ColorType::Indexed {
+                // Only required to pass the validation, the palette chunk itself is copied.
+                let entries = 1usize << (info.bit_depth as u8).min(BitDepth::Eight as u8);
+                encoder.set_palette(vec![0; 3 * entries]);
+            }
+            encoder.write_header()?.write_image_data(pixels)?;
+        }
+
+        Ok(encoded_image_data(&png)?.1)
+    }
+
+    /// Filters and compresses the decoded image data as the seven passes of Adam7.
+    fn encode_interlaced(
+        &self,
+        pixels: &[u8],
+        info: &crate::Info,
+    ) -> Result<Vec<u8>, EncodingError> {
+        let bits_pp = info.color_type.samples() * info.bit_depth as usize;
+        let line_size = info.raw_row_length() - 1;
+        let bpp = info.bpp_in_prediction();
+        let mut zlib = ZlibEncoder::new(Vec::new(), self.compression);
+
+        for pass in 1..=7 {
+            let (width, height) = adam7::pass_size(pass, info.width, info.height);
+            if width == 0 {
+                continue;
+            }
+            let out_len = info.raw_row_length_from_width(width) - 1;
+            let mut prev = vec![0; out_len];
+            let mut current = vec![0; out_len];
+            let mut filtered = vec![0; out_len];
+
+            for y in 0..height {
+                let start = adam7::image_row(pass, y) as usize * line_size;
+                let line = &pixels[start..start + line_size];
+                current.iter_mut().for_each(|byte| *byte = 0);
+                for x in 0..width {
+                    let from = adam7::image_column(pass, x) as usize * bits_pp;
+                    copy_pixel(line, from, &mut current, x as usize * bits_pp, bits_pp);
+                }
+                filtered.copy_from_slice(&current);
+                let filter_type =
+                    filter(self.filter, self.adaptive_filter, bpp, &prev, &mut filtered);
+                zlib.write_all(&[filter_type as u8])?;
+                zlib.write_all(&filtered)?;
+                mem::swap(&mut prev, &mut current);
+            }
+        }
+
+        Ok(zlib.finish()?)
+    }
+}
+
+/// Copies the pixel at the bit offset `from` of `line` to the bit offset `to` of the zeroed `out`.
+fn copy_pixel(line: &[u8], from: usize, out: &mut [u8], to: usize, bits_pp: usize) {
+    if bits_pp >= 8 {
+        let bytes = bits_pp / 8;
+        out[to / 8..to / 8 + bytes].copy_from_slice(&line[from / 8..from / 8 + bytes]);
+    } else {
+        // Sub-byte pixels start in the high-order bits.
+        let mask = (1u8 << bits_pp) - 1;
+        let value = (line[from / 8] >> (8 - from % 8 - bits_pp)) & mask;
+        out[to / 8] |= value << (8 - to % 8 - bits_pp);
+    }
+}
+
+impl error::Error for TranscodingError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            TranscodingError::Decoding(err) => Some(err),
+            TranscodingError::Encoding(err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for TranscodingError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TranscodingError::Decoding(err) => write!(fmt, "{}", err),
+            TranscodingError::Encoding(err) => write!(fmt, "{}", err),
+        }
+    }
+}
+
+impl From<DecodingError> for TranscodingError {
+    fn from(err: DecodingError) -> Self {
+        TranscodingError::Decoding(err)
+    }
+}
+
+impl From<EncodingError> for TranscodingError {
+    fn from(err: EncodingError) -> Self {
+        TranscodingError::Encoding(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn decode(png: &[u8]) -> (crate::OutputInfo, Vec<u8>) {
+        let mut reader = Decoder::new(png).read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        (info, buf)
+    }
+
+    fn chunk_types(png: &[u8]) -> Vec<ChunkType> {
+        ChunkReader::new(png)
+            .map(|raw| raw.unwrap().chunk_type)
+            .collect()
+    }
+
+    #[test]
+    fn transcode_keeps_chunks() {
+        let mut png = Vec::new();
+        {
+            let mut encoder = Encoder::new(&mut png, 4, 4);
+            encoder
+                .add_text_chunk("before".into(), "image".into())
+                .unwrap();
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_chunk(ChunkType(*b"prIv"), b"safe").unwrap();
+            writer.write_chunk(ChunkType(*b"prIV"), b"unsafe").unwrap();
+            let data: Vec<u8> = (0..16).collect();
+            writer.write_image_data(&data).unwrap();
+            writer.write_chunk(chunk::tEXt, b"after\0image").unwrap();
+        }
+
+        let mut transcoder = Transcoder::new();
+        transcoder.set_filter(FilterType::Paeth);
+        let mut out = Vec::new();
+        transcoder.transcode(&png, &mut out).unwrap();
+        assert_eq!(decode(&out).1, decode(&png).1);
+        let expected = [
+            chunk::IHDR,
+            chunk::tEXt,
+            ChunkType(*b"prIv"),
+            chunk::IDAT,
+            chunk::tEXt,
+            chunk::IEND,
+        ];
+        assert_eq!(chunk_types(&out), expected);
+
+        transcoder.set_copy_unsafe_chunks(true);
+        out.clear();
+        transcoder.transcode(&png, &mut out).unwrap();
+        assert!(chunk_types(&out).contains(&ChunkType(*b"prIV")));
+    }
+
+    #[test]
+    fn transcode_keeps_rendering_intent() {
+        use crate::SrgbRenderingIntent;
+        use std::convert::TryFrom;
+
+        let srgb = |png: &[u8]| Decoder::new(png).read_info().unwrap().info().srgb;
+        for raw in 0..4 {
+            let intent = SrgbRenderingIntent::try_from(raw).unwrap();
+            assert_eq!(u8::from(intent), raw);
+            assert_eq!(SrgbRenderingIntent::from_icc(intent.to_icc()), Some(intent));
+
+            let mut png = Vec::new();
+            {
+                let mut encoder = Encoder::new(&mut png, 1, 1);
+                encoder.set_srgb(intent);
+                encoder
+                    .write_header()
+                    .unwrap()
+                    .write_image_data(&[0])
+                    .unwrap();
+            }
+            assert_eq!(srgb(&png), Some(intent));
+
+            let mut out = Vec::new();
+            Transcoder::new().transcode(&png, &mut out).unwrap();
+            assert_eq!(srgb(&out), Some(intent));
+        }
+        assert_eq!(SrgbRenderingIntent::try_from(4), Err(4));
+    }
+
+    #[test]
+    fn transcode_interlaced() {
+        for name in &["basi0g01", "basi3p02", "basi6a16"] {
+            let path = format!("tests/pngsuite/{}.png", name);
+            let png = fs::read(path).unwrap();
+            let mut out = Vec::new();
+            Transcoder::new().transcode(&png, &mut out).unwrap();
+
+            let (info, data) = decode(&out);
+            assert!(!Decoder::new(&*out).read_info().unwrap().info().interlaced);
+            assert_eq!(info.color_type, decode(&png).0.color_type);
+            assert_eq!(data, decode(&png).1);
+        }
+    }
+
+    #[test]
+    fn transcode_to_interlaced() {
+        for name in &["basn0g01", "basn3p02", "basn2c16", "s07n3p02", "s01n3p01"] {
+            let path = format!("tests/pngsuite/{}.png", name);
+            let png = fs::read(path).unwrap();
+            let mut transcoder = Transcoder::new();
+            transcoder.set_interlaced(true);
+            transcoder.set_adaptive_filter(AdaptiveFilterType::Adaptive);
+            let mut interlaced = Vec::new();
+            transcoder.transcode(&png, &mut interlaced).unwrap();
+
+            assert!(
+                Decoder::new(&*interlaced)
+                    .read_info()
+                    .unwrap()
+                    .info()
+                    .interlaced
+            );
+            assert_eq!(decode(&interlaced).1, decode(&png).1, "{}", name);
+            assert_eq!(chunk_types(&interlaced), chunk_types(&png), "{}", name);
+
+            let mut out = Vec::new();
+            Transcoder::new().transcode(&interlaced, &mut out).unwrap();
+            assert!(!Decoder::new(&*out).read_info().unwrap().info().interlaced);
+            assert_eq!(decode(&out).1, decode(&png).1, "{}", name);
+        }
+    }
+}
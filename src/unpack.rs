@@ -0,0 +1,92 @@
+//! Expanding packed samples of low bit depths to one byte per sample.
+//!
+//! Rows of image data with a bit depth below eight pack multiple samples into each byte, starting
+//! in the high-order bits. Grayscale and indexed images use these bit depths.
+
+use crate::common::BitDepth;
+use crate::utils::subbyte_pixels;
+
+/// Unpacks the samples of `packed` into `out`, one byte per sample, keeping their values.
+///
+/// This fills `out` or stops once `packed` runs out of samples, and returns the number of
+/// samples written. Extra samples in the padding of the last byte of a row are unpacked as well,
+/// so `out` should have the length of the row in samples.
+///
+/// # Panics
+///
+/// If `bit_depth` is [`BitDepth::Sixteen`].
+///
+/// ```
+/// use png::unpack::unpack_bits;
+/// let mut out = [0; 5];
+/// assert_eq!(unpack_bits(&[0b1101_0010, 0b1100_0000], png::BitDepth::Two, &mut out), 5);
+/// assert_eq!(out, [3, 1, 0, 2, 3]);
+/// ```
+pub fn unpack_bits(packed: &[u8], bit_depth: BitDepth, out: &mut [u8]) -> usize {
+    match bit_depth {
+        BitDepth::Eight => {
+            let len = packed.len().min(out.len());
+            out[..len].copy_from_slice(&packed[..len]);
+            len
+        }
+        BitDepth::Sixteen => panic!("Sixteen bit samples can not be unpacked to bytes"),
+        _ => {
+            let samples = subbyte_pixels(packed, bit_depth as usize);
+            out.iter_mut()
+                .zip(samples)
+                .map(|(out, sample)| *out = sample)
+                .count()
+        }
+    }
+}
+
+/// Unpacks the samples of `packed` into `out` like [`unpack_bits`], scaling them to the full
+/// range of a byte.
+///
+/// The largest sample value becomes `255`, as the `EXPAND` transformation does for grayscale.
+///
+/// ```
+/// use png::unpack::unpack_bits_scaled;
+/// let mut out = [0; 4];
+/// unpack_bits_scaled(&[0b1101_0010], png::BitDepth::Two, &mut out);
+/// assert_eq!(out, [255, 85, 0, 170]);
+/// ```
+pub fn unpack_bits_scaled(packed: &[u8], bit_depth: BitDepth, out: &mut [u8]) -> usize {
+    let count = unpack_bits(packed, bit_depth, out);
+    let scale = match bit_depth {
+        BitDepth::One => 0xff,
+        BitDepth::Two => 0x55,
+        BitDepth::Four => 0x11,
+        _ => 1,
+    };
+    out[..count].iter_mut().for_each(|sample| *sample *= scale);
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+
+    #[test]
+    fn matches_in_place_unpacking() {
+        let packed = [0b1001_0110, 0b0111_1000, 0b1110_0001];
+        for &(bit_depth, scale) in &[
+            (BitDepth::One, 0xff),
+            (BitDepth::Two, 0x55),
+            (BitDepth::Four, 0x11),
+        ] {
+            let samples = 24 / bit_depth as usize;
+            let mut in_place = vec![0; samples];
+            in_place[..3].copy_from_slice(&packed);
+            utils::unpack_bits(&mut in_place, 1, bit_depth as u8, |sample, chunk| {
+                chunk[0] = sample * scale
+            });
+
+            let mut out = vec![0; samples + 1];
+            assert_eq!(unpack_bits_scaled(&packed, bit_depth, &mut out), samples);
+            assert_eq!(out[..samples], in_place[..]);
+            assert_eq!(out[samples], 0);
+        }
+    }
+}
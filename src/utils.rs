@@ -1,6 +1,50 @@
 //! Utility functions
 use std::iter::{repeat, StepBy};
 use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use crate::adam7;
+use crate::common::PhaseTimings;
+
+/// Starts measuring a step, there is no clock on `wasm32-unknown-unknown`.
+pub fn start_timer() -> Option<Instant> {
+    if cfg!(all(target_arch = "wasm32", target_os = "unknown")) {
+        None
+    } else {
+        Some(Instant::now())
+    }
+}
+
+/// Starts measuring if `enabled`, for times that are only measured on request.
+pub fn start_timer_if(enabled: bool) -> Option<Instant> {
+    if enabled {
+        start_timer()
+    } else {
+        None
+    }
+}
+
+pub fn stop_timer(start: Option<Instant>, total: &mut Duration) {
+    if let Some(start) = start {
+        *total += start.elapsed();
+    }
+}
+
+/// Starts measuring a phase if `timings` are collected.
+pub fn start_phase(timings: &Option<PhaseTimings>) -> Option<Instant> {
+    timings.as_ref().and_then(|_| start_timer())
+}
+
+/// Adds the time since `start` to one phase of `timings`.
+pub fn stop_phase(
+    timings: &mut Option<PhaseTimings>,
+    start: Option<Instant>,
+    phase: fn(&mut PhaseTimings) -> &mut Duration,
+) {
+    if let Some(timings) = timings {
+        stop_timer(start, phase(timings));
+    }
+}
 
 #[inline(always)]
 pub fn unpack_bits<F>(buf: &mut [u8], channels: usize, bit_depth: u8, func: F)
@@ -125,20 +169,9 @@ impl Adam7Iterator {
 
     /// Calculates the bounds of the current pass
     fn init_pass(&mut self) {
-        let w = f64::from(self.width);
-        let h = f64::from(self.height);
-        let (line_width, lines) = match self.current_pass {
-            1 => (w / 8.0, h / 8.0),
-            2 => ((w - 4.0) / 8.0, h / 8.0),
-            3 => (w / 4.0, (h - 4.0) / 8.0),
-            4 => ((w - 2.0) / 4.0, h / 4.0),
-            5 => (w / 2.0, (h - 2.0) / 4.0),
-            6 => ((w - 1.0) / 2.0, h / 2.0),
-            7 => (w, (h - 1.0) / 2.0),
-            _ => unreachable!(),
-        };
-        self.line_width = line_width.ceil() as u32;
-        self.lines = lines.ceil() as u32;
+        let (line_width, lines) = adam7::pass_size(self.current_pass, self.width, self.height);
+        self.line_width = line_width;
+        self.lines = lines;
         self.line = 0;
     }
 
@@ -166,7 +199,7 @@ impl Iterator for Adam7Iterator {
     }
 }
 
-fn subbyte_pixels(scanline: &[u8], bits_pp: usize) -> impl Iterator<Item = u8> + '_ {
+pub(crate) fn subbyte_pixels(scanline: &[u8], bits_pp: usize) -> impl Iterator<Item = u8> + '_ {
     (0..scanline.len() * 8)
         .step_by(bits_pp)
         .map(move |bit_idx| {
@@ -193,19 +226,21 @@ fn expand_adam7_bits(
     line_no: usize,
     bits_pp: usize,
 ) -> StepBy<Range<usize>> {
-    let (line_mul, line_off, samp_mul, samp_off) = match pass {
-        1 => (8, 0, 8, 0),
-        2 => (8, 0, 8, 4),
-        3 => (8, 4, 4, 0),
-        4 => (4, 0, 4, 2),
-        5 => (4, 2, 2, 0),
-        6 => (2, 0, 2, 1),
-        7 => (2, 1, 1, 0),
-        _ => panic!("Adam7 pass out of range: {}", pass),
-    };
-
     // the equivalent line number in progressive scan
-    let prog_line = line_mul * line_no + line_off;
+    let prog_line = adam7::image_row(pass, line_no as u32) as usize;
+    adam7_row_bits(pass, width, prog_line, bits_pp)
+}
+
+/// The bit positions of the pixels of a pass in the image row `prog_line`.
+fn adam7_row_bits(
+    pass: u8,
+    width: usize,
+    prog_line: usize,
+    bits_pp: usize,
+) -> StepBy<Range<usize>> {
+    let samp_off = adam7::image_column(pass, 0) as usize;
+    let samp_mul = adam7::image_column(pass, 1) as usize - samp_off;
+
     // line width is rounded up to the next byte
     let line_width = (width * bits_pp + 7) & !7;
     let line_start = prog_line * line_width;
@@ -224,21 +259,39 @@ pub fn expand_pass(
     line_no: u32,
     bits_pp: u8,
 ) {
-    let width = width as usize;
-    let line_no = line_no as usize;
-    let bits_pp = bits_pp as usize;
-
     // pass is out of range but don't blow up
     if pass == 0 || pass > 7 {
         return;
     }
 
-    let bit_indices = expand_adam7_bits(pass, width, line_no, bits_pp);
+    let bit_indices = expand_adam7_bits(pass, width as usize, line_no as usize, bits_pp.into());
+    expand_bits(img, scanline, bit_indices, bits_pp.into())
+}
+
+/// Expands a line of an Adam 7 pass into the row `prog_line` of the image, which need not be the
+/// row of the line in the pass geometry.
+///
+/// The pixels of the pass are overwritten, others in the image buffer are kept.
+pub fn expand_pass_to_row(
+    img: &mut [u8],
+    width: u32,
+    scanline: &[u8],
+    pass: u8,
+    prog_line: u32,
+    bits_pp: u8,
+) {
+    let bits_pp = bits_pp.into();
+    let bit_indices = adam7_row_bits(pass, width as usize, prog_line as usize, bits_pp);
+    expand_bits(img, scanline, bit_indices, bits_pp)
+}
 
+/// Copies the pixels of `scanline` to the bit positions `bit_indices` of `img`.
+fn expand_bits(img: &mut [u8], scanline: &[u8], bit_indices: StepBy<Range<usize>>, bits_pp: usize) {
     if bits_pp < 8 {
+        let mask = (1u8 << bits_pp) - 1;
         for (pos, px) in bit_indices.zip(subbyte_pixels(scanline, bits_pp)) {
             let rem = 8 - pos % 8 - bits_pp;
-            img[pos / 8] |= px << rem as u8;
+            img[pos / 8] = img[pos / 8] & !(mask << rem) | px << rem as u8;
         }
     } else {
         let bytes_pp = bits_pp / 8;
@@ -310,7 +363,7 @@ fn test_expand_adam7_bits() {
             expected(start, 8, 4)
         );
 
-        let start = (8 * line_no + 4) as usize * width as usize;
+        let start = (8 * line_no + 4) * width;
 
         assert_eq!(
             expand_adam7_bits(3, width, line_no, bits_pp).collect::<Vec<_>>(),
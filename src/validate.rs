@@ -0,0 +1,794 @@
+//! Checks of a PNG file against the specification, like `pngcheck`.
+//!
+//! [`validate`] reads a whole file and reports its problems as [`Finding`]s instead of stopping
+//! at the first one like the [`Decoder`]. Besides the structure of the chunks it checks their
+//! order, the values of the metadata and the consistency of an animation, and finally decodes
+//! the image data.
+//!
+//! ```
+//! let file = std::fs::File::open("tests/pngsuite/basn0g08.png").unwrap();
+//! let report = png::validate(file);
+//! assert!(report.is_valid());
+//! for finding in &report.findings {
+//!     println!("{}", finding);
+//! }
+//! ```
+use std::fmt;
+use std::io::Read;
+
+use crate::chunk::{self, ChunkType, OrderChecker, OrderViolationKind, IDAT, IEND, IHDR, PLTE};
+use crate::common::{BitDepth, BlendOp, ColorType, DisposeOp};
+use crate::decoder::{ChunkContext, Decoder, DecodingError};
+use crate::text_metadata::check_keyword;
+
+/// How serious a [`Finding`] is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    /// The file violates the specification, decoders may reject it.
+    Error,
+    /// The file is valid but unusual, for example a text keyword with a trailing space.
+    Warning,
+    /// A description of the file, such as its dimensions.
+    Info,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str(match self {
+            Level::Error => "error",
+            Level::Warning => "warning",
+            Level::Info => "info",
+        })
+    }
+}
+
+/// A problem or a fact found by [`validate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Finding {
+    /// How serious the finding is.
+    pub level: Level,
+    /// The chunk that the finding is about, `None` if it concerns the whole file.
+    pub chunk: Option<ChunkContext>,
+    /// A description of the finding.
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}: {}", self.level, self.message)?;
+        match &self.chunk {
+            Some(chunk) => write!(fmt, " (in the {})", chunk),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The findings of [`validate`] in the order of the file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// All findings, the problems of the image data come last.
+    pub findings: Vec<Finding>,
+}
+
+impl ValidationReport {
+    /// Returns true if there is no finding of [`Level::Error`].
+    pub fn is_valid(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    /// The findings of [`Level::Error`].
+    pub fn errors(&self) -> impl Iterator<Item = &Finding> + '_ {
+        self.findings
+            .iter()
+            .filter(|finding| finding.level == Level::Error)
+    }
+
+    /// The findings of [`Level::Warning`].
+    pub fn warnings(&self) -> impl Iterator<Item = &Finding> + '_ {
+        self.findings
+            .iter()
+            .filter(|finding| finding.level == Level::Warning)
+    }
+}
+
+/// Reads a PNG file from `r` and checks it against the specification.
+///
+/// Failing to read `r` is reported as an error of the file.
+pub fn validate<R: Read>(mut r: R) -> ValidationReport {
+    let mut data = Vec::new();
+    let mut validator = Validator::default();
+    match r.read_to_end(&mut data) {
+        Ok(_) => validator.file(&data),
+        Err(err) => validator.push(Level::Error, format!("reading failed: {}", err)),
+    }
+    validator.report
+}
+
+#[derive(Clone, Copy)]
+struct Header {
+    width: u32,
+    height: u32,
+    color_type: ColorType,
+    bit_depth: BitDepth,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ImageData {
+    Before,
+    Inside,
+    After,
+}
+
+impl Default for ImageData {
+    fn default() -> Self {
+        ImageData::Before
+    }
+}
+
+#[derive(Default)]
+struct Validator {
+    report: ValidationReport,
+    /// The chunk that is checked.
+    chunk: Option<ChunkContext>,
+    /// The number of chunks of each type so far.
+    counts: Vec<(ChunkType, usize)>,
+    /// The ordering rules of the chunks after the image header.
+    order: OrderChecker,
+    header: Option<Header>,
+    palette_entries: Option<usize>,
+    image_data: ImageData,
+    /// The number of frames announced by `acTL`.
+    num_frames: Option<u32>,
+    frames: u32,
+    /// The next sequence number of `fcTL` and `fdAT` chunks.
+    sequence: u32,
+    /// Whether the current frame has an `fcTL` chunk and its data is stored in `fdAT` chunks.
+    frame_data: bool,
+}
+
+impl Validator {
+    fn push(&mut self, level: Level, message: String) {
+        self.report.findings.push(Finding {
+            level,
+            chunk: self.chunk,
+            message,
+        });
+    }
+
+    fn error(&mut self, message: String) {
+        self.push(Level::Error, message);
+    }
+
+    fn warning(&mut self, message: String) {
+        self.push(Level::Warning, message);
+    }
+
+    fn count(&self, chunk_type: ChunkType) -> usize {
+        self.counts
+            .iter()
+            .find(|(type_, _)| *type_ == chunk_type)
+            .map_or(0, |&(_, count)| count)
+    }
+
+    fn file(&mut self, data: &[u8]) {
+        if !data.starts_with(&[137, 80, 78, 71, 13, 10, 26, 10]) {
+            self.error("the PNG signature is missing".into());
+            return;
+        }
+
+        let mut pos = 8;
+        let complete = loop {
+            self.chunk = None;
+            let header = match data.get(pos..pos + 8) {
+                Some(header) => header,
+                None if pos == data.len() => {
+                    self.error("the IEND chunk is missing".into());
+                    break false;
+                }
+                None => {
+                    self.error(format!("truncated chunk header at offset {:#x}", pos));
+                    break false;
+                }
+            };
+            let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+            let chunk_type = ChunkType([header[4], header[5], header[6], header[7]]);
+            let index = self.count(chunk_type);
+            match self
+                .counts
+                .iter_mut()
+                .find(|(type_, _)| *type_ == chunk_type)
+            {
+                Some((_, count)) => *count += 1,
+                None => self.counts.push((chunk_type, 1)),
+            }
+            self.chunk = Some(ChunkContext {
+                chunk_type,
+                index,
+                offset: pos as u64,
+            });
+
+            if !chunk_type.is_valid() {
+                self.error(format!("invalid chunk type {:?}", chunk_type));
+                break false;
+            }
+            if length > i32::MAX as u32 {
+                self.error(format!("the length {} exceeds 2^31 - 1", length));
+                break false;
+            }
+            let start = pos + 8;
+            let end = start + length as usize;
+            let crc = match data.get(end..end + 4) {
+                Some(crc) => u32::from_be_bytes([crc[0], crc[1], crc[2], crc[3]]),
+                None => {
+                    self.error(format!(
+                        "the chunk is truncated, {} of {} bytes are present",
+                        data.len() - start,
+                        u64::from(length) + 4
+                    ));
+                    break false;
+                }
+            };
+            let chunk_data = &data[start..end];
+            if !chunk::verify_crc(chunk_type, chunk_data, crc) {
+                self.error(format!(
+                    "CRC mismatch, stored {:#010x} but computed {:#010x}",
+                    crc,
+                    chunk::crc(chunk_type, chunk_data)
+                ));
+            }
+            self.check_chunk(chunk_type, chunk_data);
+            pos = end + 4;
+
+            if chunk_type == IEND {
+                if pos < data.len() {
+                    self.warning(format!("{} bytes after the IEND chunk", data.len() - pos));
+                }
+                break true;
+            }
+        };
+        self.chunk = None;
+        self.check_file();
+        if complete && self.report.is_valid() {
+            self.check_image_data(data);
+        }
+    }
+
+    fn check_chunk(&mut self, chunk_type: ChunkType, data: &[u8]) {
+        if self.header.is_none() && chunk_type != IHDR {
+            if self.count(IHDR) == 0 && self.counts.len() == 1 {
+                self.error(format!("the first chunk is {} instead of IHDR", chunk_type));
+            }
+            return;
+        }
+        if self.image_data == ImageData::Inside && chunk_type != IDAT {
+            self.image_data = ImageData::After;
+        }
+        if !self.check_order(chunk_type) {
+            return;
+        }
+
+        match chunk_type {
+            IHDR => self.check_ihdr(data),
+            PLTE => self.check_plte(data),
+            IDAT => self.check_idat(),
+            IEND if !data.is_empty() => self.error("the IEND chunk is not empty".into()),
+            IEND => {}
+            chunk::tRNS => self.check_trns(data),
+            chunk::gAMA => self.check_gama(data),
+            chunk::cHRM => {
+                self.check_length(data, 32);
+            }
+            chunk::sRGB => {
+                if self.check_length(data, 1) && data[0] > 3 {
+                    self.error(format!("invalid rendering intent {}", data[0]));
+                }
+                if self.count(chunk::iCCP) > 0 {
+                    self.warning("the image has both an sRGB and an iCCP chunk".into());
+                }
+            }
+            chunk::iCCP => {
+                if let Some(rest) = self.check_keyword(data) {
+                    if rest.first() != Some(&0) {
+                        self.error("unknown compression method".into());
+                    }
+                }
+                if self.count(chunk::sRGB) > 0 {
+                    self.warning("the image has both an sRGB and an iCCP chunk".into());
+                }
+            }
+            chunk::cICP => {
+                self.check_length(data, 4);
+            }
+            chunk::bKGD => self.check_bkgd(data),
+            chunk::pHYs if self.check_length(data, 9) && data[8] > 1 => {
+                self.error(format!("invalid unit {}", data[8]));
+            }
+            chunk::tIME => self.check_time(data),
+            chunk::tEXt => {
+                self.check_keyword(data);
+            }
+            chunk::zTXt => {
+                if let Some(rest) = self.check_keyword(data) {
+                    if rest.first() != Some(&0) {
+                        self.error("unknown compression method".into());
+                    }
+                }
+            }
+            chunk::iTXt => {
+                if let Some(rest) = self.check_keyword(data) {
+                    match rest {
+                        [flag, method, ..] if *flag > 1 || (*flag == 1 && *method != 0) => {
+                            self.error("invalid compression flag or method".into())
+                        }
+                        [_, _, ..] => {}
+                        _ => self.error("the chunk is truncated after the keyword".into()),
+                    }
+                }
+            }
+            chunk::acTL => self.check_actl(data),
+            chunk::fcTL => self.check_fctl(data),
+            chunk::fdAT => self.check_fdat(data),
+            _ if chunk::is_critical(chunk_type) => {
+                self.error(format!("unknown critical chunk {}", chunk_type));
+            }
+            _ if !chunk::KNOWN_ANCILLARY.contains(&chunk_type) && self.count(chunk_type) == 1 => {
+                self.push(
+                    Level::Info,
+                    format!("unknown ancillary chunk {}", chunk_type),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    /// Checks the place of a chunk with [`chunk::check_order`], returning false for a duplicate.
+    fn check_order(&mut self, chunk_type: ChunkType) -> bool {
+        let mut violations = Vec::new();
+        self.order.check(chunk_type, &mut violations);
+        let mut unique = true;
+        for violation in violations {
+            let message = match violation.kind {
+                OrderViolationKind::Duplicate => {
+                    unique = false;
+                    "the chunk must appear at most once".into()
+                }
+                OrderViolationKind::MustPrecede(IDAT) => {
+                    "the chunk must appear before the IDAT chunks".into()
+                }
+                OrderViolationKind::MustPrecede(other) => {
+                    format!("the chunk must appear before the {} chunk", other)
+                }
+                OrderViolationKind::MustFollow(other) => {
+                    format!("the chunk must appear after the {} chunk", other)
+                }
+                OrderViolationKind::NotConsecutive => "the IDAT chunks are not consecutive".into(),
+                // Chunks before the image header or after `IEND` are not checked, and missing
+                // chunks are reported by `check_file`.
+                OrderViolationKind::NotFirst
+                | OrderViolationKind::AfterEnd
+                | OrderViolationKind::Missing => violation.to_string(),
+            };
+            self.error(message);
+        }
+        unique
+    }
+
+    /// Checks the length of a chunk with fixed size, returning whether it is right.
+    fn check_length(&mut self, data: &[u8], len: usize) -> bool {
+        if data.len() == len {
+            true
+        } else {
+            self.error(format!("the length is {} instead of {}", data.len(), len));
+            false
+        }
+    }
+
+    /// Checks the keyword of a text or profile chunk and returns the data after it.
+    fn check_keyword<'a>(&mut self, data: &'a [u8]) -> Option<&'a [u8]> {
+        let end = match data.iter().position(|&b| b == 0) {
+            Some(end) => end,
+            None => {
+                self.error("the keyword is not terminated by a null byte".into());
+                return None;
+            }
+        };
+        let keyword: String = data[..end].iter().map(|&b| char::from(b)).collect();
+        if let Err(err) = check_keyword(&keyword) {
+            self.warning(format!("invalid keyword {:?}, {}", keyword, err));
+        }
+        Some(&data[end + 1..])
+    }
+
+    fn check_ihdr(&mut self, data: &[u8]) {
+        if !self.check_length(data, 13) {
+            return;
+        }
+        let width = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+        let height = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+        if width == 0 || height == 0 || width > i32::MAX as u32 || height > i32::MAX as u32 {
+            self.error(format!("invalid dimensions {}x{}", width, height));
+        }
+        let (bit_depth, color_type) =
+            match (BitDepth::from_u8(data[8]), ColorType::from_u8(data[9])) {
+                (Some(bit_depth), Some(color_type))
+                    if color_type.is_combination_allowed(bit_depth) =>
+                {
+                    (bit_depth, color_type)
+                }
+                _ => {
+                    self.error(format!(
+                        "invalid bit depth {} for color type {}",
+                        data[8], data[9]
+                    ));
+                    return;
+                }
+            };
+        if data[10] != 0 {
+            self.error(format!("unknown compression method {}", data[10]));
+        }
+        if data[11] != 0 {
+            self.error(format!("unknown filter method {}", data[11]));
+        }
+        if data[12] > 1 {
+            self.error(format!("unknown interlace method {}", data[12]));
+        }
+        self.push(
+            Level::Info,
+            format!(
+                "{}x{} {:?} image with {} bits per sample{}",
+                width,
+                height,
+                color_type,
+                bit_depth as u8,
+                if data[12] == 1 { ", interlaced" } else { "" }
+            ),
+        );
+        self.header = Some(Header {
+            width,
+            height,
+            color_type,
+            bit_depth,
+        });
+    }
+
+    fn check_plte(&mut self, data: &[u8]) {
+        if data.is_empty() || data.len() % 3 != 0 || data.len() > 3 * 256 {
+            self.error(format!("invalid palette length {}", data.len()));
+            return;
+        }
+        let entries = data.len() / 3;
+        self.palette_entries = Some(entries);
+        let header = self.header.unwrap();
+        match header.color_type {
+            ColorType::Grayscale | ColorType::GrayscaleAlpha => {
+                self.error("a grayscale image must not have a palette".into());
+            }
+            ColorType::Indexed if entries > 1 << header.bit_depth as u8 => {
+                self.error(format!(
+                    "{} palette entries exceed the bit depth {}",
+                    entries, header.bit_depth as u8
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    fn check_idat(&mut self) {
+        let header = self.header.unwrap();
+        match self.image_data {
+            ImageData::Before => {
+                if header.color_type == ColorType::Indexed && self.palette_entries.is_none() {
+                    self.error("the palette of an indexed image is missing".into());
+                }
+                if self.frames > 0 {
+                    // The default image is the first frame.
+                    self.frame_data = false;
+                }
+                self.image_data = ImageData::Inside;
+            }
+            ImageData::Inside | ImageData::After => {}
+        }
+    }
+
+    fn check_trns(&mut self, data: &[u8]) {
+        let header = self.header.unwrap();
+        match header.color_type {
+            ColorType::Grayscale => {
+                self.check_length(data, 2);
+            }
+            ColorType::Rgb => {
+                self.check_length(data, 6);
+            }
+            ColorType::Indexed => match self.palette_entries {
+                None => self.error("the tRNS chunk must appear after the PLTE chunk".into()),
+                Some(entries) if data.len() > entries => self.error(format!(
+                    "{} transparency entries for {} palette entries",
+                    data.len(),
+                    entries
+                )),
+                Some(_) => {}
+            },
+            ColorType::GrayscaleAlpha | ColorType::Rgba => {
+                self.error("an image with alpha channel must not have a tRNS chunk".into());
+            }
+        }
+    }
+
+    fn check_gama(&mut self, data: &[u8]) {
+        if !self.check_length(data, 4) {
+            return;
+        }
+        let gamma = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+        if gamma == 0 {
+            self.error("the gamma is zero".into());
+        } else if !(1_000..=1_000_000).contains(&gamma) {
+            self.warning(format!(
+                "unusual gamma {}, the decoding exponent is {:.2}",
+                f64::from(gamma) / 100_000.0,
+                100_000.0 / f64::from(gamma)
+            ));
+        }
+    }
+
+    fn check_bkgd(&mut self, data: &[u8]) {
+        let header = self.header.unwrap();
+        match header.color_type {
+            ColorType::Indexed => match self.palette_entries {
+                None => self.error("the bKGD chunk must appear after the PLTE chunk".into()),
+                Some(entries) => {
+                    if self.check_length(data, 1) && usize::from(data[0]) >= entries {
+                        self.error(format!("palette index {} out of range", data[0]));
+                    }
+                }
+            },
+            ColorType::Grayscale | ColorType::GrayscaleAlpha => {
+                self.check_length(data, 2);
+            }
+            ColorType::Rgb | ColorType::Rgba => {
+                self.check_length(data, 6);
+            }
+        }
+    }
+
+    fn check_time(&mut self, data: &[u8]) {
+        if !self.check_length(data, 7) {
+            return;
+        }
+        let (month, day, hour, minute, second) = (data[2], data[3], data[4], data[5], data[6]);
+        if !(1..=12).contains(&month)
+            || !(1..=31).contains(&day)
+            || hour > 23
+            || minute > 59
+            || second > 60
+        {
+            self.warning("invalid modification time".into());
+        }
+    }
+
+    fn check_sequence(&mut self, sequence: u32) {
+        if sequence != self.sequence {
+            self.error(format!(
+                "sequence number {} instead of {}",
+                sequence, self.sequence
+            ));
+        }
+        self.sequence = sequence.wrapping_add(1);
+    }
+
+    fn check_actl(&mut self, data: &[u8]) {
+        if !self.check_length(data, 8) {
+            return;
+        }
+        let num_frames = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+        if num_frames == 0 {
+            self.error("the animation has zero frames".into());
+        }
+        self.num_frames = Some(num_frames);
+    }
+
+    fn check_fctl(&mut self, data: &[u8]) {
+        if !self.check_length(data, 26) {
+            return;
+        }
+        let read = |pos: usize| {
+            u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
+        };
+        self.check_sequence(read(0));
+        self.frames += 1;
+        self.frame_data = self.image_data != ImageData::Before;
+
+        let header = self.header.unwrap();
+        let (width, height, x, y) = (read(4), read(8), read(12), read(16));
+        let inside = width > 0
+            && height > 0
+            && x.checked_add(width)
+                .map_or(false, |end| end <= header.width)
+            && y.checked_add(height)
+                .map_or(false, |end| end <= header.height);
+        if !inside {
+            self.error(format!(
+                "the frame of {}x{} at ({}, {}) is outside of the image",
+                width, height, x, y
+            ));
+        } else if !self.frame_data && (width, height, x, y) != (header.width, header.height, 0, 0) {
+            self.error("the frame of the default image must cover the image".into());
+        }
+        if DisposeOp::from_u8(data[24]).is_none() {
+            self.error(format!("invalid dispose operation {}", data[24]));
+        }
+        if BlendOp::from_u8(data[25]).is_none() {
+            self.error(format!("invalid blend operation {}", data[25]));
+        }
+    }
+
+    fn check_fdat(&mut self, data: &[u8]) {
+        if data.len() < 4 {
+            self.error("the chunk has no sequence number".into());
+            return;
+        }
+        self.check_sequence(u32::from_be_bytes([data[0], data[1], data[2], data[3]]));
+        if !self.frame_data {
+            self.error("the fdAT chunk does not follow an fcTL chunk".into());
+        }
+    }
+
+    /// Checks the chunks that must exist, after all chunks were read.
+    fn check_file(&mut self) {
+        if self.header.is_none() {
+            if self.count(IHDR) == 0 {
+                self.error("the IHDR chunk is missing".into());
+            }
+            return;
+        }
+        if self.count(IDAT) == 0 {
+            self.error("the IDAT chunk is missing".into());
+        }
+        if let Some(num_frames) = self.num_frames {
+            if num_frames != self.frames {
+                self.error(format!(
+                    "the acTL chunk announces {} frames but there are {}",
+                    num_frames, self.frames
+                ));
+            }
+            self.push(Level::Info, format!("animation of {} frames", self.frames));
+        }
+        let chunks: usize = self.counts.iter().map(|&(_, count)| count).sum();
+        self.push(Level::Info, format!("{} chunks", chunks));
+    }
+
+    /// Decodes all frames, which checks the compressed data and the filters of the rows.
+    fn check_image_data(&mut self, data: &[u8]) {
+        let mut reader = match Decoder::new(data).read_info() {
+            Ok(reader) => reader,
+            Err(err) => return self.decoding_error(err),
+        };
+        let mut buf = vec![0; reader.output_buffer_size()];
+        loop {
+            match reader.next_frame(&mut buf) {
+                Ok(_) => {}
+                Err(DecodingError::Parameter(_)) => break,
+                Err(err) => return self.decoding_error(err),
+            }
+            if reader.info().animation_control.is_none() {
+                break;
+            }
+        }
+    }
+
+    fn decoding_error(&mut self, err: DecodingError) {
+        self.chunk = err.chunk_context();
+        match err {
+            DecodingError::LimitsExceeded => {
+                self.warning("the image data was not checked, it exceeds the limits".into())
+            }
+            err => self.error(format!("the image data can not be decoded, {}", err)),
+        }
+        self.chunk = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(report: &ValidationReport, level: Level) -> Vec<&str> {
+        report
+            .findings
+            .iter()
+            .filter(|finding| finding.level == level)
+            .map(|finding| finding.message.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn pngsuite() {
+        for path in glob::glob("tests/pngsuite/*.png").unwrap() {
+            let path = path.unwrap();
+            let report = validate(std::fs::File::open(&path).unwrap());
+            let invalid = path.file_name().unwrap().to_str().unwrap().starts_with('x');
+            assert_eq!(
+                report.is_valid(),
+                !invalid,
+                "{:?}: {:?}",
+                path,
+                report.findings
+            );
+        }
+    }
+
+    #[test]
+    fn findings() {
+        let mut png = Vec::new();
+        let mut encoder = crate::Encoder::new(&mut png, 1, 1);
+        encoder.add_text_chunk("Title ".into(), "x".into()).unwrap();
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_chunk(chunk::gAMA, &[0, 0, 0, 10]).unwrap();
+        writer.write_image_data(&[0]).unwrap();
+        writer.write_chunk(chunk::pHYs, &[0; 9]).unwrap();
+        writer.finish().unwrap();
+
+        let report = validate(&png[..]);
+        assert_eq!(
+            messages(&report, Level::Error),
+            ["the chunk must appear before the IDAT chunks"]
+        );
+        let warnings = messages(&report, Level::Warning);
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].starts_with("invalid keyword \"Title \""));
+        assert!(warnings[1].starts_with("unusual gamma"));
+        let chunk = report.errors().next().unwrap().chunk.unwrap();
+        assert_eq!(chunk.chunk_type, chunk::pHYs);
+
+        // A truncated file is reported without decoding the image data.
+        let report = validate(&png[..png.len() - 2]);
+        assert!(messages(&report, Level::Error)
+            .last()
+            .unwrap()
+            .starts_with("the chunk is truncated"));
+    }
+
+    #[test]
+    fn animation() {
+        let mut png = Vec::new();
+        let mut encoder = crate::Encoder::new(&mut png, 2, 2);
+        encoder.set_animated(3, 0).unwrap();
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[0; 4]).unwrap();
+        writer.write_image_data(&[1; 4]).unwrap();
+        drop(writer);
+
+        let report = validate(&png[..]);
+        assert_eq!(
+            messages(&report, Level::Error),
+            ["the acTL chunk announces 3 frames but there are 2"]
+        );
+        assert!(messages(&report, Level::Info).contains(&"animation of 2 frames"));
+    }
+
+    #[test]
+    fn order() {
+        let mut png = Vec::new();
+        let mut encoder = crate::Encoder::new(&mut png, 1, 1);
+        encoder.set_color(ColorType::Indexed);
+        encoder.set_palette(vec![0; 3]);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_chunk(chunk::cICP, &[1, 13, 0, 1]).unwrap();
+        writer.write_chunk(chunk::eXIf, b"MM\0\x2a").unwrap();
+        writer.write_chunk(chunk::eXIf, b"MM\0\x2a").unwrap();
+        writer.write_image_data(&[0]).unwrap();
+        writer.finish().unwrap();
+
+        let report = validate(&png[..]);
+        assert_eq!(
+            messages(&report, Level::Error),
+            [
+                "the chunk must appear before the PLTE chunk",
+                "the chunk must appear at most once"
+            ]
+        );
+        let chunks: Vec<_> = report.errors().map(|f| f.chunk.unwrap()).collect();
+        assert_eq!(chunks[0].chunk_type, chunk::cICP);
+        assert_eq!((chunks[1].chunk_type, chunks[1].index), (chunk::eXIf, 1));
+    }
+}
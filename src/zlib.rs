@@ -0,0 +1,182 @@
+//! The zlib compressor of the encoder.
+//!
+//! Image data and text are compressed with the `deflate` crate by default. The `miniz-encoder`
+//! feature selects the compressor of `miniz_oxide` instead, which is also used for decompression,
+//! so that a build needs only one zlib implementation at run time.
+use std::io::{self, Write};
+
+use crate::common::Compression;
+
+/// A zlib compressor writing into `W`.
+#[cfg(not(feature = "miniz-encoder"))]
+pub(crate) struct ZlibEncoder<W: Write> {
+    inner: deflate::write::ZlibEncoder<W>,
+}
+
+#[cfg(not(feature = "miniz-encoder"))]
+impl<W: Write> ZlibEncoder<W> {
+    pub(crate) fn new(writer: W, compression: Compression) -> Self {
+        let options = match compression {
+            Compression::Default => deflate::CompressionOptions::default(),
+            Compression::Fast => deflate::CompressionOptions::fast(),
+            Compression::Best => deflate::CompressionOptions::high(),
+            Compression::Huffman => deflate::CompressionOptions::huffman_only(),
+            Compression::Rle => deflate::CompressionOptions::rle(),
+        };
+        ZlibEncoder {
+            inner: deflate::write::ZlibEncoder::new(writer, options),
+        }
+    }
+
+    /// Writes the end of the stream, returning the inner writer.
+    pub(crate) fn finish(self) -> io::Result<W> {
+        self.inner.finish()
+    }
+}
+
+#[cfg(not(feature = "miniz-encoder"))]
+impl<W: Write> Write for ZlibEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A zlib compressor writing into `W`.
+#[cfg(feature = "miniz-encoder")]
+pub(crate) struct ZlibEncoder<W: Write> {
+    compressor: Box<miniz_oxide::deflate::core::CompressorOxide>,
+    /// The writer, until the stream is finished.
+    writer: Option<W>,
+    out_buffer: Vec<u8>,
+}
+
+#[cfg(feature = "miniz-encoder")]
+impl<W: Write> ZlibEncoder<W> {
+    pub(crate) fn new(writer: W, compression: Compression) -> Self {
+        use miniz_oxide::deflate::core::{
+            create_comp_flags_from_zip_params, CompressionStrategy, CompressorOxide,
+        };
+
+        let (level, strategy) = match compression {
+            Compression::Default => (6, CompressionStrategy::Default),
+            Compression::Fast => (1, CompressionStrategy::Default),
+            Compression::Best => (9, CompressionStrategy::Default),
+            Compression::Huffman => (6, CompressionStrategy::HuffmanOnly),
+            Compression::Rle => (6, CompressionStrategy::RLE),
+        };
+        // Positive window bits select the zlib wrapper.
+        let flags = create_comp_flags_from_zip_params(level, 15, strategy as i32);
+        ZlibEncoder {
+            compressor: Box::new(CompressorOxide::new(flags)),
+            writer: Some(writer),
+            out_buffer: vec![0; 1 << 15],
+        }
+    }
+
+    /// Writes the end of the stream, returning the inner writer.
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        use miniz_oxide::deflate::core::TDEFLFlush;
+
+        self.compress(&[], TDEFLFlush::Finish)?;
+        Ok(self
+            .writer
+            .take()
+            .expect("the writer is only taken when finishing"))
+    }
+
+    /// Compresses all of `data`, then flushes as requested.
+    fn compress(
+        &mut self,
+        mut data: &[u8],
+        flush: miniz_oxide::deflate::core::TDEFLFlush,
+    ) -> io::Result<()> {
+        use miniz_oxide::deflate::core::{compress, TDEFLFlush, TDEFLStatus};
+
+        let writer = match self.writer.as_mut() {
+            Some(writer) => writer,
+            None => return Err(io::Error::new(io::ErrorKind::Other, "stream is finished")),
+        };
+        loop {
+            let (status, consumed, written) =
+                compress(&mut self.compressor, data, &mut self.out_buffer, flush);
+            writer.write_all(&self.out_buffer[..written])?;
+            data = &data[consumed..];
+            match status {
+                TDEFLStatus::Done => return Ok(()),
+                // A flush is complete once the output buffer was not filled.
+                TDEFLStatus::Okay
+                    if data.is_empty()
+                        && (flush == TDEFLFlush::None
+                            || flush == TDEFLFlush::Sync && written < self.out_buffer.len()) =>
+                {
+                    return Ok(())
+                }
+                TDEFLStatus::Okay => {}
+                TDEFLStatus::BadParam | TDEFLStatus::PutBufFailed => {
+                    return Err(io::Error::new(io::ErrorKind::Other, "compression failed"))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "miniz-encoder")]
+impl<W: Write> Write for ZlibEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        use miniz_oxide::deflate::core::TDEFLFlush;
+
+        self.compress(buf, TDEFLFlush::None)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        use miniz_oxide::deflate::core::TDEFLFlush;
+
+        self.compress(&[], TDEFLFlush::Sync)
+    }
+}
+
+#[cfg(feature = "miniz-encoder")]
+impl<W: Write> Drop for ZlibEncoder<W> {
+    /// Writes the end of the stream if it was not finished, ignoring errors.
+    fn drop(&mut self) {
+        use miniz_oxide::deflate::core::TDEFLFlush;
+
+        if self.writer.is_some() && !std::thread::panicking() {
+            let _ = self.compress(&[], TDEFLFlush::Finish);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ZlibEncoder;
+    use crate::common::Compression;
+    use std::io::Write;
+
+    #[test]
+    fn round_trip() {
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251 * (i / 7)) as u8).collect();
+        for &compression in &[
+            Compression::Default,
+            Compression::Fast,
+            Compression::Best,
+            Compression::Huffman,
+            Compression::Rle,
+        ] {
+            let mut encoder = ZlibEncoder::new(Vec::new(), compression);
+            let (start, end) = data.split_at(30_000);
+            encoder.write_all(start).unwrap();
+            // Flushing in between must not end the stream.
+            encoder.flush().unwrap();
+            encoder.write_all(end).unwrap();
+            let compressed = encoder.finish().unwrap();
+            let decompressed = miniz_oxide::inflate::decompress_to_vec_zlib(&compressed).unwrap();
+            assert!(decompressed == data, "{:?}", compression);
+        }
+    }
+}